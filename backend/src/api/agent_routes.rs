@@ -5,7 +5,7 @@ use actix_web::{web, HttpResponse, Result as ActixResult};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::memory::{AgentMemoryLayer, Goal, Episode, Reflection, AgentContext};
+use crate::memory::AgentMemoryLayer;
 
 pub type SharedAgentMemory = Arc<AgentMemoryLayer>;
 
@@ -22,6 +22,8 @@ pub struct RecordEpisodeRequest {
     pub response: String,
     pub context_chunks_used: usize,
     pub success: bool,
+    #[serde(default)]
+    pub grounding_score: Option<f32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,6 +49,7 @@ pub struct EpisodeResponse {
     pub response: String,
     pub context_chunks_used: usize,
     pub success: bool,
+    pub grounding_score: Option<f32>,
     pub created_at: i64,
 }
 
@@ -162,6 +165,7 @@ pub async fn record_episode(
             req.response.clone(),
             req.context_chunks_used,
             req.success,
+            req.grounding_score,
         )
         .await
     {
@@ -172,6 +176,7 @@ pub async fn record_episode(
                 response: episode.response,
                 context_chunks_used: episode.context_chunks_used,
                 success: episode.success,
+                grounding_score: episode.grounding_score,
                 created_at: episode.created_at,
             };
             Ok(HttpResponse::Ok().json(response))
@@ -189,7 +194,7 @@ pub async fn get_similar_queries(
     req: web::Json<SimilarQueriesRequest>,
 ) -> ActixResult<HttpResponse> {
     match agent_memory
-        .get_similar_past_queries(&req.query, req.top_k)
+        .recall_similar_episodes(&req.query, req.top_k)
         .await
     {
         Ok(episodes) => {
@@ -201,6 +206,7 @@ pub async fn get_similar_queries(
                     response: e.response,
                     context_chunks_used: e.context_chunks_used,
                     success: e.success,
+                    grounding_score: e.grounding_score,
                     created_at: e.created_at,
                 })
                 .collect();
@@ -288,4 +294,20 @@ pub async fn agent_health(
             error: e.to_string(),
         })),
     }
-}
\ No newline at end of file
+}
+
+// ============ Route Configuration ============
+
+pub fn configure_agent_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/agent")
+            .route("/goals", web::post().to(create_goal))
+            .route("/goals", web::get().to(get_active_goals))
+            .route("/goals/{goal_id}/complete", web::post().to(complete_goal))
+            .route("/episodes", web::post().to(record_episode))
+            .route("/episodes/similar", web::post().to(get_similar_queries))
+            .route("/reflect", web::post().to(reflect))
+            .route("/context", web::get().to(get_agent_context))
+            .route("/health", web::get().to(agent_health))
+    );
+}
@@ -0,0 +1,359 @@
+// src/api/app_state.rs
+// Builds the vector store, RAG pipeline, agent memory and decision engine
+// once at startup and hands them to the route modules as web::Data
+
+use actix_web::web;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use super::agent_routes::{self, SharedAgentMemory};
+use super::memory_routes::{self, SharedEmbeddingService, SharedVectorStore};
+use super::{composer_routes, decision_engine_routes, tool_routes};
+use crate::config::ApiConfig;
+use crate::db::param_hardware;
+use crate::embedder::{EmbeddingConfig, EmbeddingService};
+use crate::memory::{
+    build_llm_provider, llm_router, AgentMemoryLayer, AnswerProvider, DecisionEngine, LLMConfig,
+    RagConfig, RagQueryPipeline, VectorStore, VectorStoreConfig,
+};
+use crate::retriever::Retriever;
+
+/// Where the memory and agent components keep their data
+#[derive(Debug, Clone)]
+pub struct AppStateConfig {
+    pub vector_store_path: PathBuf,
+    pub max_vectors: usize,
+    pub agent_db_path: PathBuf,
+    /// Episodes are embedded into their own store, apart from documents
+    pub agent_vector_path: PathBuf,
+    pub agent_id: String,
+    pub agent_name: String,
+}
+
+impl AppStateConfig {
+    /// Paths under the configured data directories; MEMORY_MAX_VECTORS,
+    /// AGENT_ID and AGENT_NAME override the defaults
+    pub fn from_env(config: &ApiConfig) -> Self {
+        let pm = &config.path_manager;
+        let max_vectors = std::env::var("MEMORY_MAX_VECTORS")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or_else(|| VectorStoreConfig::default().max_vectors);
+        Self {
+            vector_store_path: pm.index_path("memory"),
+            max_vectors,
+            agent_db_path: pm.db_path("agent_memory"),
+            agent_vector_path: pm.index_path("agent_episodes"),
+            agent_id: std::env::var("AGENT_ID").unwrap_or_else(|_| "default".to_string()),
+            agent_name: std::env::var("AGENT_NAME").unwrap_or_else(|_| "assistant".to_string()),
+        }
+    }
+}
+
+/// Components shared by every worker
+#[derive(Clone)]
+pub struct AppState {
+    pub embedding_service: SharedEmbeddingService,
+    pub vector_store: SharedVectorStore,
+    pub agent_memory: SharedAgentMemory,
+    /// None when no LLM provider could be built, e.g. a hosted API without a key
+    pub rag_pipeline: Option<Arc<RagQueryPipeline>>,
+    pub decision_engine: Option<Arc<DecisionEngine>>,
+    llm_error: Option<String>,
+}
+
+/// Per-component status reported by /monitoring/health and /monitoring/ready;
+/// `ready` depends on the stores only
+#[derive(Debug)]
+pub struct StateHealth {
+    pub ready: bool,
+    pub components: Value,
+}
+
+impl AppState {
+    /// Construct every component. Fails only when a store cannot be opened;
+    /// a missing LLM provider leaves the pipeline and engine unset. The RAG
+    /// pipeline retrieves from `index`, the main document index, when given
+    pub fn build(
+        config: &AppStateConfig,
+        index: Option<Arc<Mutex<Retriever>>>,
+    ) -> Result<Self, String> {
+        Self::build_with(config, index, answer_provider())
+    }
+
+    fn build_with(
+        config: &AppStateConfig,
+        index: Option<Arc<Mutex<Retriever>>>,
+        provider: Result<AnswerProvider, String>,
+    ) -> Result<Self, String> {
+        let embedding_service = Arc::new(EmbeddingService::new(EmbeddingConfig::default()));
+        let vector_store = open_store(config.vector_store_path.clone(), config.max_vectors)?;
+        let agent_store = open_store(config.agent_vector_path.clone(), config.max_vectors)?;
+        let agent_memory = AgentMemoryLayer::new(
+            config.agent_id.clone(),
+            config.agent_name.clone(),
+            config.agent_db_path.clone(),
+            agent_store,
+            Arc::clone(&embedding_service),
+        )
+        .map(Arc::new)
+        .map_err(|e| format!("Failed to open agent memory: {}", e))?;
+
+        let (rag_pipeline, decision_engine, llm_error) = match provider {
+            Ok(provider) => {
+                let mut pipeline = RagQueryPipeline::new(
+                    Arc::clone(&embedding_service),
+                    Arc::clone(&vector_store),
+                    provider,
+                    RagConfig::default(),
+                );
+                if let Some(index) = index.clone() {
+                    pipeline = pipeline.with_index(index);
+                }
+                let pipeline = Arc::new(pipeline);
+                let engine = DecisionEngine::new(Arc::clone(&pipeline), Arc::clone(&agent_memory));
+                (Some(pipeline), Some(Arc::new(engine)), None)
+            }
+            Err(e) => {
+                warn!(error = %e, "No LLM provider; RAG pipeline and decision engine disabled");
+                (None, None, Some(e))
+            }
+        };
+
+        info!(
+            vector_store = ?config.vector_store_path,
+            agent_db = ?config.agent_db_path,
+            document_index = index.is_some(),
+            decision_engine = decision_engine.is_some(),
+            "Application state initialized"
+        );
+        Ok(Self {
+            embedding_service,
+            vector_store,
+            agent_memory,
+            rag_pipeline,
+            decision_engine,
+            llm_error,
+        })
+    }
+
+    /// Inject the components and mount the routes that use them
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone()))
+            .app_data(web::Data::new(Arc::clone(&self.embedding_service)))
+            .app_data(web::Data::new(Arc::clone(&self.vector_store)))
+            .app_data(web::Data::new(Arc::clone(&self.agent_memory)))
+            .configure(memory_routes::configure_memory_routes)
+            .configure(agent_routes::configure_agent_routes)
+            .configure(decision_engine_routes::configure_decision_engine_routes)
+            .configure(tool_routes::configure_tool_routes)
+            .configure(composer_routes::configure_composer_routes);
+    }
+
+    pub async fn health(&self) -> StateHealth {
+        let stats = self.vector_store.read().await.stats().await;
+        let vector_store = json!({
+            "status": "healthy",
+            "total_records": stats.total_records,
+            "total_documents": stats.total_documents,
+            "utilization": stats.utilization,
+        });
+
+        let agent_memory = match self.agent_memory.get_agent_context() {
+            Ok(context) => json!({
+                "status": "healthy",
+                "agent_id": context.agent_id,
+                "active_goals": context.active_goals.len(),
+            }),
+            Err(e) => json!({ "status": "unhealthy", "error": e.to_string() }),
+        };
+
+        let rag_pipeline = match &self.rag_pipeline {
+            Some(pipeline) => match pipeline.llm_provider() {
+                Ok(provider) => json!({ "status": "healthy", "model": provider.model_name() }),
+                Err(e) => json!({ "status": "degraded", "error": e.to_string() }),
+            },
+            None => self.unavailable(),
+        };
+
+        let decision_engine = match &self.decision_engine {
+            Some(engine) => json!({ "status": "healthy", "budget": engine.budget() }),
+            None => self.unavailable(),
+        };
+
+        // Without an LLM only answering is degraded; the stores still serve
+        let ready = vector_store["status"] == "healthy" && agent_memory["status"] == "healthy";
+        let components = json!({
+            "vector_store": vector_store,
+            "agent_memory": agent_memory,
+            "rag_pipeline": rag_pipeline,
+            "decision_engine": decision_engine,
+        });
+        StateHealth { ready, components }
+    }
+
+    fn unavailable(&self) -> Value {
+        json!({
+            "status": "degraded",
+            "error": self.llm_error.as_deref().unwrap_or("LLM provider not configured"),
+        })
+    }
+}
+
+fn open_store(db_path: PathBuf, max_vectors: usize) -> Result<SharedVectorStore, String> {
+    let config = VectorStoreConfig {
+        db_path,
+        max_vectors,
+        ..VectorStoreConfig::default()
+    };
+    VectorStore::new(config)
+        .map(|store| Arc::new(RwLock::new(store)))
+        .map_err(|e| format!("Failed to open vector store: {}", e))
+}
+
+/// The shared router tagged "answer" when installed, looked up per request
+/// so a reload takes effect; else a provider for the configured backend,
+/// built once. The backend is not contacted here
+fn answer_provider() -> Result<AnswerProvider, String> {
+    match llm_router::global() {
+        Some(_) => Ok(AnswerProvider::Routed("answer".to_string())),
+        None => build_llm_provider(LLMConfig::from_hardware(&param_hardware::global_config()))
+            .map(|provider| AnswerProvider::Fixed(Arc::from(provider)))
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::llm_settings::LlmConfig;
+    use crate::memory::{ChatMessage, LLMError, LLMProvider, Tool};
+
+    /// Replies in order
+    struct Scripted {
+        replies: Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for Scripted {
+        async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+            self.chat(&[ChatMessage::user(prompt)], &LlmConfig::default())
+                .await
+        }
+        async fn generate_with_config(
+            &self,
+            prompt: &str,
+            config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.chat(&[ChatMessage::user(prompt)], config).await
+        }
+        async fn chat(
+            &self,
+            _messages: &[ChatMessage],
+            _config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.replies
+                .lock()
+                .unwrap()
+                .pop()
+                .map(str::to_string)
+                .ok_or_else(|| LLMError::GenerationFailed("script exhausted".into()))
+        }
+        fn model_name(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    fn state_config(dir: &std::path::Path) -> AppStateConfig {
+        AppStateConfig {
+            vector_store_path: dir.join("memory"),
+            max_vectors: 1000,
+            agent_db_path: dir.join("agent_memory.db"),
+            agent_vector_path: dir.join("agent_episodes"),
+            agent_id: "test".to_string(),
+            agent_name: "test".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_indexed_file_is_found_by_the_decision_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let retriever = Retriever::new_with_vector_file(
+            dir.path().join("index").to_str().unwrap(),
+            dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        let index = Arc::new(Mutex::new(retriever));
+        let replies = [
+            r#"{"thought": "Look it up", "action": "semantic_search", "input": {"query": "When does the Lisbon office open?"}}"#,
+            r#"{"thought": "Done", "action": "final_answer", "input": {"answer": "At 8am [1]."}}"#,
+            r#"{"thought": "Look it up", "action": "semantic_search", "input": {"query": "When does the Lisbon office open?"}}"#,
+            r#"{"thought": "Done", "action": "final_answer", "input": {"answer": "Unknown."}}"#,
+        ];
+        let provider: Arc<dyn LLMProvider> = Arc::new(Scripted {
+            replies: Mutex::new(replies.iter().rev().copied().collect()),
+        });
+        let state = AppState::build_with(
+            &state_config(dir.path()),
+            Some(Arc::clone(&index)),
+            Ok(provider.into()),
+        )
+        .unwrap();
+
+        // Uploads go through the main index only
+        let upload = dir.path().join("offices.txt");
+        std::fs::write(&upload, "The Lisbon office opens at 8am on weekdays.").unwrap();
+        {
+            let mut retriever = index.lock().unwrap();
+            let chunker = crate::memory::chunker_factory::FixedChunker;
+            let chunks = crate::index::index_file(
+                &mut retriever,
+                &upload,
+                crate::config::ChunkerMode::Fixed,
+                &chunker,
+            )
+            .unwrap();
+            assert_eq!(chunks, 1);
+            retriever.commit().unwrap();
+        }
+
+        let engine = state.decision_engine.as_ref().unwrap();
+        let query = "When does the Lisbon office open?";
+        let result = engine.execute_query(query, None).await.unwrap();
+        assert_eq!(result.steps[0].tool, Tool::SemanticSearch);
+        assert!(result.steps[0]
+            .observation
+            .contains("The Lisbon office opens at 8am"));
+        assert_eq!(result.answer, "At 8am [1].");
+
+        // Deleting the document from the index removes it from retrieval
+        {
+            let mut retriever = index.lock().unwrap();
+            let ids = retriever.chunk_ids_with_prefix("offices.txt#");
+            retriever.delete_chunks(&ids).unwrap();
+        }
+        let result = engine.execute_query(query, None).await.unwrap();
+        assert!(!result.steps[0].observation.contains("Lisbon"));
+    }
+
+    #[tokio::test]
+    async fn test_ready_without_an_llm_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::build_with(
+            &state_config(dir.path()),
+            None,
+            Err("No API key configured".to_string()),
+        )
+        .unwrap();
+
+        let health = state.health().await;
+        assert!(health.ready);
+        assert_eq!(health.components["vector_store"]["status"], "healthy");
+        assert_eq!(health.components["rag_pipeline"]["status"], "degraded");
+        assert_eq!(health.components["decision_engine"]["status"], "degraded");
+    }
+}
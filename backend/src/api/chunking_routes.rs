@@ -0,0 +1,274 @@
+// src/api/chunking_routes.rs
+// Chunking preview: run one or more chunker configs over a document without
+// touching the index, so sizes and thresholds can be compared before they
+// are committed via /config/chunk_size
+
+use super::{
+    generate_request_id, validate_chunk_request, ChunkConfigCommitRequest, ChunkerConfigSnapshot,
+    UPLOAD_DIR,
+};
+use crate::config::{ApiConfig, ChunkerMode};
+use crate::db::chunk_settings;
+use crate::memory::chunker::ChunkerConfig;
+use crate::memory::chunker_factory::{create_chunker, Boundary, ChunkingStats};
+use crate::memory::tokenizer;
+use actix_web::{web, Error, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+
+const MAX_PREVIEW_CONFIGS: usize = 8;
+
+#[derive(Debug, Deserialize)]
+pub struct ChunkingPreviewRequest {
+    /// Document text to chunk
+    pub text: Option<String>,
+    /// Or the name of an uploaded document
+    pub file: Option<String>,
+    /// Configs to compare (default: the active mode and chunk settings)
+    #[serde(default)]
+    pub configs: Vec<PreviewConfig>,
+}
+
+/// A chunker mode plus overrides of the committed chunk settings
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PreviewConfig {
+    /// "fixed", "lightweight" or "semantic" (default: the active mode)
+    pub mode: Option<String>,
+    pub label: Option<String>,
+    pub target_size: Option<usize>,
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+    pub overlap: Option<usize>,
+    pub semantic_similarity_threshold: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct PreviewChunk {
+    index: usize,
+    text: String,
+    tokens: usize,
+    boundary: Boundary,
+    table: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct PreviewResult {
+    label: String,
+    mode: String,
+    /// Settings as requested
+    config: ChunkerConfigSnapshot,
+    /// Settings after clamping to the embedding model's input limit
+    effective_config: ChunkerConfigSnapshot,
+    chunk_count: usize,
+    total_tokens: usize,
+    min_tokens: usize,
+    max_tokens: usize,
+    avg_tokens: f32,
+    duration_ms: u128,
+    /// Only the semantic chunker records stats
+    stats: Option<ChunkingStats>,
+    chunks: Vec<PreviewChunk>,
+}
+
+pub fn configure_chunking_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/chunking/preview", web::post().to(chunking_preview));
+}
+
+async fn chunking_preview(
+    config: web::Data<ApiConfig>,
+    payload: web::Json<ChunkingPreviewRequest>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let body = payload.into_inner();
+
+    let (source, text) = match load_source(&body) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "status": "invalid",
+                "message": message,
+                "request_id": request_id
+            })));
+        }
+    };
+
+    let configs = if body.configs.is_empty() {
+        vec![PreviewConfig::default()]
+    } else {
+        body.configs
+    };
+    if configs.len() > MAX_PREVIEW_CONFIGS {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "status": "invalid",
+            "message": format!("at most {} configs per preview", MAX_PREVIEW_CONFIGS),
+            "request_id": request_id
+        })));
+    }
+
+    let base = chunk_settings::global_config();
+    let mut resolved = Vec::with_capacity(configs.len());
+    for (i, preview) in configs.iter().enumerate() {
+        match resolve_config(preview, &base, config.chunker_mode) {
+            Ok((mode, chunker_config)) => {
+                let label = preview
+                    .label
+                    .clone()
+                    .unwrap_or_else(|| format!("{}-{}", mode_name(mode), i + 1));
+                resolved.push((label, mode, chunker_config));
+            }
+            Err(message) => {
+                return Ok(HttpResponse::BadRequest().json(json!({
+                    "status": "invalid",
+                    "message": format!("configs[{}]: {}", i, message),
+                    "request_id": request_id
+                })));
+            }
+        }
+    }
+
+    // The semantic chunker embeds every segment, so keep it off the executor
+    let chars = text.chars().count();
+    let results = web::block(move || {
+        resolved
+            .into_iter()
+            .map(|(label, mode, chunker_config)| run_preview(label, mode, &chunker_config, &text))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    tracing::info!(
+        request_id = %request_id,
+        source = %source,
+        chars,
+        configs = results.len(),
+        "Chunking preview"
+    );
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "request_id": request_id,
+        "source": source,
+        "chars": chars,
+        "tokenizer": tokenizer::embedding_tokenizer().name(),
+        "results": results
+    })))
+}
+
+fn load_source(body: &ChunkingPreviewRequest) -> Result<(String, String), String> {
+    match (&body.text, &body.file) {
+        (Some(text), None) => {
+            if text.trim().is_empty() {
+                Err("text is empty".into())
+            } else {
+                Ok(("text".into(), text.clone()))
+            }
+        }
+        (None, Some(file)) => {
+            if file.is_empty() || file.contains(['/', '\\']) || file.contains("..") {
+                return Err("file must be the name of an uploaded document".into());
+            }
+            let path = Path::new(UPLOAD_DIR).join(file);
+            if !path.is_file() {
+                return Err(format!("document not found: {}", file));
+            }
+            crate::index::extract_text(&path)
+                .map(|text| (file.clone(), text))
+                .ok_or_else(|| format!("cannot extract text from {}", file))
+        }
+        _ => Err("provide exactly one of text or file".into()),
+    }
+}
+
+fn resolve_config(
+    preview: &PreviewConfig,
+    base: &ChunkerConfig,
+    default_mode: ChunkerMode,
+) -> Result<(ChunkerMode, ChunkerConfig), String> {
+    let mode = match &preview.mode {
+        Some(raw) => raw.parse()?,
+        None => default_mode,
+    };
+    let request = ChunkConfigCommitRequest {
+        target_size: preview.target_size.unwrap_or(base.target_size),
+        min_size: preview.min_size.unwrap_or(base.min_size),
+        max_size: preview.max_size.unwrap_or(base.max_size),
+        overlap: preview.overlap.unwrap_or(base.overlap),
+        semantic_similarity_threshold: Some(
+            preview
+                .semantic_similarity_threshold
+                .unwrap_or(base.semantic_similarity_threshold),
+        ),
+    };
+    validate_chunk_request(&request)?;
+    Ok((
+        mode,
+        ChunkerConfig {
+            target_size: request.target_size,
+            min_size: request.min_size,
+            max_size: request.max_size,
+            overlap: request.overlap,
+            semantic_similarity_threshold: request
+                .semantic_similarity_threshold
+                .unwrap_or(base.semantic_similarity_threshold),
+        },
+    ))
+}
+
+fn run_preview(
+    label: String,
+    mode: ChunkerMode,
+    chunker_config: &ChunkerConfig,
+    text: &str,
+) -> PreviewResult {
+    let tokenizer = tokenizer::embedding_tokenizer();
+    let effective = chunker_config
+        .clone()
+        .fit_embedding_limit(tokenizer.max_input_tokens());
+
+    let start = std::time::Instant::now();
+    let chunker = create_chunker(mode.into(), chunker_config);
+    let chunks = chunker.chunk_with_metadata(text);
+    let duration_ms = start.elapsed().as_millis();
+
+    let chunks: Vec<PreviewChunk> = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| PreviewChunk {
+            index,
+            tokens: tokenizer.count_tokens(&chunk.text),
+            text: chunk.text,
+            boundary: chunk.boundary,
+            table: chunk.table,
+        })
+        .collect();
+    let total_tokens: usize = chunks.iter().map(|c| c.tokens).sum();
+
+    PreviewResult {
+        label,
+        mode: mode_name(mode).into(),
+        config: ChunkerConfigSnapshot::from(chunker_config),
+        effective_config: ChunkerConfigSnapshot::from(&effective),
+        chunk_count: chunks.len(),
+        total_tokens,
+        min_tokens: chunks.iter().map(|c| c.tokens).min().unwrap_or(0),
+        max_tokens: chunks.iter().map(|c| c.tokens).max().unwrap_or(0),
+        avg_tokens: if chunks.is_empty() {
+            0.0
+        } else {
+            total_tokens as f32 / chunks.len() as f32
+        },
+        duration_ms,
+        stats: chunker.stats(),
+        chunks,
+    }
+}
+
+fn mode_name(mode: ChunkerMode) -> &'static str {
+    match mode {
+        ChunkerMode::Fixed => "fixed",
+        ChunkerMode::Lightweight => "lightweight",
+        ChunkerMode::Semantic => "semantic",
+    }
+}
@@ -134,7 +134,7 @@ pub async fn execute_chain(
 
         // Build formatted query if we have previous result
         let formatted_query = if let Some(prev_result) = &previous_result {
-            let extracted = ResultFormatter::extract_key_data(&prev_result, &current_intent);
+            let extracted = ResultFormatter::extract_key_data(prev_result, &current_intent);
             let formatted = ResultFormatter::build_next_query(
                 &extracted,
                 &step.query,
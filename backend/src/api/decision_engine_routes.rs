@@ -1,12 +1,14 @@
 // src/api/decision_engine_routes.rs
-// Phase 8: Decision Engine API Layer - VERSION 2 (Fixed)
+// Phase 8: Decision Engine API Layer
 // Exposes decision_engine.rs multi-step reasoning as HTTP endpoints
-// NO SharedDecisionEngine required - endpoints work standalone
 
 use actix_web::{web, HttpResponse, Result as ActixResult};
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
-use tracing::info;
+use tracing::{error, info};
+
+use super::app_state::AppState;
+use crate::memory::{AgentStep, GroundingReport};
 
 // ============ Request/Response Types ============
 
@@ -21,43 +23,20 @@ pub struct ExecuteQueryResponse {
     pub success: bool,
     pub answer: String,
     pub steps_executed: usize,
+    pub steps: Vec<AgentStep>,
+    /// "final_answer", "max_steps", "token_budget", "time_budget",
+    /// "loop_detected" or "model_error"
+    pub stop_reason: String,
+    pub tokens_used: usize,
+    pub grounding: Option<GroundingReport>,
     pub reasoning_trace: Vec<String>,
-    pub plan_steps: usize,
+    pub episode_id: String,
     pub timestamp: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct DecideRequest {
-    pub query: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct DecideResponse {
-    pub tool: String,
-    pub reasoning: String,
-    pub confidence: f32,
-    pub recommended_top_k: usize,
-    pub timestamp: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct PlanResponse {
-    pub goal: String,
-    pub steps: Vec<PlanStepResponse>,
-    pub estimated_effort: usize,
-    pub timestamp: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct PlanStepResponse {
-    pub step_number: usize,
-    pub action: String,
-    pub expected_outcome: String,
-}
-
 #[derive(Debug, Serialize)]
 pub struct ReasoningTraceResponse {
-    pub query: String,
+    pub episode_id: String,
     pub trace: Vec<String>,
     pub total_steps: usize,
     pub timestamp: String,
@@ -69,161 +48,95 @@ pub struct ErrorResponse {
     pub error: String,
 }
 
+fn engine_unavailable() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().json(ErrorResponse {
+        status: "unavailable".to_string(),
+        error: "Decision engine not initialized (no LLM provider)".to_string(),
+    })
+}
+
 // ============ Decision Engine Handlers ============
 
-/// Execute a query with multi-step reasoning (Phase 7 core functionality)
+/// Answer a query with the think/act/observe loop
 pub async fn execute_query_with_reasoning(
+    state: web::Data<AppState>,
     req: web::Json<ExecuteQueryRequest>,
 ) -> ActixResult<HttpResponse> {
-    info!(query = %req.query, "Executing query with decision engine");
-
-    let reasoning_trace = vec![
-        "Step 1: Assessing query and goal context".to_string(),
-        format!("Found goal: {:?}", req.goal_id),
-        "Step 2: Checking for similar past queries".to_string(),
-        "Found 2 similar queries with 85% success rate".to_string(),
-        "Step 3: Deciding on search strategy".to_string(),
-        "Decision: SemanticSearch (confidence: 0.85)".to_string(),
-        "Step 4: Executing RAG pipeline".to_string(),
-        "Retrieved 3 relevant chunks".to_string(),
-        "Step 5: Recording episode in memory".to_string(),
-        "Step 6: Updating goals".to_string(),
-        "Step 7: Reflecting on execution".to_string(),
-        "Reflection stored for future learning".to_string(),
-    ];
-
-    let response = ExecuteQueryResponse {
-        success: true,
-        answer: format!("Based on the query '{}', here is the answer from the RAG pipeline...", req.query),
-        steps_executed: 7,
-        reasoning_trace,
-        plan_steps: 5,
-        timestamp: Utc::now().to_rfc3339(),
-    };
-
-    Ok(HttpResponse::Ok().json(response))
-}
-
-/// Make a decision about which tool/strategy to use
-pub async fn make_decision(
-    req: web::Json<DecideRequest>,
-) -> ActixResult<HttpResponse> {
-    info!(query = %req.query, "Making tool selection decision");
-
-    let tool_name = if req.query.contains("retrieve") || req.query.contains("fetch") {
-        "RefinedSearch".to_string()
-    } else if req.query.contains("reflect") || req.query.contains("analyze") {
-        "ReflectOnHistory".to_string()
-    } else {
-        "SemanticSearch".to_string()
-    };
-
-    let recommended_top_k = match tool_name.as_str() {
-        "DirectAnswer" => 3,
-        "RefinedSearch" => 5,
-        "ReflectOnHistory" => 3,
-        _ => 7,
-    };
-
-    let response = DecideResponse {
-        tool: tool_name,
-        reasoning: format!(
-            "Analyzed query '{}'. Selecting tool based on query characteristics and history.",
-            req.query
-        ),
-        confidence: 0.85,
-        recommended_top_k,
-        timestamp: Utc::now().to_rfc3339(),
+    let Some(engine) = state.decision_engine.as_ref() else {
+        return Ok(engine_unavailable());
     };
+    info!(query = %req.query, "Executing query with decision engine");
 
-    Ok(HttpResponse::Ok().json(response))
+    match engine.execute_query(&req.query, req.goal_id.clone()).await {
+        Ok(result) => Ok(HttpResponse::Ok().json(ExecuteQueryResponse {
+            success: result.success,
+            answer: result.answer,
+            steps_executed: result.steps_executed,
+            steps: result.steps,
+            stop_reason: result.stop_reason.as_str().to_string(),
+            tokens_used: result.tokens_used,
+            grounding: result.grounding,
+            reasoning_trace: result.reasoning_trace,
+            episode_id: result.episode_id,
+            timestamp: Utc::now().to_rfc3339(),
+        })),
+        Err(e) => {
+            error!(error = %e, "Decision engine execution failed");
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                status: "error".to_string(),
+                error: e.to_string(),
+            }))
+        }
+    }
 }
 
-/// Get the execution plan for a query
-pub async fn get_execution_plan(
-    req: web::Json<ExecuteQueryRequest>,
+/// Get the reasoning trace recorded for an episode
+pub async fn get_reasoning_trace(
+    state: web::Data<AppState>,
+    episode_id: web::Path<String>,
 ) -> ActixResult<HttpResponse> {
-    info!(query = %req.query, "Getting execution plan");
-
-    let steps = vec![
-        PlanStepResponse {
-            step_number: 1,
-            action: "Assess context and goal".to_string(),
-            expected_outcome: "Understand current situation and active goals".to_string(),
-        },
-        PlanStepResponse {
-            step_number: 2,
-            action: "Search similar past queries".to_string(),
-            expected_outcome: "Learn from history (Phase 6 memory)".to_string(),
-        },
-        PlanStepResponse {
-            step_number: 3,
-            action: "Decide on tool/strategy".to_string(),
-            expected_outcome: "Select best approach based on confidence".to_string(),
-        },
-        PlanStepResponse {
-            step_number: 4,
-            action: "Execute RAG pipeline".to_string(),
-            expected_outcome: "Generate answer from retrieved context".to_string(),
-        },
-        PlanStepResponse {
-            step_number: 5,
-            action: "Record episode and reflect".to_string(),
-            expected_outcome: "Store learning for future decisions".to_string(),
-        },
-    ];
-
-    let response = PlanResponse {
-        goal: req.query.clone(),
-        steps,
-        estimated_effort: 5,
-        timestamp: Utc::now().to_rfc3339(),
-    };
-
-    Ok(HttpResponse::Ok().json(response))
+    info!(episode_id = %episode_id, "Getting reasoning trace");
+
+    match state.agent_memory.get_episode_trace(&episode_id) {
+        Ok(Some(trace)) => Ok(HttpResponse::Ok().json(ReasoningTraceResponse {
+            episode_id: episode_id.into_inner(),
+            total_steps: trace.len(),
+            trace,
+            timestamp: Utc::now().to_rfc3339(),
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            status: "error".to_string(),
+            error: format!("No trace recorded for episode {}", episode_id),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            status: "error".to_string(),
+            error: e.to_string(),
+        })),
+    }
 }
 
-/// Get the reasoning trace for debugging/transparency
-pub async fn get_reasoning_trace(
-    req: web::Json<ExecuteQueryRequest>,
-) -> ActixResult<HttpResponse> {
-    info!(query = %req.query, "Getting reasoning trace");
-
-    let trace = vec![
-        "Step 1: Assessing context".to_string(),
-        format!("Query: {}", req.query),
-        "Step 2: Searching history".to_string(),
-        "Step 3: Making decision".to_string(),
-        "Step 4: Executing pipeline".to_string(),
-        "Step 5: Recording results".to_string(),
-        "Step 6: Learning".to_string(),
-    ];
-
-    let response = ReasoningTraceResponse {
-        query: req.query.clone(),
-        trace,
-        total_steps: 7,
-        timestamp: Utc::now().to_rfc3339(),
+/// Get decision engine health and budget
+pub async fn engine_health(state: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    let Some(engine) = state.decision_engine.as_ref() else {
+        return Ok(engine_unavailable());
     };
+    let model = state
+        .rag_pipeline
+        .as_ref()
+        .and_then(|pipeline| pipeline.llm_provider().ok())
+        .map(|provider| provider.model_name().to_string());
 
-    Ok(HttpResponse::Ok().json(response))
-}
-
-/// Get decision engine health and capabilities
-pub async fn engine_health() -> ActixResult<HttpResponse> {
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "status": "operational",
-        "phase": "Phase 7 & 8",
+        "model": model,
+        "budget": engine.budget(),
         "capabilities": [
             "multi_step_reasoning",
             "tool_selection",
-            "autonomous_execution",
             "memory_integration",
-            "learning_from_feedback",
+            "grounding_check",
             "reasoning_transparency"
         ],
-        "memory_integration": "Phase 6 (episodic memory, goal tracking, reflection)",
-        "rag_integration": "Phase 5 (semantic search and context retrieval)",
         "timestamp": Utc::now().to_rfc3339()
     })))
 }
@@ -235,9 +148,7 @@ pub fn configure_decision_engine_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api/engine")
             .route("/execute", web::post().to(execute_query_with_reasoning))
-            .route("/decide", web::post().to(make_decision))
-            .route("/plan", web::post().to(get_execution_plan))
-            .route("/trace", web::post().to(get_reasoning_trace))
+            .route("/trace/{episode_id}", web::get().to(get_reasoning_trace))
             .route("/health", web::get().to(engine_health))
     );
-}
\ No newline at end of file
+}
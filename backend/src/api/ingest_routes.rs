@@ -0,0 +1,822 @@
+// src/api/ingest_routes.rs
+// Endpoints for ingestion sources beyond the document folder
+
+use super::{
+    generate_request_id, get_jobs_map, is_reindex_in_progress, reindex_status_handler, AsyncJob,
+    REINDEX_IN_PROGRESS, RETRIEVER,
+};
+use crate::config::ApiConfig;
+use crate::ingest::code::{find_definitions, index_repository, CodeIngestConfig, CodeLanguage};
+use crate::ingest::crawler::{self, CrawlConfig};
+use crate::ingest::email::{self, EmailFilter};
+use crate::ingest::feeds::{self, FeedError, NewSubscription, SubscriptionUpdate};
+use crate::ingest::open_db;
+use crate::ingest::structured::{self, StructuredIngestConfig};
+use actix_web::{http::StatusCode, web, Error, HttpResponse};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CodeIngestRequest {
+    /// Local path of the repository checkout
+    pub path: String,
+    pub max_tokens: Option<usize>,
+    /// Restrict to these languages ("rust", "python", "typescript", "go")
+    #[serde(default)]
+    pub languages: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailIngestRequest {
+    /// An .eml/.mbox file or a directory containing them
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailSearchQuery {
+    pub q: String,
+    #[serde(flatten)]
+    pub filter: EmailFilter,
+    pub limit: Option<usize>,
+    /// Neighbouring thread messages to include on each side of a hit
+    pub context: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StructuredIngestRequest {
+    /// A .csv, .tsv, .json or .jsonl file
+    pub path: String,
+    #[serde(flatten)]
+    pub config: StructuredIngestConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StructuredSearchRequest {
+    pub q: String,
+    pub dataset: Option<String>,
+    /// Exact (case-insensitive) matches on metadata columns
+    #[serde(default)]
+    pub filters: HashMap<String, String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeedEntriesQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DefinitionQuery {
+    pub symbol: String,
+    pub limit: Option<usize>,
+}
+
+pub fn configure_ingest_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/ingest/code", web::post().to(ingest_code_handler))
+        .route(
+            "/search/definition",
+            web::get().to(search_definition_handler),
+        )
+        .route("/ingest/email", web::post().to(ingest_email_handler))
+        .route("/email/search", web::get().to(search_email_handler))
+        .route(
+            "/email/threads/{thread_id}",
+            web::get().to(email_thread_handler),
+        )
+        .route(
+            "/ingest/structured",
+            web::post().to(ingest_structured_handler),
+        )
+        .route("/structured/datasets", web::get().to(list_datasets_handler))
+        .route(
+            "/structured/datasets/{dataset}",
+            web::get().to(dataset_schema_handler),
+        )
+        .route(
+            "/structured/search",
+            web::post().to(search_structured_handler),
+        )
+        .route("/feeds", web::get().to(list_feeds_handler))
+        .route("/feeds", web::post().to(create_feed_handler))
+        .route("/feeds/{id}", web::get().to(get_feed_handler))
+        .route("/feeds/{id}", web::put().to(update_feed_handler))
+        .route("/feeds/{id}", web::delete().to(delete_feed_handler))
+        .route("/feeds/{id}/poll", web::post().to(poll_feed_handler))
+        .route("/feeds/{id}/entries", web::get().to(feed_entries_handler))
+        .route("/crawl", web::post().to(crawl_handler))
+        .route(
+            "/crawl/status/{job_id}",
+            web::get().to(reindex_status_handler),
+        );
+}
+
+/// POST /ingest/code - index a local source repository
+async fn ingest_code_handler(req: web::Json<CodeIngestRequest>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+
+    if is_reindex_in_progress() {
+        return Ok(HttpResponse::TooManyRequests().json(json!({
+            "status": "busy",
+            "message": "Reindex already in progress",
+            "request_id": request_id
+        })));
+    }
+
+    let mut config = CodeIngestConfig::from_env();
+    if let Some(max_tokens) = req.max_tokens {
+        config.max_tokens = max_tokens.max(16);
+    }
+    for lang in &req.languages {
+        match lang.parse::<CodeLanguage>() {
+            Ok(l) => config.languages.push(l),
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(json!({
+                    "status": "error",
+                    "message": e,
+                    "request_id": request_id
+                })));
+            }
+        }
+    }
+
+    let Some(retriever) = RETRIEVER.get() else {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": "Retriever not initialized",
+            "request_id": request_id
+        })));
+    };
+
+    let root = PathBuf::from(&req.path);
+    if let Err(e) = config.validate_root(&root) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "status": "error",
+            "message": e,
+            "request_id": request_id
+        })));
+    }
+
+    let retriever = retriever.clone();
+    let result = web::block(move || {
+        let mut retriever = retriever.lock().unwrap();
+        index_repository(&mut retriever, &root, &config)
+    })
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    match result {
+        Ok(report) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "report": report,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": format!("Code ingestion failed: {}", e),
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// GET /search/definition?symbol=X - definitions of a symbol with line links
+async fn search_definition_handler(
+    query: web::Query<DefinitionQuery>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let Some(retriever) = RETRIEVER.get() else {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": "Retriever not initialized",
+            "request_id": request_id
+        })));
+    };
+
+    let retriever = retriever.lock().unwrap();
+    match find_definitions(&retriever, &query.symbol, query.limit.unwrap_or(10)) {
+        Ok(definitions) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "symbol": query.symbol,
+            "definitions": definitions,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": e,
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// POST /ingest/email - index an mbox/.eml archive
+async fn ingest_email_handler(
+    req: web::Json<EmailIngestRequest>,
+    config: web::Data<ApiConfig>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+
+    if is_reindex_in_progress() {
+        return Ok(HttpResponse::TooManyRequests().json(json!({
+            "status": "busy",
+            "message": "Reindex already in progress",
+            "request_id": request_id
+        })));
+    }
+
+    let Some(retriever) = RETRIEVER.get() else {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": "Retriever not initialized",
+            "request_id": request_id
+        })));
+    };
+
+    // Only mailboxes below INGEST_ALLOWED_ROOTS; nothing when it is unset
+    let path = match crate::ingest::check_ingest_path(Path::new(&req.path)) {
+        Ok(path) => path,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "status": "error",
+                "message": e,
+                "request_id": request_id
+            })));
+        }
+    };
+
+    let retriever = retriever.clone();
+    let chunker_mode = config.chunker_mode;
+    let result = web::block(move || {
+        let conn = open_db()?;
+        let chunker = crate::index::default_chunker(chunker_mode);
+        let mut retriever = retriever.lock().unwrap();
+        email::index_mailbox(&mut retriever, &conn, &path, chunker.as_ref())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    match result {
+        Ok(report) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "report": report,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": format!("Email ingestion failed: {}", e),
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// GET /email/search?q=...&from=&to=&subject=&since=&until=&thread_id=&context=
+async fn search_email_handler(query: web::Query<EmailSearchQuery>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let Some(retriever) = RETRIEVER.get() else {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": "Retriever not initialized",
+            "request_id": request_id
+        })));
+    };
+
+    let result = open_db().and_then(|conn| {
+        let retriever = retriever.lock().unwrap();
+        email::search_messages(
+            &retriever,
+            &conn,
+            &query.q,
+            &query.filter,
+            query.limit.unwrap_or(10),
+            query.context.unwrap_or(1),
+        )
+        .map_err(|e| e.to_string())
+    });
+
+    match result {
+        Ok(results) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "results": results,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": e,
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// GET /email/threads/{thread_id} - all messages of a thread in date order
+async fn email_thread_handler(thread_id: web::Path<String>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let result = open_db()
+        .and_then(|conn| email::thread_messages(&conn, &thread_id).map_err(|e| e.to_string()));
+
+    match result {
+        Ok(messages) if messages.is_empty() => Ok(HttpResponse::NotFound().json(json!({
+            "status": "error",
+            "message": "Thread not found",
+            "request_id": request_id
+        }))),
+        Ok(messages) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "thread_id": thread_id.as_str(),
+            "messages": messages,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": e,
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// POST /ingest/structured - index a CSV/TSV/JSON/JSONL file row by row
+async fn ingest_structured_handler(
+    req: web::Json<StructuredIngestRequest>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+
+    if is_reindex_in_progress() {
+        return Ok(HttpResponse::TooManyRequests().json(json!({
+            "status": "busy",
+            "message": "Reindex already in progress",
+            "request_id": request_id
+        })));
+    }
+
+    let Some(retriever) = RETRIEVER.get() else {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": "Retriever not initialized",
+            "request_id": request_id
+        })));
+    };
+
+    let path = match crate::ingest::check_ingest_path(Path::new(&req.path)) {
+        Ok(path) if path.is_file() => path,
+        Ok(_) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "status": "error",
+                "message": format!("'{}' is not a file", req.path),
+                "request_id": request_id
+            })));
+        }
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "status": "error",
+                "message": e,
+                "request_id": request_id
+            })));
+        }
+    };
+
+    let retriever = retriever.clone();
+    let config = req.into_inner().config;
+    let result = web::block(move || {
+        let mut conn = open_db()?;
+        let mut retriever = retriever.lock().unwrap();
+        structured::index_structured_file(&mut retriever, &mut conn, &path, &config)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    match result {
+        Ok(report) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "report": report,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": format!("Structured ingestion failed: {}", e),
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// GET /structured/datasets - schemas of all ingested datasets
+async fn list_datasets_handler() -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    match open_db().and_then(|conn| structured::list_datasets(&conn).map_err(|e| e.to_string())) {
+        Ok(datasets) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "datasets": datasets,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": e,
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// GET /structured/datasets/{dataset} - saved schema and source location
+async fn dataset_schema_handler(dataset: web::Path<String>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let result = open_db()
+        .and_then(|conn| structured::load_schema(&conn, &dataset).map_err(|e| e.to_string()));
+
+    match result {
+        Ok(Some(schema)) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "schema": schema,
+            "request_id": request_id
+        }))),
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "status": "error",
+            "message": "Dataset not found",
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": e,
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// POST /structured/search - keyword search over rows with metadata filters
+async fn search_structured_handler(
+    req: web::Json<StructuredSearchRequest>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let Some(retriever) = RETRIEVER.get() else {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": "Retriever not initialized",
+            "request_id": request_id
+        })));
+    };
+
+    let result = open_db().and_then(|conn| {
+        let retriever = retriever.lock().unwrap();
+        structured::search_rows(
+            &retriever,
+            &conn,
+            &req.q,
+            req.dataset.as_deref(),
+            &req.filters,
+            req.limit.unwrap_or(10),
+        )
+        .map_err(|e| e.to_string())
+    });
+
+    match result {
+        Ok(results) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "results": results,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": e,
+            "request_id": request_id
+        }))),
+    }
+}
+
+fn feed_error_response(err: FeedError, request_id: String) -> HttpResponse {
+    let mut response = match err {
+        FeedError::Invalid(_) => HttpResponse::BadRequest(),
+        FeedError::NotFound(_) => HttpResponse::NotFound(),
+        FeedError::Fetch(_) | FeedError::Parse(_) => HttpResponse::BadGateway(),
+        FeedError::Index(_) | FeedError::Database(_) => HttpResponse::InternalServerError(),
+    };
+    response.json(json!({
+        "status": "error",
+        "message": err.to_string(),
+        "request_id": request_id
+    }))
+}
+
+fn feeds_db() -> Result<rusqlite::Connection, FeedError> {
+    open_db().map_err(FeedError::Database)
+}
+
+/// GET /feeds - subscriptions with polling state
+async fn list_feeds_handler() -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    match feeds_db().and_then(|conn| feeds::list_subscriptions(&conn)) {
+        Ok(subscriptions) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "count": subscriptions.len(),
+            "subscriptions": subscriptions,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(feed_error_response(e, request_id)),
+    }
+}
+
+/// POST /feeds - subscribe to an RSS/Atom feed
+async fn create_feed_handler(
+    req: web::Json<NewSubscription>,
+    config: web::Data<ApiConfig>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let hosts = &config.crawl_allowed_hosts;
+    match feeds_db().and_then(|conn| feeds::create_subscription(&conn, &req, hosts)) {
+        Ok(subscription) => Ok(HttpResponse::Created().json(json!({
+            "status": "success",
+            "subscription": subscription,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(feed_error_response(e, request_id)),
+    }
+}
+
+/// GET /feeds/{id}
+async fn get_feed_handler(id: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let id = id.into_inner();
+    let result = feeds_db()
+        .and_then(|conn| feeds::get_subscription(&conn, id)?.ok_or(FeedError::NotFound(id)));
+    match result {
+        Ok(subscription) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "subscription": subscription,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(feed_error_response(e, request_id)),
+    }
+}
+
+/// PUT /feeds/{id} - partial update of a subscription
+async fn update_feed_handler(
+    id: web::Path<i64>,
+    req: web::Json<SubscriptionUpdate>,
+    config: web::Data<ApiConfig>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let hosts = &config.crawl_allowed_hosts;
+    match feeds_db().and_then(|conn| feeds::update_subscription(&conn, *id, &req, hosts)) {
+        Ok(subscription) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "subscription": subscription,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(feed_error_response(e, request_id)),
+    }
+}
+
+/// DELETE /feeds/{id} - unsubscribe and remove its entries from the index
+async fn delete_feed_handler(id: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let Some(retriever) = RETRIEVER.get().map(Arc::clone) else {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": "Retriever not initialized",
+            "request_id": request_id
+        })));
+    };
+    let id = id.into_inner();
+    let result = web::block(move || {
+        let conn = feeds_db()?;
+        let mut retriever = retriever.lock().unwrap();
+        feeds::delete_subscription(&conn, &mut retriever, id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(entries_removed)) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "message": format!("Deleted subscription {}", id),
+            "entries_removed": entries_removed,
+            "request_id": request_id
+        }))),
+        Ok(Err(e)) => Ok(feed_error_response(e, request_id)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": format!("Delete task failed: {}", e),
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// POST /feeds/{id}/poll - poll a subscription now, ignoring its schedule
+async fn poll_feed_handler(
+    id: web::Path<i64>,
+    config: web::Data<ApiConfig>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    if is_reindex_in_progress() {
+        return Ok(HttpResponse::TooManyRequests().json(json!({
+            "status": "busy",
+            "message": "Reindex in progress; try again shortly",
+            "request_id": request_id
+        })));
+    }
+    let Some(retriever) = RETRIEVER.get().map(Arc::clone) else {
+        return Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": "Retriever not initialized",
+            "request_id": request_id
+        })));
+    };
+    let id = id.into_inner();
+    let subscription = match feeds_db()
+        .and_then(|conn| feeds::get_subscription(&conn, id)?.ok_or(FeedError::NotFound(id)))
+    {
+        Ok(subscription) => subscription,
+        Err(e) => return Ok(feed_error_response(e, request_id)),
+    };
+
+    let result = match feeds::FeedFetcher::new(config.crawl_allowed_hosts.clone()) {
+        Ok(fetcher) => {
+            feeds::poll_subscription(&fetcher, &subscription, &retriever, config.chunker_mode).await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(report) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "report": report,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(feed_error_response(e, request_id)),
+    }
+}
+
+/// GET /feeds/{id}/entries - ingested entries, newest first
+async fn feed_entries_handler(
+    id: web::Path<i64>,
+    query: web::Query<FeedEntriesQuery>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let id = id.into_inner();
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let result = feeds_db().and_then(|conn| {
+        if feeds::get_subscription(&conn, id)?.is_none() {
+            return Err(FeedError::NotFound(id));
+        }
+        feeds::list_entries(&conn, id, limit)
+    });
+    match result {
+        Ok(entries) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "count": entries.len(),
+            "entries": entries,
+            "request_id": request_id
+        }))),
+        Err(e) => Ok(feed_error_response(e, request_id)),
+    }
+}
+
+/// POST /crawl - start a background crawl job; poll /crawl/status/{job_id}
+async fn crawl_handler(
+    req: web::Json<CrawlConfig>,
+    config: web::Data<ApiConfig>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+
+    match launch_async_crawl_job(req.into_inner(), config) {
+        Ok(job_id) => Ok(HttpResponse::Accepted().json(json!({
+            "status": "accepted",
+            "job_id": job_id,
+            "request_id": request_id
+        }))),
+        Err((status, message)) => Ok(HttpResponse::build(status).json(json!({
+            "status": if status == StatusCode::TOO_MANY_REQUESTS { "busy" } else { "error" },
+            "message": message,
+            "request_id": request_id
+        }))),
+    }
+}
+
+/// Clears REINDEX_IN_PROGRESS when dropped, so a panicking job releases it
+struct ReindexGuard;
+
+impl Drop for ReindexGuard {
+    fn drop(&mut self) {
+        REINDEX_IN_PROGRESS.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Run a crawl on the async job infrastructure shared with /reindex/async.
+/// The crawl holds the reindex guard since it writes to the same index, so
+/// uploads skip indexing meanwhile. Each page is committed as its own batch
+/// and the vectors are saved once the guard is released.
+fn launch_async_crawl_job(
+    mut crawl_config: CrawlConfig,
+    config: web::Data<ApiConfig>,
+) -> Result<String, (StatusCode, String)> {
+    if crawl_config.seeds.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "At least one seed URL is required".to_string(),
+        ));
+    }
+    if config.crawl_allowed_hosts.is_empty() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Crawling is disabled; set CRAWL_ALLOWED_HOSTS to the hosts it may fetch".to_string(),
+        ));
+    }
+    crawl_config.host_allowlist = config.crawl_allowed_hosts.clone();
+    let Some(retriever) = RETRIEVER.get().map(Arc::clone) else {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Retriever not initialized".to_string(),
+        ));
+    };
+    if REINDEX_IN_PROGRESS
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Reindex already in progress".to_string(),
+        ));
+    }
+    let guard = ReindexGuard;
+
+    let job_id = Uuid::new_v4().to_string();
+    let jobs = get_jobs_map();
+    jobs.lock().unwrap().insert(
+        job_id.clone(),
+        AsyncJob {
+            job_id: job_id.clone(),
+            kind: "crawl".to_string(),
+            status: "running".to_string(),
+            started_at: Utc::now().to_rfc3339(),
+            completed_at: None,
+            vectors_indexed: None,
+            mappings_indexed: None,
+            error: None,
+            progress: None,
+        },
+    );
+
+    let job_id_clone = job_id.clone();
+    let chunker_mode = config.chunker_mode;
+    actix_web::rt::spawn(async move {
+        let update = |f: &dyn Fn(&mut AsyncJob)| {
+            if let Some(job) = jobs.lock().unwrap().get_mut(&job_id_clone) {
+                f(job);
+            }
+        };
+
+        let result = crawler::crawl(
+            &crawl_config,
+            |page| {
+                let retriever = Arc::clone(&retriever);
+                async move {
+                    tokio::task::spawn_blocking(move || {
+                        let chunker = crate::index::default_chunker(chunker_mode);
+                        let mut retriever = retriever.lock().unwrap();
+                        crawler::index_page(&mut retriever, chunker.as_ref(), &page)
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(format!("indexing task failed: {}", e)))
+                }
+            },
+            |progress| {
+                let value = serde_json::to_value(progress).ok();
+                update(&|job| job.progress = value.clone());
+            },
+        )
+        .await
+        .map_err(|e| e.to_string());
+
+        // Page commits skip the live vector save while the guard is held
+        let (save, vectors, mappings) = {
+            let mut retriever = retriever.lock().unwrap();
+            drop(guard);
+            (
+                retriever.force_save(),
+                retriever.metrics.total_vectors,
+                retriever.metrics.total_documents_indexed,
+            )
+        };
+        update(&|job| {
+            job.completed_at = Some(Utc::now().to_rfc3339());
+            job.vectors_indexed = Some(vectors);
+            job.mappings_indexed = Some(mappings);
+            match (&result, &save) {
+                (Ok(report), Ok(())) => {
+                    job.status = "completed".to_string();
+                    job.progress = serde_json::to_value(report).ok();
+                }
+                (Err(e), _) => {
+                    job.status = "failed".to_string();
+                    job.error = Some(e.clone());
+                }
+                (Ok(_), Err(e)) => {
+                    job.status = "failed".to_string();
+                    job.error = Some(format!("saving vectors failed: {}", e));
+                }
+            }
+        });
+    });
+
+    Ok(job_id)
+}
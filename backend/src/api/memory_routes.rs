@@ -3,12 +3,14 @@
 
 use actix_web::{web, HttpResponse, Result as ActixResult};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::info;
 
 use crate::embedder::EmbeddingService;
-use crate::memory::{VectorStore, VectorRecord};
+use crate::memory::{ParentRecord, VectorRecord, VectorStore};
+use crate::security::redaction::{self, RedactionSession};
 
 // Shared state for vector store and embedding service
 pub type SharedVectorStore = Arc<RwLock<VectorStore>>;
@@ -26,6 +28,52 @@ pub struct AddChunkRequest {
     pub source: String,
     #[serde(default)]
     pub embedding: Option<Vec<f32>>,
+    /// Parent section returned as context when this chunk matches
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Store as a parent section (context only, not searchable)
+    #[serde(default)]
+    pub is_parent: bool,
+}
+
+impl AddChunkRequest {
+    /// Parents are context for their children and cannot be dropped, so
+    /// their content is masked like a field
+    fn parent_record(&self, redaction: &mut RedactionSession<'_>) -> ParentRecord {
+        ParentRecord {
+            chunk_id: self.chunk_id.clone(),
+            document_id: self.document_id.clone(),
+            content: redaction.field(&self.content),
+            chunk_index: self.chunk_index,
+            token_count: self.token_count,
+        }
+    }
+
+    /// The chunk with redacted content, or None when the PII policy drops it.
+    /// A supplied embedding is only kept if redaction left the content as is
+    async fn record(
+        &self,
+        redaction: &mut RedactionSession<'_>,
+        embedding_service: &EmbeddingService,
+    ) -> Option<VectorRecord> {
+        let content = redaction.chunk(&self.content)?;
+        let embedding = match &self.embedding {
+            Some(emb) if content == self.content => emb.clone(),
+            _ => embedding_service.embed_text(&content).await,
+        };
+        let mut record = VectorRecord::new(
+            self.chunk_id.clone(),
+            self.document_id.clone(),
+            content,
+            embedding,
+            self.chunk_index,
+            self.token_count,
+            self.source.clone(),
+            chrono::Utc::now().timestamp(),
+        );
+        record.parent_id = self.parent_id.clone();
+        Some(record)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -94,23 +142,25 @@ pub async fn add_chunk(
 ) -> ActixResult<HttpResponse> {
     info!(chunk_id = %req.chunk_id, "Adding chunk to vector store");
 
-    // Generate embedding if not provided
-    let embedding = if let Some(emb) = &req.embedding {
-        emb.clone()
-    } else {
-        embedding_service.embed_text(&req.content).await
-    };
+    let mut redaction = redaction::session(&req.document_id);
+    if req.is_parent {
+        let parent = req.parent_record(&mut redaction);
+        redaction.finish();
+        vector_store.write().await.add_parent(parent);
+        return Ok(HttpResponse::Ok().json(MessageResponse {
+            status: "success".to_string(),
+            message: format!("Parent section {} added", req.chunk_id),
+        }));
+    }
 
-    let record = VectorRecord::new(
-        req.chunk_id.clone(),
-        req.document_id.clone(),
-        req.content.clone(),
-        embedding,
-        req.chunk_index,
-        req.token_count,
-        req.source.clone(),
-        chrono::Utc::now().timestamp(),
-    );
+    let record = req.record(&mut redaction, &embedding_service).await;
+    redaction.finish();
+    let Some(record) = record else {
+        return Ok(HttpResponse::Ok().json(MessageResponse {
+            status: "dropped".to_string(),
+            message: format!("Chunk {} dropped by the PII policy", req.chunk_id),
+        }));
+    };
 
     let mut store = vector_store.write().await;
     match store.add_record(record).await {
@@ -134,34 +184,35 @@ pub async fn add_chunks_batch(
     info!(count = req.chunks.len(), "Adding batch of chunks");
 
     let mut records = Vec::new();
+    let mut parents = Vec::new();
+    // One redaction report per document
+    let mut sessions: BTreeMap<&str, RedactionSession<'static>> = BTreeMap::new();
 
     for chunk_req in &req.chunks {
-        // Generate embedding if not provided
-        let embedding = if let Some(emb) = &chunk_req.embedding {
-            emb.clone()
-        } else {
-            embedding_service.embed_text(&chunk_req.content).await
-        };
-
-        let record = VectorRecord::new(
-            chunk_req.chunk_id.clone(),
-            chunk_req.document_id.clone(),
-            chunk_req.content.clone(),
-            embedding,
-            chunk_req.chunk_index,
-            chunk_req.token_count,
-            chunk_req.source.clone(),
-            chrono::Utc::now().timestamp(),
-        );
-
-        records.push(record);
+        let redaction = sessions
+            .entry(chunk_req.document_id.as_str())
+            .or_insert_with(|| redaction::session(&chunk_req.document_id));
+        if chunk_req.is_parent {
+            parents.push(chunk_req.parent_record(redaction));
+            continue;
+        }
+        if let Some(record) = chunk_req.record(redaction, &embedding_service).await {
+            records.push(record);
+        }
+    }
+    for redaction in sessions.into_values() {
+        redaction.finish();
     }
 
+    let added = parents.len() + records.len();
     let mut store = vector_store.write().await;
+    for parent in parents {
+        store.add_parent(parent);
+    }
     match store.add_records(records).await {
         Ok(()) => Ok(HttpResponse::Ok().json(MessageResponse {
             status: "success".to_string(),
-            message: format!("Added {} chunks", req.chunks.len()),
+            message: format!("Added {} chunks", added),
         })),
         Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
             status: "error".to_string(),
@@ -286,9 +337,7 @@ pub async fn delete_chunk(
 }
 
 /// Get vector store statistics
-pub async fn get_stats(
-    vector_store: web::Data<SharedVectorStore>,
-) -> ActixResult<HttpResponse> {
+pub async fn get_stats(vector_store: web::Data<SharedVectorStore>) -> ActixResult<HttpResponse> {
     info!("Retrieving vector store stats");
 
     let store = vector_store.read().await;
@@ -302,9 +351,7 @@ pub async fn get_stats(
 }
 
 /// Clear all records (use with caution!)
-pub async fn clear_store(
-    vector_store: web::Data<SharedVectorStore>,
-) -> ActixResult<HttpResponse> {
+pub async fn clear_store(vector_store: web::Data<SharedVectorStore>) -> ActixResult<HttpResponse> {
     info!("⚠️  Clearing vector store");
 
     let mut store = vector_store.write().await;
@@ -333,4 +380,20 @@ pub async fn memory_health(
         "total_documents": stats.total_documents,
         "timestamp": chrono::Utc::now().to_rfc3339()
     })))
-}
\ No newline at end of file
+}
+
+// ============ Route Configuration ============
+
+pub fn configure_memory_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/memory")
+            .route("/chunks", web::post().to(add_chunk))
+            .route("/chunks/batch", web::post().to(add_chunks_batch))
+            .route("/chunks", web::delete().to(delete_chunk))
+            .route("/search", web::post().to(search_chunks))
+            .route("/documents/{document_id}", web::get().to(get_document_chunks))
+            .route("/stats", web::get().to(get_stats))
+            .route("/clear", web::post().to(clear_store))
+            .route("/health", web::get().to(memory_health))
+    );
+}
@@ -14,6 +14,7 @@ use actix_cors::Cors;
 use actix_multipart::Multipart;
 use actix_web::{http::StatusCode, web, App, Error, HttpResponse, HttpServer};
 use chrono::Utc;
+use futures_util::future::Either;
 use futures_util::stream::StreamExt;
 use serde::Serialize;
 use serde_json::{json, Value};
@@ -41,12 +42,14 @@ pub fn is_reindex_in_progress() -> bool {
 #[derive(Clone, Debug, serde::Serialize)]
 struct AsyncJob {
     job_id: String,
+    kind: String,   // "reindex", "crawl"
     status: String, // "pending", "running", "completed", "failed"
     started_at: String,
     completed_at: Option<String>,
     vectors_indexed: Option<usize>,
     mappings_indexed: Option<usize>,
     error: Option<String>,
+    progress: Option<serde_json::Value>,
 }
 
 static ASYNC_JOBS: OnceLock<Arc<Mutex<HashMap<String, AsyncJob>>>> = OnceLock::new();
@@ -513,8 +516,9 @@ fn validate_hardware_request(req: &HardwareConfigRequest) -> Result<(), String>
     Ok(())
 }
 
-pub async fn health_check() -> Result<HttpResponse, Error> {
+pub async fn health_check(state: web::Data<app_state::AppState>) -> Result<HttpResponse, Error> {
     let request_id = generate_request_id();
+    let subsystems = state.health().await.components;
     if let Some(retriever) = RETRIEVER.get() {
         let retriever = retriever.lock().unwrap();
         match retriever.health_check() {
@@ -523,6 +527,7 @@ pub async fn health_check() -> Result<HttpResponse, Error> {
                 "documents": retriever.metrics.total_documents_indexed,
                 "vectors": retriever.metrics.total_vectors,
                 "index_path": retriever.metrics.index_path,
+                "subsystems": subsystems,
                 "request_id": request_id
             }))),
             Err(e) => {
@@ -530,6 +535,7 @@ pub async fn health_check() -> Result<HttpResponse, Error> {
                 Ok(HttpResponse::ServiceUnavailable().json(json!({
                     "status": "unhealthy",
                     "error": e.to_string(),
+                    "subsystems": subsystems,
                     "request_id": request_id
                 })))
             }
@@ -542,6 +548,7 @@ pub async fn health_check() -> Result<HttpResponse, Error> {
         Ok(HttpResponse::ServiceUnavailable().json(json!({
             "status": "unhealthy",
             "error": "Retriever not initialized",
+            "subsystems": subsystems,
             "request_id": request_id
         })))
     }
@@ -553,19 +560,29 @@ async fn root_handler() -> Result<HttpResponse, Error> {
         .body("✅ Backend is running (Actix Web)\n\nTry /health or /ready\n"))
 }
 
-async fn ready_check() -> Result<HttpResponse, Error> {
+async fn ready_check(state: web::Data<app_state::AppState>) -> Result<HttpResponse, Error> {
     let request_id = generate_request_id();
+    let health = state.health().await;
     if let Some(retriever) = RETRIEVER.get() {
         match retriever.lock() {
             Ok(retriever) => match retriever.ready_check() {
-                Ok(_) => Ok(HttpResponse::Ok().json(json!({
+                Ok(_) if health.ready => Ok(HttpResponse::Ok().json(json!({
                     "status": "ready",
+                    "subsystems": health.components,
+                    "timestamp": Utc::now().to_rfc3339(),
+                    "request_id": request_id
+                }))),
+                Ok(_) => Ok(HttpResponse::ServiceUnavailable().json(json!({
+                    "status": "not ready",
+                    "error": "One or more subsystems unavailable",
+                    "subsystems": health.components,
                     "timestamp": Utc::now().to_rfc3339(),
                     "request_id": request_id
                 }))),
                 Err(e) => Ok(HttpResponse::ServiceUnavailable().json(json!({
                     "status": "not ready",
                     "error": e.to_string(),
+                    "subsystems": health.components,
                     "timestamp": Utc::now().to_rfc3339(),
                     "request_id": request_id
                 }))),
@@ -851,6 +868,12 @@ async fn commit_hardware_config(
     let params = crate::db::param_hardware::HardwareParams::from(body.clone());
     match crate::db::param_hardware::save_default_db(&params) {
         Ok(_) => {
+            // Model/context changes affect chunk sizes and prompt budgets
+            crate::memory::tokenizer::load_active(&params);
+            // The configured backend is the router's primary route
+            if let Err(e) = crate::memory::llm_router::reload() {
+                tracing::warn!(request_id = %request_id, "LLM router not reloaded: {}", e);
+            }
             tracing::info!(
                 request_id = %request_id,
                 num_thread = params.num_thread,
@@ -1054,6 +1077,22 @@ async fn delete_api_key(path: web::Path<String>) -> Result<HttpResponse, Error>
     }
 }
 
+async fn get_llm_routes() -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    match crate::memory::llm_router::global() {
+        Some(router) => Ok(HttpResponse::Ok().json(json!({
+            "status": "ok",
+            "router": router.snapshot(),
+            "request_id": request_id,
+        }))),
+        None => Ok(HttpResponse::ServiceUnavailable().json(json!({
+            "status": "unavailable",
+            "error": "LLM router not initialized",
+            "request_id": request_id,
+        }))),
+    }
+}
+
 async fn get_cache_monitor_info() -> Result<HttpResponse, Error> {
     let request_id = generate_request_id();
     let retriever = match RETRIEVER.get() {
@@ -1254,41 +1293,18 @@ async fn upload_document_inner(
                 "error": "Reindex already in progress; automatic indexing skipped",
             }));
         } else if let Some(handle) = RETRIEVER.get() {
-            match handle.lock() {
-                Ok(mut retriever) => {
-                    let chunker = crate::index::default_chunker(config.chunker_mode);
-                    let chunker_ref = chunker.as_ref();
-                    for filename in &uploaded_files {
-                        let path = Path::new(UPLOAD_DIR).join(filename);
-                        match index::index_file(
-                            &mut *retriever,
-                            &path,
-                            config.chunker_mode,
-                            chunker_ref,
-                        ) {
-                            Ok(chunks) => indexed_files.push(json!({
-                                "file": filename,
-                                "chunks_indexed": chunks,
-                            })),
-                            Err(err) => index_errors.push(json!({
-                                "file": filename,
-                                "error": err,
-                            })),
-                        }
-                    }
-                    if let Err(err) = retriever.commit() {
-                        index_errors.push(json!({
-                            "file": null,
-                            "error": format!("commit failed: {}", err),
-                        }));
-                    }
-                }
-                Err(_) => {
-                    index_errors.push(json!({
-                        "file": null,
-                        "error": "Failed to lock retriever for indexing",
-                    }));
+            // Extraction, summaries and embeddings block, so they run off the worker
+            let files = uploaded_files.clone();
+            let chunker_mode = config.chunker_mode;
+            match web::block(move || index_uploads(handle, &files, chunker_mode)).await {
+                Ok((indexed, errors)) => {
+                    indexed_files = indexed;
+                    index_errors.extend(errors);
                 }
+                Err(err) => index_errors.push(json!({
+                    "file": null,
+                    "error": format!("indexing task failed: {}", err),
+                })),
             }
         } else {
             index_errors.push(json!({
@@ -1307,6 +1323,129 @@ async fn upload_document_inner(
     })))
 }
 
+/// Index uploaded files and commit, returning (indexed files, errors).
+/// Blocking: the retriever lock is held for the whole batch.
+fn index_uploads(
+    handle: &Mutex<Retriever>,
+    uploaded_files: &[String],
+    chunker_mode: crate::config::ChunkerMode,
+) -> (Vec<Value>, Vec<Value>) {
+    let mut indexed_files = Vec::new();
+    let mut index_errors = Vec::new();
+    let Ok(mut retriever) = handle.lock() else {
+        index_errors.push(json!({
+            "file": null,
+            "error": "Failed to lock retriever for indexing",
+        }));
+        return (indexed_files, index_errors);
+    };
+    let chunker = crate::index::default_chunker(chunker_mode);
+    for filename in uploaded_files {
+        let path = Path::new(UPLOAD_DIR).join(filename);
+        match index::index_file(&mut retriever, &path, chunker_mode, chunker.as_ref()) {
+            Ok(chunks) => indexed_files.push(json!({
+                "file": filename,
+                "chunks_indexed": chunks,
+                "duplicate": duplicate_record(filename),
+            })),
+            Err(err) => index_errors.push(json!({
+                "file": filename,
+                "error": err,
+            })),
+        }
+    }
+    if let Err(err) = retriever.commit() {
+        index_errors.push(json!({
+            "file": null,
+            "error": format!("commit failed: {}", err),
+        }));
+    }
+    (indexed_files, index_errors)
+}
+
+/// Dedup record of a just-indexed file, if it matched an earlier document
+fn duplicate_record(filename: &str) -> Option<crate::ingest::dedup::DocumentRecord> {
+    let conn = crate::ingest::open_db().ok()?;
+    crate::ingest::dedup::get_document(&conn, filename)
+        .ok()
+        .flatten()
+        .filter(|record| record.duplicate_of.is_some())
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DuplicatesQuery {
+    pub limit: Option<usize>,
+}
+
+pub async fn list_duplicates(query: web::Query<DuplicatesQuery>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let config = crate::ingest::DedupConfig::global();
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let result = crate::ingest::open_db().and_then(|conn| {
+        crate::ingest::dedup::duplicate_report(&conn, config.hit_distance, limit)
+            .map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(report) => Ok(HttpResponse::Ok().json(json!({
+            "status": "ok",
+            "policy": config.policy,
+            "threshold": config.threshold,
+            "report": report,
+            "request_id": request_id
+        }))),
+        Err(err) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": format!("Failed to build duplicate report: {}", err),
+            "request_id": request_id
+        }))),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct RedactionsQuery {
+    /// Report for one document
+    pub document: Option<String>,
+    /// Include documents without findings
+    #[serde(default)]
+    pub all: bool,
+    pub limit: Option<usize>,
+}
+
+pub async fn list_redactions(query: web::Query<RedactionsQuery>) -> Result<HttpResponse, Error> {
+    use crate::security::redaction;
+    let request_id = generate_request_id();
+    let policy = redaction::active().map(|r| r.policy());
+    let conn = match crate::ingest::open_db() {
+        Ok(conn) => conn,
+        Err(err) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "status": "error",
+                "message": format!("Failed to open database: {}", err),
+                "request_id": request_id
+            })));
+        }
+    };
+    let result = match &query.document {
+        Some(document) => redaction::get_report(&conn, document).map(|r| r.into_iter().collect()),
+        None => {
+            redaction::list_reports(&conn, !query.all, query.limit.unwrap_or(100).clamp(1, 1000))
+        }
+    };
+    match result {
+        Ok(reports) => Ok(HttpResponse::Ok().json(json!({
+            "status": "ok",
+            "policy": policy.unwrap_or(redaction::RedactionPolicy::Off),
+            "reports": reports,
+            "request_id": request_id
+        }))),
+        Err(err) => Ok(HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": format!("Failed to read redaction reports: {}", err),
+            "request_id": request_id
+        }))),
+    }
+}
+
 pub async fn list_documents() -> Result<HttpResponse, Error> {
     let request_id = generate_request_id();
     let mut files = Vec::new();
@@ -1332,11 +1471,21 @@ pub async fn delete_document(path: web::Path<String>) -> Result<HttpResponse, Er
     let filename = path.into_inner();
     let filepath = format!("{}/{}", UPLOAD_DIR, filename);
     match fs::remove_file(&filepath) {
-        Ok(_) => Ok(HttpResponse::Ok().json(json!({
-            "status": "success",
-            "message": format!("Deleted {}", filename),
-            "request_id": request_id
-        }))),
+        Ok(_) => {
+            if let Ok(conn) = crate::ingest::open_db() {
+                if let Err(err) = crate::security::redaction::delete_report(&conn, &filename) {
+                    tracing::warn!(request_id = %request_id, error = %err, "Failed to drop redaction report");
+                }
+                if let Err(err) = crate::ingest::dedup::forget_document(&conn, &filename) {
+                    tracing::warn!(request_id = %request_id, error = %err, "Failed to drop fingerprints");
+                }
+            }
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "message": format!("Deleted {}", filename),
+                "request_id": request_id
+            })))
+        }
         Err(_) => Ok(HttpResponse::NotFound().json(json!({
             "status": "error",
             "message": "File not found",
@@ -1437,12 +1586,14 @@ fn launch_async_reindex_job(config: web::Data<ApiConfig>) -> Result<String, (Sta
     let job_id = Uuid::new_v4().to_string();
     let job = AsyncJob {
         job_id: job_id.clone(),
+        kind: "reindex".to_string(),
         status: "pending".to_string(),
         started_at: Utc::now().to_rfc3339(),
         completed_at: None,
         vectors_indexed: None,
         mappings_indexed: None,
         error: None,
+        progress: None,
     };
 
     let jobs = get_jobs_map();
@@ -1566,11 +1717,13 @@ pub async fn reindex_status_handler(path: web::Path<String>) -> Result<HttpRespo
         Ok(HttpResponse::Ok().json(json!({
             "status": job.status,
             "job_id": job.job_id,
+            "kind": job.kind,
             "started_at": job.started_at,
             "completed_at": job.completed_at,
             "vectors_indexed": job.vectors_indexed,
             "mappings_indexed": job.mappings_indexed,
             "error": job.error,
+            "progress": job.progress,
             "request_id": request_id
         })))
     } else {
@@ -1601,6 +1754,7 @@ pub async fn index_info_handler() -> Result<HttpResponse, Error> {
             },
             "total_documents": retriever.metrics.total_documents_indexed,
             "total_vectors": retriever.metrics.total_vectors,
+            "deleted_vector_slots": retriever.metrics.deleted_vector_slots,
             "request_id": request_id
         })))
     } else {
@@ -1617,9 +1771,13 @@ async fn search_documents_inner(query: web::Query<SearchQuery>) -> Result<HttpRe
     if let Some(retriever) = RETRIEVER.get() {
         let mut retriever = retriever.lock().unwrap();
         let results = retriever.search(&query.q).unwrap_or_default();
+        let definitions = crate::ingest::parse_definition_query(&query.q)
+            .map(|symbol| crate::ingest::find_definitions(&retriever, &symbol, 5))
+            .and_then(Result::ok);
         Ok(HttpResponse::Ok().json(json!({
             "status": "success",
             "results": results,
+            "definitions": definitions,
             "request_id": request_id
         })))
     } else {
@@ -1911,7 +2069,18 @@ fn parse_log_line(line: &str) -> LogEntry {
     }
 }
 
+pub mod agent_routes;
+pub mod app_state;
+pub mod chunking_routes;
+pub mod composer_routes;
+pub mod decision_engine_routes;
+pub mod ingest_routes;
+pub mod memory_routes;
+pub mod prompt_routes;
+pub mod stream_routes;
+pub mod structured_routes;
 pub mod sys_routes;
+pub mod tool_routes;
 
 pub fn start_api_server(
     config: &ApiConfig,
@@ -1935,9 +2104,22 @@ pub fn start_api_server(
     let force_single_worker = std::env::var("NO_DOTENV")
         .map(|v| v.to_lowercase() == "true" || v == "1")
         .unwrap_or(false);
+    let app_state = match app_state::AppState::build(
+        &app_state::AppStateConfig::from_env(config),
+        RETRIEVER.get().cloned(),
+    ) {
+        Ok(state) => state,
+        Err(e) => {
+            return Either::Left(std::future::ready(Err(std::io::Error::other(format!(
+                "Failed to initialize application state: {}",
+                e
+            )))))
+        }
+    };
     let api_config = config.clone();
     let mut http_server = HttpServer::new(move || {
         let api_config = api_config.clone();
+        let app_state = app_state.clone();
         // Shared RateLimiter across workers (middleware-only enforcement)
         let rl_cfg = crate::security::rate_limiter::RateLimiterConfig {
             enabled: rate_limit_enabled,
@@ -2032,6 +2214,8 @@ pub fn start_api_server(
             .route("/", web::get().to(root_handler))
             .route("/upload", web::post().to(upload_document_inner))
             .route("/documents", web::get().to(list_documents))
+            .route("/documents/duplicates", web::get().to(list_duplicates))
+            .route("/documents/redactions", web::get().to(list_redactions))
             .route("/documents/{filename}", web::delete().to(delete_document))
             .route("/config/chunk_size", web::post().to(commit_chunk_config))
             .route("/config/llm", web::get().to(get_llm_config))
@@ -2056,6 +2240,7 @@ pub fn start_api_server(
             .route("/summarize", web::post().to(summarize))
             .route("/save_vectors", web::post().to(save_vectors_handler))
             .route("/monitor/cache/info", web::get().to(get_cache_monitor_info))
+            .route("/monitor/llm/routes", web::get().to(get_llm_routes))
             .route(
                 "/monitor/rate_limits/info",
                 web::get().to(get_rate_limit_monitor_info),
@@ -2076,13 +2261,21 @@ pub fn start_api_server(
             // ============================================================================
             .route("/agent", web::post().to(run_agent))
             .route("/agent/chat", web::get().to(run_agent_get))
+            .configure(chunking_routes::configure_chunking_routes)
+            .configure(ingest_routes::configure_ingest_routes)
+            .configure(prompt_routes::configure_prompt_routes)
+            .configure(stream_routes::configure_stream_routes)
+            .configure(structured_routes::configure_structured_routes)
+            .configure(|cfg| app_state.configure(cfg))
             .service(web::scope("/sys").configure(sys_routes::sys_routes))
     });
     if force_single_worker {
         http_server = http_server.workers(1);
     }
-    http_server
-        .bind(bind_addr.clone())
-        .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", bind_addr, e))
-        .run()
+    Either::Right(
+        http_server
+            .bind(bind_addr.clone())
+            .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", bind_addr, e))
+            .run(),
+    )
 }
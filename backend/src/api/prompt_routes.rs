@@ -0,0 +1,144 @@
+// src/api/prompt_routes.rs
+// Prompt template registry: list, update (new version, per-model override
+// or rollback) and preview-render with sample variables.
+
+use super::generate_request_id;
+use crate::db::prompt_templates::{
+    self, placeholders, render_text, PromptTemplateError, PromptUpdate,
+};
+use actix_web::{web, Error, HttpResponse};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PreviewRequest {
+    pub name: String,
+    /// Render as this model would see it (per-model overrides)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Stored version to render; default is the active one
+    #[serde(default)]
+    pub version: Option<u32>,
+    /// Unsaved draft to render and validate instead of a stored version
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+}
+
+pub fn configure_prompt_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/config/prompts", web::get().to(get_prompts))
+        .route("/config/prompts", web::post().to(commit_prompt))
+        .route("/config/prompts/preview", web::post().to(preview_prompt));
+}
+
+async fn get_prompts() -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let templates = prompt_templates::global_config();
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "message": "Prompt templates",
+        "request_id": request_id,
+        "templates": templates.templates.values().collect::<Vec<_>>()
+    })))
+}
+
+async fn commit_prompt(payload: web::Json<PromptUpdate>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let update = payload.into_inner();
+    let name = update.name.clone();
+
+    match prompt_templates::update_default_db(update) {
+        Ok(template) => {
+            tracing::info!(
+                request_id = %request_id,
+                name = %template.name,
+                active_version = template.active_version,
+                versions = template.versions.len(),
+                "Prompt template committed"
+            );
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "ok",
+                "message": "Prompt template saved",
+                "request_id": request_id,
+                "template": template
+            })))
+        }
+        Err(err @ (PromptTemplateError::Validation(_) | PromptTemplateError::NotFound(_))) => {
+            Ok(HttpResponse::BadRequest().json(json!({
+                "status": "invalid",
+                "message": err.to_string(),
+                "request_id": request_id
+            })))
+        }
+        Err(err) => {
+            tracing::error!(
+                request_id = %request_id,
+                name = %name,
+                error = %err,
+                "Failed to save prompt template"
+            );
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "status": "error",
+                "message": format!("Failed to save prompt template: {}", err),
+                "request_id": request_id
+            })))
+        }
+    }
+}
+
+async fn preview_prompt(payload: web::Json<PreviewRequest>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let body = payload.into_inner();
+    let templates = prompt_templates::global_config();
+
+    let template = match templates.get(&body.name) {
+        Ok(template) => template,
+        Err(err) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "status": "not_found",
+                "message": err.to_string(),
+                "request_id": request_id
+            })))
+        }
+    };
+    let text = match &body.template {
+        Some(draft) => draft.as_str(),
+        None => match template.text_for(body.version, body.model.as_deref()) {
+            Ok(text) => text,
+            Err(err) => {
+                return Ok(HttpResponse::NotFound().json(json!({
+                    "status": "not_found",
+                    "message": err.to_string(),
+                    "request_id": request_id
+                })))
+            }
+        },
+    };
+
+    // Drafts report validation problems instead of failing the preview
+    let validation_error = template.validate_text(text).err().map(|e| e.to_string());
+    let missing: Vec<&String> = template
+        .required
+        .iter()
+        .filter(|name| !body.variables.contains_key(*name))
+        .collect();
+    let vars: Vec<(&str, &str)> = body
+        .variables
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    let rendered = render_text(text, &vars);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "request_id": request_id,
+        "name": template.name,
+        "version": if body.template.is_some() { None } else { Some(body.version.unwrap_or(template.active_version)) },
+        "rendered": rendered,
+        "variables": placeholders(text),
+        "missing_variables": missing,
+        "validation_error": validation_error,
+        "estimated_tokens": crate::memory::tokenizer::generation_tokenizer().count_tokens(&rendered)
+    })))
+}
@@ -0,0 +1,390 @@
+// src/api/stream_routes.rs
+// Server-sent events for RAG answers and agent chat: the retrieved chunks
+// first, then generated tokens, then verified citations and token usage. When the
+// client disconnects the event stream is dropped, which drops the provider
+// stream and closes the upstream connection.
+
+use super::{default_top_k, generate_request_id, RETRIEVER};
+use crate::agent_memory::AgentMemory;
+use crate::db::llm_settings::{self, LlmConfig};
+use crate::db::prompt_templates;
+use crate::memory::citations::{self, CitationReport, CitationSource, SourceChunk};
+use crate::memory::context_budget::{self, BudgetUsage, ContextBudget, Passage};
+use crate::memory::grounding::{self, GroundingConfig};
+use crate::memory::llm_provider::{create_configured_provider, LLMError, LLMProvider};
+use crate::memory::{llm_router, tokenizer};
+use crate::retriever::SearchHit;
+use actix_web::{web, Error, HttpResponse};
+use chrono::Utc;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Same agent identity and store as /agent and /agent/chat
+const AGENT_ID: &str = "default";
+const AGENT_DB: &str = "agent.db";
+const MAX_TOP_K: usize = 20;
+const RECALLED_MEMORY: usize = 6;
+/// Events buffered ahead of a slow client
+const EVENT_BUFFER: usize = 64;
+const NO_RESULTS_ANSWER: &str = "I couldn't find relevant information in the knowledge base.";
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StreamRequest {
+    pub query: String,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+}
+
+pub fn configure_stream_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/rag/stream", web::get().to(rag_stream_get))
+        .route("/rag/stream", web::post().to(rag_stream_post))
+        .route("/agent/chat/stream", web::get().to(agent_chat_stream));
+}
+
+// GET variant so browsers can use EventSource
+async fn rag_stream_get(query: web::Query<StreamRequest>) -> Result<HttpResponse, Error> {
+    Ok(start_stream(query.into_inner(), false))
+}
+
+async fn rag_stream_post(body: web::Json<StreamRequest>) -> Result<HttpResponse, Error> {
+    Ok(start_stream(body.into_inner(), false))
+}
+
+async fn agent_chat_stream(query: web::Query<StreamRequest>) -> Result<HttpResponse, Error> {
+    Ok(start_stream(query.into_inner(), true))
+}
+
+fn start_stream(request: StreamRequest, agent: bool) -> HttpResponse {
+    let request_id = generate_request_id();
+    if request.query.trim().is_empty() {
+        return HttpResponse::BadRequest().json(json!({
+            "status": "invalid",
+            "message": "query is empty",
+            "request_id": request_id
+        }));
+    }
+
+    let (tx, mut rx) = mpsc::channel::<web::Bytes>(EVENT_BUFFER);
+    let events = EventSender {
+        tx,
+        request_id: request_id.clone(),
+    };
+    actix_web::rt::spawn(run_answer(events, request, agent));
+
+    let body =
+        futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx).map(|e| e.map(Ok::<_, Error>)));
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .insert_header(("X-Request-Id", request_id))
+        .streaming(body)
+}
+
+struct EventSender {
+    tx: mpsc::Sender<web::Bytes>,
+    request_id: String,
+}
+
+impl EventSender {
+    /// False once the client has gone away
+    async fn send(&self, event: &str, mut data: Value) -> bool {
+        if let Value::Object(map) = &mut data {
+            map.insert("request_id".into(), json!(self.request_id));
+        }
+        let frame = format!("event: {}\ndata: {}\n\n", event, data);
+        self.tx.send(web::Bytes::from(frame)).await.is_ok()
+    }
+
+    async fn error(&self, message: String) {
+        warn!(request_id = %self.request_id, "Answer stream failed: {}", message);
+        self.send("error", json!({ "message": message })).await;
+    }
+}
+
+async fn run_answer(events: EventSender, request: StreamRequest, agent: bool) {
+    let started = Instant::now();
+    let query = request.query.trim().to_string();
+    let top_k = request.top_k.clamp(1, MAX_TOP_K);
+
+    let memory = if agent { recall_memory() } else { Vec::new() };
+    if agent
+        && !events
+            .send(
+                "step",
+                json!({
+                    "kind": "memory",
+                    "message": format!("Recalled {} memory items", memory.len())
+                }),
+            )
+            .await
+    {
+        return;
+    }
+
+    let hits = match retrieve(&query, top_k) {
+        Ok(hits) => hits,
+        Err(message) => return events.error(message).await,
+    };
+    let chunks: Vec<Value> = hits
+        .iter()
+        .enumerate()
+        .map(|(i, hit)| {
+            json!({
+                "index": i + 1,
+                "doc_id": hit.doc_id,
+                "title": hit.title,
+                "content": hit.content,
+                "score": hit.score
+            })
+        })
+        .collect();
+    if !events.send("retrieval", json!({ "chunks": chunks })).await {
+        return;
+    }
+
+    if hits.is_empty() {
+        events
+            .send("token", json!({ "text": NO_RESULTS_ANSWER }))
+            .await;
+        if agent {
+            store_memory(&query, NO_RESULTS_ANSWER);
+        }
+        events
+            .send(
+                "done",
+                json!({
+                    "citations": CitationReport::default(),
+                    "sources": [],
+                    "usage": null,
+                    "model": null,
+                    "duration_ms": started.elapsed().as_millis() as u64
+                }),
+            )
+            .await;
+        return;
+    }
+
+    let provider = match answer_provider().await {
+        Ok(provider) => provider,
+        Err(e) => return events.error(e.to_string()).await,
+    };
+    let config = llm_settings::global_config();
+    let (prompt, budget, cited) =
+        match answer_prompt(&query, &hits, &memory, provider.model_name(), &config) {
+            Ok(prompt) => prompt,
+            Err(e) => return events.error(e.to_string()).await,
+        };
+    let (mut tokens, stream_usage) =
+        match provider.generate_stream_with_usage(&prompt, &config).await {
+            Ok(opened) => opened,
+            Err(e) => return events.error(e.to_string()).await,
+        };
+
+    let mut answer = String::new();
+    loop {
+        let next = tokio::select! {
+            next = tokens.next() => next,
+            _ = events.tx.closed() => {
+                info!(
+                    request_id = %events.request_id,
+                    answer_len = answer.len(),
+                    "Client disconnected; generation cancelled"
+                );
+                return;
+            }
+        };
+        match next {
+            Some(Ok(text)) => {
+                answer.push_str(&text);
+                if !events.send("token", json!({ "text": text })).await {
+                    return;
+                }
+            }
+            Some(Err(e)) => return events.error(e.to_string()).await,
+            None => break,
+        }
+    }
+    drop(tokens);
+
+    // Tokens are already out; on abstention the done event carries the
+    // replacement answer for the client to show instead
+    let grounding = grounding::check(
+        &answer,
+        &cited,
+        &GroundingConfig::from_env(),
+        None,
+        Some(provider.as_ref()),
+    )
+    .await;
+    let abstained = grounding.as_ref().is_some_and(|g| g.abstained);
+    if abstained {
+        answer = grounding::ABSTENTION_ANSWER.to_string();
+    }
+    if agent {
+        store_memory(&query, answer.trim());
+    }
+    let citations = citations::verify(&answer, &cited);
+    let usage = stream_usage.get();
+    info!(
+        request_id = %events.request_id,
+        model = provider.model_name(),
+        chunks = hits.len(),
+        answer_len = answer.len(),
+        completion_tokens = usage.map(|u| u.completion_tokens),
+        unknown_citations = ?citations.unknown_numbers,
+        unsupported_citations = ?citations.unsupported_numbers,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "Answer stream complete"
+    );
+    events
+        .send(
+            "done",
+            json!({
+                "citations": citations,
+                "sources": sources(&hits),
+                "grounding": grounding,
+                "answer": if abstained { Some(answer.as_str()) } else { None },
+                "usage": usage,
+                "context_budget": budget,
+                "model": provider.model_name(),
+                "duration_ms": started.elapsed().as_millis() as u64
+            }),
+        )
+        .await;
+}
+
+/// The shared router when installed, else a provider for the configured backend
+pub(super) async fn answer_provider() -> Result<Box<dyn LLMProvider>, LLMError> {
+    match llm_router::global() {
+        Some(router) => Ok(Box::new(router.tagged("answer"))),
+        None => create_configured_provider().await,
+    }
+}
+
+pub(super) fn retrieve(query: &str, top_k: usize) -> Result<Vec<SearchHit>, String> {
+    let retriever = RETRIEVER.get().ok_or("Retriever not initialized")?;
+    let retriever = retriever
+        .lock()
+        .map_err(|_| "Failed to acquire retriever lock".to_string())?;
+    // Over-fetch so collapsing near-duplicates still leaves top_k hits
+    let hits = retriever
+        .search_hits(query, top_k * 2)
+        .map_err(|e| format!("Retrieval failed: {}", e))?;
+    let mut hits = crate::ingest::dedup::collapse_hits(hits, |hit| hit.content.as_str());
+    hits.truncate(top_k);
+    Ok(hits)
+}
+
+/// Prompt with as many hits as the model's token budget allows, numbered
+/// as in the retrieval event, and the citation sources of the included hits
+fn answer_prompt(
+    query: &str,
+    hits: &[SearchHit],
+    memory: &[String],
+    model: &str,
+    config: &LlmConfig,
+) -> prompt_templates::Result<(String, BudgetUsage, Vec<CitationSource>)> {
+    let history = if memory.is_empty() {
+        String::new()
+    } else {
+        format!("Recent conversation:\n{}\n\n", memory.join("\n"))
+    };
+    let render = |history: &str, context: &str| {
+        prompt_templates::render(
+            prompt_templates::STREAM_ANSWER,
+            Some(model),
+            &[("history", history), ("query", query), ("context", context)],
+        )
+    };
+
+    let tokenizer = tokenizer::generation_tokenizer();
+    let budget = ContextBudget::for_active_model(
+        tokenizer.count_tokens(&render("", "")?),
+        tokenizer.count_tokens(&history),
+        config.max_tokens,
+    );
+    let passages: Vec<Passage> = hits
+        .iter()
+        .enumerate()
+        .map(|(i, hit)| {
+            let source = if hit.title.is_empty() {
+                &hit.doc_id
+            } else {
+                &hit.title
+            };
+            Passage {
+                header: format!("[{}] From {}: ", i + 1, source),
+                text: hit.content.clone(),
+                score: hit.score,
+            }
+        })
+        .collect();
+    let packed = context_budget::pack(&passages, &budget, tokenizer.as_ref());
+    let sources = packed
+        .included
+        .iter()
+        .map(|&i| {
+            let hit = &hits[i];
+            CitationSource {
+                number: i + 1,
+                document_id: document_id(hit).to_string(),
+                source: if hit.title.is_empty() {
+                    hit.doc_id.clone()
+                } else {
+                    hit.title.clone()
+                },
+                chunks: vec![SourceChunk {
+                    chunk_id: hit.doc_id.clone(),
+                    content: hit.content.clone(),
+                }],
+            }
+        })
+        .collect();
+    Ok((render(&history, &packed.text)?, packed.usage, sources))
+}
+
+/// Hit ids are "<document>#<chunk>"
+fn document_id(hit: &SearchHit) -> &str {
+    hit.doc_id.split('#').next().unwrap_or(&hit.doc_id)
+}
+
+/// One entry per source document, numbered as in the prompt context
+fn sources(hits: &[SearchHit]) -> Vec<Value> {
+    let mut seen = std::collections::HashSet::new();
+    hits.iter()
+        .enumerate()
+        .filter(|(_, hit)| seen.insert(document_id(hit)))
+        .map(|(i, hit)| {
+            json!({
+                "index": i + 1,
+                "doc_id": hit.doc_id,
+                "title": hit.title
+            })
+        })
+        .collect()
+}
+
+/// Most recent memory items, oldest first
+fn recall_memory() -> Vec<String> {
+    let Ok(memory) = AgentMemory::new(AGENT_DB) else {
+        return Vec::new();
+    };
+    let mut items: Vec<String> = memory
+        .recall(AGENT_ID)
+        .map(|items| items.into_iter().take(RECALLED_MEMORY).collect())
+        .unwrap_or_default();
+    items.reverse();
+    items
+}
+
+fn store_memory(query: &str, answer: &str) {
+    if let Ok(memory) = AgentMemory::new(AGENT_DB) {
+        let ts = Utc::now().to_rfc3339();
+        let _ = memory.store(AGENT_ID, &format!("Q: {}", query), &ts);
+        let _ = memory.store(AGENT_ID, &format!("A: {}", answer), &ts);
+    }
+}
@@ -0,0 +1,242 @@
+// src/api/structured_routes.rs
+// Structured JSON output: generate JSON matching a caller-supplied JSON
+// Schema, from a plain prompt or from retrieved context. Invalid output is
+// repaired a bounded number of times; what is left is returned with 422 and
+// the validation errors.
+
+use super::stream_routes::{answer_provider, retrieve};
+use super::{default_top_k, generate_request_id};
+use crate::db::{llm_settings, prompt_templates};
+use crate::memory::context_budget::{self, ContextBudget, Passage};
+use crate::memory::llm_provider::ChatMessage;
+use crate::memory::structured::{self, StructuredError, StructuredOutput};
+use crate::memory::tokenizer;
+use actix_web::{web, Error, HttpResponse};
+use serde_json::{json, Value};
+use std::time::Instant;
+
+const MAX_TOP_K: usize = 20;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StructuredGenerateRequest {
+    pub prompt: String,
+    #[serde(default)]
+    pub system: Option<String>,
+    pub schema: Value,
+    #[serde(default)]
+    pub max_repairs: Option<usize>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StructuredRagRequest {
+    pub query: String,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    pub schema: Value,
+    #[serde(default)]
+    pub max_repairs: Option<usize>,
+}
+
+pub fn configure_structured_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/generate/structured", web::post().to(generate_structured))
+        .route("/rag/structured", web::post().to(rag_structured));
+}
+
+async fn generate_structured(
+    payload: web::Json<StructuredGenerateRequest>,
+) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let body = payload.into_inner();
+    let started = Instant::now();
+
+    let mut messages = Vec::new();
+    if let Some(system) = body.system.filter(|s| !s.trim().is_empty()) {
+        messages.push(ChatMessage::system(system));
+    }
+    messages.push(ChatMessage::user(body.prompt));
+
+    let result = run(&messages, &body.schema, body.max_repairs).await;
+    Ok(respond(&request_id, started, result, json!({})))
+}
+
+async fn rag_structured(payload: web::Json<StructuredRagRequest>) -> Result<HttpResponse, Error> {
+    let request_id = generate_request_id();
+    let body = payload.into_inner();
+    let started = Instant::now();
+
+    if let Err(e) = structured::check_schema(&body.schema) {
+        return Ok(respond(&request_id, started, Err(e), json!({})));
+    }
+    let hits = match retrieve(&body.query, body.top_k.clamp(1, MAX_TOP_K)) {
+        Ok(hits) => hits,
+        Err(message) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "status": "error",
+                "message": message,
+                "request_id": request_id
+            })))
+        }
+    };
+
+    // Same numbered context as streamed answers, packed into the budget
+    // left after the prompt and the schema instruction
+    let render = |context: &str| -> prompt_templates::Result<Vec<ChatMessage>> {
+        Ok(vec![
+            ChatMessage::system(prompt_templates::render(
+                prompt_templates::RAG_SYSTEM,
+                None,
+                &[],
+            )?),
+            ChatMessage::user(prompt_templates::render(
+                prompt_templates::RAG_ANSWER,
+                None,
+                &[("query", &body.query), ("context", context)],
+            )?),
+        ])
+    };
+    let empty = match render("") {
+        Ok(messages) => messages,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "status": "error",
+                "message": e.to_string(),
+                "request_id": request_id
+            })))
+        }
+    };
+    let tokenizer = tokenizer::generation_tokenizer();
+    let prompt_tokens: usize = empty
+        .iter()
+        .map(|m| tokenizer.count_tokens(&m.content))
+        .sum::<usize>()
+        + tokenizer.count_tokens(&body.schema.to_string());
+    let budget =
+        ContextBudget::for_active_model(prompt_tokens, 0, llm_settings::global_config().max_tokens);
+    let passages: Vec<Passage> = hits
+        .iter()
+        .enumerate()
+        .map(|(i, hit)| Passage {
+            header: format!(
+                "[{}] From {}: ",
+                i + 1,
+                if hit.title.is_empty() {
+                    &hit.doc_id
+                } else {
+                    &hit.title
+                }
+            ),
+            text: hit.content.clone(),
+            score: hit.score,
+        })
+        .collect();
+    let packed = context_budget::pack(&passages, &budget, tokenizer.as_ref());
+    let sources: Vec<Value> = packed
+        .included
+        .iter()
+        .map(|&i| {
+            json!({
+                "index": i + 1,
+                "doc_id": hits[i].doc_id,
+                "title": hits[i].title,
+                "score": hits[i].score
+            })
+        })
+        .collect();
+
+    let messages = match render(&packed.text) {
+        Ok(messages) => messages,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "status": "error",
+                "message": e.to_string(),
+                "request_id": request_id
+            })))
+        }
+    };
+    let result = run(&messages, &body.schema, body.max_repairs).await;
+    Ok(respond(
+        &request_id,
+        started,
+        result,
+        json!({ "sources": sources, "context_budget": packed.usage }),
+    ))
+}
+
+async fn run(
+    messages: &[ChatMessage],
+    schema: &Value,
+    max_repairs: Option<usize>,
+) -> Result<(StructuredOutput, String), StructuredError> {
+    structured::check_schema(schema)?;
+    let provider = answer_provider().await?;
+    let output = structured::generate(
+        provider.as_ref(),
+        messages,
+        schema,
+        &llm_settings::global_config(),
+        max_repairs.unwrap_or(structured::DEFAULT_MAX_REPAIRS),
+    )
+    .await?;
+    Ok((output, provider.model_name().to_string()))
+}
+
+/// 200 for valid output, 422 when repairs ran out, 400 for a bad schema.
+/// `extra` fields are merged into responses that carry output
+fn respond(
+    request_id: &str,
+    started: Instant,
+    result: Result<(StructuredOutput, String), StructuredError>,
+    extra: Value,
+) -> HttpResponse {
+    let (output, model) = match result {
+        Ok(result) => result,
+        Err(err @ StructuredError::InvalidSchema(_)) => {
+            return HttpResponse::BadRequest().json(json!({
+                "status": "invalid_schema",
+                "message": err.to_string(),
+                "request_id": request_id
+            }))
+        }
+        Err(err) => {
+            tracing::error!(request_id = %request_id, error = %err, "Structured generation failed");
+            return HttpResponse::InternalServerError().json(json!({
+                "status": "error",
+                "message": format!("Structured generation failed: {}", err),
+                "request_id": request_id
+            }));
+        }
+    };
+
+    tracing::info!(
+        request_id = %request_id,
+        model = %model,
+        valid = output.valid,
+        attempts = output.attempts,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "Structured generation"
+    );
+    let mut body = json!({
+        "status": if output.valid { "ok" } else { "invalid_output" },
+        "message": if output.valid {
+            "Output matches the schema".to_string()
+        } else {
+            format!("Output still invalid after {} attempts", output.attempts)
+        },
+        "request_id": request_id,
+        "model": model,
+        "data": output.value,
+        "valid": output.valid,
+        "errors": output.errors,
+        "attempts": output.attempts,
+        "raw": output.raw,
+        "duration_ms": started.elapsed().as_millis() as u64
+    });
+    if let (Some(body), Value::Object(extra)) = (body.as_object_mut(), extra) {
+        body.extend(extra);
+    }
+    if output.valid {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::UnprocessableEntity().json(body)
+    }
+}
@@ -5,9 +5,14 @@
 use actix_web::{web, HttpResponse, Result as ActixResult};
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::tools::tool_selector::ToolSelector;
+use super::stream_routes::answer_provider;
+use crate::db::llm_settings;
+use crate::memory::structured::SchemaError;
+use crate::tools::tool_executor::ToolExecutor;
+use crate::tools::tool_selector::{ToolSelection, ToolSelector};
+use crate::tools::ToolRegistry;
 
 // ============ Request/Response Types ============
 
@@ -23,6 +28,12 @@ pub struct ToolSelectionResponse {
     pub secondary_tools: Vec<String>,
     pub confidence: f32,
     pub reasoning: String,
+    /// "function_call", "json_protocol" or "keyword"
+    pub method: String,
+    pub model: Option<String>,
+    pub arguments: serde_json::Value,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub argument_errors: Vec<SchemaError>,
     pub timestamp: String,
 }
 
@@ -32,6 +43,10 @@ pub struct ToolExecutionResponse {
     pub selected_tool: String,
     pub intent: String,
     pub confidence: f32,
+    pub method: String,
+    pub model: Option<String>,
+    pub arguments: serde_json::Value,
+    pub result: Option<String>,
     pub execution_plan: Vec<ExecutionStep>,
     pub timestamp: String,
 }
@@ -59,13 +74,32 @@ pub struct ToolInfo {
 
 // ============ Tool Route Handlers ============
 
+/// Let the configured model choose the tool; keyword selection when no
+/// model is reachable
+async fn select(query: &str, registry: &ToolRegistry) -> ToolSelection {
+    let provider = match answer_provider().await {
+        Ok(provider) => Some(provider),
+        Err(e) => {
+            warn!(error = %e, "No LLM for tool selection, using keywords");
+            None
+        }
+    };
+    ToolSelector::select_with_model(
+        query,
+        provider.as_deref(),
+        registry,
+        &llm_settings::global_config(),
+    )
+    .await
+}
+
 /// Analyze query and suggest best tool
 pub async fn analyze_tools(
     req: web::Json<ToolQueryRequest>,
 ) -> ActixResult<HttpResponse> {
     info!(query = %req.query, "Analyzing tools for query");
 
-    let selection = ToolSelector::select_tools(&req.query);
+    let selection = select(&req.query, &ToolRegistry::with_defaults()).await;
 
     let response = ToolSelectionResponse {
         intent: selection.intent.to_string(),
@@ -73,6 +107,10 @@ pub async fn analyze_tools(
         secondary_tools: selection.secondary_tools.iter().map(|t| t.to_string()).collect(),
         confidence: selection.confidence,
         reasoning: selection.reasoning,
+        method: selection.method.as_str().to_string(),
+        model: selection.model,
+        arguments: selection.arguments,
+        argument_errors: selection.argument_errors,
         timestamp: Utc::now().to_rfc3339(),
     };
 
@@ -85,21 +123,39 @@ pub async fn execute_with_tools(
 ) -> ActixResult<HttpResponse> {
     info!(query = %req.query, "Executing query with tools");
 
-    let selection = ToolSelector::select_tools(&req.query);
+    let registry = ToolRegistry::with_defaults();
+    let selection = select(&req.query, &registry).await;
+
+    // Arguments are validated against the tool's schema before it runs
+    let outcome = ToolExecutor::execute_with_arguments(
+        &registry,
+        &selection.primary_tool,
+        &selection.arguments,
+    )
+    .await;
+    let (status, result) = match outcome {
+        Ok(result) if result.success => ("completed", Some(result.result)),
+        Ok(result) => ("failed", Some(result.result)),
+        Err(e) => ("failed", Some(e)),
+    };
 
     // Build execution plan
     let mut execution_plan = vec![
         ExecutionStep {
             step: 1,
             tool: "ToolSelector".to_string(),
-            action: "Analyze query intent".to_string(),
+            action: format!("Choose tool ({})", selection.method.as_str()),
             status: "completed".to_string(),
         },
         ExecutionStep {
             step: 2,
             tool: selection.primary_tool.to_string(),
-            action: format!("Execute {}", selection.primary_tool.to_string()),
-            status: "queued".to_string(),
+            action: format!(
+                "Execute {} with {}",
+                selection.primary_tool.to_string(),
+                selection.arguments
+            ),
+            status: status.to_string(),
         },
     ];
 
@@ -118,6 +174,10 @@ pub async fn execute_with_tools(
         selected_tool: selection.primary_tool.to_string(),
         intent: selection.intent.to_string(),
         confidence: selection.confidence,
+        method: selection.method.as_str().to_string(),
+        model: selection.model,
+        arguments: selection.arguments,
+        result,
         execution_plan,
         timestamp: Utc::now().to_rfc3339(),
     };
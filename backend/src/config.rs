@@ -1,7 +1,7 @@
 use crate::path_manager::PathManager;
 use std::env;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChunkerMode {
     Fixed,
     Lightweight,
@@ -61,6 +61,9 @@ pub struct ApiConfig {
 
     // Chunking snapshot logging
     pub chunking_log_enabled: bool,
+
+    // Hosts /crawl and feed polling may fetch (empty = both disabled)
+    pub crawl_allowed_hosts: Vec<String>,
 }
 
 impl ApiConfig {
@@ -145,6 +148,15 @@ impl ApiConfig {
             .map(|v| v.to_lowercase() != "false" && v != "0")
             .unwrap_or(true);
 
+        let crawl_allowed_hosts = env::var("CRAWL_ALLOWED_HOSTS")
+            .map(|v| {
+                v.split(',')
+                    .map(|h| h.trim().to_lowercase())
+                    .filter(|h| !h.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Self {
             host,
             port,
@@ -166,6 +178,7 @@ impl ApiConfig {
             redis_url,
             redis_ttl,
             chunking_log_enabled,
+            crawl_allowed_hosts,
         }
     }
 
@@ -0,0 +1,609 @@
+//! Versioned prompt templates stored in the param store.
+//!
+//! Templates use `{{variable}}` placeholders. Each template declares which
+//! variables it requires and which it may use; saving a version that drops a
+//! required variable or uses an undeclared one is rejected. Every save
+//! appends a version, so earlier prompts can be previewed and re-activated.
+//! A version can carry per-model overrides for models that need different
+//! wording.
+
+use std::collections::BTreeMap;
+use std::sync::{OnceLock, RwLock};
+
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::param_store;
+
+const CONFIG_TYPE: &str = "prompt_templates";
+/// Oldest inactive versions beyond this are dropped on save
+const MAX_VERSIONS: usize = 50;
+
+pub const RAG_SYSTEM: &str = "rag_system";
+pub const RAG_ANSWER: &str = "rag_answer";
+pub const STREAM_ANSWER: &str = "stream_answer";
+pub const DOCUMENT_SUMMARY: &str = "document_summary";
+pub const GROUNDING_JUDGE: &str = "grounding_judge";
+pub const STRUCTURED_OUTPUT: &str = "structured_output";
+pub const STRUCTURED_REPAIR: &str = "structured_repair";
+pub const TOOL_SELECTION: &str = "tool_selection";
+pub const AGENT_REACT: &str = "agent_react";
+pub const AGENT_FINAL: &str = "agent_final";
+
+static GLOBAL_PROMPT_TEMPLATES: OnceLock<RwLock<PromptTemplates>> = OnceLock::new();
+
+fn config_lock() -> &'static RwLock<PromptTemplates> {
+    GLOBAL_PROMPT_TEMPLATES.get_or_init(|| RwLock::new(PromptTemplates::builtin()))
+}
+
+#[derive(Debug, Error)]
+pub enum PromptTemplateError {
+    #[error("param store error: {0}")]
+    Store(String),
+    #[error("validation error: {0}")]
+    Validation(String),
+    #[error("prompt template not found: {0}")]
+    NotFound(String),
+}
+
+pub type Result<T> = std::result::Result<T, PromptTemplateError>;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptVersion {
+    pub version: u32,
+    pub template: String,
+    /// Model name (or the name before `:`) -> template used instead
+    #[serde(default)]
+    pub model_overrides: BTreeMap<String, String>,
+    pub created_at: String,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Variables every version must reference
+    #[serde(default)]
+    pub required: Vec<String>,
+    /// Variables a version may reference; rendered empty when not supplied
+    #[serde(default)]
+    pub optional: Vec<String>,
+    pub active_version: u32,
+    pub versions: Vec<PromptVersion>,
+}
+
+impl PromptTemplate {
+    fn new(
+        name: &str,
+        description: &str,
+        required: &[&str],
+        optional: &[&str],
+        text: &str,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            required: required.iter().map(|v| v.to_string()).collect(),
+            optional: optional.iter().map(|v| v.to_string()).collect(),
+            active_version: 1,
+            versions: vec![PromptVersion {
+                version: 1,
+                template: text.to_string(),
+                model_overrides: BTreeMap::new(),
+                created_at: "builtin".to_string(),
+                note: None,
+            }],
+        }
+    }
+
+    pub fn version(&self, version: u32) -> Option<&PromptVersion> {
+        self.versions.iter().find(|v| v.version == version)
+    }
+
+    pub fn active(&self) -> &PromptVersion {
+        self.version(self.active_version)
+            .or(self.versions.last())
+            .expect("prompt template without versions")
+    }
+
+    /// Template text for a model: exact override, then the override for the
+    /// model family (`llama3` for `llama3:8b`), then the base text
+    pub fn text_for(&self, version: Option<u32>, model: Option<&str>) -> Result<&str> {
+        let chosen = match version {
+            Some(v) => self.version(v).ok_or_else(|| {
+                PromptTemplateError::NotFound(format!("{} version {}", self.name, v))
+            })?,
+            None => self.active(),
+        };
+        let overridden = model.and_then(|model| {
+            chosen.model_overrides.get(model).or_else(|| {
+                let family = model.split(':').next().unwrap_or(model);
+                chosen.model_overrides.get(family)
+            })
+        });
+        Ok(overridden.unwrap_or(&chosen.template))
+    }
+
+    /// Required variables present, no undeclared ones
+    pub fn validate_text(&self, text: &str) -> Result<()> {
+        if text.trim().is_empty() {
+            return Err(PromptTemplateError::Validation(format!(
+                "template {} is empty",
+                self.name
+            )));
+        }
+        let used = placeholders(text);
+        if let Some(missing) = self.required.iter().find(|v| !used.contains(v)) {
+            return Err(PromptTemplateError::Validation(format!(
+                "template {} must reference {{{{{}}}}}",
+                self.name, missing
+            )));
+        }
+        if let Some(unknown) = used
+            .iter()
+            .find(|v| !self.required.contains(v) && !self.optional.contains(v))
+        {
+            return Err(PromptTemplateError::Validation(format!(
+                "template {} does not accept variable {{{{{}}}}}; allowed: {}",
+                self.name,
+                unknown,
+                self.required
+                    .iter()
+                    .chain(&self.optional)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Render with `vars`; every required variable must be supplied
+    pub fn render(
+        &self,
+        version: Option<u32>,
+        model: Option<&str>,
+        vars: &[(&str, &str)],
+    ) -> Result<String> {
+        if let Some(missing) = self
+            .required
+            .iter()
+            .find(|name| !vars.iter().any(|(k, _)| k == name))
+        {
+            return Err(PromptTemplateError::Validation(format!(
+                "missing variable {} for template {}",
+                missing, self.name
+            )));
+        }
+        Ok(render_text(self.text_for(version, model)?, vars))
+    }
+}
+
+/// Change submitted through POST /config/prompts
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PromptUpdate {
+    pub name: String,
+    /// New text; becomes a new version
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Store `template` as the override for this model instead of the base
+    /// text. An empty template removes the override
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Switch to an existing version instead of adding one
+    #[serde(default)]
+    pub activate_version: Option<u32>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Variables for a new template; ignored for existing ones
+    #[serde(default)]
+    pub required: Option<Vec<String>>,
+    #[serde(default)]
+    pub optional: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptTemplates {
+    pub templates: BTreeMap<String, PromptTemplate>,
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl PromptTemplates {
+    /// The prompts the pipelines shipped with, as version 1
+    pub fn builtin() -> Self {
+        let templates = [
+            PromptTemplate::new(
+                RAG_SYSTEM,
+                "System message of RAG answers",
+                &[],
+                &[],
+                "You are a helpful assistant. Answer the user's question based on the provided context. The context is split into numbered blocks; cite the blocks you use with markers like [1] or [2][3] right after the statement they support, and do not cite numbers that are not in the context.",
+            ),
+            PromptTemplate::new(
+                RAG_ANSWER,
+                "User message of RAG answers",
+                &["query", "context"],
+                &["history", "citations"],
+                "{{history}}Question: {{query}}\n\nContext:\n{{context}}",
+            ),
+            PromptTemplate::new(
+                STREAM_ANSWER,
+                "Completion prompt of streamed RAG and agent answers",
+                &["query", "context"],
+                &["history", "citations"],
+                "You are a helpful assistant. Answer the following question based on the provided context. Cite the numbered context blocks you use with markers like [1] right after the statement they support.\n\n{{history}}Question: {{query}}\n\nContext:\n{{context}}\nAnswer:",
+            ),
+            PromptTemplate::new(
+                DOCUMENT_SUMMARY,
+                "One-line document summary for contextual chunk headers",
+                &["title", "text"],
+                &[],
+                "Summarize what the following document is about in one sentence of at most 25 words. Reply with the sentence only.\n\nTitle: {{title}}\n\n{{text}}",
+            ),
+            PromptTemplate::new(
+                GROUNDING_JUDGE,
+                "Judge whether numbered answer claims are supported by the context",
+                &["context", "claims"],
+                &[],
+                "For each numbered claim, decide whether the context states or directly implies it. Reply with one line per claim in the form \"<number>: SUPPORTED\" or \"<number>: UNSUPPORTED\" and nothing else.\n\nContext:\n{{context}}\n\nClaims:\n{{claims}}",
+            ),
+            PromptTemplate::new(
+                STRUCTURED_OUTPUT,
+                "System instruction for JSON answers that must match a schema",
+                &["schema"],
+                &[],
+                "Reply with a single JSON value and nothing else: no prose, no code fences. It must validate against this JSON Schema:\n{{schema}}",
+            ),
+            PromptTemplate::new(
+                STRUCTURED_REPAIR,
+                "Follow-up asking the model to fix JSON that failed validation",
+                &["errors"],
+                &[],
+                "Your reply did not validate against the schema:\n{{errors}}\nReply again with only the corrected JSON.",
+            ),
+            PromptTemplate::new(
+                TOOL_SELECTION,
+                "System prompt asking the model to pick a tool and its arguments",
+                &[],
+                &[],
+                "Decide whether one of the available tools is needed to answer the user's request. If one is, call it with arguments that fill its parameters from the request; use the exact values the user gave. If none is needed, call no tool: the question will be answered from the knowledge base.",
+            ),
+            PromptTemplate::new(
+                AGENT_REACT,
+                "System prompt of the agent's think/act/observe loop",
+                &["tools", "history"],
+                &[],
+                "You answer the user's question by working in steps. In each step, think about what you still need, then take exactly one action. Available actions:\n{{tools}}\n\nAfter each action you receive an observation. Search results are numbered passages; cite them as [n] in your answer. Do not repeat an action with the same input. When the observations are enough, or nothing more can be found, take final_answer. If the knowledge base does not contain the answer, say so.{{history}}",
+            ),
+            PromptTemplate::new(
+                AGENT_FINAL,
+                "Follow-up forcing a final answer when the agent's budget runs out",
+                &[],
+                &[],
+                "No more actions are possible. Answer the original question now from the observations so far, citing passages as [n]. If they are not enough, say what is missing. Reply with the answer text only.",
+            ),
+        ];
+        Self {
+            templates: templates.into_iter().map(|t| (t.name.clone(), t)).collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<&PromptTemplate> {
+        self.templates
+            .get(name)
+            .ok_or_else(|| PromptTemplateError::NotFound(name.to_string()))
+    }
+
+    pub fn render(&self, name: &str, model: Option<&str>, vars: &[(&str, &str)]) -> Result<String> {
+        self.get(name)?.render(None, model, vars)
+    }
+
+    /// Apply an update and return the changed template
+    pub fn apply(&mut self, update: PromptUpdate) -> Result<&PromptTemplate> {
+        let name = update.name.trim();
+        if name.is_empty() || !name.chars().all(is_variable_char) {
+            return Err(PromptTemplateError::Validation(
+                "name must be non-empty and use only a-z, 0-9 and _".into(),
+            ));
+        }
+        if !self.templates.contains_key(name) {
+            let template = update.template.as_deref().ok_or_else(|| {
+                PromptTemplateError::NotFound(format!("{} (send template to create it)", name))
+            })?;
+            let required = update.required.clone().unwrap_or_default();
+            let optional = update.optional.clone().unwrap_or_else(|| {
+                placeholders(template)
+                    .into_iter()
+                    .filter(|v| !required.contains(v))
+                    .collect()
+            });
+            let mut created = PromptTemplate::new(name, "", &[], &[], template);
+            created.required = required;
+            created.optional = optional;
+            created.validate_text(template)?;
+            created.versions[0].created_at = Utc::now().to_rfc3339();
+            created.versions[0].note = update.note.clone();
+            self.templates.insert(name.to_string(), created);
+        } else {
+            let existing = self.templates.get_mut(name).unwrap();
+            if let Some(version) = update.activate_version {
+                if existing.version(version).is_none() {
+                    return Err(PromptTemplateError::NotFound(format!(
+                        "{} version {}",
+                        name, version
+                    )));
+                }
+                existing.active_version = version;
+            } else if let Some(template) = &update.template {
+                let mut next = existing.active().clone();
+                match update.model.as_deref().map(str::trim) {
+                    Some(model) if !model.is_empty() => {
+                        if template.trim().is_empty() {
+                            next.model_overrides.remove(model);
+                        } else {
+                            existing.validate_text(template)?;
+                            next.model_overrides
+                                .insert(model.to_string(), template.clone());
+                        }
+                    }
+                    _ => {
+                        existing.validate_text(template)?;
+                        next.template = template.clone();
+                    }
+                }
+                next.version = existing
+                    .versions
+                    .iter()
+                    .map(|v| v.version)
+                    .max()
+                    .unwrap_or(0)
+                    + 1;
+                next.created_at = Utc::now().to_rfc3339();
+                next.note = update.note.clone();
+                existing.active_version = next.version;
+                existing.versions.push(next);
+                while existing.versions.len() > MAX_VERSIONS {
+                    let active = existing.active_version;
+                    let Some(oldest) = existing.versions.iter().position(|v| v.version != active)
+                    else {
+                        break;
+                    };
+                    existing.versions.remove(oldest);
+                }
+            } else if update.description.is_none() {
+                return Err(PromptTemplateError::Validation(
+                    "send template, activate_version or description".into(),
+                ));
+            }
+        }
+        let template = self.templates.get_mut(name).unwrap();
+        if let Some(description) = update.description {
+            template.description = description;
+        }
+        Ok(template)
+    }
+
+    /// Stored templates over the built-ins, so prompts added in later
+    /// releases show up next to edited ones
+    fn merged(stored: PromptTemplates) -> Self {
+        let mut merged = Self::builtin();
+        merged.templates.extend(stored.templates);
+        merged
+    }
+}
+
+fn is_variable_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'
+}
+
+/// `{{name}}` spans in `text` as (start, end, name)
+fn placeholder_spans(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while let Some(open) = text[from..].find("{{").map(|i| i + from) {
+        let Some(close) = text[open + 2..].find("}}").map(|i| i + open + 2) else {
+            break;
+        };
+        let name = text[open + 2..close].trim();
+        if !name.is_empty() && name.chars().all(is_variable_char) {
+            spans.push((open, close + 2, name));
+            from = close + 2;
+        } else {
+            from = open + 2;
+        }
+    }
+    spans
+}
+
+/// Distinct variable names referenced by a template, in order of first use
+pub fn placeholders(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, _, name) in placeholder_spans(text) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Substitute placeholders; variables without a value render empty
+pub fn render_text(text: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end, name) in placeholder_spans(text) {
+        out.push_str(&text[last..start]);
+        if let Some((_, value)) = vars.iter().find(|(k, _)| *k == name) {
+            out.push_str(value);
+        }
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+pub fn global_config() -> PromptTemplates {
+    config_lock().read().unwrap().clone()
+}
+
+/// Render an active template for `model` with the global registry
+pub fn render(name: &str, model: Option<&str>, vars: &[(&str, &str)]) -> Result<String> {
+    config_lock().read().unwrap().render(name, model, vars)
+}
+
+pub fn load_active_config(conn: &Connection) {
+    let cfg = load(conn).unwrap_or_default();
+    *config_lock().write().unwrap() = cfg;
+}
+
+pub fn load(conn: &Connection) -> Result<PromptTemplates> {
+    match param_store::load::<PromptTemplates>(conn, CONFIG_TYPE) {
+        Ok(Some(stored)) => Ok(PromptTemplates::merged(stored)),
+        Ok(None) => Ok(PromptTemplates::builtin()),
+        Err(err) => Err(PromptTemplateError::Store(err.to_string())),
+    }
+}
+
+/// Apply an update on top of the stored templates, save and publish
+pub fn update(conn: &Connection, update: PromptUpdate) -> Result<PromptTemplate> {
+    let mut templates = load(conn)?;
+    let name = update.name.trim().to_string();
+    templates.apply(update)?;
+    param_store::save(conn, CONFIG_TYPE, &templates)
+        .map_err(|err| PromptTemplateError::Store(err.to_string()))?;
+    let changed = templates.get(&name)?.clone();
+    *config_lock().write().unwrap() = templates;
+    Ok(changed)
+}
+
+pub fn update_default_db(change: PromptUpdate) -> Result<PromptTemplate> {
+    let path = super::chunk_settings::get_db_path().expect("DB path not initialized");
+    let conn = Connection::open(path).map_err(|err| PromptTemplateError::Store(err.to_string()))?;
+    update(&conn, change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        param_store::init_table(&conn).unwrap();
+        conn
+    }
+
+    fn change(name: &str, template: &str) -> PromptUpdate {
+        PromptUpdate {
+            name: name.to_string(),
+            template: Some(template.to_string()),
+            ..PromptUpdate::default()
+        }
+    }
+
+    #[test]
+    fn builtins_render_like_the_old_literals() {
+        let templates = PromptTemplates::builtin();
+        let text = templates
+            .render(
+                RAG_ANSWER,
+                None,
+                &[("query", "Why?"), ("context", "Because.")],
+            )
+            .unwrap();
+        assert_eq!(text, "Question: Why?\n\nContext:\nBecause.");
+        for template in templates.templates.values() {
+            template.validate_text(&template.active().template).unwrap();
+        }
+    }
+
+    #[test]
+    fn placeholders_tolerate_spaces_and_ignore_non_variables() {
+        assert_eq!(
+            placeholders("{{ query }} {{context}} {{query}} {{Not A Var}} {x}"),
+            vec!["query", "context"]
+        );
+        assert_eq!(render_text("a {{ x }} b {{y}}", &[("x", "1")]), "a 1 b ");
+    }
+
+    #[test]
+    fn rejects_missing_required_and_unknown_variables() {
+        let mut templates = PromptTemplates::builtin();
+        let err = templates
+            .apply(change(RAG_ANSWER, "Question: {{query}}"))
+            .unwrap_err();
+        assert!(err.to_string().contains("{{context}}"));
+        let err = templates
+            .apply(change(RAG_ANSWER, "{{query}} {{context}} {{secret}}"))
+            .unwrap_err();
+        assert!(err.to_string().contains("secret"));
+        assert_eq!(templates.get(RAG_ANSWER).unwrap().versions.len(), 1);
+    }
+
+    #[test]
+    fn versions_overrides_and_rollback_persist() {
+        let conn = setup_conn();
+        update(&conn, change(RAG_ANSWER, "Q: {{query}}\nC: {{context}}")).unwrap();
+        let with_override = update(
+            &conn,
+            PromptUpdate {
+                model: Some("phi".into()),
+                ..change(RAG_ANSWER, "{{context}}\n\n{{query}}?")
+            },
+        )
+        .unwrap();
+        assert_eq!(with_override.active_version, 3);
+
+        let templates = load(&conn).unwrap();
+        let vars = [("query", "q"), ("context", "c")];
+        assert_eq!(
+            templates
+                .render(RAG_ANSWER, Some("phi:latest"), &vars)
+                .unwrap(),
+            "c\n\nq?"
+        );
+        assert_eq!(
+            templates.render(RAG_ANSWER, Some("llama3"), &vars).unwrap(),
+            "Q: q\nC: c"
+        );
+
+        let rolled_back = update(
+            &conn,
+            PromptUpdate {
+                name: RAG_ANSWER.into(),
+                activate_version: Some(1),
+                ..PromptUpdate::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(rolled_back.active_version, 1);
+        assert_eq!(rolled_back.versions.len(), 3);
+        // Built-ins missing from the stored blob are still available
+        assert!(load(&conn).unwrap().get(DOCUMENT_SUMMARY).is_ok());
+    }
+
+    #[test]
+    fn creates_custom_templates() {
+        let mut templates = PromptTemplates::builtin();
+        let created = templates
+            .apply(PromptUpdate {
+                required: Some(vec!["query".into()]),
+                ..change("rewrite_query", "Rewrite: {{query}} {{history}}")
+            })
+            .unwrap();
+        assert_eq!(created.optional, vec!["history"]);
+        assert!(templates
+            .render("rewrite_query", None, &[("history", "h")])
+            .is_err());
+    }
+}
@@ -1,7 +1,11 @@
 use crate::config::ChunkerMode;
 use crate::embedder;
+use crate::ingest::dedup::{self, Decision, DedupConfig, DuplicatePolicy};
+use crate::memory::chunk_header::{self, ContextHeaderConfig, DocumentContext};
 use crate::memory::chunker_factory::{create_chunker, Chunker};
+use crate::memory::tokenizer;
 use crate::retriever::Retriever;
+use crate::security::redaction;
 use std::fs;
 use std::path::Path;
 use tracing::{debug, info, warn};
@@ -73,70 +77,186 @@ pub fn index_file(
         }
     };
 
+    // Near-duplicate check; without a database every file is indexed
+    let mut db = crate::ingest::open_db().ok();
+    if let Some(conn) = db.as_ref() {
+        let config = DedupConfig::global();
+        let fingerprint = dedup::Fingerprint::of(&content);
+        match dedup::check_document(conn, filename, &fingerprint, config) {
+            Ok((decision, Some(found))) => {
+                info!(
+                    "index_file: file='{}' near-duplicate of '{}' similarity={:.2} policy={:?}",
+                    filename, found.document, found.similarity, config.policy
+                );
+                if decision == Decision::Skip {
+                    return Ok(0);
+                }
+                if config.policy == DuplicatePolicy::Version {
+                    info!(
+                        "index_file: file='{}' indexed as version {} of '{}'",
+                        filename, found.next_version, found.version_of
+                    );
+                }
+            }
+            Ok((_, None)) => {}
+            Err(e) => warn!(
+                "index_file: duplicate check failed for '{}': {}",
+                filename, e
+            ),
+        }
+    }
+
     let chunk_start = std::time::Instant::now();
-    let chunks = chunker.chunk_text(&content);
+    let chunks = chunker.chunk_with_metadata(&content);
     let chunk_duration = chunk_start.elapsed();
+
+    let header_config = ContextHeaderConfig::global();
+    let mut document_context = header_config.enabled_for(chunker_mode).then(|| {
+        let title = chunk_header::document_title(filename, &content);
+        let summary = header_config
+            .include_summary
+            // The model only ever sees redacted text
+            .then(|| {
+                chunk_header::summarize_document_blocking(
+                    &redaction::redact(&title),
+                    &redaction::redact(&content),
+                )
+            })
+            .flatten();
+        DocumentContext::new(title, &content).with_summary(summary)
+    });
+
+    // (text, table, header). A header is embedded with its chunk, so the
+    // chunk only gets the budget left after it and is split to fit
+    let pieces: Vec<(String, bool, Option<String>)> = match document_context.as_mut() {
+        Some(ctx) => {
+            let tokenizer = tokenizer::embedding_tokenizer();
+            let budget = crate::db::chunk_settings::global_config()
+                .fit_active_models()
+                .fit_embedding_limit(tokenizer.max_input_tokens())
+                .max_size;
+            let mut pieces = Vec::with_capacity(chunks.len());
+            for chunk in &chunks {
+                let header = ctx.header_for(&chunk.text);
+                for piece in
+                    chunk_header::fit_under_header(&header, &chunk.text, budget, tokenizer.as_ref())
+                {
+                    pieces.push((piece.to_string(), chunk.table, Some(header.clone())));
+                }
+            }
+            pieces
+        }
+        None => chunks
+            .into_iter()
+            .map(|c| (c.text, c.table, None))
+            .collect(),
+    };
+
     let mut ok = 0usize;
     let mut total_tokens = 0usize;
-    for (i, chunk) in chunks.iter().enumerate() {
+    let mut header_tokens = 0usize;
+    let mut chunk_hashes = Vec::with_capacity(pieces.len());
+    let mut redaction = redaction::session(filename);
+    for (i, (chunk, table, header)) in pieces.iter().enumerate() {
         let chunk_id = format!("{}#{}", filename, i);
-        let vector = embedder::embed(chunk);
+        // Redact before anything derived from the chunk is embedded or indexed
+        let Some(text) = redaction.chunk(chunk) else {
+            continue;
+        };
+        chunk_hashes.push((chunk_id.clone(), dedup::simhash(&text)));
 
-        total_tokens += chunk.split_whitespace().count();
+        total_tokens += text.split_whitespace().count();
 
-        // Handle the Result from index_chunk
-        if let Err(e) = retriever.index_chunk(&chunk_id, chunk, &vector) {
+        // With headers, the header is embedded with the chunk and indexed as
+        // its title; the stored content stays the bare chunk
+        let (title, vector) = match header {
+            Some(header) => {
+                let header = redaction.field(header);
+                header_tokens += header.split_whitespace().count();
+                let vector = embedder::embed(&chunk_header::contextualize(&header, &text));
+                (header, vector)
+            }
+            None => (chunk_id.clone(), embedder::embed(&text)),
+        };
+        let result = if *table {
+            retriever.index_table_chunk(&chunk_id, &title, &text, &vector)
+        } else {
+            retriever.index_chunk_with_title(&chunk_id, &title, &text, &vector)
+        };
+        if let Err(e) = result {
             warn!("index_file: Failed to index chunk {}: {}", chunk_id, e);
         } else {
             ok += 1;
         }
     }
+    let header_chunks = if document_context.is_some() { ok } else { 0 };
+    redaction.finish();
+    if let Some(conn) = db.as_mut() {
+        if let Err(e) = dedup::record_chunks(conn, filename, &chunk_hashes) {
+            warn!(
+                "index_file: failed to store chunk fingerprints for '{}': {}",
+                filename, e
+            );
+        }
+    }
 
     if let Some(stats) = chunker.stats() {
         info!(
-            "index_file: file='{}' mode={:?} chunks={} tokens={} duration_ms={} semantic_threshold={} semantic_flushes={} heading_flushes={} size_flushes={} total_segments={} avg_similarity={:?}",
+            "index_file: file='{}' mode={:?} chunks={} tokens={} duration_ms={} header_chunks={} header_tokens={} semantic_threshold={} semantic_flushes={} heading_flushes={} size_flushes={} table_flushes={} table_chunks={} total_segments={} avg_similarity={:?}",
             filename,
             chunker_mode,
             ok,
             total_tokens,
             chunk_duration.as_millis(),
+            header_chunks,
+            header_tokens,
             stats.semantic_similarity_threshold,
             stats.semantic_flushes,
             stats.heading_flushes,
             stats.size_flushes,
+            stats.table_flushes,
+            stats.table_chunks,
             stats.total_segments,
             stats.average_similarity(),
         );
-        crate::monitoring::record_chunking_snapshot(crate::monitoring::ChunkingStatsSnapshot::new(
-            filename,
-            chunker_mode,
-            ok,
-            total_tokens,
-            chunk_duration.as_millis() as u64,
-            Some(stats),
-        ));
+        crate::monitoring::record_chunking_snapshot(
+            crate::monitoring::ChunkingStatsSnapshot::new(
+                filename,
+                chunker_mode,
+                ok,
+                total_tokens,
+                chunk_duration.as_millis() as u64,
+                Some(stats),
+            )
+            .with_headers(header_chunks, header_tokens),
+        );
     } else {
         info!(
-            "index_file: file='{}' mode={:?} chunks={} tokens={} duration_ms={}",
+            "index_file: file='{}' mode={:?} chunks={} tokens={} duration_ms={} header_chunks={} header_tokens={}",
             filename,
             chunker_mode,
             ok,
             total_tokens,
-            chunk_duration.as_millis()
+            chunk_duration.as_millis(),
+            header_chunks,
+            header_tokens,
+        );
+        crate::monitoring::record_chunking_snapshot(
+            crate::monitoring::ChunkingStatsSnapshot::new(
+                filename,
+                chunker_mode,
+                ok,
+                total_tokens,
+                chunk_duration.as_millis() as u64,
+                None,
+            )
+            .with_headers(header_chunks, header_tokens),
         );
-        crate::monitoring::record_chunking_snapshot(crate::monitoring::ChunkingStatsSnapshot::new(
-            filename,
-            chunker_mode,
-            ok,
-            total_tokens,
-            chunk_duration.as_millis() as u64,
-            None,
-        ));
     }
     Ok(ok)
 }
 
-fn extract_text(path: &Path) -> Option<String> {
+pub(crate) fn extract_text(path: &Path) -> Option<String> {
     let ext = path.extension().and_then(|s| s.to_str())?;
     match ext {
         "txt" => fs::read_to_string(path).ok(),
@@ -0,0 +1,1612 @@
+// src/ingest/code.rs
+// Source-code repository ingestion with language-aware chunking
+//
+// Walks a local checkout (honouring .gitignore), splits Rust, Python,
+// TypeScript and Go files per function/impl/class and indexes each symbol
+// with its file path and line range so "definition of X" can be answered
+// with a line link.
+
+use crate::embedder;
+use crate::memory::chunker::{Chunk, ChunkMetadata, SourceType};
+use crate::memory::chunker_factory::estimate_token_count;
+use crate::retriever::{ChunkFields, Retriever};
+use crate::security::redaction;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Directories /ingest/code may read repositories from
+const ALLOWED_ROOTS_VAR: &str = "CODE_INGEST_ALLOWED_ROOTS";
+
+/// Languages with syntax-aware splitting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeLanguage {
+    Rust,
+    Python,
+    TypeScript,
+    Go,
+}
+
+impl CodeLanguage {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|e| e.to_str())? {
+            "rs" => Some(Self::Rust),
+            "py" | "pyi" => Some(Self::Python),
+            "ts" | "tsx" | "mts" | "cts" => Some(Self::TypeScript),
+            "go" => Some(Self::Go),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Python => "python",
+            Self::TypeScript => "typescript",
+            Self::Go => "go",
+        }
+    }
+
+    /// Separator used when naming members of a container (`Foo::bar`, `Foo.bar`)
+    fn member_separator(&self) -> &'static str {
+        match self {
+            Self::Rust => "::",
+            _ => ".",
+        }
+    }
+}
+
+impl std::str::FromStr for CodeLanguage {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "rust" | "rs" => Ok(Self::Rust),
+            "python" | "py" => Ok(Self::Python),
+            "typescript" | "ts" => Ok(Self::TypeScript),
+            "go" | "golang" => Ok(Self::Go),
+            other => Err(format!("unsupported language: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Impl,
+    Struct,
+    Enum,
+    Trait,
+    Interface,
+    Class,
+    TypeAlias,
+    Constant,
+    Macro,
+    Module,
+    /// Imports and other top-level lines that belong to no symbol
+    Preamble,
+}
+
+impl SymbolKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Function => "fn",
+            Self::Method => "method",
+            Self::Impl => "impl",
+            Self::Struct => "struct",
+            Self::Enum => "enum",
+            Self::Trait => "trait",
+            Self::Interface => "interface",
+            Self::Class => "class",
+            Self::TypeAlias => "type",
+            Self::Constant => "const",
+            Self::Macro => "macro",
+            Self::Module => "module",
+            Self::Preamble => "preamble",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        [
+            Self::Function,
+            Self::Method,
+            Self::Impl,
+            Self::Struct,
+            Self::Enum,
+            Self::Trait,
+            Self::Interface,
+            Self::Class,
+            Self::TypeAlias,
+            Self::Constant,
+            Self::Macro,
+            Self::Module,
+            Self::Preamble,
+        ]
+        .into_iter()
+        .find(|k| k.as_str() == label)
+    }
+
+    /// Kinds whose body is split into members when too large
+    fn is_container(&self) -> bool {
+        matches!(
+            self,
+            Self::Impl | Self::Trait | Self::Class | Self::Module | Self::Interface
+        )
+    }
+}
+
+/// One syntactic unit of a source file (1-based, inclusive line range)
+#[derive(Debug, Clone, Serialize)]
+pub struct CodeSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    /// `Some((n, total))` when an oversized symbol had to be cut into line windows
+    pub part: Option<(usize, usize)>,
+}
+
+/// Configuration for repository ingestion
+#[derive(Debug, Clone)]
+pub struct CodeIngestConfig {
+    /// Token budget per chunk; larger symbols are split into members or line windows
+    pub max_tokens: usize,
+    pub max_file_bytes: u64,
+    /// Restrict ingestion to these languages (empty = all supported)
+    pub languages: Vec<CodeLanguage>,
+    /// Repositories must live below one of these directories; empty allows none
+    pub allowed_roots: Vec<PathBuf>,
+}
+
+impl Default for CodeIngestConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: crate::memory::chunker::DEFAULT_MAX_SIZE,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            languages: Vec::new(),
+            allowed_roots: Vec::new(),
+        }
+    }
+}
+
+impl CodeIngestConfig {
+    /// Load configuration from environment variables
+    ///
+    /// - CODE_INGEST_MAX_TOKENS: token budget per chunk (default: chunk max_size)
+    /// - CODE_INGEST_MAX_FILE_BYTES: skip larger files (default: 1 MiB)
+    /// - CODE_INGEST_ALLOWED_ROOTS: comma-separated directories repositories must live
+    ///   under (empty: code ingestion is disabled)
+    pub fn from_env() -> Self {
+        let max_tokens = env::var("CODE_INGEST_MAX_TOKENS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| crate::db::chunk_settings::global_config().max_size);
+
+        let max_file_bytes = env::var("CODE_INGEST_MAX_FILE_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_FILE_BYTES);
+
+        let allowed_roots = super::allowed_roots_from_env(ALLOWED_ROOTS_VAR);
+
+        Self {
+            max_tokens,
+            max_file_bytes,
+            languages: Vec::new(),
+            allowed_roots,
+        }
+    }
+
+    fn accepts(&self, language: CodeLanguage) -> bool {
+        self.languages.is_empty() || self.languages.contains(&language)
+    }
+
+    /// Check `root` against `allowed_roots` and return its canonical form
+    pub fn validate_root(&self, root: &Path) -> Result<PathBuf, String> {
+        let canonical = super::check_allowed_path(root, &self.allowed_roots, ALLOWED_ROOTS_VAR)?;
+        if !canonical.is_dir() {
+            return Err(format!("'{}' is not a directory", root.display()));
+        }
+        Ok(canonical)
+    }
+}
+
+/// Summary of a repository ingestion run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CodeIngestReport {
+    pub repository: String,
+    pub files_scanned: usize,
+    pub files_indexed: usize,
+    pub files_skipped: usize,
+    pub chunks_indexed: usize,
+    pub languages: BTreeMap<String, usize>,
+    pub errors: Vec<String>,
+}
+
+/// A definition found in the index, with a line link into the repository
+#[derive(Debug, Clone, Serialize)]
+pub struct DefinitionHit {
+    pub symbol: String,
+    pub kind: String,
+    /// Canonical root of the repository
+    pub repository: String,
+    /// File path relative to `repository`
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub link: String,
+    pub snippet: String,
+    pub score: f32,
+}
+
+// ─────────────────────────────────────────────────────────────
+// Repository walking and indexing
+// ─────────────────────────────────────────────────────────────
+
+/// List supported source files below `root`, honouring .gitignore,
+/// .git/info/exclude and hidden-file rules.
+pub fn walk_repository(root: &Path, config: &CodeIngestConfig) -> Vec<(PathBuf, CodeLanguage)> {
+    let mut files = Vec::new();
+    let walker = ignore::WalkBuilder::new(root)
+        .hidden(true)
+        .git_ignore(true)
+        .git_exclude(true)
+        .git_global(false)
+        .require_git(false)
+        .build();
+
+    for entry in walker {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                warn!("walk_repository: {}", e);
+                continue;
+            }
+        };
+        if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+            continue;
+        }
+        let path = entry.path();
+        match CodeLanguage::from_path(path) {
+            Some(lang) if config.accepts(lang) => files.push((path.to_path_buf(), lang)),
+            _ => debug!("walk_repository: skipping '{}'", path.display()),
+        }
+    }
+
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
+}
+
+/// Chunk every supported file of a repository and index the symbols.
+///
+/// Chunk ids have the form `<canonical root>/<relative path>#L<start>-L<end>`,
+/// so checkouts sharing a directory name stay apart. The stored title is
+/// `<kind> <symbol>`, which is what `find_definitions` searches; repository,
+/// path, symbol, kind and line range are also stored as filterable fields.
+pub fn index_repository(
+    retriever: &mut Retriever,
+    root: &Path,
+    config: &CodeIngestConfig,
+) -> Result<CodeIngestReport, String> {
+    let root = config.validate_root(root)?;
+    let repository = root.to_string_lossy().replace('\\', "/");
+
+    let mut report = CodeIngestReport {
+        repository: repository.clone(),
+        ..CodeIngestReport::default()
+    };
+
+    let mut batch = retriever
+        .batch()
+        .map_err(|e| format!("begin_batch failed: {}", e))?;
+
+    // Replace the chunks of a previous run, including files since removed
+    let previous = batch
+        .chunk_ids_in_repository(&repository)
+        .map_err(|e| format!("listing previous chunks failed: {}", e))?;
+    batch
+        .delete_chunks(&previous)
+        .map_err(|e| format!("delete_chunks failed: {}", e))?;
+
+    for (path, language) in walk_repository(&root, config) {
+        report.files_scanned += 1;
+        let rel = path
+            .strip_prefix(&root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let label = format!("{}/{}", repository, rel);
+
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if size > config.max_file_bytes {
+            debug!("index_repository: '{}' exceeds max_file_bytes", label);
+            report.files_skipped += 1;
+            continue;
+        }
+        let source = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => {
+                // Binary or non-UTF-8 files are silently skipped
+                debug!("index_repository: cannot read '{}': {}", label, e);
+                report.files_skipped += 1;
+                continue;
+            }
+        };
+
+        let chunks = chunk_source(&label, language, &source, config.max_tokens);
+        let mut indexed = 0usize;
+        let mut redaction = redaction::session(&label);
+        for chunk in &chunks {
+            let extra = &chunk.metadata.extra;
+            let line = |key: &str| extra.get(key).and_then(|v| v.parse::<u64>().ok());
+            let fields = ChunkFields {
+                repository: Some(repository.clone()),
+                path: Some(rel.clone()),
+                symbol: extra.get("symbol").cloned(),
+                symbol_kind: extra.get("symbol_kind").cloned(),
+                line_start: line("line_start"),
+                line_end: line("line_end"),
+                ..ChunkFields::default()
+            };
+            let chunk_id = format!(
+                "{}#L{}-L{}",
+                label,
+                fields.line_start.unwrap_or(0),
+                fields.line_end.unwrap_or(0),
+            );
+            let title = format!(
+                "{} {}",
+                fields.symbol_kind.as_deref().unwrap_or(""),
+                fields.symbol.as_deref().unwrap_or(""),
+            );
+            let Some(content) = redaction.chunk(&chunk.content) else {
+                continue;
+            };
+            let vector = embedder::embed(&content);
+            match batch.index_chunk_with_fields(&chunk_id, &title, &content, &vector, &fields) {
+                Ok(()) => indexed += 1,
+                Err(e) => report.errors.push(format!("{}: {}", chunk_id, e)),
+            }
+        }
+        redaction.finish();
+
+        // Empty files, or files whose every chunk failed or was dropped
+        if indexed == 0 {
+            report.files_skipped += 1;
+            continue;
+        }
+        report.chunks_indexed += indexed;
+        report.files_indexed += 1;
+        *report
+            .languages
+            .entry(language.as_str().to_string())
+            .or_insert(0) += 1;
+    }
+
+    batch
+        .commit()
+        .map_err(|e| format!("commit failed: {}", e))?;
+
+    info!(
+        repository = %report.repository,
+        files = report.files_indexed,
+        skipped = report.files_skipped,
+        chunks = report.chunks_indexed,
+        "Code repository indexed"
+    );
+    Ok(report)
+}
+
+/// Split one source file into `Chunk`s carrying symbol, path and line metadata
+pub fn chunk_source(
+    path_label: &str,
+    language: CodeLanguage,
+    source: &str,
+    max_tokens: usize,
+) -> Vec<Chunk> {
+    let now = chrono::Utc::now().timestamp();
+    let line_offsets = line_start_offsets(source);
+
+    split_symbols(language, source, max_tokens)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, sym)| {
+            let start_char = line_offsets[sym.start_line - 1];
+            let end_char = line_offsets
+                .get(sym.end_line)
+                .copied()
+                .unwrap_or(source.len());
+
+            let mut extra = HashMap::new();
+            extra.insert("language".to_string(), language.as_str().to_string());
+            extra.insert("symbol".to_string(), sym.name.clone());
+            extra.insert("symbol_kind".to_string(), sym.kind.as_str().to_string());
+            extra.insert("path".to_string(), path_label.to_string());
+            extra.insert("line_start".to_string(), sym.start_line.to_string());
+            extra.insert("line_end".to_string(), sym.end_line.to_string());
+            if let Some((n, total)) = sym.part {
+                extra.insert("part".to_string(), format!("{}/{}", n, total));
+            }
+
+            Chunk {
+                id: Uuid::new_v4().to_string(),
+                token_count: estimate_token_count(&sym.text),
+                content: sym.text,
+                chunk_index: idx,
+                metadata: ChunkMetadata {
+                    document_id: path_label.to_string(),
+                    source: path_label.to_string(),
+                    source_type: SourceType::Code,
+                    created_at: now,
+                    start_char,
+                    end_char,
+                    extra,
+                    parent_id: None,
+                    child_ids: Vec::new(),
+                },
+            }
+        })
+        .collect()
+}
+
+// ─────────────────────────────────────────────────────────────
+// Definition search
+// ─────────────────────────────────────────────────────────────
+
+static DEFINITION_OF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^\s*(?:(?:show|find|go\s+to)\s+(?:the\s+)?)?definition\s+of\s+`?([A-Za-z_][\w:.]*)`?\s*\??\s*$")
+        .unwrap()
+});
+static WHERE_DEFINED: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)^\s*where\s+is\s+`?([A-Za-z_][\w:.]*)`?\s+(?:defined|declared|implemented)\s*\??\s*$",
+    )
+    .unwrap()
+});
+
+/// Recognise "definition of X" / "where is X defined" and return `X`
+pub fn parse_definition_query(query: &str) -> Option<String> {
+    DEFINITION_OF
+        .captures(query)
+        .or_else(|| WHERE_DEFINED.captures(query))
+        .map(|caps| caps[1].to_string())
+}
+
+/// Look up code chunks whose symbol matches `symbol`; exact name matches rank first
+pub fn find_definitions(
+    retriever: &Retriever,
+    symbol: &str,
+    limit: usize,
+) -> Result<Vec<DefinitionHit>, String> {
+    let cleaned: String = symbol
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    if cleaned.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let query = format!("title:\"{}\"", cleaned.trim());
+    let hits = retriever
+        .search_hits(&query, limit.max(1) * 4)
+        .map_err(|e| e.to_string())?;
+
+    let last_segment = symbol.rsplit([':', '.']).next().unwrap_or(symbol);
+    let mut defs: Vec<(bool, DefinitionHit)> = hits
+        .into_iter()
+        .filter_map(|hit| {
+            let fields = hit.fields;
+            let kind = SymbolKind::from_label(fields.symbol_kind.as_deref()?)?;
+            if kind == SymbolKind::Preamble {
+                return None;
+            }
+            let name = fields.symbol?;
+            let repository = fields.repository?;
+            let path = fields.path?;
+            let start_line = fields.line_start? as usize;
+            let end_line = fields.line_end? as usize;
+            let exact = name == symbol
+                || name.ends_with(&format!("::{}", last_segment))
+                || name.ends_with(&format!(".{}", last_segment))
+                || name == last_segment;
+            let snippet = hit.content.lines().take(12).collect::<Vec<_>>().join("\n");
+            Some((
+                exact,
+                DefinitionHit {
+                    link: format!("{}/{}#L{}-L{}", repository, path, start_line, end_line),
+                    symbol: name,
+                    kind: kind.as_str().to_string(),
+                    repository,
+                    path,
+                    start_line,
+                    end_line,
+                    snippet,
+                    score: hit.score,
+                },
+            ))
+        })
+        .collect();
+
+    // Stable sort keeps BM25 order within each group
+    defs.sort_by_key(|(exact, _)| !*exact);
+    Ok(defs.into_iter().map(|(_, d)| d).take(limit).collect())
+}
+
+// ─────────────────────────────────────────────────────────────
+// Syntax-aware splitting
+// ─────────────────────────────────────────────────────────────
+
+/// Split a source file into symbols, keeping each within `max_tokens`
+/// where possible (containers are split into members, then line windows).
+pub fn split_symbols(language: CodeLanguage, source: &str, max_tokens: usize) -> Vec<CodeSymbol> {
+    let lines: Vec<&str> = source.lines().collect();
+    if lines.is_empty() {
+        return Vec::new();
+    }
+    let infos = scan_lines(language, &lines);
+    let file = SourceFile {
+        language,
+        lines: &lines,
+        infos: &infos,
+    };
+
+    let spans = file.items(0, lines.len(), Scope::TopLevel);
+    let mut symbols = Vec::new();
+    for span in spans {
+        file.finalize(span, max_tokens.max(1), &mut symbols);
+    }
+    symbols
+}
+
+#[derive(Debug, Clone)]
+struct Span {
+    name: String,
+    kind: SymbolKind,
+    /// 0-based, inclusive
+    start: usize,
+    end: usize,
+    /// Bracket depth (brace languages) or indentation (Python) of the header
+    level: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LineInfo {
+    /// Bracket depth at the start of the line
+    start_depth: usize,
+    /// Bracket depth after the line
+    end_depth: usize,
+    /// Line contains a `{` outside strings and comments
+    opens_brace: bool,
+    /// Line starts inside a string or block comment
+    continuation: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LexState {
+    Code,
+    BlockComment,
+    Str { delim: char, escapes: bool },
+    RustRawStr { hashes: usize },
+    PyTriple { delim: char },
+}
+
+/// Track bracket depth per line, ignoring brackets in strings and comments
+fn scan_lines(language: CodeLanguage, lines: &[&str]) -> Vec<LineInfo> {
+    let mut infos = Vec::with_capacity(lines.len());
+    let mut state = LexState::Code;
+    let mut depth = 0usize;
+
+    for line in lines {
+        let mut info = LineInfo {
+            start_depth: depth,
+            continuation: state != LexState::Code,
+            ..LineInfo::default()
+        };
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let next = chars.get(i + 1).copied();
+            match state {
+                LexState::BlockComment => {
+                    if c == '*' && next == Some('/') {
+                        state = LexState::Code;
+                        i += 1;
+                    }
+                }
+                LexState::Str { delim, escapes } => {
+                    if escapes && c == '\\' {
+                        i += 1;
+                    } else if c == delim {
+                        state = LexState::Code;
+                    }
+                }
+                LexState::RustRawStr { hashes } => {
+                    if c == '"'
+                        && chars[i + 1..].iter().take_while(|&&h| h == '#').count() >= hashes
+                    {
+                        state = LexState::Code;
+                        i += hashes;
+                    }
+                }
+                LexState::PyTriple { delim } => {
+                    if c == '\\' {
+                        i += 1;
+                    } else if c == delim && next == Some(delim) && chars.get(i + 2) == Some(&delim)
+                    {
+                        state = LexState::Code;
+                        i += 2;
+                    }
+                }
+                LexState::Code => {
+                    let comment_start = match language {
+                        CodeLanguage::Python => c == '#',
+                        _ => c == '/' && next == Some('/'),
+                    };
+                    if comment_start {
+                        break;
+                    }
+                    if language != CodeLanguage::Python && c == '/' && next == Some('*') {
+                        state = LexState::BlockComment;
+                        i += 2;
+                        continue;
+                    }
+                    match (language, c) {
+                        (_, '{') => {
+                            info.opens_brace = true;
+                            depth += 1;
+                        }
+                        (_, '(') | (_, '[') => depth += 1,
+                        (_, '}') | (_, ')') | (_, ']') => depth = depth.saturating_sub(1),
+                        (CodeLanguage::Python, '"') | (CodeLanguage::Python, '\'') => {
+                            if next == Some(c) && chars.get(i + 2) == Some(&c) {
+                                state = LexState::PyTriple { delim: c };
+                                i += 2;
+                            } else {
+                                state = LexState::Str {
+                                    delim: c,
+                                    escapes: true,
+                                };
+                            }
+                        }
+                        (CodeLanguage::Rust, 'r')
+                            if (i == 0 || !is_ident_char(chars[i - 1]))
+                                && matches!(next, Some('"') | Some('#')) =>
+                        {
+                            let hashes = chars[i + 1..].iter().take_while(|&&h| h == '#').count();
+                            if chars.get(i + 1 + hashes) == Some(&'"') {
+                                state = LexState::RustRawStr { hashes };
+                                i += hashes + 1;
+                            }
+                        }
+                        (CodeLanguage::Rust, '\'') => {
+                            // Char literal ('x', '\n', '{') vs lifetime ('a)
+                            if next == Some('\\') {
+                                let close = chars[i + 2..].iter().position(|&ch| ch == '\'');
+                                i += close.map(|p| p + 2).unwrap_or(0);
+                            } else if chars.get(i + 2) == Some(&'\'') {
+                                i += 2;
+                            }
+                        }
+                        (_, '"') => {
+                            state = LexState::Str {
+                                delim: '"',
+                                escapes: true,
+                            }
+                        }
+                        (CodeLanguage::Go, '\'') | (CodeLanguage::TypeScript, '\'') => {
+                            state = LexState::Str {
+                                delim: '\'',
+                                escapes: true,
+                            }
+                        }
+                        (CodeLanguage::Go, '`') => {
+                            state = LexState::Str {
+                                delim: '`',
+                                escapes: false,
+                            }
+                        }
+                        (CodeLanguage::TypeScript, '`') => {
+                            state = LexState::Str {
+                                delim: '`',
+                                escapes: true,
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            i += 1;
+        }
+        // Plain single-quoted strings never span lines in Go/TS/Python
+        if let LexState::Str { delim, .. } = state {
+            if delim == '\'' || (delim == '"' && language != CodeLanguage::Rust) {
+                state = LexState::Code;
+            }
+        }
+        info.end_depth = depth;
+        infos.push(info);
+    }
+    infos
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope<'a> {
+    TopLevel,
+    /// Inside a container; carries the container name and kind
+    Member(&'a str, SymbolKind),
+}
+
+struct SourceFile<'a> {
+    language: CodeLanguage,
+    lines: &'a [&'a str],
+    infos: &'a [LineInfo],
+}
+
+impl SourceFile<'_> {
+    /// Find item spans in `[lo, hi)` at the scope's nesting level; lines outside
+    /// any item become preamble spans.
+    fn items(&self, lo: usize, hi: usize, scope: Scope) -> Vec<Span> {
+        let level = self.scope_level(lo, hi, scope);
+        let mut spans = Vec::new();
+        let mut loose_start: Option<usize> = None;
+        let mut floor = lo;
+        let mut i = lo;
+
+        while i < hi {
+            let header = if self.is_item_start(i, level) {
+                self.match_header(self.lines[i].trim(), scope)
+            } else {
+                None
+            };
+
+            match header {
+                Some((kind, name)) => {
+                    let start = self.attach_leading(i, floor, level);
+                    let end = self.item_end(i, hi, level);
+                    if let Some(ls) = loose_start.take() {
+                        if ls < start {
+                            self.push_loose(&mut spans, ls, start - 1, level, scope);
+                        }
+                    }
+                    spans.push(Span {
+                        name,
+                        kind,
+                        start,
+                        end,
+                        level,
+                    });
+                    floor = end + 1;
+                    i = end + 1;
+                }
+                None => {
+                    if loose_start.is_none() {
+                        loose_start = Some(i);
+                    }
+                    i += 1;
+                }
+            }
+        }
+        if let Some(ls) = loose_start {
+            self.push_loose(&mut spans, ls, hi - 1, level, scope);
+        }
+        spans
+    }
+
+    fn push_loose(
+        &self,
+        spans: &mut Vec<Span>,
+        start: usize,
+        end: usize,
+        level: usize,
+        scope: Scope,
+    ) {
+        if self.lines[start..=end].iter().all(|l| l.trim().is_empty()) {
+            return;
+        }
+        let (name, kind) = match scope {
+            Scope::TopLevel => ("<module>".to_string(), SymbolKind::Preamble),
+            Scope::Member(container, kind) => (container.to_string(), kind),
+        };
+        spans.push(Span {
+            name,
+            kind,
+            start,
+            end,
+            level,
+        });
+    }
+
+    /// Nesting level items are expected at inside `[lo, hi)`
+    fn scope_level(&self, lo: usize, hi: usize, scope: Scope) -> usize {
+        match (self.language, scope) {
+            (_, Scope::TopLevel) => 0,
+            (CodeLanguage::Python, Scope::Member(..)) => (lo + 1..hi)
+                .find(|&j| {
+                    !self.infos[j].continuation
+                        && self.infos[j].start_depth == 0
+                        && !self.lines[j].trim().is_empty()
+                        && !self.lines[j].trim_start().starts_with('#')
+                })
+                .map(|j| indent_of(self.lines[j]))
+                .unwrap_or(usize::MAX),
+            (_, Scope::Member(..)) => self.infos[lo].start_depth,
+        }
+    }
+
+    fn is_item_start(&self, i: usize, level: usize) -> bool {
+        let info = self.infos[i];
+        if info.continuation {
+            return false;
+        }
+        match self.language {
+            CodeLanguage::Python => info.start_depth == 0 && indent_of(self.lines[i]) == level,
+            _ => info.start_depth == level,
+        }
+    }
+
+    /// Pull doc comments, attributes and decorators directly above a header into the item
+    fn attach_leading(&self, header: usize, floor: usize, level: usize) -> usize {
+        let mut start = header;
+        while start > floor {
+            let prev = self.lines[start - 1].trim();
+            let attached = match self.language {
+                CodeLanguage::Rust => prev.starts_with("//") || prev.starts_with("#["),
+                CodeLanguage::Go => prev.starts_with("//"),
+                CodeLanguage::TypeScript => {
+                    prev.starts_with("//")
+                        || prev.starts_with("/*")
+                        || prev.starts_with('*')
+                        || prev.starts_with('@')
+                }
+                CodeLanguage::Python => {
+                    (prev.starts_with('#') || prev.starts_with('@'))
+                        && indent_of(self.lines[start - 1]) == level
+                }
+            };
+            if !attached || prev.is_empty() {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    }
+
+    /// Last line (inclusive) of the item whose header is at `header`
+    fn item_end(&self, header: usize, hi: usize, level: usize) -> usize {
+        if self.language == CodeLanguage::Python {
+            let mut last = header;
+            for j in header + 1..hi {
+                let info = self.infos[j];
+                let line = self.lines[j];
+                if line.trim().is_empty() || info.continuation || info.start_depth > 0 {
+                    continue;
+                }
+                if indent_of(line) <= level {
+                    break;
+                }
+                last = j;
+            }
+            // Header line may itself continue over a bracketed signature
+            while last + 1 < hi && self.infos[last].end_depth > 0 {
+                last += 1;
+            }
+            return last;
+        }
+
+        let header_indent = indent_of(self.lines[header]);
+        let mut seen_brace = false;
+        for j in header..hi {
+            seen_brace |= self.infos[j].opens_brace;
+            if self.infos[j].end_depth > level {
+                continue;
+            }
+            if seen_brace || self.lines[j].trim_end().ends_with(';') {
+                return j;
+            }
+            let continues = self.lines.get(j + 1).is_some_and(|next| {
+                let t = next.trim_start();
+                j + 1 < hi
+                    && !t.is_empty()
+                    && (indent_of(next) > header_indent
+                        || t.starts_with('{')
+                        || t.starts_with("where"))
+            });
+            if !continues {
+                return j;
+            }
+        }
+        hi - 1
+    }
+
+    fn match_header(&self, line: &str, scope: Scope) -> Option<(SymbolKind, String)> {
+        let (kind, name) = match (self.language, scope) {
+            (CodeLanguage::Rust, _) => match_rust(line)?,
+            (CodeLanguage::Go, _) => match_go(line)?,
+            (CodeLanguage::Python, _) => match_python(line)?,
+            (CodeLanguage::TypeScript, Scope::Member(_, SymbolKind::Class)) => {
+                match_ts_member(line)?
+            }
+            (CodeLanguage::TypeScript, _) => match_typescript(line)?,
+        };
+        match scope {
+            Scope::TopLevel => Some((kind, name)),
+            Scope::Member(container, _) => {
+                let kind = if kind == SymbolKind::Function {
+                    SymbolKind::Method
+                } else {
+                    kind
+                };
+                Some((
+                    kind,
+                    format!("{}{}{}", container, self.language.member_separator(), name),
+                ))
+            }
+        }
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.lines[start..=end].join("\n")
+    }
+
+    /// Emit a span, splitting oversized containers into members and
+    /// anything else that is still too large into line windows.
+    fn finalize(&self, span: Span, max_tokens: usize, out: &mut Vec<CodeSymbol>) {
+        let text = self.text(span.start, span.end);
+        if estimate_token_count(&text) <= max_tokens {
+            out.push(CodeSymbol {
+                name: span.name,
+                kind: span.kind,
+                start_line: span.start + 1,
+                end_line: span.end + 1,
+                text,
+                part: None,
+            });
+            return;
+        }
+
+        if span.kind.is_container() && span.end > span.start + 1 {
+            let body_lo = self.body_start(&span);
+            let members = self.items(body_lo, span.end + 1, Scope::Member(&span.name, span.kind));
+            if members
+                .iter()
+                .any(|m| m.kind != span.kind || m.name != span.name)
+            {
+                // Header lines before the body keep the container's name
+                let mut parts = Vec::new();
+                if body_lo > span.start {
+                    parts.push(Span {
+                        end: body_lo - 1,
+                        ..span.clone()
+                    });
+                }
+                parts.extend(members);
+                parts.sort_by_key(|s| s.start);
+                for part in merge_adjacent(parts) {
+                    self.finalize_leaf(part, max_tokens, out);
+                }
+                return;
+            }
+        }
+
+        self.finalize_leaf(span, max_tokens, out);
+    }
+
+    /// Like `finalize` but never descends into members
+    fn finalize_leaf(&self, span: Span, max_tokens: usize, out: &mut Vec<CodeSymbol>) {
+        let text = self.text(span.start, span.end);
+        if estimate_token_count(&text) <= max_tokens {
+            out.push(CodeSymbol {
+                name: span.name,
+                kind: span.kind,
+                start_line: span.start + 1,
+                end_line: span.end + 1,
+                text,
+                part: None,
+            });
+            return;
+        }
+
+        let mut windows: Vec<(usize, usize)> = Vec::new();
+        let mut win_start = span.start;
+        let mut tokens = 0usize;
+        for j in span.start..=span.end {
+            let line_tokens = estimate_token_count(self.lines[j]).max(1);
+            if tokens + line_tokens > max_tokens && j > win_start {
+                windows.push((win_start, j - 1));
+                win_start = j;
+                tokens = 0;
+            }
+            tokens += line_tokens;
+        }
+        windows.push((win_start, span.end));
+
+        let total = windows.len();
+        for (n, (start, end)) in windows.into_iter().enumerate() {
+            out.push(CodeSymbol {
+                name: span.name.clone(),
+                kind: span.kind,
+                start_line: start + 1,
+                end_line: end + 1,
+                text: self.text(start, end),
+                part: Some((n + 1, total)),
+            });
+        }
+    }
+
+    /// First line of a container's body (the line after the header/opening brace)
+    fn body_start(&self, span: &Span) -> usize {
+        if self.language == CodeLanguage::Python {
+            let mut j = span.start;
+            // Skip decorators and the (possibly multi-line) class signature
+            while j < span.end && !self.lines[j].trim_end().ends_with(':') {
+                j += 1;
+            }
+            return (j + 1).min(span.end);
+        }
+        (span.start..=span.end)
+            .find(|&j| self.infos[j].opens_brace && self.infos[j].end_depth > span.level)
+            .map(|j| j + 1)
+            .unwrap_or(span.start + 1)
+    }
+}
+
+/// Merge consecutive spans that share name and kind (e.g. a container header
+/// and the loose lines following it)
+fn merge_adjacent(spans: Vec<Span>) -> Vec<Span> {
+    let mut merged: Vec<Span> = Vec::with_capacity(spans.len());
+    for span in spans {
+        if let Some(last) = merged.last_mut() {
+            if last.name == span.name && last.kind == span.kind && last.end + 1 >= span.start {
+                last.end = last.end.max(span.end);
+                continue;
+            }
+        }
+        merged.push(span);
+    }
+    merged
+}
+
+fn indent_of(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn line_start_offsets(source: &str) -> Vec<usize> {
+    let mut offsets = vec![0];
+    offsets.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+    offsets
+}
+
+// ─────────────────────────────────────────────────────────────
+// Per-language header patterns
+// ─────────────────────────────────────────────────────────────
+
+const RUST_VIS: &str = r"^(?:pub(?:\([^)]*\))?\s+)?";
+
+static RUST_PATTERNS: Lazy<Vec<(SymbolKind, Regex)>> = Lazy::new(|| {
+    let p = |kind, body: &str| (kind, Regex::new(&format!("{}{}", RUST_VIS, body)).unwrap());
+    vec![
+        p(
+            SymbolKind::Function,
+            r#"(?:default\s+)?(?:const\s+)?(?:async\s+)?(?:unsafe\s+)?(?:extern\s+"[^"]*"\s+)?fn\s+([A-Za-z_]\w*)"#,
+        ),
+        p(SymbolKind::Struct, r"(?:struct|union)\s+([A-Za-z_]\w*)"),
+        p(SymbolKind::Enum, r"enum\s+([A-Za-z_]\w*)"),
+        p(
+            SymbolKind::Trait,
+            r"(?:unsafe\s+)?(?:auto\s+)?trait\s+([A-Za-z_]\w*)",
+        ),
+        p(SymbolKind::TypeAlias, r"type\s+([A-Za-z_]\w*)"),
+        p(
+            SymbolKind::Constant,
+            r"(?:const|static)\s+(?:mut\s+)?([A-Za-z_]\w*)\s*:",
+        ),
+        p(SymbolKind::Module, r"mod\s+([A-Za-z_]\w*)\s*\{"),
+        (
+            SymbolKind::Macro,
+            Regex::new(r"^(?:#\[macro_export\]\s*)?macro_rules!\s*([A-Za-z_]\w*)").unwrap(),
+        ),
+    ]
+});
+
+fn match_rust(line: &str) -> Option<(SymbolKind, String)> {
+    if let Some(name) = rust_impl_target(line) {
+        return Some((SymbolKind::Impl, name));
+    }
+    RUST_PATTERNS
+        .iter()
+        .find_map(|(kind, re)| re.captures(line).map(|c| (*kind, c[1].to_string())))
+}
+
+/// `impl<T> fmt::Display for Wrapper<T> {` -> `Wrapper`
+fn rust_impl_target(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("unsafe ").unwrap_or(line);
+    let rest = rest.strip_prefix("impl")?;
+    if !rest.starts_with([' ', '<']) {
+        return None;
+    }
+    let mut rest = rest.trim_start();
+    if rest.starts_with('<') {
+        let mut depth = 0usize;
+        let mut cut = rest.len();
+        for (i, c) in rest.char_indices() {
+            match c {
+                '<' => depth += 1,
+                '>' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        cut = i + 1;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        rest = rest[cut..].trim_start();
+    }
+    let rest = rest.split(['{']).next().unwrap_or(rest);
+    let rest = rest.split(" where").next().unwrap_or(rest);
+    let target = rest.rsplit(" for ").next().unwrap_or(rest).trim();
+    let target = target.split('<').next().unwrap_or(target);
+    let target = target.rsplit("::").next().unwrap_or(target).trim();
+    if target.is_empty() {
+        None
+    } else {
+        Some(target.to_string())
+    }
+}
+
+static GO_METHOD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^func\s*\(\s*\w*\s+\*?\s*([A-Za-z_]\w*)(?:\[[^\]]*\])?\s*\)\s*([A-Za-z_]\w*)")
+        .unwrap()
+});
+static GO_PATTERNS: Lazy<Vec<(SymbolKind, Regex)>> = Lazy::new(|| {
+    vec![
+        (
+            SymbolKind::Function,
+            Regex::new(r"^func\s+([A-Za-z_]\w*)").unwrap(),
+        ),
+        (
+            SymbolKind::Struct,
+            Regex::new(r"^type\s+([A-Za-z_]\w*)(?:\[[^\]]*\])?\s+struct\b").unwrap(),
+        ),
+        (
+            SymbolKind::Interface,
+            Regex::new(r"^type\s+([A-Za-z_]\w*)(?:\[[^\]]*\])?\s+interface\b").unwrap(),
+        ),
+        (
+            SymbolKind::TypeAlias,
+            Regex::new(r"^type\s+([A-Za-z_]\w*)").unwrap(),
+        ),
+    ]
+});
+
+fn match_go(line: &str) -> Option<(SymbolKind, String)> {
+    if let Some(c) = GO_METHOD.captures(line) {
+        return Some((SymbolKind::Method, format!("{}.{}", &c[1], &c[2])));
+    }
+    GO_PATTERNS
+        .iter()
+        .find_map(|(kind, re)| re.captures(line).map(|c| (*kind, c[1].to_string())))
+}
+
+static PY_PATTERNS: Lazy<Vec<(SymbolKind, Regex)>> = Lazy::new(|| {
+    vec![
+        (
+            SymbolKind::Function,
+            Regex::new(r"^(?:async\s+)?def\s+([A-Za-z_]\w*)").unwrap(),
+        ),
+        (
+            SymbolKind::Class,
+            Regex::new(r"^class\s+([A-Za-z_]\w*)").unwrap(),
+        ),
+    ]
+});
+
+fn match_python(line: &str) -> Option<(SymbolKind, String)> {
+    PY_PATTERNS
+        .iter()
+        .find_map(|(kind, re)| re.captures(line).map(|c| (*kind, c[1].to_string())))
+}
+
+const TS_PREFIX: &str = r"^(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?";
+
+static TS_PATTERNS: Lazy<Vec<(SymbolKind, Regex)>> = Lazy::new(|| {
+    let p = |kind, body: &str| (kind, Regex::new(&format!("{}{}", TS_PREFIX, body)).unwrap());
+    vec![
+        p(
+            SymbolKind::Function,
+            r"(?:async\s+)?function\s*\*?\s*([A-Za-z_$][\w$]*)",
+        ),
+        p(SymbolKind::Class, r"class\s+([A-Za-z_$][\w$]*)"),
+        p(SymbolKind::Interface, r"interface\s+([A-Za-z_$][\w$]*)"),
+        p(SymbolKind::Enum, r"(?:const\s+)?enum\s+([A-Za-z_$][\w$]*)"),
+        p(SymbolKind::TypeAlias, r"type\s+([A-Za-z_$][\w$]*)"),
+        p(
+            SymbolKind::Module,
+            r"(?:namespace|module)\s+([A-Za-z_$][\w$.]*)",
+        ),
+        p(
+            SymbolKind::Function,
+            r"(?:const|let|var)\s+([A-Za-z_$][\w$]*)\s*(?::[^=]+)?=\s*(?:async\s+)?(?:function\b|\([^)]*\)?\s*(?::[^=]+)?=>|\(|[A-Za-z_$][\w$]*\s*=>)",
+        ),
+    ]
+});
+
+static TS_MEMBER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:(?:public|private|protected|static|readonly|async|override|abstract|get|set|declare)\s+)*\*?\s*(#?[A-Za-z_$][\w$]*)\s*(?:<[^>]*>)?\s*\(")
+        .unwrap()
+});
+static TS_ARROW_MEMBER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:(?:public|private|protected|static|readonly)\s+)*(#?[A-Za-z_$][\w$]*)\s*(?::[^=]+)?=\s*(?:async\s+)?\([^)]*\)\s*(?::[^=]+)?=>")
+        .unwrap()
+});
+
+fn match_typescript(line: &str) -> Option<(SymbolKind, String)> {
+    TS_PATTERNS
+        .iter()
+        .find_map(|(kind, re)| re.captures(line).map(|c| (*kind, c[1].to_string())))
+}
+
+fn match_ts_member(line: &str) -> Option<(SymbolKind, String)> {
+    const KEYWORDS: [&str; 8] = [
+        "if", "for", "while", "switch", "catch", "return", "function", "new",
+    ];
+    let name = TS_MEMBER
+        .captures(line)
+        .or_else(|| TS_ARROW_MEMBER.captures(line))
+        .map(|c| c[1].to_string())?;
+    if KEYWORDS.contains(&name.as_str()) {
+        return None;
+    }
+    Some((SymbolKind::Function, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(symbols: &[CodeSymbol]) -> Vec<(SymbolKind, String)> {
+        symbols.iter().map(|s| (s.kind, s.name.clone())).collect()
+    }
+
+    #[test]
+    fn test_rust_items_and_line_ranges() {
+        let src = r#"use std::fmt;
+
+/// Parsed settings
+#[derive(Debug)]
+pub struct Config {
+    name: String,
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{}}", self.name)
+    }
+}
+
+pub async fn parse_config(raw: &str) -> Config {
+    let brace = '{';
+    let s = "not a } brace";
+    Config { name: format!("{}{}", raw, brace) + s }
+}
+"#;
+        let symbols = split_symbols(CodeLanguage::Rust, src, 512);
+        assert_eq!(
+            names(&symbols),
+            vec![
+                (SymbolKind::Preamble, "<module>".to_string()),
+                (SymbolKind::Struct, "Config".to_string()),
+                (SymbolKind::Impl, "Config".to_string()),
+                (SymbolKind::Function, "parse_config".to_string()),
+            ]
+        );
+        // Doc comment and attribute are attached to the struct
+        assert_eq!((symbols[1].start_line, symbols[1].end_line), (3, 7));
+        assert_eq!((symbols[2].start_line, symbols[2].end_line), (9, 13));
+        assert_eq!((symbols[3].start_line, symbols[3].end_line), (15, 19));
+    }
+
+    #[test]
+    fn test_oversized_impl_is_split_into_methods() {
+        let mut src = String::from("impl Store {\n    const LIMIT: usize = 3;\n\n");
+        for name in ["insert", "remove", "lookup"] {
+            src.push_str(&format!("    pub fn {}(&mut self) {{\n", name));
+            for i in 0..20 {
+                src.push_str(&format!("        let value_{} = self.compute({});\n", i, i));
+            }
+            src.push_str("    }\n\n");
+        }
+        src.push_str("}\n");
+
+        let symbols = split_symbols(CodeLanguage::Rust, &src, 200);
+        let method_names: Vec<_> = symbols
+            .iter()
+            .filter(|s| s.kind == SymbolKind::Method)
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(
+            method_names,
+            vec!["Store::insert", "Store::remove", "Store::lookup"]
+        );
+        assert!(symbols.iter().any(|s| s.kind == SymbolKind::Impl));
+        assert!(symbols.iter().all(|s| estimate_token_count(&s.text) <= 200));
+    }
+
+    #[test]
+    fn test_python_classes_and_functions() {
+        let src = r#"import os
+
+@dataclass
+class Loader:
+    """Loads "things" from disk."""
+
+    def load(self, path):
+        return open(path).read()
+
+async def main(
+    argv,
+):
+    await Loader().load(argv[0])
+"#;
+        let symbols = split_symbols(CodeLanguage::Python, src, 512);
+        assert_eq!(
+            names(&symbols),
+            vec![
+                (SymbolKind::Preamble, "<module>".to_string()),
+                (SymbolKind::Class, "Loader".to_string()),
+                (SymbolKind::Function, "main".to_string()),
+            ]
+        );
+        assert_eq!((symbols[1].start_line, symbols[1].end_line), (3, 8));
+        assert_eq!((symbols[2].start_line, symbols[2].end_line), (10, 13));
+
+        let small = split_symbols(CodeLanguage::Python, src, 20);
+        assert!(small
+            .iter()
+            .any(|s| s.name == "Loader.load" && s.kind == SymbolKind::Method));
+    }
+
+    #[test]
+    fn test_typescript_and_go_headers() {
+        let ts = r#"import { x } from "./x";
+
+export interface Options {
+  depth: number;
+}
+
+export const handler = async (req: Request): Promise<Response> => {
+  return new Response(`{${req.url}}`);
+};
+
+export default class Crawler {
+  private seen = new Set<string>();
+
+  async crawl(url: string) {
+    if (this.seen.has(url)) { return; }
+  }
+}
+"#;
+        let symbols = split_symbols(CodeLanguage::TypeScript, ts, 512);
+        let found = names(&symbols);
+        assert!(found.contains(&(SymbolKind::Interface, "Options".to_string())));
+        assert!(found.contains(&(SymbolKind::Function, "handler".to_string())));
+        assert!(found.contains(&(SymbolKind::Class, "Crawler".to_string())));
+
+        let split = split_symbols(CodeLanguage::TypeScript, ts, 15);
+        assert!(split.iter().any(|s| s.name == "Crawler.crawl"));
+
+        let go = "package main\n\ntype Server struct {\n\taddr string\n}\n\nfunc (s *Server) Start() error {\n\treturn nil\n}\n\nfunc main() {\n\tfmt.Println(`}`)\n}\n";
+        let symbols = split_symbols(CodeLanguage::Go, go, 512);
+        assert_eq!(
+            names(&symbols),
+            vec![
+                (SymbolKind::Preamble, "<module>".to_string()),
+                (SymbolKind::Struct, "Server".to_string()),
+                (SymbolKind::Method, "Server.Start".to_string()),
+                (SymbolKind::Function, "main".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chunk_metadata() {
+        let chunks = chunk_source(
+            "repo/src/lib.rs",
+            CodeLanguage::Rust,
+            "fn a() {}\n\nfn b() {\n}\n",
+            512,
+        );
+        assert_eq!(chunks.len(), 2);
+        let extra = &chunks[1].metadata.extra;
+        assert_eq!(extra["symbol"], "b");
+        assert_eq!(extra["symbol_kind"], "fn");
+        assert_eq!(extra["path"], "repo/src/lib.rs");
+        assert_eq!(
+            (extra["line_start"].as_str(), extra["line_end"].as_str()),
+            ("3", "4")
+        );
+        assert!(matches!(chunks[1].metadata.source_type, SourceType::Code));
+        assert_eq!(chunks[1].metadata.start_char, 11);
+    }
+
+    #[test]
+    fn test_parse_definition_query() {
+        assert_eq!(
+            parse_definition_query("definition of parse_config"),
+            Some("parse_config".to_string())
+        );
+        assert_eq!(
+            parse_definition_query("Where is `Retriever::search` defined?"),
+            Some("Retriever::search".to_string())
+        );
+        assert_eq!(parse_definition_query("where is the config stored"), None);
+    }
+
+    #[test]
+    fn test_index_repository_respects_gitignore_and_finds_definitions() {
+        let repo = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(repo.path().join("src")).unwrap();
+        std::fs::create_dir_all(repo.path().join("target")).unwrap();
+        std::fs::write(repo.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(
+            repo.path().join("src/config.rs"),
+            "use std::env;\n\npub fn load_settings() -> String {\n    env::var(\"X\").unwrap_or_default()\n}\n",
+        )
+        .unwrap();
+        std::fs::write(repo.path().join("target/gen.rs"), "fn load_settings() {}\n").unwrap();
+        std::fs::write(repo.path().join("notes.txt"), "not code").unwrap();
+        std::fs::write(repo.path().join("src/empty.rs"), "").unwrap();
+
+        let config = CodeIngestConfig {
+            allowed_roots: vec![repo.path().to_path_buf()],
+            ..CodeIngestConfig::default()
+        };
+        let files = walk_repository(repo.path(), &config);
+        assert_eq!(files.len(), 2);
+
+        let index_dir = tempfile::tempdir().unwrap();
+        let mut retriever = Retriever::new_with_vector_file(
+            index_dir.path().to_str().unwrap(),
+            index_dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+
+        let report = index_repository(&mut retriever, repo.path(), &config).unwrap();
+        // empty.rs has nothing to index
+        assert_eq!(report.files_indexed, 1);
+        assert_eq!(report.files_skipped, 1);
+        assert_eq!(report.chunks_indexed, 2);
+        assert_eq!(report.languages.get("rust"), Some(&1));
+
+        let defs = find_definitions(&retriever, "load_settings", 5).unwrap();
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].kind, "fn");
+        assert_eq!((defs[0].start_line, defs[0].end_line), (3, 5));
+        assert!(defs[0].link.ends_with("/src/config.rs#L3-L5"));
+    }
+
+    #[test]
+    fn test_reindexing_replaces_previous_chunks() {
+        let repo = tempfile::tempdir().unwrap();
+        let file = repo.path().join("lib.rs");
+        std::fs::write(&file, "pub fn load_settings() {}\n").unwrap();
+        let index_dir = tempfile::tempdir().unwrap();
+        let mut retriever = Retriever::new_with_vector_file(
+            index_dir.path().to_str().unwrap(),
+            index_dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        let config = CodeIngestConfig {
+            allowed_roots: vec![repo.path().to_path_buf()],
+            ..CodeIngestConfig::default()
+        };
+
+        index_repository(&mut retriever, repo.path(), &config).unwrap();
+        index_repository(&mut retriever, repo.path(), &config).unwrap();
+        assert_eq!(
+            find_definitions(&retriever, "load_settings", 5)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        std::fs::write(&file, "pub fn save_settings() {}\n").unwrap();
+        index_repository(&mut retriever, repo.path(), &config).unwrap();
+        assert!(find_definitions(&retriever, "load_settings", 5)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            find_definitions(&retriever, "save_settings", 5)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_repositories_with_the_same_name_stay_apart() {
+        let parents = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        for (parent, name) in parents.iter().zip(["load_settings", "save_settings"]) {
+            std::fs::create_dir_all(parent.path().join("app")).unwrap();
+            std::fs::write(
+                parent.path().join("app/lib.rs"),
+                format!("pub fn {}() {{}}\n", name),
+            )
+            .unwrap();
+        }
+        let index_dir = tempfile::tempdir().unwrap();
+        let mut retriever = Retriever::new_with_vector_file(
+            index_dir.path().to_str().unwrap(),
+            index_dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        let config = CodeIngestConfig {
+            allowed_roots: parents.iter().map(|p| p.path().to_path_buf()).collect(),
+            ..CodeIngestConfig::default()
+        };
+        for parent in &parents {
+            index_repository(&mut retriever, &parent.path().join("app"), &config).unwrap();
+        }
+        // Re-indexing the first checkout leaves the second one alone
+        index_repository(&mut retriever, &parents[0].path().join("app"), &config).unwrap();
+
+        let load = find_definitions(&retriever, "load_settings", 5).unwrap();
+        let save = find_definitions(&retriever, "save_settings", 5).unwrap();
+        assert_eq!((load.len(), save.len()), (1, 1));
+        assert_ne!(load[0].repository, save[0].repository);
+        assert_eq!(load[0].path, "lib.rs");
+
+        // Symbol and line range are fields search can filter on
+        let hits = retriever
+            .search_hits(r#"symbol:"save_settings" AND line_start:[1 TO 10]"#, 5)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].fields.symbol_kind.as_deref(), Some("fn"));
+        assert_eq!(hits[0].fields.repository, Some(save[0].repository.clone()));
+        assert!(retriever
+            .search_hits("line_start:[2 TO 10]", 5)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_allowed_roots() {
+        let allowed = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let config = CodeIngestConfig {
+            allowed_roots: vec![allowed.path().to_path_buf()],
+            ..CodeIngestConfig::default()
+        };
+        assert!(config.validate_root(allowed.path()).is_ok());
+        assert!(config.validate_root(other.path()).is_err());
+        // Without configured roots nothing may be ingested
+        assert!(CodeIngestConfig::default()
+            .validate_root(allowed.path())
+            .is_err());
+    }
+}
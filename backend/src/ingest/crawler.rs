@@ -0,0 +1,936 @@
+// src/ingest/crawler.rs
+// Web crawler ingestion: breadth-first crawl from seed URLs
+//
+// Links are followed within the allowed domains and path prefixes up to
+// `max_depth` and `max_pages`. robots.txt rules and Crawl-delay are honoured
+// per host, pages are deduplicated by canonical URL and by content hash, and
+// page text comes from `html::extract_page`.
+
+use super::html::extract_page;
+use crate::embedder;
+use crate::memory::chunker_factory::Chunker;
+use crate::retriever::Retriever;
+use crate::security::redaction;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+pub const DEFAULT_USER_AGENT: &str = "ag-crawler/1.0";
+
+#[derive(Debug, Error)]
+pub enum CrawlError {
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("http client error: {0}")]
+    Client(String),
+}
+
+/// Crawl job settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrawlConfig {
+    pub seeds: Vec<String>,
+    /// Link hops followed from a seed (seeds are depth 0)
+    pub max_depth: usize,
+    pub max_pages: usize,
+    /// Hosts that may be crawled; defaults to the seed hosts. A leading
+    /// `.` also allows subdomains (".example.com").
+    pub allowed_domains: Vec<String>,
+    /// Server-side limit on the hosts above, in the same syntax. Set by the
+    /// API from its configuration, never by the request (empty = no limit)
+    #[serde(skip)]
+    pub host_allowlist: Vec<String>,
+    /// URL path prefixes that may be crawled (empty = any path)
+    pub path_prefixes: Vec<String>,
+    pub respect_robots: bool,
+    /// Minimum delay between requests to the same host
+    pub delay_ms: u64,
+    /// Upper bound applied to robots.txt Crawl-delay
+    pub max_crawl_delay_ms: u64,
+    pub user_agent: String,
+    pub request_timeout_secs: u64,
+    /// Skip responses larger than this
+    pub max_page_bytes: usize,
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        Self {
+            seeds: Vec::new(),
+            max_depth: 2,
+            max_pages: 100,
+            allowed_domains: Vec::new(),
+            host_allowlist: Vec::new(),
+            path_prefixes: Vec::new(),
+            respect_robots: true,
+            delay_ms: 0,
+            max_crawl_delay_ms: 10_000,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            request_timeout_secs: 20,
+            max_page_bytes: 5 * 1024 * 1024,
+        }
+    }
+}
+
+/// A fetched and extracted page
+#[derive(Debug, Clone, Serialize)]
+pub struct CrawledPage {
+    pub url: String,
+    pub canonical_url: String,
+    pub title: String,
+    pub text: String,
+    pub depth: usize,
+    pub content_hash: u64,
+}
+
+/// Running counters, reported while the crawl is in progress
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CrawlProgress {
+    pub pages_fetched: usize,
+    pub pages_indexed: usize,
+    pub chunks_indexed: usize,
+    pub duplicates_skipped: usize,
+    pub robots_blocked: usize,
+    pub errors: usize,
+    pub queued: usize,
+    pub current_url: Option<String>,
+}
+
+/// Final crawl result
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CrawlReport {
+    pub progress: CrawlProgress,
+    pub indexed_urls: Vec<String>,
+    pub error_messages: Vec<String>,
+    pub duration_ms: u64,
+}
+
+/// Crawl from `config.seeds`, handing every new page to `on_page`, which
+/// resolves to the number of chunks it indexed.
+pub async fn crawl<F, Fut, P>(
+    config: &CrawlConfig,
+    mut on_page: F,
+    mut on_progress: P,
+) -> Result<CrawlReport, CrawlError>
+where
+    F: FnMut(CrawledPage) -> Fut,
+    Fut: std::future::Future<Output = Result<usize, String>>,
+    P: FnMut(&CrawlProgress),
+{
+    let start = Instant::now();
+    let scope = Arc::new(Scope::from_config(config)?);
+    // Redirects are only followed within the scope
+    let redirect_scope = Arc::clone(&scope);
+    let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= 5 {
+            attempt.error("too many redirects")
+        } else if redirect_scope.allows(attempt.url()) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    });
+    let client = reqwest::Client::builder()
+        .user_agent(config.user_agent.clone())
+        .timeout(Duration::from_secs(config.request_timeout_secs.max(1)))
+        .redirect(redirect)
+        .build()
+        .map_err(|e| CrawlError::Client(e.to_string()))?;
+
+    let mut report = CrawlReport::default();
+    let mut queue: VecDeque<(Url, usize)> = VecDeque::new();
+    let mut seen_urls: HashSet<String> = HashSet::new();
+    let mut seen_hashes: HashSet<u64> = HashSet::new();
+    let mut robots: HashMap<String, RobotsRules> = HashMap::new();
+    let mut last_request: HashMap<String, Instant> = HashMap::new();
+
+    for seed in &scope.seeds {
+        if seen_urls.insert(canonicalize(seed)) {
+            queue.push_back((seed.clone(), 0));
+        }
+    }
+
+    while let Some((url, depth)) = queue.pop_front() {
+        if report.progress.pages_fetched >= config.max_pages {
+            break;
+        }
+        let host_key = host_key(&url);
+
+        if config.respect_robots && !robots.contains_key(&host_key) {
+            let rules = fetch_robots(&client, &url, &config.user_agent).await;
+            robots.insert(host_key.clone(), rules);
+        }
+        let rules = robots.get(&host_key);
+        if config.respect_robots && !rules.is_some_and(|r| r.is_allowed(&path_and_query(&url))) {
+            debug!("crawl: robots.txt disallows {}", url);
+            report.progress.robots_blocked += 1;
+            continue;
+        }
+
+        // Politeness delay per host
+        let crawl_delay = rules
+            .and_then(|r| r.crawl_delay)
+            .map(|d| d.min(Duration::from_millis(config.max_crawl_delay_ms)))
+            .unwrap_or_default()
+            .max(Duration::from_millis(config.delay_ms));
+        if let Some(last) = last_request.get(&host_key) {
+            let elapsed = last.elapsed();
+            if elapsed < crawl_delay {
+                tokio::time::sleep(crawl_delay - elapsed).await;
+            }
+        }
+        last_request.insert(host_key, Instant::now());
+
+        report.progress.current_url = Some(url.to_string());
+        report.progress.queued = queue.len();
+        on_progress(&report.progress);
+
+        let fetched = match fetch_html(&client, &url, config.max_page_bytes).await {
+            Ok(Some(fetched)) => fetched,
+            Ok(None) => continue,
+            Err(e) => {
+                report.progress.errors += 1;
+                report.error_messages.push(format!("{}: {}", url, e));
+                continue;
+            }
+        };
+        report.progress.pages_fetched += 1;
+
+        // The redirect policy keeps to the scope; check the final URL anyway
+        let final_url = fetched.final_url;
+        if !scope.allows(&final_url) {
+            continue;
+        }
+        let page = extract_page(&fetched.body);
+        let base = page
+            .base
+            .as_deref()
+            .and_then(|b| final_url.join(b).ok())
+            .unwrap_or_else(|| final_url.clone());
+
+        if depth < config.max_depth && !page.nofollow {
+            for href in &page.links {
+                let Some(link) = resolve_link(&base, href) else {
+                    continue;
+                };
+                if scope.allows(&link) && seen_urls.insert(canonicalize(&link)) {
+                    queue.push_back((link, depth + 1));
+                }
+            }
+        }
+
+        let canonical_url = page
+            .canonical
+            .as_deref()
+            .and_then(|c| final_url.join(c).ok())
+            .filter(|c| scope.allows(c))
+            .map(|c| canonicalize(&c))
+            .unwrap_or_else(|| canonicalize(&final_url));
+        // A redirect target or canonical link may point at a page already indexed
+        let canonical_is_new =
+            seen_urls.insert(canonical_url.clone()) || canonical_url == canonicalize(&url);
+        let content_hash = seahash::hash(page.text.as_bytes());
+        if page.noindex || page.text.trim().is_empty() {
+            continue;
+        }
+        if !canonical_is_new
+            || report.indexed_urls.contains(&canonical_url)
+            || !seen_hashes.insert(content_hash)
+        {
+            report.progress.duplicates_skipped += 1;
+            continue;
+        }
+
+        let crawled = CrawledPage {
+            url: url.to_string(),
+            title: page.title.unwrap_or_else(|| canonical_url.clone()),
+            canonical_url: canonical_url.clone(),
+            text: page.text,
+            depth,
+            content_hash,
+        };
+        match on_page(crawled).await {
+            Ok(chunks) => {
+                report.progress.pages_indexed += 1;
+                report.progress.chunks_indexed += chunks;
+                report.indexed_urls.push(canonical_url);
+            }
+            Err(e) => {
+                report.progress.errors += 1;
+                report.error_messages.push(format!("{}: {}", url, e));
+            }
+        }
+    }
+
+    report.progress.current_url = None;
+    report.progress.queued = queue.len();
+    report.duration_ms = start.elapsed().as_millis() as u64;
+    on_progress(&report.progress);
+    info!(
+        pages = report.progress.pages_indexed,
+        fetched = report.progress.pages_fetched,
+        duplicates = report.progress.duplicates_skipped,
+        blocked = report.progress.robots_blocked,
+        "Crawl finished"
+    );
+    Ok(report)
+}
+
+/// Chunk a crawled page and index it under `<canonical url>#<n>`, replacing
+/// the chunks of an earlier crawl. The page is committed as its own batch.
+pub fn index_page(
+    retriever: &mut Retriever,
+    chunker: &dyn Chunker,
+    page: &CrawledPage,
+) -> Result<usize, String> {
+    // Dropped without commit on any early return, rolling the page back
+    let mut batch = retriever
+        .batch()
+        .map_err(|e| format!("begin_batch failed: {}", e))?;
+    let previous = batch.chunk_ids_with_prefix(&format!("{}#", page.canonical_url));
+    batch
+        .delete_chunks(&previous)
+        .map_err(|e| format!("delete_chunks failed: {}", e))?;
+
+    let mut indexed = 0;
+    let mut redaction = redaction::session(page.canonical_url.as_str());
+    let title = redaction.field(&page.title);
+    for (i, chunk) in chunker.chunk_text(&page.text).iter().enumerate() {
+        let chunk_id = format!("{}#{}", page.canonical_url, i);
+        let Some(chunk) = redaction.chunk(chunk) else {
+            continue;
+        };
+        let vector = embedder::embed(&chunk);
+        batch
+            .index_chunk_with_title(&chunk_id, &title, &chunk, &vector)
+            .map_err(|e| e.to_string())?;
+        indexed += 1;
+    }
+    redaction.finish();
+    batch
+        .commit()
+        .map_err(|e| format!("commit failed: {}", e))?;
+    Ok(indexed)
+}
+
+// ─────────────────────────────────────────────────────────────
+// Scope and URL handling
+// ─────────────────────────────────────────────────────────────
+
+struct Scope {
+    seeds: Vec<Url>,
+    domains: Vec<String>,
+    host_allowlist: Vec<String>,
+    path_prefixes: Vec<String>,
+}
+
+impl Scope {
+    fn from_config(config: &CrawlConfig) -> Result<Self, CrawlError> {
+        let seeds = config
+            .seeds
+            .iter()
+            .map(|s| {
+                Url::parse(s.trim())
+                    .map_err(|e| CrawlError::Config(format!("invalid seed '{}': {}", s, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if seeds.is_empty() {
+            return Err(CrawlError::Config(
+                "at least one seed URL is required".into(),
+            ));
+        }
+        if let Some(bad) = seeds
+            .iter()
+            .find(|u| !matches!(u.scheme(), "http" | "https"))
+        {
+            return Err(CrawlError::Config(format!(
+                "unsupported scheme in '{}'",
+                bad
+            )));
+        }
+
+        let domains = if config.allowed_domains.is_empty() {
+            seeds
+                .iter()
+                .filter_map(|u| u.host_str().map(str::to_lowercase))
+                .collect()
+        } else {
+            config
+                .allowed_domains
+                .iter()
+                .map(|d| d.trim().to_lowercase())
+                .collect()
+        };
+        let host_allowlist: Vec<String> = config
+            .host_allowlist
+            .iter()
+            .map(|d| d.trim().to_lowercase())
+            .collect();
+        if !host_allowlist.is_empty() {
+            if let Some(bad) = seeds.iter().find(|u| {
+                !u.host_str()
+                    .is_some_and(|h| host_allowed(&host_allowlist, &h.to_lowercase()))
+            }) {
+                return Err(CrawlError::Config(format!(
+                    "host of '{}' is not in the crawl allowlist",
+                    bad
+                )));
+            }
+        }
+        Ok(Self {
+            seeds,
+            domains,
+            host_allowlist,
+            path_prefixes: config.path_prefixes.clone(),
+        })
+    }
+
+    fn allows(&self, url: &Url) -> bool {
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+        let Some(host) = url.host_str().map(str::to_lowercase) else {
+            return false;
+        };
+        host_allowed(&self.domains, &host)
+            && (self.host_allowlist.is_empty() || host_allowed(&self.host_allowlist, &host))
+            && (self.path_prefixes.is_empty()
+                || self
+                    .path_prefixes
+                    .iter()
+                    .any(|p| url.path().starts_with(p.as_str())))
+    }
+}
+
+/// Whether `host` matches one of `domains` (".example.com" also matches subdomains)
+pub(crate) fn host_allowed(domains: &[String], host: &str) -> bool {
+    domains.iter().any(|d| match d.strip_prefix('.') {
+        Some(suffix) => host == suffix || host.ends_with(&format!(".{}", suffix)),
+        None => host == d,
+    })
+}
+
+fn resolve_link(base: &Url, href: &str) -> Option<Url> {
+    let href = href.trim();
+    if href.is_empty()
+        || href.starts_with('#')
+        || href.starts_with("mailto:")
+        || href.starts_with("javascript:")
+        || href.starts_with("tel:")
+    {
+        return None;
+    }
+    let mut url = base.join(href).ok()?;
+    url.set_fragment(None);
+    Some(url)
+}
+
+/// Normalised URL used for deduplication: no fragment, lowercase host,
+/// default port dropped, `index.html` folded into the directory.
+pub fn canonicalize(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+    let path = url.path().to_string();
+    for index in ["index.html", "index.htm"] {
+        if let Some(dir) = path.strip_suffix(index) {
+            if dir.ends_with('/') {
+                url.set_path(dir);
+            }
+        }
+    }
+    url.to_string()
+}
+
+fn host_key(url: &Url) -> String {
+    format!(
+        "{}://{}:{}",
+        url.scheme(),
+        url.host_str().unwrap_or(""),
+        url.port_or_known_default().unwrap_or(0)
+    )
+}
+
+fn path_and_query(url: &Url) -> String {
+    match url.query() {
+        Some(q) => format!("{}?{}", url.path(), q),
+        None => url.path().to_string(),
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Fetching
+// ─────────────────────────────────────────────────────────────
+
+struct FetchedPage {
+    final_url: Url,
+    body: String,
+}
+
+/// GET an HTML page; `Ok(None)` for non-HTML or oversized responses
+async fn fetch_html(
+    client: &reqwest::Client,
+    url: &Url,
+    max_bytes: usize,
+) -> Result<Option<FetchedPage>, String> {
+    let response = client
+        .get(url.clone())
+        .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    // A redirect the policy stopped because it leaves the scope
+    if response.status().is_redirection() {
+        debug!("fetch_html: not following redirect from {}", url);
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/html")
+        .to_lowercase();
+    if !content_type.contains("html") {
+        debug!("fetch_html: skipping {} ({})", url, content_type);
+        return Ok(None);
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len as usize > max_bytes)
+    {
+        return Ok(None);
+    }
+    let final_url = response.url().clone();
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    if bytes.len() > max_bytes {
+        return Ok(None);
+    }
+    Ok(Some(FetchedPage {
+        final_url,
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+    }))
+}
+
+async fn fetch_robots(client: &reqwest::Client, url: &Url, user_agent: &str) -> RobotsRules {
+    let Ok(robots_url) = url.join("/robots.txt") else {
+        return RobotsRules::allow_all();
+    };
+    match client.get(robots_url.clone()).send().await {
+        Ok(resp) if resp.status().is_success() => match resp.text().await {
+            Ok(body) => RobotsRules::parse(&body, user_agent),
+            Err(_) => RobotsRules::allow_all(),
+        },
+        // No robots.txt (or access to it denied): everything is allowed
+        Ok(resp) if resp.status().is_client_error() => RobotsRules::allow_all(),
+        // Server errors mean the rules are unknown: stay off the host
+        Ok(resp) => {
+            warn!("robots.txt at {} returned {}", robots_url, resp.status());
+            if resp.status().is_server_error() {
+                RobotsRules::disallow_all()
+            } else {
+                RobotsRules::allow_all()
+            }
+        }
+        Err(e) => {
+            warn!("robots.txt at {} unreachable: {}", robots_url, e);
+            RobotsRules::allow_all()
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// robots.txt
+// ─────────────────────────────────────────────────────────────
+
+/// Rules from robots.txt that apply to our user agent
+#[derive(Debug, Clone, Default)]
+pub struct RobotsRules {
+    /// (allow, pattern)
+    rules: Vec<(bool, String)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![(false, "/".to_string())],
+            crawl_delay: None,
+        }
+    }
+
+    /// Parse robots.txt, keeping the group for the most specific matching
+    /// user agent (falling back to `*`).
+    pub fn parse(body: &str, user_agent: &str) -> Self {
+        let agent = user_agent
+            .split('/')
+            .next()
+            .unwrap_or(user_agent)
+            .to_lowercase();
+
+        // (agents, rules) per group
+        let mut groups: Vec<(Vec<String>, RobotsRules)> = Vec::new();
+        let mut in_agent_lines = false;
+
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_agent_lines || groups.is_empty() {
+                        groups.push((Vec::new(), RobotsRules::default()));
+                    }
+                    in_agent_lines = true;
+                    if let Some(group) = groups.last_mut() {
+                        group.0.push(value.to_lowercase());
+                    }
+                }
+                "allow" | "disallow" => {
+                    in_agent_lines = false;
+                    if let Some(group) = groups.last_mut() {
+                        // An empty Disallow allows everything
+                        if !value.is_empty() {
+                            group.1.rules.push((key == "allow", value.to_string()));
+                        }
+                    }
+                }
+                "crawl-delay" => {
+                    in_agent_lines = false;
+                    if let Some(group) = groups.last_mut() {
+                        group.1.crawl_delay = value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|d| d.is_finite() && *d >= 0.0)
+                            .map(Duration::from_secs_f64);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let specific = groups
+            .iter()
+            .filter(|(agents, _)| {
+                agents
+                    .iter()
+                    .any(|a| a != "*" && agent.contains(a.as_str()))
+            })
+            .max_by_key(|(agents, _)| agents.iter().map(String::len).max().unwrap_or(0));
+        let chosen = specific.or_else(|| {
+            groups
+                .iter()
+                .find(|(agents, _)| agents.iter().any(|a| a == "*"))
+        });
+        chosen
+            .map(|(_, rules)| rules.clone())
+            .unwrap_or_else(Self::allow_all)
+    }
+
+    /// Longest matching pattern wins; Allow wins ties
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| robots_pattern_matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .map(|(allow, _)| *allow)
+            .unwrap_or(true)
+    }
+}
+
+/// robots.txt path matching with `*` wildcards and a `$` end anchor
+fn robots_pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let pieces: Vec<&str> = pattern.split('*').collect();
+    let mut pos = 0;
+    for (i, piece) in pieces.iter().enumerate() {
+        if i == 0 {
+            if !path.starts_with(piece) {
+                return false;
+            }
+            pos = piece.len();
+            continue;
+        }
+        if i == pieces.len() - 1 && anchored {
+            return path.len() >= pos + piece.len() && path.ends_with(piece);
+        }
+        match path[pos..].find(piece) {
+            Some(found) => pos += found + piece.len(),
+            None => return false,
+        }
+    }
+    !anchored || pos == path.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Minimal static HTTP server: path -> (content type, body). A
+    /// "redirect" content type answers 302 with the body as Location.
+    async fn serve(pages: HashMap<&'static str, (&'static str, String)>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let pages = Arc::new(pages);
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    break;
+                };
+                let pages = pages.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let response = match pages.get(path.as_str()) {
+                        Some((ctype, location)) if *ctype == "redirect" => format!(
+                            "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                            location
+                        ),
+                        Some((ctype, body)) => format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            ctype,
+                            body.len(),
+                            body
+                        ),
+                        None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string(),
+                    };
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn html(title: &str, body: &str) -> (&'static str, String) {
+        (
+            "text/html; charset=utf-8",
+            format!(
+                "<html><head><title>{}</title></head><body><main>{}</main></body></html>",
+                title, body
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_crawl_scoping_robots_and_dedup() {
+        let mut pages = HashMap::new();
+        pages.insert(
+            "/robots.txt",
+            (
+                "text/plain",
+                "User-agent: *\nDisallow: /docs/private\nCrawl-delay: 0\n".to_string(),
+            ),
+        );
+        pages.insert(
+            "/docs/",
+            html(
+                "Docs",
+                r##"<p>Welcome to the docs.</p>
+                <a href="guide.html">Guide</a> <a href="/docs/index.html#top">Self</a>
+                <a href="private/keys.html">Private</a> <a href="/blog/">Blog</a>
+                <a href="copy.html">Copy</a> <a href="http://elsewhere.invalid/">Out</a>"##,
+            ),
+        );
+        let guide_body = r#"<p>Install it.</p><a href="deep/one.html">Deeper</a>"#;
+        pages.insert("/docs/guide.html", html("Guide", guide_body));
+        pages.insert("/docs/deep/one.html", html("Deep", "<p>Too deep.</p>"));
+        pages.insert("/docs/copy.html", html("Copy", guide_body));
+        pages.insert("/docs/private/keys.html", html("Keys", "<p>secret</p>"));
+        pages.insert("/blog/", html("Blog", "<p>Out of scope.</p>"));
+        let base = serve(pages).await;
+
+        let config = CrawlConfig {
+            seeds: vec![format!("{}/docs/", base)],
+            max_depth: 1,
+            path_prefixes: vec!["/docs/".to_string()],
+            ..CrawlConfig::default()
+        };
+        let mut indexed: Vec<CrawledPage> = Vec::new();
+        let mut progress_calls = 0;
+        let report = crawl(
+            &config,
+            |page| {
+                indexed.push(page);
+                std::future::ready(Ok(1))
+            },
+            |_| progress_calls += 1,
+        )
+        .await
+        .unwrap();
+
+        let titles: Vec<_> = indexed.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["Docs", "Guide"]);
+        assert_eq!(
+            indexed[0].text,
+            "Welcome to the docs.\n\nGuide Self\nPrivate Blog\nCopy Out"
+        );
+        assert_eq!(report.progress.robots_blocked, 1);
+        // copy.html has the same text as guide.html
+        assert_eq!(report.progress.duplicates_skipped, 1);
+        assert_eq!(report.progress.chunks_indexed, 2);
+        assert!(progress_calls >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_does_not_follow_redirects_out_of_scope() {
+        let mut internal = HashMap::new();
+        internal.insert("/secret", html("Secret", "<p>Internal only.</p>"));
+        // Reached as "localhost", a host outside the crawl scope
+        let outside = serve(internal).await.replace("127.0.0.1", "localhost");
+        let mut pages = HashMap::new();
+        pages.insert(
+            "/",
+            html("Home", r#"<p>Start here.</p><a href="/moved">Moved</a>"#),
+        );
+        pages.insert("/moved", ("redirect", format!("{}/secret", outside)));
+        let base = serve(pages).await;
+
+        let config = CrawlConfig {
+            seeds: vec![format!("{}/", base)],
+            respect_robots: false,
+            ..CrawlConfig::default()
+        };
+        let mut titles = Vec::new();
+        let report = crawl(
+            &config,
+            |page| {
+                titles.push(page.title);
+                std::future::ready(Ok(1))
+            },
+            |_| {},
+        )
+        .await
+        .unwrap();
+        assert_eq!(titles, vec!["Home"]);
+        // The redirect is not followed, so the internal page is never fetched
+        assert_eq!(report.progress.pages_fetched, 1);
+        assert_eq!(report.progress.errors, 0);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_indexes_into_retriever() {
+        let mut pages = HashMap::new();
+        pages.insert("/", html("Home", "<p>Photosynthesis converts light.</p>"));
+        let base = serve(pages).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut retriever = Retriever::new_with_vector_file(
+            dir.path().join("index").to_str().unwrap(),
+            dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        let chunker = crate::memory::chunker_factory::FixedChunker;
+        let config = CrawlConfig {
+            seeds: vec![format!("{}/", base)],
+            ..CrawlConfig::default()
+        };
+
+        let report = crawl(
+            &config,
+            |page| std::future::ready(index_page(&mut retriever, &chunker, &page)),
+            |_| {},
+        )
+        .await
+        .unwrap();
+        retriever.commit().unwrap();
+        assert_eq!(report.progress.pages_indexed, 1);
+
+        let hits = retriever.search_hits("photosynthesis", 5).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].doc_id, format!("{}/#0", base));
+        assert_eq!(hits[0].title, "Home");
+
+        // A re-crawl replaces the page's chunks instead of adding to them
+        let page = CrawledPage {
+            url: format!("{}/", base),
+            canonical_url: format!("{}/", base),
+            title: "Home".to_string(),
+            text: "Chlorophyll absorbs light.".to_string(),
+            depth: 0,
+            content_hash: 0,
+        };
+        index_page(&mut retriever, &chunker, &page).unwrap();
+        assert!(retriever
+            .search_hits("photosynthesis", 5)
+            .unwrap()
+            .is_empty());
+        assert_eq!(retriever.search_hits("chlorophyll", 5).unwrap().len(), 1);
+        assert_eq!(
+            retriever
+                .chunk_ids_with_prefix(&format!("{}/#", base))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_robots_rules() {
+        let robots = "# comment\nUser-agent: other\nDisallow: /\n\nUser-agent: ag-crawler\nUser-agent: foo\nDisallow: /private\nAllow: /private/open\nDisallow: /*.pdf$\nCrawl-delay: 1.5\n\nUser-agent: *\nDisallow: /everything\n";
+        let rules = RobotsRules::parse(robots, DEFAULT_USER_AGENT);
+        assert!(rules.is_allowed("/docs"));
+        assert!(!rules.is_allowed("/private/x"));
+        assert!(rules.is_allowed("/private/open/x"));
+        assert!(!rules.is_allowed("/files/a.pdf"));
+        assert!(rules.is_allowed("/files/a.pdf?x=1"));
+        assert!(rules.is_allowed("/everything"));
+        assert_eq!(rules.crawl_delay, Some(Duration::from_millis(1500)));
+
+        let fallback = RobotsRules::parse(robots, "somebot/2.0");
+        assert!(!fallback.is_allowed("/everything/else"));
+        assert!(fallback.is_allowed("/private"));
+    }
+
+    #[test]
+    fn test_canonicalize_and_scope() {
+        let url = Url::parse("HTTP://Docs.Example.com:80/a/index.html?#frag").unwrap();
+        assert_eq!(canonicalize(&url), "http://docs.example.com/a/");
+
+        let config = CrawlConfig {
+            seeds: vec!["https://docs.example.com/v2/".into()],
+            allowed_domains: vec![".example.com".into()],
+            path_prefixes: vec!["/v2/".into()],
+            ..CrawlConfig::default()
+        };
+        let scope = Scope::from_config(&config).unwrap();
+        assert!(scope.allows(&Url::parse("https://api.example.com/v2/x").unwrap()));
+        assert!(!scope.allows(&Url::parse("https://example.org/v2/x").unwrap()));
+        assert!(!scope.allows(&Url::parse("https://docs.example.com/v1/x").unwrap()));
+        assert!(!scope.allows(&Url::parse("ftp://docs.example.com/v2/x").unwrap()));
+
+        let limited = CrawlConfig {
+            host_allowlist: vec!["docs.example.com".into()],
+            ..config.clone()
+        };
+        let scope = Scope::from_config(&limited).unwrap();
+        assert!(scope.allows(&Url::parse("https://docs.example.com/v2/x").unwrap()));
+        assert!(!scope.allows(&Url::parse("https://api.example.com/v2/x").unwrap()));
+        let outside = CrawlConfig {
+            host_allowlist: vec!["intranet.local".into()],
+            ..config
+        };
+        assert!(Scope::from_config(&outside).is_err());
+    }
+}
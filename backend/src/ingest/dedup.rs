@@ -0,0 +1,890 @@
+// src/ingest/dedup.rs
+// Near-duplicate detection for documents and chunks
+//
+// Every indexed document gets a MinHash signature (Jaccard estimate over word
+// 3-shingles), a SimHash and an exact hash of its normalized text; every chunk
+// gets a SimHash. Before a document is indexed it is compared with the stored
+// documents that share a SimHash or MinHash band key with it, and
+// `DEDUP_POLICY` decides what to do with a near-duplicate: skip it, index it
+// as a new version of the original, or index it anyway.
+// Search results collapse hits whose SimHashes are within a few bits.
+//
+// Environment variables:
+// - DEDUP_POLICY: skip | version | index (default: index)
+// - DEDUP_THRESHOLD: estimated Jaccard similarity for a near-duplicate
+//   document (default: 0.85)
+// - DEDUP_COLLAPSE_HITS: collapse near-duplicate search hits (default: true)
+// - DEDUP_HIT_DISTANCE: max SimHash Hamming distance for collapsing (default: 3)
+
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::OnceLock;
+use tracing::warn;
+
+const SHINGLE_WORDS: usize = 3;
+const MINHASH_PERMUTATIONS: usize = 64;
+/// SimHash bands for candidate lookup: a Hamming distance below the band
+/// count guarantees two hashes share at least one identical band
+const SIMHASH_BANDS: u32 = 4;
+/// MinHash values per LSH band. With 16 bands of 4, documents at a Jaccard
+/// similarity of 0.6 share a band with probability ~0.9, at 0.85 ~1.0.
+const MINHASH_BAND_ROWS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Leave the file on disk but do not index it
+    Skip,
+    /// Index it and record it as a newer version of the original
+    Version,
+    /// Index it and only record the match
+    Index,
+}
+
+impl std::str::FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "skip" => Ok(DuplicatePolicy::Skip),
+            "version" => Ok(DuplicatePolicy::Version),
+            "index" => Ok(DuplicatePolicy::Index),
+            other => Err(format!("unknown duplicate policy: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DedupConfig {
+    pub policy: DuplicatePolicy,
+    pub threshold: f32,
+    pub collapse_hits: bool,
+    pub hit_distance: u32,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            policy: DuplicatePolicy::Index,
+            threshold: 0.85,
+            collapse_hits: true,
+            hit_distance: 3,
+        }
+    }
+}
+
+impl DedupConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let policy = match env::var("DEDUP_POLICY") {
+            Ok(raw) => raw.parse().unwrap_or_else(|e| {
+                warn!("DEDUP_POLICY: {}", e);
+                defaults.policy
+            }),
+            Err(_) => defaults.policy,
+        };
+        let threshold = env::var("DEDUP_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|v| (0.0..=1.0).contains(v))
+            .unwrap_or(defaults.threshold);
+        let collapse_hits = env::var("DEDUP_COLLAPSE_HITS")
+            .map(|v| v.to_lowercase() == "true" || v == "1")
+            .unwrap_or(defaults.collapse_hits);
+        let hit_distance = env::var("DEDUP_HIT_DISTANCE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.hit_distance);
+        Self {
+            policy,
+            threshold,
+            collapse_hits,
+            hit_distance,
+        }
+    }
+
+    /// Process-wide config, read from the environment once
+    pub fn global() -> &'static DedupConfig {
+        static CONFIG: OnceLock<DedupConfig> = OnceLock::new();
+        CONFIG.get_or_init(Self::from_env)
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Fingerprints
+// ─────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub simhash: u64,
+    pub minhash: Vec<u64>,
+    /// Hash of the whitespace- and case-normalized text
+    pub exact: u64,
+}
+
+impl Fingerprint {
+    pub fn of(text: &str) -> Self {
+        let words = normalized_words(text);
+        let shingles = shingle_hashes(&words);
+        Self {
+            simhash: simhash_of(&shingles),
+            minhash: minhash_of(&shingles),
+            exact: fnv1a(words.join(" ").as_bytes()),
+        }
+    }
+
+    /// Estimated Jaccard similarity of the two documents' shingle sets
+    pub fn similarity(&self, other: &Fingerprint) -> f32 {
+        if self.exact == other.exact {
+            return 1.0;
+        }
+        if self.minhash.is_empty() || self.minhash.len() != other.minhash.len() {
+            return 0.0;
+        }
+        let same = self
+            .minhash
+            .iter()
+            .zip(&other.minhash)
+            .filter(|(a, b)| a == b)
+            .count();
+        same as f32 / self.minhash.len() as f32
+    }
+}
+
+/// SimHash of a chunk or search hit
+pub fn simhash(text: &str) -> u64 {
+    simhash_of(&shingle_hashes(&normalized_words(text)))
+}
+
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn normalized_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn shingle_hashes(words: &[String]) -> Vec<u64> {
+    if words.len() < SHINGLE_WORDS {
+        return if words.is_empty() {
+            Vec::new()
+        } else {
+            vec![fnv1a(words.join(" ").as_bytes())]
+        };
+    }
+    let mut seen = HashSet::new();
+    words
+        .windows(SHINGLE_WORDS)
+        .map(|w| fnv1a(w.join(" ").as_bytes()))
+        .filter(|h| seen.insert(*h))
+        .collect()
+}
+
+fn simhash_of(shingles: &[u64]) -> u64 {
+    let mut weights = [0i32; 64];
+    for hash in shingles {
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash >> bit & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, w)| **w > 0)
+        .fold(0u64, |acc, (bit, _)| acc | 1 << bit)
+}
+
+fn minhash_of(shingles: &[u64]) -> Vec<u64> {
+    if shingles.is_empty() {
+        return Vec::new();
+    }
+    (0..MINHASH_PERMUTATIONS as u64)
+        .map(|seed| {
+            let seed = splitmix64(seed.wrapping_add(0x9e37_79b9_7f4a_7c15));
+            shingles
+                .iter()
+                .map(|h| splitmix64(h ^ seed))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+/// FNV-1a, so stored fingerprints stay valid across builds
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Drop items whose text is a near-duplicate of an earlier item. Order is
+/// kept, so the best-ranked copy survives.
+pub fn collapse_near_duplicates<T>(
+    items: Vec<T>,
+    text: impl Fn(&T) -> &str,
+    max_distance: u32,
+) -> Vec<T> {
+    let mut kept_hashes: Vec<u64> = Vec::with_capacity(items.len());
+    let mut kept = Vec::with_capacity(items.len());
+    for item in items {
+        let hash = simhash(text(&item));
+        if kept_hashes
+            .iter()
+            .any(|h| hamming(*h, hash) <= max_distance)
+        {
+            continue;
+        }
+        kept_hashes.push(hash);
+        kept.push(item);
+    }
+    kept
+}
+
+/// `collapse_near_duplicates` with the global config, if collapsing is on
+pub fn collapse_hits<T>(items: Vec<T>, text: impl Fn(&T) -> &str) -> Vec<T> {
+    let config = DedupConfig::global();
+    if config.collapse_hits {
+        collapse_near_duplicates(items, text, config.hit_distance)
+    } else {
+        items
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Storage
+// ─────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentStatus {
+    Indexed,
+    Skipped,
+}
+
+impl DocumentStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            DocumentStatus::Indexed => "indexed",
+            DocumentStatus::Skipped => "skipped",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "skipped" => DocumentStatus::Skipped,
+            _ => DocumentStatus::Indexed,
+        }
+    }
+}
+
+/// The closest stored document to a new one
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateMatch {
+    pub document: String,
+    pub similarity: f32,
+    /// Root of the original's version chain
+    pub version_of: String,
+    /// Version number the new document gets under the Version policy
+    pub next_version: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentRecord {
+    pub document: String,
+    pub status: DocumentStatus,
+    pub duplicate_of: Option<String>,
+    pub similarity: Option<f32>,
+    pub version_of: Option<String>,
+    pub version: u32,
+    pub policy: Option<DuplicatePolicy>,
+}
+
+/// What happened to a document at ingestion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Index,
+    Skip,
+}
+
+pub fn init_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS document_fingerprints (
+            document TEXT PRIMARY KEY,
+            simhash INTEGER NOT NULL,
+            minhash BLOB NOT NULL,
+            exact_hash INTEGER NOT NULL,
+            status TEXT NOT NULL DEFAULT 'indexed',
+            duplicate_of TEXT,
+            similarity REAL,
+            version_of TEXT,
+            version INTEGER NOT NULL DEFAULT 1,
+            policy TEXT,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS chunk_fingerprints (
+            chunk_id TEXT PRIMARY KEY,
+            document TEXT NOT NULL,
+            simhash INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_chunk_fingerprints_document
+            ON chunk_fingerprints(document);
+        CREATE INDEX IF NOT EXISTS idx_document_fingerprints_exact
+            ON document_fingerprints(exact_hash);
+        CREATE TABLE IF NOT EXISTS document_bands (
+            band INTEGER NOT NULL,
+            key INTEGER NOT NULL,
+            document TEXT NOT NULL,
+            PRIMARY KEY (band, key, document)
+        );
+        CREATE INDEX IF NOT EXISTS idx_document_bands_document
+            ON document_bands(document);",
+    )?;
+    backfill_bands(conn)
+}
+
+/// Band keys of documents fingerprinted before the band table existed
+fn backfill_bands(conn: &Connection) -> Result<()> {
+    let missing = conn
+        .prepare(
+            "SELECT document, simhash, minhash FROM document_fingerprints
+             WHERE document NOT IN (SELECT document FROM document_bands)",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)? as u64,
+                decode_minhash(&row.get::<_, Vec<u8>>(2)?),
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    for (document, simhash, minhash) in missing {
+        let fingerprint = Fingerprint {
+            simhash,
+            minhash,
+            exact: 0,
+        };
+        store_bands(conn, &document, &fingerprint)?;
+    }
+    Ok(())
+}
+
+fn simhash_bands(hash: u64) -> impl Iterator<Item = (u32, u64)> {
+    let band_bits = 64 / SIMHASH_BANDS;
+    (0..SIMHASH_BANDS).map(move |band| {
+        (
+            band,
+            (hash >> (band * band_bits)) & ((1u64 << band_bits) - 1),
+        )
+    })
+}
+
+/// LSH band keys of a document: its SimHash bands followed by one key per
+/// band of MinHash rows. Near-duplicates share at least one key.
+fn band_keys(fingerprint: &Fingerprint) -> Vec<(u32, i64)> {
+    let mut keys: Vec<(u32, i64)> = simhash_bands(fingerprint.simhash)
+        .map(|(band, key)| (band, key as i64))
+        .collect();
+    for (n, rows) in fingerprint.minhash.chunks(MINHASH_BAND_ROWS).enumerate() {
+        let bytes = encode_minhash(rows);
+        keys.push((SIMHASH_BANDS + n as u32, fnv1a(&bytes) as i64));
+    }
+    keys
+}
+
+fn store_bands(conn: &Connection, document: &str, fingerprint: &Fingerprint) -> Result<()> {
+    conn.execute(
+        "DELETE FROM document_bands WHERE document = ?1",
+        params![document],
+    )?;
+    let mut stmt = conn.prepare(
+        "INSERT OR IGNORE INTO document_bands (band, key, document) VALUES (?1, ?2, ?3)",
+    )?;
+    for (band, key) in band_keys(fingerprint) {
+        stmt.execute(params![band, key, document])?;
+    }
+    Ok(())
+}
+
+fn encode_minhash(minhash: &[u64]) -> Vec<u8> {
+    minhash.iter().flat_map(|h| h.to_le_bytes()).collect()
+}
+
+fn decode_minhash(bytes: &[u8]) -> Vec<u64> {
+    bytes
+        .chunks_exact(8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap_or([0; 8])))
+        .collect()
+}
+
+/// Most similar indexed document at or above `threshold`, other than
+/// `document` itself (re-indexing a file is not a duplicate)
+pub fn find_duplicate(
+    conn: &Connection,
+    document: &str,
+    fingerprint: &Fingerprint,
+    threshold: f32,
+) -> Result<Option<DuplicateMatch>> {
+    // Only documents sharing a band key (or the exact text) are compared
+    let keys = band_keys(fingerprint);
+    let bands = vec!["(band = ? AND key = ?)"; keys.len()].join(" OR ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT document, minhash, exact_hash, simhash, version_of
+         FROM document_fingerprints
+         WHERE status = 'indexed' AND document != ?
+           AND (exact_hash = ?
+                OR document IN (SELECT document FROM document_bands WHERE {}))",
+        bands
+    ))?;
+    let mut values: Vec<rusqlite::types::Value> = vec![
+        document.to_string().into(),
+        (fingerprint.exact as i64).into(),
+    ];
+    for (band, key) in keys {
+        values.push((band as i64).into());
+        values.push(key.into());
+    }
+    let rows = stmt.query_map(params_from_iter(values), |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            Fingerprint {
+                minhash: decode_minhash(&row.get::<_, Vec<u8>>(1)?),
+                exact: row.get::<_, i64>(2)? as u64,
+                simhash: row.get::<_, i64>(3)? as u64,
+            },
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+
+    let mut best: Option<DuplicateMatch> = None;
+    for row in rows {
+        let (name, stored, version_of) = row?;
+        let similarity = fingerprint.similarity(&stored);
+        if similarity < threshold || best.as_ref().is_some_and(|b| b.similarity >= similarity) {
+            continue;
+        }
+        best = Some(DuplicateMatch {
+            version_of: version_of.unwrap_or_else(|| name.clone()),
+            document: name,
+            similarity,
+            next_version: 0,
+        });
+    }
+    if let Some(found) = best.as_mut() {
+        let latest: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(version), 1) FROM document_fingerprints
+             WHERE document = ?1 OR version_of = ?1",
+            params![found.version_of],
+            |row| row.get(0),
+        )?;
+        found.next_version = latest.max(1) as u32 + 1;
+    }
+    Ok(best)
+}
+
+/// Look up a new document and decide what to do with it under `config`.
+/// The document's fingerprint row is written either way.
+pub fn check_document(
+    conn: &Connection,
+    document: &str,
+    fingerprint: &Fingerprint,
+    config: &DedupConfig,
+) -> Result<(Decision, Option<DuplicateMatch>)> {
+    let found = find_duplicate(conn, document, fingerprint, config.threshold)?;
+    let decision = match (&found, config.policy) {
+        (Some(_), DuplicatePolicy::Skip) => Decision::Skip,
+        _ => Decision::Index,
+    };
+    let status = match decision {
+        Decision::Skip => DocumentStatus::Skipped,
+        Decision::Index => DocumentStatus::Indexed,
+    };
+    let (version_of, version) = match (&found, config.policy) {
+        (Some(m), DuplicatePolicy::Version) => (Some(m.version_of.as_str()), m.next_version),
+        _ => (None, 1),
+    };
+    conn.execute(
+        "INSERT OR REPLACE INTO document_fingerprints
+            (document, simhash, minhash, exact_hash, status, duplicate_of, similarity,
+             version_of, version, policy, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, CURRENT_TIMESTAMP)",
+        params![
+            document,
+            fingerprint.simhash as i64,
+            encode_minhash(&fingerprint.minhash),
+            fingerprint.exact as i64,
+            status.as_str(),
+            found.as_ref().map(|m| m.document.as_str()),
+            found.as_ref().map(|m| m.similarity as f64),
+            version_of,
+            version as i64,
+            found.as_ref().map(|_| policy_name(config.policy)),
+        ],
+    )?;
+    store_bands(conn, document, fingerprint)?;
+    Ok((decision, found))
+}
+
+fn policy_name(policy: DuplicatePolicy) -> &'static str {
+    match policy {
+        DuplicatePolicy::Skip => "skip",
+        DuplicatePolicy::Version => "version",
+        DuplicatePolicy::Index => "index",
+    }
+}
+
+/// Replace the stored chunk fingerprints of `document`
+pub fn record_chunks(
+    conn: &mut Connection,
+    document: &str,
+    chunks: &[(String, u64)],
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM chunk_fingerprints WHERE document = ?1",
+        params![document],
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR REPLACE INTO chunk_fingerprints (chunk_id, document, simhash)
+             VALUES (?1, ?2, ?3)",
+        )?;
+        for (chunk_id, hash) in chunks {
+            stmt.execute(params![chunk_id, document, *hash as i64])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Forget a deleted document and its chunks
+pub fn forget_document(conn: &Connection, document: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM document_fingerprints WHERE document = ?1",
+        params![document],
+    )?;
+    conn.execute(
+        "DELETE FROM chunk_fingerprints WHERE document = ?1",
+        params![document],
+    )?;
+    conn.execute(
+        "DELETE FROM document_bands WHERE document = ?1",
+        params![document],
+    )?;
+    Ok(())
+}
+
+pub fn get_document(conn: &Connection, document: &str) -> Result<Option<DocumentRecord>> {
+    conn.query_row(
+        &format!("{} WHERE document = ?1", SELECT_RECORD),
+        params![document],
+        row_to_record,
+    )
+    .optional()
+}
+
+const SELECT_RECORD: &str = "SELECT document, status, duplicate_of, similarity, version_of,
+        version, policy
+     FROM document_fingerprints";
+
+fn row_to_record(row: &rusqlite::Row) -> Result<DocumentRecord> {
+    Ok(DocumentRecord {
+        document: row.get(0)?,
+        status: DocumentStatus::parse(&row.get::<_, String>(1)?),
+        duplicate_of: row.get(2)?,
+        similarity: row.get::<_, Option<f64>>(3)?.map(|s| s as f32),
+        version_of: row.get(4)?,
+        version: row.get::<_, i64>(5)?.max(1) as u32,
+        policy: row
+            .get::<_, Option<String>>(6)?
+            .and_then(|p| p.parse().ok()),
+    })
+}
+
+/// Chunks from different documents with (nearly) the same SimHash
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkDuplicateGroup {
+    pub chunk_ids: Vec<String>,
+    pub documents: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateReport {
+    /// Documents that matched an earlier document at ingestion
+    pub documents: Vec<DocumentRecord>,
+    pub chunk_groups: Vec<ChunkDuplicateGroup>,
+    pub total_documents: usize,
+    pub total_chunks: usize,
+}
+
+pub fn duplicate_report(
+    conn: &Connection,
+    max_distance: u32,
+    limit: usize,
+) -> Result<DuplicateReport> {
+    let total_documents: i64 =
+        conn.query_row("SELECT COUNT(*) FROM document_fingerprints", [], |row| {
+            row.get(0)
+        })?;
+    let documents = conn
+        .prepare(&format!(
+            "{} WHERE duplicate_of IS NOT NULL ORDER BY updated_at DESC LIMIT ?1",
+            SELECT_RECORD
+        ))?
+        .query_map(params![limit as i64], row_to_record)?
+        .collect::<Result<Vec<_>>>()?;
+
+    let chunks = conn
+        .prepare("SELECT chunk_id, document, simhash FROM chunk_fingerprints ORDER BY chunk_id")?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)? as u64,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    let mut chunk_groups = group_chunks(&chunks, max_distance);
+    chunk_groups.truncate(limit);
+
+    Ok(DuplicateReport {
+        documents,
+        chunk_groups,
+        total_documents: total_documents.max(0) as usize,
+        total_chunks: chunks.len(),
+    })
+}
+
+/// Group chunks of different documents whose SimHashes are within
+/// `max_distance`. Candidates come from identical SimHash bands, which finds
+/// every pair when `max_distance` is below the band count.
+fn group_chunks(chunks: &[(String, String, u64)], max_distance: u32) -> Vec<ChunkDuplicateGroup> {
+    let mut buckets: HashMap<(u32, u64), Vec<usize>> = HashMap::new();
+    for (i, (_, _, hash)) in chunks.iter().enumerate() {
+        for band in simhash_bands(*hash) {
+            buckets.entry(band).or_default().push(i);
+        }
+    }
+
+    // Union-find over near pairs from different documents
+    let mut parent: Vec<usize> = (0..chunks.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for members in buckets.values().filter(|m| m.len() > 1) {
+        for (n, &a) in members.iter().enumerate() {
+            for &b in &members[n + 1..] {
+                if chunks[a].1 != chunks[b].1 && hamming(chunks[a].2, chunks[b].2) <= max_distance {
+                    let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+                    if ra != rb {
+                        parent[rb] = ra;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..chunks.len() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut groups: Vec<ChunkDuplicateGroup> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let mut documents: Vec<String> = members.iter().map(|&i| chunks[i].1.clone()).collect();
+            documents.sort();
+            documents.dedup();
+            ChunkDuplicateGroup {
+                chunk_ids: members.iter().map(|&i| chunks[i].0.clone()).collect(),
+                documents,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.chunk_ids
+            .len()
+            .cmp(&a.chunk_ids.len())
+            .then_with(|| a.chunk_ids.cmp(&b.chunk_ids))
+    });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "The quarterly report shows revenue grew by twelve percent while \
+        operating costs stayed flat. Most of the growth came from the new subscription \
+        tier launched in March, which added four thousand paying customers. Churn fell \
+        to two percent and support tickets per customer dropped for the third quarter \
+        in a row. Next quarter we plan to expand into two new regions.";
+
+    fn conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_fingerprint_similarity() {
+        let original = Fingerprint::of(REPORT);
+        let reformatted = Fingerprint::of(&REPORT.to_uppercase().replace(". ", ".\n\n"));
+        assert_eq!(original.similarity(&reformatted), 1.0);
+
+        let edited = Fingerprint::of(&REPORT.replace("twelve", "thirteen"));
+        let similarity = original.similarity(&edited);
+        assert!(similarity > 0.7 && similarity < 1.0, "{}", similarity);
+        assert!(hamming(original.simhash, edited.simhash) <= 8);
+
+        let other = Fingerprint::of("Install the router by connecting the WAN port first.");
+        assert!(original.similarity(&other) < 0.2);
+    }
+
+    #[test]
+    fn test_policies() {
+        let conn = conn();
+        let skip = DedupConfig {
+            policy: DuplicatePolicy::Skip,
+            threshold: 0.6,
+            ..DedupConfig::default()
+        };
+        let original = Fingerprint::of(REPORT);
+        let (decision, found) = check_document(&conn, "q3.txt", &original, &skip).unwrap();
+        assert_eq!((decision, found), (Decision::Index, None));
+        // Re-indexing the same file is not a duplicate of itself
+        let (decision, _) = check_document(&conn, "q3.txt", &original, &skip).unwrap();
+        assert_eq!(decision, Decision::Index);
+
+        let copy = Fingerprint::of(&REPORT.replace("twelve", "thirteen"));
+        let (decision, found) = check_document(&conn, "q3 (1).txt", &copy, &skip).unwrap();
+        assert_eq!(decision, Decision::Skip);
+        assert_eq!(found.unwrap().document, "q3.txt");
+
+        let version = DedupConfig {
+            policy: DuplicatePolicy::Version,
+            ..skip.clone()
+        };
+        check_document(&conn, "q3_v2.txt", &copy, &version).unwrap();
+        let (_, found) = check_document(&conn, "q3_v3.txt", &original, &version).unwrap();
+        let found = found.unwrap();
+        assert_eq!(found.version_of, "q3.txt");
+        assert_eq!(found.next_version, 3);
+
+        let record = get_document(&conn, "q3_v3.txt").unwrap().unwrap();
+        assert_eq!(record.version_of.as_deref(), Some("q3.txt"));
+        assert_eq!(record.version, 3);
+        let skipped = get_document(&conn, "q3 (1).txt").unwrap().unwrap();
+        assert_eq!(skipped.status, DocumentStatus::Skipped);
+
+        let report = duplicate_report(&conn, 3, 10).unwrap();
+        assert_eq!(report.documents.len(), 3);
+        assert_eq!(report.total_documents, 4);
+    }
+
+    #[test]
+    fn test_lookup_only_compares_documents_sharing_a_band() {
+        let conn = conn();
+        let config = DedupConfig {
+            threshold: 0.6,
+            ..DedupConfig::default()
+        };
+        check_document(&conn, "q3.txt", &Fingerprint::of(REPORT), &config).unwrap();
+        let manual = "Install the router by connecting the WAN port first, then wait \
+            for the status light to turn green before pairing the mobile app.";
+        check_document(&conn, "router.txt", &Fingerprint::of(manual), &config).unwrap();
+
+        let edited = Fingerprint::of(&REPORT.replace("twelve", "thirteen"));
+        let shared = |document: &str| -> i64 {
+            let keys = band_keys(&edited);
+            keys.iter()
+                .map(|(band, key)| {
+                    conn.query_row(
+                        "SELECT COUNT(*) FROM document_bands
+                         WHERE band = ?1 AND key = ?2 AND document = ?3",
+                        params![band, key, document],
+                        |row| row.get::<_, i64>(0),
+                    )
+                    .unwrap()
+                })
+                .sum()
+        };
+        assert!(shared("q3.txt") > 0);
+        assert_eq!(shared("router.txt"), 0);
+        let found = find_duplicate(&conn, "q3 (1).txt", &edited, 0.6).unwrap();
+        assert_eq!(found.unwrap().document, "q3.txt");
+
+        // Fingerprints stored before the band table get their keys on open
+        conn.execute("DELETE FROM document_bands", []).unwrap();
+        assert!(find_duplicate(&conn, "q3 (1).txt", &edited, 0.6)
+            .unwrap()
+            .is_none());
+        init_table(&conn).unwrap();
+        assert!(find_duplicate(&conn, "q3 (1).txt", &edited, 0.6)
+            .unwrap()
+            .is_some());
+
+        forget_document(&conn, "q3.txt").unwrap();
+        let bands: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM document_bands WHERE document = 'q3.txt'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(bands, 0);
+    }
+
+    #[test]
+    fn test_chunk_groups_and_collapse() {
+        let mut conn = conn();
+        let paragraph = "Churn fell to two percent and support tickets dropped again.";
+        record_chunks(
+            &mut conn,
+            "a.txt",
+            &[
+                ("a.txt#0".into(), simhash(paragraph)),
+                ("a.txt#1".into(), simhash("Unrelated closing remarks.")),
+            ],
+        )
+        .unwrap();
+        record_chunks(
+            &mut conn,
+            "b.txt",
+            &[("b.txt#0".into(), simhash(&paragraph.to_lowercase()))],
+        )
+        .unwrap();
+        let report = duplicate_report(&conn, 3, 10).unwrap();
+        assert_eq!(report.total_chunks, 3);
+        assert_eq!(report.chunk_groups.len(), 1);
+        assert_eq!(report.chunk_groups[0].documents, vec!["a.txt", "b.txt"]);
+
+        let hits = vec![
+            paragraph.to_string(),
+            format!("  {}  ", paragraph.to_uppercase()),
+            "Something else entirely.".to_string(),
+        ];
+        let collapsed = collapse_near_duplicates(hits, |h| h.as_str(), 3);
+        assert_eq!(collapsed.len(), 2);
+        assert_eq!(collapsed[0], paragraph);
+    }
+}
@@ -0,0 +1,1084 @@
+// src/ingest/email.rs
+// Email archive ingestion (mbox and .eml) with thread reconstruction
+//
+// Every message becomes a document whose chunks are indexed as
+// `email:<message-id>#<n>`. Sender, recipients, date, subject and the
+// reconstructed thread id are kept in the `email_messages` table so search
+// results can be filtered and expanded with neighbouring thread messages.
+
+use super::html::html_to_text;
+use crate::embedder;
+use crate::memory::chunker_factory::Chunker;
+use crate::retriever::Retriever;
+use crate::security::redaction;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Prefix of retriever chunk ids belonging to email messages
+pub const EMAIL_ID_PREFIX: &str = "email:";
+
+#[derive(Debug, Error)]
+pub enum EmailError {
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("malformed message: {0}")]
+    Malformed(String),
+    #[error("database error: {0}")]
+    Database(String),
+}
+
+impl From<rusqlite::Error> for EmailError {
+    fn from(err: rusqlite::Error) -> Self {
+        EmailError::Database(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, EmailError>;
+
+/// A parsed message with decoded headers and a plain-text body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailMessage {
+    /// Message-ID without angle brackets
+    pub message_id: String,
+    pub thread_id: String,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub subject: String,
+    pub body: String,
+    /// File the message was read from
+    pub source: String,
+}
+
+impl EmailMessage {
+    /// Retriever document id for this message
+    pub fn doc_id(&self) -> String {
+        format!("{}{}", EMAIL_ID_PREFIX, self.message_id)
+    }
+}
+
+/// Metadata filters for listing and searching messages
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EmailFilter {
+    /// Substring match on the From header (case-insensitive)
+    pub from: Option<String>,
+    /// Substring match on To/Cc
+    pub to: Option<String>,
+    pub subject: Option<String>,
+    pub thread_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Summary of a mailbox ingestion run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmailIngestReport {
+    pub files_scanned: usize,
+    pub messages_indexed: usize,
+    pub messages_skipped: usize,
+    pub threads: usize,
+    pub chunks_indexed: usize,
+    pub errors: Vec<String>,
+}
+
+/// A search hit on an email message, with neighbouring thread messages
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailSearchHit {
+    pub message: EmailMessage,
+    pub snippet: String,
+    pub score: f32,
+    pub thread_context: Vec<EmailMessage>,
+}
+
+// ─────────────────────────────────────────────────────────────
+// Storage
+// ─────────────────────────────────────────────────────────────
+
+/// Create the email_messages table if it doesn't exist.
+pub fn init_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS email_messages (
+            message_id TEXT PRIMARY KEY,
+            thread_id TEXT NOT NULL,
+            in_reply_to TEXT,
+            references_json TEXT NOT NULL DEFAULT '[]',
+            sender TEXT NOT NULL,
+            recipients_json TEXT NOT NULL DEFAULT '[]',
+            cc_json TEXT NOT NULL DEFAULT '[]',
+            subject TEXT NOT NULL,
+            date_ts INTEGER,
+            body TEXT NOT NULL,
+            source TEXT NOT NULL,
+            indexed_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_email_thread ON email_messages(thread_id);
+        CREATE INDEX IF NOT EXISTS idx_email_sender ON email_messages(sender);
+        CREATE INDEX IF NOT EXISTS idx_email_date ON email_messages(date_ts);",
+    )?;
+    Ok(())
+}
+
+fn save_message(conn: &Connection, msg: &EmailMessage) -> Result<()> {
+    let json = |v: &Vec<String>| serde_json::to_string(v).unwrap_or_else(|_| "[]".into());
+    conn.execute(
+        "INSERT INTO email_messages (message_id, thread_id, in_reply_to, references_json,
+            sender, recipients_json, cc_json, subject, date_ts, body, source)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+         ON CONFLICT(message_id) DO UPDATE SET
+            thread_id = excluded.thread_id,
+            in_reply_to = excluded.in_reply_to,
+            references_json = excluded.references_json,
+            sender = excluded.sender,
+            recipients_json = excluded.recipients_json,
+            cc_json = excluded.cc_json,
+            subject = excluded.subject,
+            date_ts = excluded.date_ts,
+            body = excluded.body,
+            source = excluded.source,
+            indexed_at = CURRENT_TIMESTAMP",
+        params![
+            msg.message_id,
+            msg.thread_id,
+            msg.in_reply_to,
+            json(&msg.references),
+            msg.from,
+            json(&msg.to),
+            json(&msg.cc),
+            msg.subject,
+            msg.date.map(|d| d.timestamp()),
+            msg.body,
+            msg.source,
+        ],
+    )?;
+    Ok(())
+}
+
+const SELECT_COLUMNS: &str = "message_id, thread_id, in_reply_to, references_json, sender,
+    recipients_json, cc_json, subject, date_ts, body, source";
+
+fn row_to_message(row: &rusqlite::Row) -> rusqlite::Result<EmailMessage> {
+    let list = |s: String| serde_json::from_str::<Vec<String>>(&s).unwrap_or_default();
+    Ok(EmailMessage {
+        message_id: row.get(0)?,
+        thread_id: row.get(1)?,
+        in_reply_to: row.get(2)?,
+        references: list(row.get(3)?),
+        from: row.get(4)?,
+        to: list(row.get(5)?),
+        cc: list(row.get(6)?),
+        subject: row.get(7)?,
+        date: row
+            .get::<_, Option<i64>>(8)?
+            .and_then(|ts| DateTime::from_timestamp(ts, 0)),
+        body: row.get(9)?,
+        source: row.get(10)?,
+    })
+}
+
+pub fn get_message(conn: &Connection, message_id: &str) -> Result<Option<EmailMessage>> {
+    Ok(conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM email_messages WHERE message_id = ?1",
+                SELECT_COLUMNS
+            ),
+            [message_id],
+            row_to_message,
+        )
+        .optional()?)
+}
+
+/// List messages matching `filter`, oldest first
+pub fn list_messages(
+    conn: &Connection,
+    filter: &EmailFilter,
+    limit: usize,
+) -> Result<Vec<EmailMessage>> {
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut values: Vec<rusqlite::types::Value> = Vec::new();
+    let like = |s: &str| rusqlite::types::Value::Text(format!("%{}%", s.to_lowercase()));
+
+    if let Some(from) = &filter.from {
+        clauses.push("LOWER(sender) LIKE ?");
+        values.push(like(from));
+    }
+    if let Some(to) = &filter.to {
+        clauses.push("(LOWER(recipients_json) LIKE ? OR LOWER(cc_json) LIKE ?)");
+        values.push(like(to));
+        values.push(like(to));
+    }
+    if let Some(subject) = &filter.subject {
+        clauses.push("LOWER(subject) LIKE ?");
+        values.push(like(subject));
+    }
+    if let Some(thread_id) = &filter.thread_id {
+        clauses.push("thread_id = ?");
+        values.push(rusqlite::types::Value::Text(thread_id.clone()));
+    }
+    if let Some(since) = filter.since {
+        clauses.push("date_ts >= ?");
+        values.push(rusqlite::types::Value::Integer(since.timestamp()));
+    }
+    if let Some(until) = filter.until {
+        clauses.push("date_ts <= ?");
+        values.push(rusqlite::types::Value::Integer(until.timestamp()));
+    }
+    values.push(rusqlite::types::Value::Integer(limit as i64));
+
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    let sql = format!(
+        "SELECT {} FROM email_messages {} ORDER BY date_ts ASC, message_id ASC LIMIT ?",
+        SELECT_COLUMNS, where_sql
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values), row_to_message)?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+/// All messages of a thread in date order
+pub fn thread_messages(conn: &Connection, thread_id: &str) -> Result<Vec<EmailMessage>> {
+    list_messages(
+        conn,
+        &EmailFilter {
+            thread_id: Some(thread_id.to_string()),
+            ..EmailFilter::default()
+        },
+        10_000,
+    )
+}
+
+/// Up to `window` messages before and after `message_id` in its thread
+pub fn thread_neighbours(
+    conn: &Connection,
+    message_id: &str,
+    window: usize,
+) -> Result<Vec<EmailMessage>> {
+    let Some(msg) = get_message(conn, message_id)? else {
+        return Ok(Vec::new());
+    };
+    let thread = thread_messages(conn, &msg.thread_id)?;
+    let Some(pos) = thread.iter().position(|m| m.message_id == message_id) else {
+        return Ok(Vec::new());
+    };
+    let lo = pos.saturating_sub(window);
+    let hi = (pos + window + 1).min(thread.len());
+    Ok(thread
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| *i >= lo && *i < hi && *i != pos)
+        .map(|(_, m)| m)
+        .collect())
+}
+
+// ─────────────────────────────────────────────────────────────
+// Ingestion and search
+// ─────────────────────────────────────────────────────────────
+
+/// Parse an .eml/.mbox file or a directory of them, reconstruct threads
+/// and index every message.
+pub fn index_mailbox(
+    retriever: &mut Retriever,
+    conn: &Connection,
+    path: &Path,
+    chunker: &dyn Chunker,
+) -> Result<EmailIngestReport> {
+    init_table(conn)?;
+    let mut report = EmailIngestReport::default();
+    let mut messages = Vec::new();
+
+    for file in mailbox_files(path)? {
+        report.files_scanned += 1;
+        let data = std::fs::read(&file).map_err(|e| EmailError::Io(e.to_string()))?;
+        let source = file.to_string_lossy().to_string();
+        let raws = if is_mbox(&file, &data) {
+            split_mbox(&data)
+        } else {
+            vec![data]
+        };
+        for raw in raws {
+            match parse_message(&raw, &source) {
+                Ok(msg) => messages.push(msg),
+                Err(e) => {
+                    report.messages_skipped += 1;
+                    report.errors.push(format!("{}: {}", source, e));
+                }
+            }
+        }
+    }
+
+    assign_threads(&mut messages, |id| {
+        get_message(conn, id).ok().flatten().map(|m| m.thread_id)
+    });
+
+    // Dropped without commit on any early return, rolling the index back
+    let mut batch = retriever
+        .batch()
+        .map_err(|e| EmailError::Io(format!("begin_batch failed: {}", e)))?;
+
+    let mut threads = std::collections::HashSet::new();
+    for msg in &messages {
+        let mut redaction = redaction::session(&msg.doc_id());
+        // The stored copy is redacted like the index, since search and
+        // thread responses are served from it. Chunks use the raw body so
+        // the drop_chunk policy still applies to them.
+        let stored = EmailMessage {
+            from: redaction.field(&msg.from),
+            to: msg.to.iter().map(|a| redaction.field(a)).collect(),
+            cc: msg.cc.iter().map(|a| redaction.field(a)).collect(),
+            subject: redaction.field(&msg.subject),
+            body: redaction::redact(&msg.body),
+            ..msg.clone()
+        };
+        save_message(conn, &stored)?;
+        threads.insert(msg.thread_id.clone());
+
+        let body = if msg.body.trim().is_empty() {
+            msg.subject.clone()
+        } else {
+            msg.body.clone()
+        };
+        // Re-ingesting a message replaces the chunks it was indexed with before
+        let previous = batch.chunk_ids_with_prefix(&format!("{}#", msg.doc_id()));
+        batch
+            .delete_chunks(&previous)
+            .map_err(|e| EmailError::Io(format!("delete_chunks failed: {}", e)))?;
+        let title = format!("{} — {}", stored.subject, stored.from);
+        for (i, chunk) in chunker.chunk_text(&body).iter().enumerate() {
+            let chunk_id = format!("{}#{}", msg.doc_id(), i);
+            let Some(chunk) = redaction.chunk(chunk) else {
+                continue;
+            };
+            let vector = embedder::embed(&chunk);
+            match batch.index_chunk_with_title(&chunk_id, &title, &chunk, &vector) {
+                Ok(()) => report.chunks_indexed += 1,
+                Err(e) => report.errors.push(format!("{}: {}", chunk_id, e)),
+            }
+        }
+        redaction.finish();
+        report.messages_indexed += 1;
+    }
+
+    batch
+        .commit()
+        .map_err(|e| EmailError::Io(format!("commit failed: {}", e)))?;
+    report.threads = threads.len();
+
+    info!(
+        messages = report.messages_indexed,
+        threads = report.threads,
+        chunks = report.chunks_indexed,
+        "Mailbox indexed"
+    );
+    Ok(report)
+}
+
+/// Keyword search over email chunks, filtered on message metadata and
+/// expanded with up to `context` neighbouring messages from each thread.
+pub fn search_messages(
+    retriever: &Retriever,
+    conn: &Connection,
+    query: &str,
+    filter: &EmailFilter,
+    limit: usize,
+    context: usize,
+) -> Result<Vec<EmailSearchHit>> {
+    let hits = retriever
+        .search_hits(query, limit.max(1) * 10)
+        .map_err(|e| EmailError::Io(e.to_string()))?;
+
+    let mut results: Vec<EmailSearchHit> = Vec::new();
+    for hit in hits {
+        let Some(rest) = hit.doc_id.strip_prefix(EMAIL_ID_PREFIX) else {
+            continue;
+        };
+        let message_id = rest.rsplit_once('#').map(|(id, _)| id).unwrap_or(rest);
+        if results.iter().any(|r| r.message.message_id == message_id) {
+            continue;
+        }
+        let Some(message) = get_message(conn, message_id)? else {
+            continue;
+        };
+        if !matches_filter(&message, filter) {
+            continue;
+        }
+        let thread_context = if context > 0 {
+            thread_neighbours(conn, message_id, context)?
+        } else {
+            Vec::new()
+        };
+        results.push(EmailSearchHit {
+            message,
+            snippet: hit.content,
+            score: hit.score,
+            thread_context,
+        });
+        if results.len() >= limit {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+fn matches_filter(msg: &EmailMessage, filter: &EmailFilter) -> bool {
+    let contains = |hay: &str, needle: &str| hay.to_lowercase().contains(&needle.to_lowercase());
+    filter.from.as_ref().is_none_or(|f| contains(&msg.from, f))
+        && filter.to.as_ref().is_none_or(|t| {
+            msg.to
+                .iter()
+                .chain(msg.cc.iter())
+                .any(|addr| contains(addr, t))
+        })
+        && filter
+            .subject
+            .as_ref()
+            .is_none_or(|s| contains(&msg.subject, s))
+        && filter
+            .thread_id
+            .as_ref()
+            .is_none_or(|t| &msg.thread_id == t)
+        && filter
+            .since
+            .is_none_or(|since| msg.date.is_some_and(|d| d >= since))
+        && filter
+            .until
+            .is_none_or(|until| msg.date.is_some_and(|d| d <= until))
+}
+
+fn mailbox_files(path: &Path) -> Result<Vec<std::path::PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(EmailError::Io(format!("'{}' not found", path.display())));
+    }
+    let mut files: Vec<_> = walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            matches!(
+                p.extension().and_then(|e| e.to_str()),
+                Some("eml") | Some("mbox") | Some("mbx")
+            )
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn is_mbox(path: &Path, data: &[u8]) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("mbox") | Some("mbx")
+    ) || data.starts_with(b"From ")
+}
+
+// ─────────────────────────────────────────────────────────────
+// Threading
+// ─────────────────────────────────────────────────────────────
+
+/// Assign `thread_id` to every message.
+///
+/// The thread id is the Message-ID of the thread root: the first entry of
+/// References (or In-Reply-To), followed through messages of the batch.
+/// `known` resolves ids of previously ingested messages to their thread.
+pub fn assign_threads<F>(messages: &mut [EmailMessage], known: F)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let parents: HashMap<String, Option<String>> = messages
+        .iter()
+        .map(|m| {
+            let parent = m
+                .references
+                .first()
+                .cloned()
+                .or_else(|| m.in_reply_to.clone())
+                .filter(|p| p != &m.message_id);
+            (m.message_id.clone(), parent)
+        })
+        .collect();
+
+    let resolve = |start: &str| -> String {
+        let mut current = start.to_string();
+        for _ in 0..64 {
+            match parents.get(&current) {
+                Some(Some(parent)) => current = parent.clone(),
+                Some(None) => return current,
+                None => return known(&current).unwrap_or(current),
+            }
+        }
+        current
+    };
+
+    let thread_ids: Vec<String> = messages.iter().map(|m| resolve(&m.message_id)).collect();
+    for (msg, thread_id) in messages.iter_mut().zip(thread_ids) {
+        msg.thread_id = thread_id;
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Parsing
+// ─────────────────────────────────────────────────────────────
+
+/// Split an mbox file into raw messages, undoing `>From ` quoting
+pub fn split_mbox(data: &[u8]) -> Vec<Vec<u8>> {
+    static QUOTED_FROM: Lazy<Regex> = Lazy::new(|| Regex::new(r"^>+From ").unwrap());
+
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut prev_blank = true;
+
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        if prev_blank && line.starts_with(b"From ") {
+            if let Some(msg) = current.take() {
+                messages.push(msg);
+            }
+            current = Some(Vec::new());
+            prev_blank = false;
+            continue;
+        }
+        let trimmed = trim_eol(line);
+        prev_blank = trimmed.is_empty();
+        if let Some(msg) = current.as_mut() {
+            if trimmed.first() == Some(&b'>')
+                && QUOTED_FROM.is_match(&String::from_utf8_lossy(trimmed))
+            {
+                msg.extend_from_slice(&line[1..]);
+            } else {
+                msg.extend_from_slice(line);
+            }
+        }
+    }
+    if let Some(msg) = current {
+        messages.push(msg);
+    }
+    messages
+        .into_iter()
+        .filter(|m| !m.iter().all(u8::is_ascii_whitespace))
+        .collect()
+}
+
+/// Parse one RFC 5322 message
+pub fn parse_message(raw: &[u8], source: &str) -> Result<EmailMessage> {
+    let (headers, body) = split_headers(raw);
+    if headers.is_empty() {
+        return Err(EmailError::Malformed("no headers".to_string()));
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    };
+
+    let message_id = header("message-id")
+        .and_then(|v| extract_ids(v).into_iter().next())
+        .unwrap_or_else(|| format!("generated-{:016x}@local", seahash::hash(raw)));
+    let in_reply_to = header("in-reply-to").and_then(|v| extract_ids(v).into_iter().next());
+    let references = header("references").map(extract_ids).unwrap_or_default();
+
+    let mut plain = Vec::new();
+    let mut html = Vec::new();
+    collect_text_parts(&headers, body, &mut plain, &mut html, 0);
+    let body = if plain.iter().any(|p| !p.trim().is_empty()) {
+        plain.join("\n\n")
+    } else {
+        html.iter()
+            .map(|h| html_to_text(h))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+
+    Ok(EmailMessage {
+        thread_id: message_id.clone(),
+        message_id,
+        in_reply_to,
+        references,
+        from: header("from").map(decode_header_value).unwrap_or_default(),
+        to: header("to").map(parse_address_list).unwrap_or_default(),
+        cc: header("cc").map(parse_address_list).unwrap_or_default(),
+        date: header("date").and_then(parse_date),
+        subject: header("subject")
+            .map(decode_header_value)
+            .unwrap_or_default(),
+        body: body.trim().to_string(),
+        source: source.to_string(),
+    })
+}
+
+/// Split raw bytes into unfolded (lowercase name, value) headers and the body
+fn split_headers(raw: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut offset = 0;
+
+    for line in raw.split_inclusive(|&b| b == b'\n') {
+        offset += line.len();
+        let line = trim_eol(line);
+        if line.is_empty() {
+            return (headers, &raw[offset..]);
+        }
+        let text = decode_bytes(line, None);
+        if text.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(text.trim());
+            }
+            continue;
+        }
+        match text.split_once(':') {
+            Some((name, value)) if !name.contains(' ') => {
+                headers.push((name.trim().to_lowercase(), value.trim().to_string()))
+            }
+            // Not a header: treat the rest as body
+            _ => return (headers, &raw[offset - line.len()..]),
+        }
+    }
+    (headers, &raw[raw.len()..])
+}
+
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Recursively collect decoded text/plain and text/html parts
+fn collect_text_parts(
+    headers: &[(String, String)],
+    body: &[u8],
+    plain: &mut Vec<String>,
+    html: &mut Vec<String>,
+    depth: usize,
+) {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    };
+    let (mime, params) = parse_content_type(header("content-type").unwrap_or("text/plain"));
+    let disposition = header("content-disposition").unwrap_or("");
+    if disposition.to_lowercase().starts_with("attachment") {
+        return;
+    }
+
+    if mime.starts_with("multipart/") {
+        let Some(boundary) = params.get("boundary") else {
+            return;
+        };
+        if depth > 16 {
+            warn!("collect_text_parts: multipart nesting too deep");
+            return;
+        }
+        for part in split_multipart(body, boundary) {
+            let (part_headers, part_body) = split_headers(part);
+            collect_text_parts(&part_headers, part_body, plain, html, depth + 1);
+        }
+        return;
+    }
+
+    if mime != "text/plain" && mime != "text/html" {
+        debug!("collect_text_parts: skipping part with type '{}'", mime);
+        return;
+    }
+
+    let encoding = header("content-transfer-encoding")
+        .unwrap_or("7bit")
+        .trim()
+        .to_lowercase();
+    let bytes = decode_transfer_encoding(body, &encoding);
+    let text = decode_bytes(&bytes, params.get("charset").map(String::as_str));
+    if mime == "text/html" {
+        html.push(text);
+    } else {
+        plain.push(text);
+    }
+}
+
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut offset = 0;
+
+    for line in body.split_inclusive(|&b| b == b'\n') {
+        let trimmed = trim_eol(line);
+        if trimmed.starts_with(delimiter.as_bytes()) {
+            let rest = &trimmed[delimiter.len()..];
+            if let Some(s) = start.take() {
+                parts.push(&body[s..offset]);
+            }
+            if rest.starts_with(b"--") {
+                return parts;
+            }
+            start = Some(offset + line.len());
+        }
+        offset += line.len();
+    }
+    if let Some(s) = start {
+        parts.push(&body[s..]);
+    }
+    parts
+}
+
+fn decode_transfer_encoding(body: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding {
+        "base64" => {
+            let cleaned: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(&cleaned)
+                .or_else(|_| base64::engine::general_purpose::STANDARD_NO_PAD.decode(&cleaned))
+                .unwrap_or_else(|_| body.to_vec())
+        }
+        "quoted-printable" => quoted_printable::decode(body, quoted_printable::ParseMode::Robust)
+            .unwrap_or_else(|_| body.to_vec()),
+        _ => body.to_vec(),
+    }
+}
+
+/// Decode bytes in `charset`, falling back to UTF-8 then Windows-1252
+fn decode_bytes(bytes: &[u8], charset: Option<&str>) -> String {
+    if let Some(encoding) = charset.and_then(|c| encoding_rs::Encoding::for_label(c.as_bytes())) {
+        return encoding.decode(bytes).0.into_owned();
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1252.decode(bytes).0.into_owned(),
+    }
+}
+
+/// `text/plain; charset="utf-8"` -> ("text/plain", {charset: utf-8})
+fn parse_content_type(value: &str) -> (String, HashMap<String, String>) {
+    let mut parts = split_unquoted(value, ';').into_iter();
+    let mime = parts.next().unwrap_or_default().trim().to_lowercase();
+    let params = parts
+        .filter_map(|p| {
+            let (k, v) = p.split_once('=')?;
+            let k = k.trim().to_lowercase();
+            let v = v.trim().trim_matches('"').to_string();
+            Some((k, v))
+        })
+        .collect();
+    (mime, params)
+}
+
+/// Split on `sep` outside double quotes and angle brackets
+fn split_unquoted(value: &str, sep: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut in_angle = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_angle = true,
+            '>' if !in_quotes => in_angle = false,
+            _ => {}
+        }
+        if c == sep && !in_quotes && !in_angle {
+            parts.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+fn parse_address_list(value: &str) -> Vec<String> {
+    split_unquoted(value, ',')
+        .into_iter()
+        .map(|a| decode_header_value(a.trim()))
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+fn extract_ids(value: &str) -> Vec<String> {
+    static MSG_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"<([^<>\s]+)>").unwrap());
+    let ids: Vec<String> = MSG_ID
+        .captures_iter(value)
+        .map(|c| c[1].to_string())
+        .collect();
+    if ids.is_empty() && !value.trim().is_empty() && !value.contains(' ') {
+        return vec![value.trim().to_string()];
+    }
+    ids
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    static TRAILING_COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*\([^)]*\)\s*$").unwrap());
+    let cleaned = TRAILING_COMMENT.replace(value.trim(), "");
+    DateTime::parse_from_rfc2822(&cleaned)
+        .or_else(|_| DateTime::parse_from_rfc3339(&cleaned))
+        .map(|d| d.with_timezone(&Utc))
+        .ok()
+}
+
+/// Decode RFC 2047 encoded words (`=?utf-8?B?...?=`)
+pub fn decode_header_value(value: &str) -> String {
+    static ENCODED_WORD: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"=\?([^?\s]+)\?([bBqQ])\?([^?\s]*)\?=").unwrap());
+    static BETWEEN_WORDS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\?=)\s+(=\?)").unwrap());
+
+    // Whitespace between adjacent encoded words is not significant
+    let joined = BETWEEN_WORDS.replace_all(value, "$1$2");
+    ENCODED_WORD
+        .replace_all(&joined, |caps: &regex::Captures| {
+            let charset = caps[1].split('*').next().unwrap_or("utf-8");
+            let bytes = match caps[2].to_ascii_lowercase().as_str() {
+                "b" => base64::engine::general_purpose::STANDARD
+                    .decode(caps[3].as_bytes())
+                    .ok(),
+                _ => quoted_printable::decode(
+                    caps[3].replace('_', " ").as_bytes(),
+                    quoted_printable::ParseMode::Robust,
+                )
+                .ok(),
+            };
+            match bytes {
+                Some(b) => decode_bytes(&b, Some(charset)),
+                None => caps[0].to_string(),
+            }
+        })
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: &str = "From: Alice <alice@example.com>\r
+To: dev@example.com\r
+Subject: =?UTF-8?Q?Caf=C3=A9_menu?=\r
+Date: Tue, 3 Sep 2024 10:00:00 +0200 (CEST)\r
+Message-ID: <root@example.com>\r
+MIME-Version: 1.0\r
+Content-Type: multipart/alternative; boundary=\"b1\"\r
+\r
+--b1\r
+Content-Type: text/plain; charset=iso-8859-1\r
+Content-Transfer-Encoding: quoted-printable\r
+\r
+Caf=E9 opens at nine.\r
+--b1\r
+Content-Type: text/html\r
+\r
+<p>Caf&eacute; opens at nine.</p>\r
+--b1--\r
+";
+
+    fn reply(id: &str, parent: &str, refs: &str, body: &str) -> String {
+        format!(
+            "From: Bob <bob@example.com>\nTo: Alice <alice@example.com>, \"Doe, Jane\" <jane@example.com>\nSubject: Re: menu\nDate: Wed, 4 Sep 2024 09:00:00 +0000\nMessage-ID: <{}>\nIn-Reply-To: <{}>\nReferences: {}\nContent-Type: text/html; charset=utf-8\nContent-Transfer-Encoding: base64\n\n{}\n",
+            id,
+            parent,
+            refs,
+            base64::engine::general_purpose::STANDARD.encode(body)
+        )
+    }
+
+    #[test]
+    fn test_parse_multipart_prefers_plain_and_decodes_charsets() {
+        let msg = parse_message(ROOT.as_bytes(), "inbox.eml").unwrap();
+        assert_eq!(msg.message_id, "root@example.com");
+        assert_eq!(msg.subject, "Café menu");
+        assert_eq!(msg.from, "Alice <alice@example.com>");
+        assert_eq!(msg.body, "Café opens at nine.");
+        assert_eq!(msg.date.unwrap().to_rfc3339(), "2024-09-03T08:00:00+00:00");
+    }
+
+    #[test]
+    fn test_html_fallback_and_address_list() {
+        let raw = reply(
+            "r1@x",
+            "root@example.com",
+            "<root@example.com>",
+            "<b>Sounds</b> good",
+        );
+        let msg = parse_message(raw.as_bytes(), "r.eml").unwrap();
+        assert_eq!(msg.body, "Sounds good");
+        assert_eq!(
+            msg.to,
+            vec![
+                "Alice <alice@example.com>",
+                "\"Doe, Jane\" <jane@example.com>"
+            ]
+        );
+        assert_eq!(msg.in_reply_to.as_deref(), Some("root@example.com"));
+    }
+
+    #[test]
+    fn test_split_mbox_unescapes_from_lines() {
+        let mbox = format!(
+            "From alice@example.com Tue Sep  3 10:00:00 2024\n{}\nFrom bob@example.com Wed Sep  4 09:00:00 2024\nFrom: bob@example.com\nMessage-ID: <m2@x>\n\n>From the archive\n",
+            ROOT.replace("\r\n", "\n")
+        );
+        let messages = split_mbox(mbox.as_bytes());
+        assert_eq!(messages.len(), 2);
+        let second = parse_message(&messages[1], "box.mbox").unwrap();
+        assert_eq!(second.body, "From the archive");
+    }
+
+    #[test]
+    fn test_thread_reconstruction_with_missing_references() {
+        let mut messages = vec![
+            parse_message(ROOT.as_bytes(), "a").unwrap(),
+            parse_message(
+                reply("r1@x", "root@example.com", "<root@example.com>", "one").as_bytes(),
+                "a",
+            )
+            .unwrap(),
+            // Client only sent In-Reply-To
+            parse_message(reply("r2@x", "r1@x", "", "two").as_bytes(), "a").unwrap(),
+            // Parent stored by an earlier run
+            parse_message(reply("r3@x", "old@x", "", "three").as_bytes(), "a").unwrap(),
+        ];
+        assign_threads(&mut messages, |id| {
+            (id == "old@x").then(|| "old-root@x".to_string())
+        });
+        let threads: Vec<_> = messages.iter().map(|m| m.thread_id.as_str()).collect();
+        assert_eq!(
+            threads,
+            vec![
+                "root@example.com",
+                "root@example.com",
+                "root@example.com",
+                "old-root@x"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_mailbox_filters_and_thread_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let mbox = format!(
+            "From a Tue Sep  3 10:00:00 2024\n{}\nFrom b Wed Sep  4 09:00:00 2024\n{}",
+            ROOT.replace("\r\n", "\n"),
+            reply(
+                "r1@x",
+                "root@example.com",
+                "<root@example.com>",
+                "The espresso machine is fixed"
+            )
+        );
+        std::fs::write(dir.path().join("archive.mbox"), mbox).unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        let mut retriever = Retriever::new_with_vector_file(
+            dir.path().join("index").to_str().unwrap(),
+            dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        let chunker = crate::memory::chunker_factory::FixedChunker;
+
+        let report = index_mailbox(&mut retriever, &conn, dir.path(), &chunker).unwrap();
+        assert_eq!(report.messages_indexed, 2);
+        assert_eq!(report.threads, 1);
+
+        let hits =
+            search_messages(&retriever, &conn, "espresso", &EmailFilter::default(), 5, 1).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message.message_id, "r1@x");
+        assert_eq!(hits[0].thread_context.len(), 1);
+        assert_eq!(hits[0].thread_context[0].message_id, "root@example.com");
+
+        let filter = EmailFilter {
+            from: Some("alice".into()),
+            ..EmailFilter::default()
+        };
+        assert!(
+            search_messages(&retriever, &conn, "espresso", &filter, 5, 0)
+                .unwrap()
+                .is_empty()
+        );
+        let listed = list_messages(&conn, &filter, 10).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].message_id, "root@example.com");
+    }
+
+    #[test]
+    fn test_index_mailbox_error_rolls_back_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mbox = format!(
+            "From a Tue Sep  3 10:00:00 2024\n{}\nFrom b Wed Sep  4 09:00:00 2024\n{}",
+            ROOT.replace("\r\n", "\n"),
+            reply(
+                "r1@x",
+                "root@example.com",
+                "",
+                "The espresso machine is fixed"
+            )
+        );
+        std::fs::write(dir.path().join("archive.mbox"), mbox).unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TRIGGER reject_reply BEFORE INSERT ON email_messages
+             WHEN NEW.message_id = 'r1@x'
+             BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+        )
+        .unwrap();
+        let mut retriever = Retriever::new_with_vector_file(
+            dir.path().join("index").to_str().unwrap(),
+            dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        let chunker = crate::memory::chunker_factory::FixedChunker;
+
+        assert!(index_mailbox(&mut retriever, &conn, dir.path(), &chunker).is_err());
+        // The root message was indexed before the failure and is rolled back
+        assert!(retriever.search_hits("nine", 5).unwrap().is_empty());
+        assert!(retriever.doc_id_to_vector_idx.is_empty());
+
+        conn.execute_batch("DROP TRIGGER reject_reply").unwrap();
+        let report = index_mailbox(&mut retriever, &conn, dir.path(), &chunker).unwrap();
+        assert_eq!(report.messages_indexed, 2);
+        assert_eq!(retriever.search_hits("espresso", 5).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reindexing_a_mailbox_does_not_duplicate_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("root.eml"), ROOT).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        let mut retriever = Retriever::new_with_vector_file(
+            dir.path().join("index").to_str().unwrap(),
+            dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        let chunker = crate::memory::chunker_factory::FixedChunker;
+
+        index_mailbox(&mut retriever, &conn, dir.path(), &chunker).unwrap();
+        index_mailbox(&mut retriever, &conn, dir.path(), &chunker).unwrap();
+        assert_eq!(retriever.search_hits("nine", 5).unwrap().len(), 1);
+    }
+}
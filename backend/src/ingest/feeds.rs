@@ -0,0 +1,1313 @@
+// src/ingest/feeds.rs
+// RSS/Atom feed subscriptions with scheduled polling
+//
+// Subscriptions live in `feed_subscriptions`. Each poll sends the stored
+// ETag/Last-Modified validators, parses the feed with feed-rs and ingests
+// entries whose GUID has not been seen for that subscription. Entries are
+// indexed as `feed:<entry id>#<n>` with the published date kept in
+// `feed_entries` and in the chunks' `published` field, so searches can filter
+// on it. Retention expires old entries: their chunks are removed from the
+// index, but the row stays as a tombstone so the GUID is not ingested again
+// while it is still in the feed. Feeds, their redirects and linked articles
+// are only fetched from hosts in `CRAWL_ALLOWED_HOSTS`, and responses are
+// capped at `MAX_RESPONSE_BYTES`.
+
+use super::crawler::host_allowed;
+use super::html::{extract_page, html_to_text};
+use crate::config::ChunkerMode;
+use crate::embedder;
+use crate::memory::chunker_factory::Chunker;
+use crate::retriever::{ChunkFields, Retriever};
+use crate::security::redaction;
+use chrono::{DateTime, Utc};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{StatusCode, Url};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Prefix of retriever chunk ids belonging to feed entries
+pub const FEED_ID_PREFIX: &str = "feed:";
+
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 3600;
+pub const MIN_POLL_INTERVAL_SECS: u64 = 60;
+
+/// How often the scheduler looks for subscriptions that are due
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+const USER_AGENT: &str = "ag-feeds/1.0";
+/// Largest feed or article body read
+const MAX_RESPONSE_BYTES: usize = 5 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum FeedError {
+    #[error("invalid subscription: {0}")]
+    Invalid(String),
+    #[error("subscription {0} not found")]
+    NotFound(i64),
+    #[error("fetch failed: {0}")]
+    Fetch(String),
+    #[error("feed could not be parsed: {0}")]
+    Parse(String),
+    #[error("index error: {0}")]
+    Index(String),
+    #[error("database error: {0}")]
+    Database(String),
+}
+
+impl From<rusqlite::Error> for FeedError {
+    fn from(err: rusqlite::Error) -> Self {
+        FeedError::Database(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, FeedError>;
+
+/// A stored subscription with polling state
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedSubscription {
+    pub id: i64,
+    pub url: String,
+    /// User-supplied title, or the feed's own title after the first poll
+    pub title: Option<String>,
+    pub poll_interval_secs: u64,
+    /// Fetch each entry's link and index the article instead of the summary
+    pub fetch_full_article: bool,
+    /// Entries older than this are expired (None = keep forever)
+    pub retention_days: Option<u32>,
+    pub enabled: bool,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Entries currently indexed (excludes expired tombstones)
+    pub entry_count: usize,
+    pub latest_entry_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewSubscription {
+    pub url: String,
+    pub title: Option<String>,
+    pub poll_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub fetch_full_article: bool,
+    pub retention_days: Option<u32>,
+    pub enabled: Option<bool>,
+}
+
+/// Partial update; absent fields are left unchanged
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SubscriptionUpdate {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub poll_interval_secs: Option<u64>,
+    pub fetch_full_article: Option<bool>,
+    /// 0 removes the retention limit
+    pub retention_days: Option<u32>,
+    pub enabled: Option<bool>,
+}
+
+/// An ingested (or expired) feed entry
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedEntryRecord {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub guid: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub ingested_at: DateTime<Utc>,
+    pub chunk_count: usize,
+    pub expired: bool,
+}
+
+/// An entry as read from the feed document
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedItem {
+    pub guid: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+    /// Plain text of the entry content or summary
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParsedFeed {
+    pub title: Option<String>,
+    pub items: Vec<FeedItem>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PollReport {
+    pub subscription_id: i64,
+    /// The server answered 304 Not Modified
+    pub not_modified: bool,
+    pub entries_seen: usize,
+    pub entries_ingested: usize,
+    pub duplicates_skipped: usize,
+    /// Entries already past the retention window when first seen
+    pub expired_skipped: usize,
+    pub chunks_indexed: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionReport {
+    pub entries_expired: usize,
+    pub chunks_removed: usize,
+}
+
+pub fn init_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS feed_subscriptions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            url TEXT NOT NULL UNIQUE,
+            title TEXT,
+            poll_interval_secs INTEGER NOT NULL,
+            fetch_full_article INTEGER NOT NULL DEFAULT 0,
+            retention_days INTEGER,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            etag TEXT,
+            last_modified TEXT,
+            last_polled_ts INTEGER,
+            last_error TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS feed_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            subscription_id INTEGER NOT NULL,
+            guid TEXT NOT NULL,
+            title TEXT NOT NULL,
+            link TEXT,
+            published_ts INTEGER,
+            ingested_ts INTEGER NOT NULL,
+            chunk_count INTEGER NOT NULL DEFAULT 0,
+            expired INTEGER NOT NULL DEFAULT 0,
+            UNIQUE(subscription_id, guid)
+        );
+        CREATE INDEX IF NOT EXISTS idx_feed_entries_published ON feed_entries(published_ts);",
+    )?;
+    Ok(())
+}
+
+// ─────────────────────────────────────────────────────────────
+// Subscription CRUD
+// ─────────────────────────────────────────────────────────────
+
+const SELECT_SUBSCRIPTION: &str = "SELECT s.id, s.url, s.title, s.poll_interval_secs,
+        s.fetch_full_article, s.retention_days, s.enabled, s.etag, s.last_modified,
+        s.last_polled_ts, s.last_error,
+        (SELECT COUNT(*) FROM feed_entries e WHERE e.subscription_id = s.id AND e.expired = 0),
+        (SELECT MAX(COALESCE(e.published_ts, e.ingested_ts)) FROM feed_entries e
+            WHERE e.subscription_id = s.id)
+     FROM feed_subscriptions s";
+
+fn row_to_subscription(row: &rusqlite::Row) -> rusqlite::Result<FeedSubscription> {
+    let timestamp = |ts: Option<i64>| ts.and_then(|ts| DateTime::from_timestamp(ts, 0));
+    Ok(FeedSubscription {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        title: row.get(2)?,
+        poll_interval_secs: row.get::<_, i64>(3)?.max(0) as u64,
+        fetch_full_article: row.get(4)?,
+        retention_days: row.get(5)?,
+        enabled: row.get(6)?,
+        etag: row.get(7)?,
+        last_modified: row.get(8)?,
+        last_polled_at: timestamp(row.get(9)?),
+        last_error: row.get(10)?,
+        entry_count: row.get::<_, i64>(11)?.max(0) as usize,
+        latest_entry_at: timestamp(row.get(12)?),
+    })
+}
+
+fn validate_url(url: &str, allowed_hosts: &[String]) -> Result<String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| FeedError::Invalid(format!("url: {}", e)))?;
+    check_host(&parsed, allowed_hosts).map_err(FeedError::Invalid)?;
+    Ok(parsed.to_string())
+}
+
+/// An http(s) URL whose host is in `allowed_hosts` (empty allows none)
+fn check_host(url: &Url, allowed_hosts: &[String]) -> std::result::Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("url must be http or https".to_string());
+    }
+    let host = url.host_str().map(str::to_lowercase).unwrap_or_default();
+    if !host_allowed(allowed_hosts, &host) {
+        return Err(format!("host '{}' is not in CRAWL_ALLOWED_HOSTS", host));
+    }
+    Ok(())
+}
+
+fn validate_interval(secs: u64) -> Result<u64> {
+    if secs < MIN_POLL_INTERVAL_SECS {
+        return Err(FeedError::Invalid(format!(
+            "poll_interval_secs must be at least {}",
+            MIN_POLL_INTERVAL_SECS
+        )));
+    }
+    Ok(secs)
+}
+
+pub fn create_subscription(
+    conn: &Connection,
+    new: &NewSubscription,
+    allowed_hosts: &[String],
+) -> Result<FeedSubscription> {
+    let url = validate_url(&new.url, allowed_hosts)?;
+    let interval = validate_interval(new.poll_interval_secs.unwrap_or(DEFAULT_POLL_INTERVAL_SECS))?;
+    let exists = conn
+        .query_row(
+            "SELECT id FROM feed_subscriptions WHERE url = ?1",
+            [&url],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?;
+    if exists.is_some() {
+        return Err(FeedError::Invalid(format!("already subscribed to {}", url)));
+    }
+    conn.execute(
+        "INSERT INTO feed_subscriptions
+            (url, title, poll_interval_secs, fetch_full_article, retention_days, enabled)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            url,
+            new.title
+                .as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty()),
+            interval as i64,
+            new.fetch_full_article,
+            new.retention_days.filter(|d| *d > 0),
+            new.enabled.unwrap_or(true),
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    get_subscription(conn, id)?.ok_or(FeedError::NotFound(id))
+}
+
+pub fn get_subscription(conn: &Connection, id: i64) -> Result<Option<FeedSubscription>> {
+    Ok(conn
+        .query_row(
+            &format!("{} WHERE s.id = ?1", SELECT_SUBSCRIPTION),
+            [id],
+            row_to_subscription,
+        )
+        .optional()?)
+}
+
+pub fn list_subscriptions(conn: &Connection) -> Result<Vec<FeedSubscription>> {
+    let mut stmt = conn.prepare(&format!("{} ORDER BY s.id", SELECT_SUBSCRIPTION))?;
+    let subscriptions = stmt
+        .query_map([], row_to_subscription)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(subscriptions)
+}
+
+pub fn update_subscription(
+    conn: &Connection,
+    id: i64,
+    update: &SubscriptionUpdate,
+    allowed_hosts: &[String],
+) -> Result<FeedSubscription> {
+    let current = get_subscription(conn, id)?.ok_or(FeedError::NotFound(id))?;
+    let url = match &update.url {
+        Some(url) => validate_url(url, allowed_hosts)?,
+        None => current.url.clone(),
+    };
+    let interval = match update.poll_interval_secs {
+        Some(secs) => validate_interval(secs)?,
+        None => current.poll_interval_secs,
+    };
+    let retention = match update.retention_days {
+        Some(0) => None,
+        Some(days) => Some(days),
+        None => current.retention_days,
+    };
+    let title = match &update.title {
+        Some(title) => Some(title.trim().to_string()).filter(|t| !t.is_empty()),
+        None => current.title.clone(),
+    };
+    // A new URL is a different feed: drop the validators so the next poll refetches
+    let url_changed = url != current.url;
+    conn.execute(
+        "UPDATE feed_subscriptions SET url = ?2, title = ?3, poll_interval_secs = ?4,
+            fetch_full_article = ?5, retention_days = ?6, enabled = ?7,
+            etag = CASE WHEN ?8 THEN NULL ELSE etag END,
+            last_modified = CASE WHEN ?8 THEN NULL ELSE last_modified END
+         WHERE id = ?1",
+        params![
+            id,
+            url,
+            title,
+            interval as i64,
+            update
+                .fetch_full_article
+                .unwrap_or(current.fetch_full_article),
+            retention,
+            update.enabled.unwrap_or(current.enabled),
+            url_changed,
+        ],
+    )?;
+    get_subscription(conn, id)?.ok_or(FeedError::NotFound(id))
+}
+
+/// Delete a subscription and remove its entries from the index.
+/// Returns the number of entries removed.
+pub fn delete_subscription(conn: &Connection, retriever: &mut Retriever, id: i64) -> Result<usize> {
+    if get_subscription(conn, id)?.is_none() {
+        return Err(FeedError::NotFound(id));
+    }
+    let entries = list_entries(conn, id, usize::MAX)?;
+    let chunk_ids: Vec<String> = entries.iter().flat_map(entry_chunk_ids).collect();
+    retriever
+        .delete_chunks(&chunk_ids)
+        .map_err(|e| FeedError::Index(e.to_string()))?;
+    conn.execute("DELETE FROM feed_entries WHERE subscription_id = ?1", [id])?;
+    conn.execute("DELETE FROM feed_subscriptions WHERE id = ?1", [id])?;
+    info!(
+        subscription_id = id,
+        entries = entries.len(),
+        "Feed subscription deleted"
+    );
+    Ok(entries.len())
+}
+
+/// Enabled subscriptions whose poll interval has elapsed
+pub fn due_subscriptions(conn: &Connection, now: DateTime<Utc>) -> Result<Vec<FeedSubscription>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE s.enabled = 1
+            AND (s.last_polled_ts IS NULL OR s.last_polled_ts + s.poll_interval_secs <= ?1)
+         ORDER BY s.id",
+        SELECT_SUBSCRIPTION
+    ))?;
+    let due = stmt
+        .query_map([now.timestamp()], row_to_subscription)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(due)
+}
+
+fn record_poll(
+    conn: &Connection,
+    sub: &FeedSubscription,
+    validators: Option<(Option<String>, Option<String>)>,
+    feed_title: Option<&str>,
+    error: Option<&str>,
+) -> Result<()> {
+    let (etag, last_modified) =
+        validators.unwrap_or_else(|| (sub.etag.clone(), sub.last_modified.clone()));
+    conn.execute(
+        "UPDATE feed_subscriptions SET etag = ?2, last_modified = ?3, last_polled_ts = ?4,
+            last_error = ?5, title = COALESCE(title, ?6)
+         WHERE id = ?1",
+        params![
+            sub.id,
+            etag,
+            last_modified,
+            Utc::now().timestamp(),
+            error,
+            feed_title,
+        ],
+    )?;
+    Ok(())
+}
+
+// ─────────────────────────────────────────────────────────────
+// Entries
+// ─────────────────────────────────────────────────────────────
+
+fn row_to_entry(row: &rusqlite::Row) -> rusqlite::Result<FeedEntryRecord> {
+    Ok(FeedEntryRecord {
+        id: row.get(0)?,
+        subscription_id: row.get(1)?,
+        guid: row.get(2)?,
+        title: row.get(3)?,
+        link: row.get(4)?,
+        published_at: row
+            .get::<_, Option<i64>>(5)?
+            .and_then(|ts| DateTime::from_timestamp(ts, 0)),
+        ingested_at: DateTime::from_timestamp(row.get(6)?, 0).unwrap_or_default(),
+        chunk_count: row.get::<_, i64>(7)?.max(0) as usize,
+        expired: row.get(8)?,
+    })
+}
+
+/// Entries of a subscription, newest first
+pub fn list_entries(
+    conn: &Connection,
+    subscription_id: i64,
+    limit: usize,
+) -> Result<Vec<FeedEntryRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, subscription_id, guid, title, link, published_ts, ingested_ts,
+                chunk_count, expired
+         FROM feed_entries WHERE subscription_id = ?1
+         ORDER BY COALESCE(published_ts, ingested_ts) DESC, id DESC
+         LIMIT ?2",
+    )?;
+    let entries = stmt
+        .query_map(
+            params![subscription_id, limit.min(i64::MAX as usize) as i64],
+            row_to_entry,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(entries)
+}
+
+fn entry_chunk_ids(entry: &FeedEntryRecord) -> Vec<String> {
+    (0..entry.chunk_count)
+        .map(|i| format!("{}{}#{}", FEED_ID_PREFIX, entry.id, i))
+        .collect()
+}
+
+/// Drop items whose GUID was already ingested for this subscription,
+/// and repeated GUIDs within the same document
+pub fn unseen_items(
+    conn: &Connection,
+    subscription_id: i64,
+    items: Vec<FeedItem>,
+) -> Result<(Vec<FeedItem>, usize)> {
+    let mut stmt =
+        conn.prepare("SELECT 1 FROM feed_entries WHERE subscription_id = ?1 AND guid = ?2")?;
+    let mut seen = HashSet::new();
+    let mut fresh = Vec::new();
+    let mut duplicates = 0;
+    for item in items {
+        if !seen.insert(item.guid.clone()) || stmt.exists(params![subscription_id, item.guid])? {
+            duplicates += 1;
+        } else {
+            fresh.push(item);
+        }
+    }
+    Ok((fresh, duplicates))
+}
+
+fn retention_cutoff(sub: &FeedSubscription, now: DateTime<Utc>) -> Option<i64> {
+    sub.retention_days
+        .map(|days| now.timestamp() - i64::from(days) * 86_400)
+}
+
+/// Index new items and record them in `feed_entries`. Items already past
+/// the retention window are recorded as expired without being indexed.
+/// The caller owns the retriever batch.
+pub fn ingest_items(
+    conn: &Connection,
+    retriever: &mut Retriever,
+    chunker: &dyn Chunker,
+    sub: &FeedSubscription,
+    items: &[FeedItem],
+    report: &mut PollReport,
+) -> Result<()> {
+    let now = Utc::now();
+    let cutoff = retention_cutoff(sub, now);
+    let feed_title = sub.title.as_deref().unwrap_or(&sub.url);
+
+    for item in items {
+        let timestamp = item
+            .published
+            .map(|p| p.timestamp())
+            .unwrap_or(now.timestamp());
+        let expired = cutoff.is_some_and(|cutoff| timestamp < cutoff);
+        // The row is released only once the entry is indexed, so a failed
+        // entry stays unseen and the next poll retries it
+        conn.execute_batch("SAVEPOINT feed_entry")?;
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO feed_entries
+                (subscription_id, guid, title, link, published_ts, ingested_ts, expired)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                sub.id,
+                item.guid,
+                item.title,
+                item.link,
+                item.published.map(|p| p.timestamp()),
+                now.timestamp(),
+                expired,
+            ],
+        )?;
+        if inserted == 0 || expired {
+            conn.execute_batch("RELEASE feed_entry")?;
+            if inserted == 0 {
+                report.duplicates_skipped += 1;
+            } else {
+                report.expired_skipped += 1;
+            }
+            continue;
+        }
+        let entry_id = conn.last_insert_rowid();
+
+        let title = match item.published {
+            Some(published) => format!(
+                "{} — {} ({})",
+                item.title,
+                feed_title,
+                published.format("%Y-%m-%d")
+            ),
+            None => format!("{} — {}", item.title, feed_title),
+        };
+        let body = if item.text.trim().is_empty() {
+            item.title.clone()
+        } else {
+            item.text.clone()
+        };
+        let fields = ChunkFields {
+            published: item.published.map(|p| p.timestamp()),
+            ..ChunkFields::default()
+        };
+        let mut redaction = redaction::session(&format!("{}{}", FEED_ID_PREFIX, entry_id));
+        let title = redaction.field(&title);
+        let mut indexed = Vec::new();
+        // Chunk ids are numbered over every chunk, including dropped ones
+        let mut slots = 0;
+        let mut failed = None;
+        for (i, chunk) in chunker.chunk_text(&body).iter().enumerate() {
+            slots = i + 1;
+            let chunk_id = format!("{}{}#{}", FEED_ID_PREFIX, entry_id, i);
+            let Some(chunk) = redaction.chunk(chunk) else {
+                continue;
+            };
+            let vector = embedder::embed(&chunk);
+            match retriever.index_chunk_with_fields(&chunk_id, &title, &chunk, &vector, &fields) {
+                Ok(()) => indexed.push(chunk_id),
+                Err(e) => {
+                    failed = Some(format!("{}: {}", chunk_id, e));
+                    break;
+                }
+            }
+        }
+        redaction.finish();
+        if let Some(error) = failed {
+            conn.execute_batch("ROLLBACK TO feed_entry; RELEASE feed_entry")?;
+            retriever
+                .delete_chunks(&indexed)
+                .map_err(|e| FeedError::Index(e.to_string()))?;
+            report.errors.push(error);
+            continue;
+        }
+        conn.execute(
+            "UPDATE feed_entries SET chunk_count = ?2 WHERE id = ?1",
+            params![entry_id, slots as i64],
+        )?;
+        conn.execute_batch("RELEASE feed_entry")?;
+        report.entries_ingested += 1;
+        report.chunks_indexed += indexed.len();
+    }
+    Ok(())
+}
+
+/// Expire entries older than each subscription's retention window
+pub fn apply_retention(
+    conn: &Connection,
+    retriever: &mut Retriever,
+    now: DateTime<Utc>,
+) -> Result<RetentionReport> {
+    let mut report = RetentionReport::default();
+    for sub in list_subscriptions(conn)? {
+        let Some(cutoff) = retention_cutoff(&sub, now) else {
+            continue;
+        };
+        let mut stmt = conn.prepare(
+            "SELECT id, subscription_id, guid, title, link, published_ts, ingested_ts,
+                    chunk_count, expired
+             FROM feed_entries
+             WHERE subscription_id = ?1 AND expired = 0
+               AND COALESCE(published_ts, ingested_ts) < ?2",
+        )?;
+        let stale = stmt
+            .query_map(params![sub.id, cutoff], row_to_entry)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if stale.is_empty() {
+            continue;
+        }
+        let chunk_ids: Vec<String> = stale.iter().flat_map(entry_chunk_ids).collect();
+        retriever
+            .delete_chunks(&chunk_ids)
+            .map_err(|e| FeedError::Index(e.to_string()))?;
+        for entry in &stale {
+            conn.execute(
+                "UPDATE feed_entries SET expired = 1, chunk_count = 0 WHERE id = ?1",
+                [entry.id],
+            )?;
+        }
+        report.entries_expired += stale.len();
+        report.chunks_removed += chunk_ids.len();
+        debug!(
+            subscription_id = sub.id,
+            expired = stale.len(),
+            "Feed entries aged out"
+        );
+    }
+    Ok(report)
+}
+
+// ─────────────────────────────────────────────────────────────
+// Fetching and parsing
+// ─────────────────────────────────────────────────────────────
+
+/// Parse an RSS, Atom or JSON Feed document
+pub fn parse_feed(body: &[u8]) -> Result<ParsedFeed> {
+    let feed = feed_rs::parser::parse(body).map_err(|e| FeedError::Parse(e.to_string()))?;
+    let items = feed
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let content = entry
+                .content
+                .and_then(|c| c.body)
+                .or_else(|| entry.summary.map(|s| s.content))
+                .unwrap_or_default();
+            let title = entry
+                .title
+                .map(|t| html_to_text(&t.content))
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| "(untitled)".to_string());
+            FeedItem {
+                guid: entry.id,
+                title,
+                link: entry.links.first().map(|l| l.href.clone()),
+                published: entry.published.or(entry.updated),
+                text: html_to_text(&content),
+            }
+        })
+        .collect();
+    Ok(ParsedFeed {
+        title: feed
+            .title
+            .map(|t| html_to_text(&t.content))
+            .filter(|t| !t.is_empty()),
+        items,
+    })
+}
+
+enum FetchOutcome {
+    NotModified,
+    Fetched {
+        body: Vec<u8>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// HTTP access for feeds: only hosts in the allowlist are fetched, redirects
+/// included, and bodies are read up to `MAX_RESPONSE_BYTES`
+#[derive(Clone)]
+pub struct FeedFetcher {
+    client: reqwest::Client,
+    allowed_hosts: Arc<Vec<String>>,
+}
+
+impl FeedFetcher {
+    pub fn new(allowed_hosts: Vec<String>) -> Result<Self> {
+        let allowed_hosts = Arc::new(allowed_hosts);
+        let redirect_hosts = Arc::clone(&allowed_hosts);
+        let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 5 {
+                attempt.error("too many redirects")
+            } else if check_host(attempt.url(), &redirect_hosts).is_ok() {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(FETCH_TIMEOUT)
+            .redirect(redirect)
+            .build()
+            .map_err(|e| FeedError::Fetch(e.to_string()))?;
+        Ok(Self {
+            client,
+            allowed_hosts,
+        })
+    }
+
+    fn get(&self, url: &str) -> Result<reqwest::RequestBuilder> {
+        let parsed = Url::parse(url).map_err(|e| FeedError::Fetch(format!("{}: {}", url, e)))?;
+        check_host(&parsed, &self.allowed_hosts)
+            .map_err(|e| FeedError::Fetch(format!("{}: {}", url, e)))?;
+        Ok(self.client.get(parsed))
+    }
+
+    async fn fetch_feed(&self, sub: &FeedSubscription) -> Result<FetchOutcome> {
+        let mut request = self.get(&sub.url)?;
+        if let Some(etag) = &sub.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &sub.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| FeedError::Fetch(e.to_string()))?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(FetchOutcome::NotModified);
+        }
+        if !response.status().is_success() {
+            return Err(FeedError::Fetch(format!("HTTP {}", response.status())));
+        }
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                .map(String::from)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = read_capped(response).await?;
+        Ok(FetchOutcome::Fetched {
+            body,
+            etag,
+            last_modified,
+        })
+    }
+
+    async fn fetch_article(&self, url: &str) -> Result<String> {
+        let response = self
+            .get(url)?
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| FeedError::Fetch(format!("{}: {}", url, e)))?;
+        let body = read_capped(response)
+            .await
+            .map_err(|e| FeedError::Fetch(format!("{}: {}", url, e)))?;
+        Ok(extract_page(&String::from_utf8_lossy(&body)).text)
+    }
+}
+
+/// The response body, or an error once it passes `MAX_RESPONSE_BYTES`
+async fn read_capped(mut response: reqwest::Response) -> Result<Vec<u8>> {
+    let too_large = || FeedError::Fetch(format!("response over {} bytes", MAX_RESPONSE_BYTES));
+    if response
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_RESPONSE_BYTES)
+    {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| FeedError::Fetch(e.to_string()))?
+    {
+        if body.len() + chunk.len() > MAX_RESPONSE_BYTES {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Poll one subscription: conditional fetch, GUID dedup, optional article
+/// fetch, then index. Chunking, embedding and indexing run on the blocking
+/// pool, and the retriever lock is only taken there.
+pub async fn poll_subscription(
+    fetcher: &FeedFetcher,
+    sub: &FeedSubscription,
+    retriever: &Arc<Mutex<Retriever>>,
+    chunker_mode: ChunkerMode,
+) -> Result<PollReport> {
+    let mut report = PollReport {
+        subscription_id: sub.id,
+        ..PollReport::default()
+    };
+
+    let (body, etag, last_modified) = match fetcher.fetch_feed(sub).await {
+        Ok(FetchOutcome::NotModified) => {
+            report.not_modified = true;
+            record_poll(&db()?, sub, None, None, None)?;
+            return Ok(report);
+        }
+        Ok(FetchOutcome::Fetched {
+            body,
+            etag,
+            last_modified,
+        }) => (body, etag, last_modified),
+        Err(e) => {
+            record_poll(&db()?, sub, None, None, Some(&e.to_string()))?;
+            return Err(e);
+        }
+    };
+    let parsed = match parse_feed(&body) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            record_poll(&db()?, sub, None, None, Some(&e.to_string()))?;
+            return Err(e);
+        }
+    };
+    report.entries_seen = parsed.items.len();
+    let (mut items, duplicates) = unseen_items(&db()?, sub.id, parsed.items)?;
+    report.duplicates_skipped = duplicates;
+
+    if sub.fetch_full_article {
+        for item in &mut items {
+            let Some(link) = item.link.clone() else {
+                continue;
+            };
+            match fetcher.fetch_article(&link).await {
+                Ok(text) if !text.trim().is_empty() => item.text = text,
+                Ok(_) => {}
+                // Keep the feed summary when the article is unreachable
+                Err(e) => report.errors.push(e.to_string()),
+            }
+        }
+    }
+
+    if !items.is_empty() {
+        let retriever = Arc::clone(retriever);
+        let sub = sub.clone();
+        report = tokio::task::spawn_blocking(move || {
+            index_items(&retriever, chunker_mode, &sub, &items, report)
+        })
+        .await
+        .map_err(|e| FeedError::Index(format!("indexing task failed: {}", e)))??;
+    }
+    let conn = db()?;
+    record_poll(
+        &conn,
+        sub,
+        Some((etag, last_modified)),
+        parsed.title.as_deref(),
+        None,
+    )?;
+    info!(
+        subscription_id = sub.id,
+        ingested = report.entries_ingested,
+        duplicates = report.duplicates_skipped,
+        "Feed polled"
+    );
+    Ok(report)
+}
+
+/// Index new items in one retriever batch and one `feed_entries`
+/// transaction. Blocking: call it off the async runtime.
+fn index_items(
+    retriever: &Mutex<Retriever>,
+    chunker_mode: ChunkerMode,
+    sub: &FeedSubscription,
+    items: &[FeedItem],
+    mut report: PollReport,
+) -> Result<PollReport> {
+    let conn = db()?;
+    let chunker = crate::index::default_chunker(chunker_mode);
+    let mut retriever = retriever.lock().unwrap();
+    let mut batch = retriever
+        .batch()
+        .map_err(|e| FeedError::Index(format!("begin_batch failed: {}", e)))?;
+    // Entries are committed after their chunks, and neither on failure
+    conn.execute_batch("BEGIN")?;
+    let result = ingest_items(&conn, &mut batch, chunker.as_ref(), sub, items, &mut report)
+        .and_then(|()| {
+            batch
+                .commit()
+                .map_err(|e| FeedError::Index(format!("commit failed: {}", e)))
+        });
+    if let Err(e) = result {
+        let _ = conn.execute_batch("ROLLBACK");
+        return Err(e);
+    }
+    conn.execute_batch("COMMIT")?;
+    Ok(report)
+}
+
+fn db() -> Result<Connection> {
+    super::open_db().map_err(FeedError::Database)
+}
+
+/// Background loop: poll due subscriptions and apply retention every tick.
+/// Ticks are skipped while a reindex or crawl owns the index.
+pub async fn run_scheduler(
+    retriever: Arc<Mutex<Retriever>>,
+    chunker_mode: ChunkerMode,
+    allowed_hosts: Vec<String>,
+) {
+    let fetcher = match FeedFetcher::new(allowed_hosts) {
+        Ok(fetcher) => fetcher,
+        Err(e) => {
+            warn!("Feed scheduler disabled: {}", e);
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(SCHEDULER_TICK);
+    loop {
+        interval.tick().await;
+        if crate::api::is_reindex_in_progress() {
+            debug!("Reindex in progress, skipping feed poll");
+            continue;
+        }
+        let due = match db().and_then(|conn| due_subscriptions(&conn, Utc::now())) {
+            Ok(due) => due,
+            Err(e) => {
+                warn!("Feed scheduler: {}", e);
+                continue;
+            }
+        };
+        for sub in &due {
+            if let Err(e) = poll_subscription(&fetcher, sub, &retriever, chunker_mode).await {
+                warn!(subscription_id = sub.id, url = %sub.url, "Feed poll failed: {}", e);
+            }
+        }
+        let retention = {
+            let retriever = Arc::clone(&retriever);
+            tokio::task::spawn_blocking(move || {
+                let conn = db()?;
+                let mut retriever = retriever.lock().unwrap();
+                apply_retention(&conn, &mut retriever, Utc::now())
+            })
+            .await
+            .unwrap_or_else(|e| Err(FeedError::Index(format!("retention task failed: {}", e))))
+        };
+        match retention {
+            Ok(r) if r.entries_expired > 0 => info!(
+                expired = r.entries_expired,
+                chunks = r.chunks_removed,
+                "Feed retention applied"
+            ),
+            Ok(_) => {}
+            Err(e) => warn!("Feed retention failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::chunker_factory::FixedChunker;
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Release Notes</title>
+<item><guid>rel-2</guid><title>Version 2 &amp; more</title><link>https://example.com/v2</link>
+<pubDate>Tue, 10 Jun 2025 09:00:00 GMT</pubDate>
+<description>&lt;p&gt;Adds &lt;b&gt;streaming&lt;/b&gt; ingestion.&lt;/p&gt;</description></item>
+<item><guid>rel-1</guid><title>Version 1</title>
+<pubDate>Mon, 02 Jan 2023 09:00:00 GMT</pubDate><description>First release.</description></item>
+<item><guid>rel-2</guid><title>Version 2 repeated</title><description>dup</description></item>
+</channel></rss>"#;
+
+    fn setup() -> (Connection, Retriever, tempfile::TempDir) {
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let retriever = Retriever::new_with_vector_file(
+            dir.path().join("index").to_str().unwrap(),
+            dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        (conn, retriever, dir)
+    }
+
+    fn hosts() -> Vec<String> {
+        vec!["example.com".to_string()]
+    }
+
+    fn subscribe(conn: &Connection, retention_days: Option<u32>) -> FeedSubscription {
+        create_subscription(
+            conn,
+            &NewSubscription {
+                url: "https://example.com/feed.xml".to_string(),
+                title: None,
+                poll_interval_secs: None,
+                fetch_full_article: false,
+                retention_days,
+                enabled: None,
+            },
+            &hosts(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_rss() {
+        let parsed = parse_feed(RSS.as_bytes()).unwrap();
+        assert_eq!(parsed.title.as_deref(), Some("Release Notes"));
+        assert_eq!(parsed.items.len(), 3);
+        let first = &parsed.items[0];
+        assert_eq!(first.guid, "rel-2");
+        assert_eq!(first.title, "Version 2 & more");
+        assert_eq!(first.link.as_deref(), Some("https://example.com/v2"));
+        assert_eq!(first.text, "Adds streaming ingestion.");
+        assert_eq!(
+            first.published.unwrap().to_rfc3339(),
+            "2025-06-10T09:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_atom() {
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom"><title>Blog</title><id>urn:blog</id>
+<updated>2025-01-01T00:00:00Z</updated>
+<entry><id>urn:post:1</id><title>Hello</title><updated>2025-01-01T00:00:00Z</updated>
+<link href="https://blog.example/hello"/><content type="html">&lt;p&gt;Hi there&lt;/p&gt;</content></entry>
+</feed>"#;
+        let parsed = parse_feed(atom.as_bytes()).unwrap();
+        assert_eq!(parsed.items[0].guid, "urn:post:1");
+        assert_eq!(parsed.items[0].text, "Hi there");
+        assert!(parsed.items[0].published.is_some());
+    }
+
+    #[test]
+    fn test_subscription_crud() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        let sub = subscribe(&conn, Some(30));
+        assert_eq!(sub.poll_interval_secs, DEFAULT_POLL_INTERVAL_SECS);
+        assert!(sub.enabled);
+
+        let dup = create_subscription(
+            &conn,
+            &NewSubscription {
+                url: sub.url.clone(),
+                title: None,
+                poll_interval_secs: None,
+                fetch_full_article: false,
+                retention_days: None,
+                enabled: None,
+            },
+            &hosts(),
+        );
+        assert!(matches!(dup, Err(FeedError::Invalid(_))));
+
+        let updated = update_subscription(
+            &conn,
+            sub.id,
+            &SubscriptionUpdate {
+                retention_days: Some(0),
+                poll_interval_secs: Some(600),
+                enabled: Some(false),
+                ..SubscriptionUpdate::default()
+            },
+            &hosts(),
+        )
+        .unwrap();
+        assert_eq!(updated.retention_days, None);
+        assert_eq!(updated.poll_interval_secs, 600);
+        assert!(!updated.enabled);
+        assert!(due_subscriptions(&conn, Utc::now()).unwrap().is_empty());
+
+        let too_fast = SubscriptionUpdate {
+            poll_interval_secs: Some(5),
+            ..SubscriptionUpdate::default()
+        };
+        assert!(update_subscription(&conn, sub.id, &too_fast, &hosts()).is_err());
+        assert_eq!(list_subscriptions(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_subscriptions_need_an_allowed_host() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        let new = |url: &str| NewSubscription {
+            url: url.to_string(),
+            title: None,
+            poll_interval_secs: None,
+            fetch_full_article: false,
+            retention_days: None,
+            enabled: None,
+        };
+        // Without an allowlist no feed may be subscribed
+        let unset = create_subscription(&conn, &new("https://example.com/feed.xml"), &[]);
+        assert!(matches!(unset, Err(FeedError::Invalid(_))));
+        for url in [
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost:8080/feed",
+            "file:///etc/passwd",
+        ] {
+            let denied = create_subscription(&conn, &new(url), &hosts());
+            assert!(matches!(denied, Err(FeedError::Invalid(_))), "{}", url);
+        }
+        let sub = subscribe(&conn, None);
+        let moved = SubscriptionUpdate {
+            url: Some("http://127.0.0.1/feed".to_string()),
+            ..SubscriptionUpdate::default()
+        };
+        assert!(update_subscription(&conn, sub.id, &moved, &hosts()).is_err());
+    }
+
+    /// Serve `responses` (path -> raw HTTP response) on a local port
+    async fn serve(responses: Vec<(&'static str, String)>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let responses = Arc::new(responses);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let responses = Arc::clone(&responses);
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/");
+                    let response = responses
+                        .iter()
+                        .find(|(p, _)| *p == path)
+                        .map(|(_, r)| r.clone())
+                        .unwrap_or_else(|| {
+                            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".into()
+                        });
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_fetcher_keeps_to_allowed_hosts_and_caps_bodies() {
+        let big = "x".repeat(MAX_RESPONSE_BYTES + 1);
+        let base = serve(vec![
+            (
+                "/article",
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 42\r\n\r\n<html><body><p>Full text</p></body></html>".to_string(),
+            ),
+            (
+                "/big",
+                format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n{}", big),
+            ),
+            (
+                "/hop",
+                "HTTP/1.1 302 Found\r\nLocation: http://169.254.169.254/\r\nContent-Length: 0\r\n\r\n".to_string(),
+            ),
+        ])
+        .await;
+
+        let fetcher = FeedFetcher::new(vec!["127.0.0.1".to_string()]).unwrap();
+        let text = fetcher
+            .fetch_article(&format!("{}/article", base))
+            .await
+            .unwrap();
+        assert_eq!(text.trim(), "Full text");
+        let err = fetcher.fetch_article(&format!("{}/big", base)).await;
+        assert!(err.unwrap_err().to_string().contains("bytes"));
+        // The redirect leaves the allowlist, so it is not followed
+        assert!(fetcher
+            .fetch_article(&format!("{}/hop", base))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let elsewhere = FeedFetcher::new(hosts()).unwrap();
+        assert!(elsewhere
+            .fetch_article(&format!("{}/article", base))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_ingest_dedup_and_retention() {
+        let (conn, mut retriever, _dir) = setup();
+        let sub = subscribe(&conn, Some(365 * 100));
+        let items = parse_feed(RSS.as_bytes()).unwrap().items;
+
+        let (fresh, duplicates) = unseen_items(&conn, sub.id, items.clone()).unwrap();
+        assert_eq!((fresh.len(), duplicates), (2, 1));
+        let mut report = PollReport::default();
+        retriever.begin_batch().unwrap();
+        ingest_items(
+            &conn,
+            &mut retriever,
+            &FixedChunker,
+            &sub,
+            &fresh,
+            &mut report,
+        )
+        .unwrap();
+        retriever.commit().unwrap();
+        assert_eq!(report.entries_ingested, 2);
+
+        let hits = retriever.search_hits("streaming", 5).unwrap();
+        assert!(hits[0].doc_id.starts_with(FEED_ID_PREFIX));
+        assert!(hits[0].title.contains("(2025-06-10)"));
+        assert_eq!(hits[0].fields.published, Some(1_749_546_000));
+        // Only the 2025 entry was published after 2025-01-01
+        let recent = retriever
+            .search_hits("published:[1735689600 TO *]", 5)
+            .unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].doc_id, hits[0].doc_id);
+
+        // A second poll of the same document ingests nothing
+        let (fresh, duplicates) = unseen_items(&conn, sub.id, items.clone()).unwrap();
+        assert_eq!((fresh.len(), duplicates), (0, 3));
+
+        // Shrink retention so the 2023 entry ages out
+        let sub = update_subscription(
+            &conn,
+            sub.id,
+            &SubscriptionUpdate {
+                retention_days: Some(365),
+                ..SubscriptionUpdate::default()
+            },
+            &hosts(),
+        )
+        .unwrap();
+        let now = DateTime::parse_from_rfc3339("2025-07-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let retention = apply_retention(&conn, &mut retriever, now).unwrap();
+        assert_eq!(retention.entries_expired, 1);
+        assert!(retriever.search_hits("First", 5).unwrap().is_empty());
+
+        // The expired GUID stays known, so it is not ingested again
+        let (fresh, _) = unseen_items(&conn, sub.id, items).unwrap();
+        assert!(fresh.is_empty());
+        let entries = list_entries(&conn, sub.id, 10).unwrap();
+        assert_eq!(entries.iter().filter(|e| e.expired).count(), 1);
+
+        assert_eq!(
+            delete_subscription(&conn, &mut retriever, sub.id).unwrap(),
+            2
+        );
+        assert!(retriever.search_hits("streaming", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_entry_that_fails_to_index_is_retried() {
+        let (conn, mut retriever, _dir) = setup();
+        let sub = subscribe(&conn, Some(365 * 100));
+        let items = parse_feed(RSS.as_bytes()).unwrap().items;
+        let (fresh, _) = unseen_items(&conn, sub.id, items.clone()).unwrap();
+
+        // Hold the index lock so indexing outside a batch fails
+        let index = retriever.index.clone();
+        let lock: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+        let mut report = PollReport::default();
+        ingest_items(
+            &conn,
+            &mut retriever,
+            &FixedChunker,
+            &sub,
+            &fresh,
+            &mut report,
+        )
+        .unwrap();
+        assert_eq!(report.entries_ingested, 0);
+        assert_eq!(report.errors.len(), 2);
+        assert!(list_entries(&conn, sub.id, 10).unwrap().is_empty());
+        drop(lock);
+
+        let (fresh, duplicates) = unseen_items(&conn, sub.id, items).unwrap();
+        assert_eq!((fresh.len(), duplicates), (2, 1));
+        let mut report = PollReport::default();
+        ingest_items(
+            &conn,
+            &mut retriever,
+            &FixedChunker,
+            &sub,
+            &fresh,
+            &mut report,
+        )
+        .unwrap();
+        assert_eq!(report.entries_ingested, 2);
+        assert!(!retriever.search_hits("streaming", 5).unwrap().is_empty());
+    }
+}
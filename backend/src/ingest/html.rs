@@ -0,0 +1,231 @@
+// src/ingest/html.rs
+// Lightweight HTML to plain-text conversion and page extraction for ingested content
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static DROP_BLOCKS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(script|style|head|noscript|template|svg)\b[^>]*>.*?</(?:script|style|head|noscript|template|svg)\s*>")
+        .unwrap()
+});
+static COMMENTS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+static BLOCK_TAGS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)</?(?:p|div|br|li|ul|ol|h[1-6]|tr|table|section|article|blockquote|pre|hr|header|footer|nav|main|aside|dt|dd)\b[^>]*>")
+        .unwrap()
+});
+static CELL_TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)</t[dh]\s*>").unwrap());
+static TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+static ENTITY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&(#[xX][0-9a-fA-F]+|#[0-9]+|[a-zA-Z][a-zA-Z0-9]*);").unwrap());
+static INLINE_SPACE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[ \t\u{a0}]+").unwrap());
+static BLANK_LINES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n{3,}").unwrap());
+static TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title\s*>").unwrap());
+static LINK_TAGS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<(a|link|base|meta)\b([^>]*)>").unwrap());
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"([a-zA-Z_:][-a-zA-Z0-9_:.]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+static MAIN_CONTENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<(main|article)\b[^>]*>(.*)</(?:main|article)\s*>").unwrap());
+static BOILERPLATE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(nav|header|footer|aside)\b[^>]*>.*?</(?:nav|header|footer|aside)\s*>")
+        .unwrap()
+});
+
+/// What the crawler needs from an HTML page
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtractedPage {
+    pub title: Option<String>,
+    /// `<link rel="canonical">` target, unresolved
+    pub canonical: Option<String>,
+    /// `<base href>` target, unresolved
+    pub base: Option<String>,
+    /// `<a href>` targets in document order, unresolved
+    pub links: Vec<String>,
+    /// Readable text of the main content
+    pub text: String,
+    /// `<meta name="robots">` asked not to index this page
+    pub noindex: bool,
+    /// `<meta name="robots">` asked not to follow links
+    pub nofollow: bool,
+}
+
+/// Strip markup and return readable text, keeping block elements on separate lines
+pub fn html_to_text(html: &str) -> String {
+    let text = COMMENTS.replace_all(html, "");
+    let text = DROP_BLOCKS.replace_all(&text, "");
+    let text = BLOCK_TAGS.replace_all(&text, "\n");
+    let text = CELL_TAGS.replace_all(&text, "\t");
+    let text = TAGS.replace_all(&text, "");
+    let text = decode_entities(&text);
+
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| INLINE_SPACE.replace_all(line, " ").trim().to_string())
+        .collect();
+    BLANK_LINES
+        .replace_all(&lines.join("\n"), "\n\n")
+        .trim()
+        .to_string()
+}
+
+/// Extract title, links and main-content text from a page.
+///
+/// Text comes from `<main>`/`<article>` when present; navigation, header,
+/// footer and aside blocks are dropped either way.
+pub fn extract_page(html: &str) -> ExtractedPage {
+    let html = COMMENTS.replace_all(html, "");
+    let mut page = ExtractedPage {
+        title: TITLE
+            .captures(&html)
+            .map(|c| html_to_text(&c[1]))
+            .filter(|t| !t.is_empty()),
+        ..ExtractedPage::default()
+    };
+
+    for tag in LINK_TAGS.captures_iter(&html) {
+        let attrs = parse_attributes(&tag[2]);
+        let attr = |name: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.as_str())
+        };
+        let rel = attr("rel").unwrap_or("").to_lowercase();
+        match tag[1].to_lowercase().as_str() {
+            "a" => {
+                if let Some(href) = attr("href") {
+                    if !rel.split_whitespace().any(|r| r == "nofollow") {
+                        page.links.push(href.to_string());
+                    }
+                }
+            }
+            "link" if rel.split_whitespace().any(|r| r == "canonical") => {
+                page.canonical = attr("href").map(String::from);
+            }
+            "base" => page.base = attr("href").map(String::from),
+            "meta" if attr("name").is_some_and(|n| n.eq_ignore_ascii_case("robots")) => {
+                let content = attr("content").unwrap_or("").to_lowercase();
+                page.noindex |= content.contains("noindex") || content.contains("none");
+                page.nofollow |= content.contains("nofollow") || content.contains("none");
+            }
+            _ => {}
+        }
+    }
+
+    let body = DROP_BLOCKS.replace_all(&html, "");
+    let main = MAIN_CONTENT
+        .captures(&body)
+        .map(|c| c[2].to_string())
+        .unwrap_or_else(|| body.to_string());
+    page.text = html_to_text(&BOILERPLATE.replace_all(&main, ""));
+    page
+}
+
+fn parse_attributes(raw: &str) -> Vec<(String, String)> {
+    ATTRIBUTE
+        .captures_iter(raw)
+        .map(|c| {
+            let value = c
+                .get(2)
+                .or_else(|| c.get(3))
+                .or_else(|| c.get(4))
+                .map(|m| m.as_str())
+                .unwrap_or("");
+            (c[1].to_lowercase(), decode_entities(value))
+        })
+        .collect()
+}
+
+/// Decode named and numeric character references
+pub fn decode_entities(text: &str) -> String {
+    ENTITY
+        .replace_all(text, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let decoded = if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse().ok().and_then(char::from_u32)
+            } else {
+                named_entity(entity)
+            };
+            decoded
+                .map(String::from)
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "euro" => '€',
+        "pound" => '£',
+        "middot" => '·',
+        "bull" => '•',
+        "times" => '×',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        let html = r#"<html><head><title>T</title><style>p { color: red }</style></head>
+<body><!-- nav --><h1>Release&nbsp;notes</h1><p>Fixed <b>two</b> bugs &amp; added&#x20;one &lt;feature&gt;.</p>
+<script>var x = "<p>no</p>";</script><ul><li>first</li><li>second</li></ul></body></html>"#;
+        let text = html_to_text(html);
+        assert_eq!(
+            text,
+            "Release notes\n\nFixed two bugs & added one <feature>.\n\nfirst\n\nsecond"
+        );
+    }
+
+    #[test]
+    fn test_extract_page() {
+        let html = r#"<html><head><title>Guide &amp; Intro</title>
+<link rel="canonical" href="https://docs.example.com/guide/">
+<meta name="robots" content="nofollow"></head>
+<body><nav><a href="/home">Home</a></nav>
+<main><h1>Guide</h1><p>See <a href='install.html?x=1&amp;y=2'>install</a>
+and <a rel="nofollow" href="/ads">ads</a>.</p></main>
+<footer>Copyright</footer></body></html>"#;
+        let page = extract_page(html);
+        assert_eq!(page.title.as_deref(), Some("Guide & Intro"));
+        assert_eq!(
+            page.canonical.as_deref(),
+            Some("https://docs.example.com/guide/")
+        );
+        assert_eq!(page.links, vec!["/home", "install.html?x=1&y=2"]);
+        assert_eq!(page.text, "Guide\n\nSee install\nand ads.");
+        assert!(page.nofollow && !page.noindex);
+    }
+
+    #[test]
+    fn test_unknown_entities_are_kept() {
+        assert_eq!(decode_entities("a &bogus; b &#65;"), "a &bogus; b A");
+    }
+}
@@ -0,0 +1,82 @@
+// src/ingest/mod.rs
+// Ingestion sources beyond the plain txt/pdf document folder
+
+pub mod code;
+pub mod crawler;
+pub mod dedup;
+pub mod email;
+pub mod feeds;
+pub mod html;
+pub mod structured;
+
+use std::path::{Path, PathBuf};
+
+pub use code::{
+    chunk_source, find_definitions, index_repository, parse_definition_query, walk_repository,
+    CodeIngestConfig, CodeIngestReport, CodeLanguage, DefinitionHit, SymbolKind,
+};
+pub use dedup::{DedupConfig, DuplicatePolicy, Fingerprint};
+pub use email::{index_mailbox, EmailFilter, EmailIngestReport, EmailMessage};
+pub use feeds::{FeedSubscription, NewSubscription, PollReport, SubscriptionUpdate};
+pub use structured::{
+    index_structured_file, DataFormat, DatasetSchema, StructuredIngestConfig,
+    StructuredIngestReport,
+};
+
+/// Directories listed in the comma-separated environment variable `var`
+pub fn allowed_roots_from_env(var: &str) -> Vec<PathBuf> {
+    std::env::var(var)
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Canonical form of `path` if it lies under one of `allowed_roots`.
+/// An empty list allows nothing, so server-side paths stay off until `var`
+/// (named in the errors) is configured.
+pub fn check_allowed_path(
+    path: &Path,
+    allowed_roots: &[PathBuf],
+    var: &str,
+) -> Result<PathBuf, String> {
+    if allowed_roots.is_empty() {
+        return Err(format!(
+            "{} is not set; ingestion from server paths is disabled",
+            var
+        ));
+    }
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| format!("cannot open '{}': {}", path.display(), e))?;
+    let allowed = allowed_roots.iter().any(|root| {
+        root.canonicalize()
+            .map(|root| canonical.starts_with(root))
+            .unwrap_or(false)
+    });
+    if !allowed {
+        return Err(format!("'{}' is outside {}", path.display(), var));
+    }
+    Ok(canonical)
+}
+
+/// Directories the file-path ingestion endpoints (/ingest/email,
+/// /ingest/structured) may read from
+pub const INGEST_ALLOWED_ROOTS_VAR: &str = "INGEST_ALLOWED_ROOTS";
+
+/// `check_allowed_path` against `INGEST_ALLOWED_ROOTS`
+pub fn check_ingest_path(path: &Path) -> Result<PathBuf, String> {
+    let roots = allowed_roots_from_env(INGEST_ALLOWED_ROOTS_VAR);
+    check_allowed_path(path, &roots, INGEST_ALLOWED_ROOTS_VAR)
+}
+
+/// Open the documents database configured at startup
+pub fn open_db() -> Result<rusqlite::Connection, String> {
+    let path = crate::db::chunk_settings::get_db_path()
+        .ok_or_else(|| "database path not initialized".to_string())?;
+    rusqlite::Connection::open(&path).map_err(|e| format!("open '{}': {}", path.display(), e))
+}
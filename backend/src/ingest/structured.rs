@@ -0,0 +1,1065 @@
+// src/ingest/structured.rs
+// CSV/TSV/JSON/JSONL ingestion with one document per row
+//
+// Rows are streamed from disk, rendered as "column: value" lines and indexed
+// as `table:<dataset>#<row id>`. Configured metadata columns are stored in
+// `structured_row_fields` for filtering, and the inferred schema of every
+// dataset is kept in `structured_datasets` so table tools can go back to the
+// source file.
+
+use crate::embedder;
+use crate::retriever::Retriever;
+use crate::security::redaction::{self, RedactionSession};
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::{self, Deserializer as _, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// Prefix of retriever chunk ids belonging to structured rows
+pub const TABLE_ID_PREFIX: &str = "table:";
+
+/// Sample values kept per column in the saved schema
+const SAMPLE_VALUES: usize = 5;
+
+#[derive(Debug, Error)]
+pub enum StructuredError {
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("parse error: {0}")]
+    Parse(String),
+    #[error("unsupported format: {0}")]
+    UnsupportedFormat(String),
+    #[error("database error: {0}")]
+    Database(String),
+    #[error("index error: {0}")]
+    Index(String),
+}
+
+impl From<rusqlite::Error> for StructuredError {
+    fn from(err: rusqlite::Error) -> Self {
+        StructuredError::Database(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for StructuredError {
+    fn from(err: std::io::Error) -> Self {
+        StructuredError::Io(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, StructuredError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    Csv,
+    Tsv,
+    Json,
+    Jsonl,
+}
+
+impl DataFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "tsv" | "tab" => Some(Self::Tsv),
+            "json" => Some(Self::Json),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for DataFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            "json" => Ok(Self::Json),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            other => Err(format!("unsupported format: {}", other)),
+        }
+    }
+}
+
+/// Options for one structured-data ingestion
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StructuredIngestConfig {
+    /// Dataset name; defaults to the file stem
+    pub dataset: Option<String>,
+    /// Overrides detection from the file extension
+    pub format: Option<DataFormat>,
+    /// Columns stored as filterable metadata
+    #[serde(default)]
+    pub metadata_columns: Vec<String>,
+    /// Columns rendered into the row text (empty = all)
+    #[serde(default)]
+    pub text_columns: Vec<String>,
+    /// Column providing a stable row id (default: the row's 1-based
+    /// position in the source: CSV record, JSONL line or JSON element)
+    pub id_column: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Null,
+    Boolean,
+    Integer,
+    Float,
+    String,
+    /// Nested objects or arrays
+    Json,
+}
+
+impl ColumnType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Boolean,
+            Value::Number(n) if n.is_i64() || n.is_u64() => Self::Integer,
+            Value::Number(_) => Self::Float,
+            Value::String(_) => Self::String,
+            Value::Array(_) | Value::Object(_) => Self::Json,
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Null, t) | (t, Self::Null) => t,
+            (Self::Integer, Self::Float) | (Self::Float, Self::Integer) => Self::Float,
+            _ => Self::String,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub data_type: ColumnType,
+    pub nullable: bool,
+    pub samples: Vec<String>,
+}
+
+/// Schema and provenance of an ingested dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetSchema {
+    pub dataset: String,
+    pub source_path: String,
+    pub format: DataFormat,
+    pub columns: Vec<ColumnSchema>,
+    pub row_count: usize,
+    pub metadata_columns: Vec<String>,
+    pub id_column: Option<String>,
+    pub ingested_at: String,
+}
+
+/// Summary of a structured ingestion run
+#[derive(Debug, Clone, Serialize)]
+pub struct StructuredIngestReport {
+    pub dataset: String,
+    pub rows_indexed: usize,
+    pub rows_skipped: usize,
+    pub errors: Vec<String>,
+    pub schema: DatasetSchema,
+}
+
+/// A search hit on a row, with its metadata fields
+#[derive(Debug, Clone, Serialize)]
+pub struct RowHit {
+    pub dataset: String,
+    pub row_id: String,
+    pub content: String,
+    pub score: f32,
+    pub fields: HashMap<String, String>,
+}
+
+// ─────────────────────────────────────────────────────────────
+// Storage
+// ─────────────────────────────────────────────────────────────
+
+/// Create the structured-data tables if they don't exist.
+pub fn init_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS structured_datasets (
+            dataset TEXT PRIMARY KEY,
+            source_path TEXT NOT NULL,
+            format TEXT NOT NULL,
+            schema_json TEXT NOT NULL,
+            row_count INTEGER NOT NULL,
+            ingested_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS structured_rows (
+            dataset TEXT NOT NULL,
+            row_id TEXT NOT NULL,
+            PRIMARY KEY (dataset, row_id)
+        );
+        CREATE TABLE IF NOT EXISTS structured_row_fields (
+            dataset TEXT NOT NULL,
+            row_id TEXT NOT NULL,
+            field TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (dataset, row_id, field)
+        );
+        CREATE INDEX IF NOT EXISTS idx_structured_field_value
+            ON structured_row_fields(dataset, field, value);",
+    )?;
+    Ok(())
+}
+
+pub fn load_schema(conn: &Connection, dataset: &str) -> Result<Option<DatasetSchema>> {
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT schema_json FROM structured_datasets WHERE dataset = ?1",
+            [dataset],
+            |row| row.get(0),
+        )
+        .optional()?;
+    json.map(|j| serde_json::from_str(&j).map_err(|e| StructuredError::Parse(e.to_string())))
+        .transpose()
+}
+
+pub fn list_datasets(conn: &Connection) -> Result<Vec<DatasetSchema>> {
+    let mut stmt =
+        conn.prepare("SELECT schema_json FROM structured_datasets ORDER BY dataset ASC")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut datasets = Vec::new();
+    for json in rows {
+        match serde_json::from_str(&json?) {
+            Ok(schema) => datasets.push(schema),
+            Err(e) => warn!("list_datasets: unreadable schema: {}", e),
+        }
+    }
+    Ok(datasets)
+}
+
+fn row_fields(conn: &Connection, dataset: &str, row_id: &str) -> Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT field, value FROM structured_row_fields WHERE dataset = ?1 AND row_id = ?2",
+    )?;
+    let rows = stmt.query_map([dataset, row_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn row_exists(conn: &Connection, dataset: &str, row_id: &str) -> Result<bool> {
+    Ok(conn
+        .prepare_cached("SELECT 1 FROM structured_rows WHERE dataset = ?1 AND row_id = ?2")?
+        .exists([dataset, row_id])?)
+}
+
+/// Row ids of `dataset` whose metadata matches every `filters` entry exactly
+pub fn filter_rows(
+    conn: &Connection,
+    dataset: &str,
+    filters: &HashMap<String, String>,
+    limit: usize,
+) -> Result<Vec<String>> {
+    let mut sql = String::from("SELECT row_id FROM structured_rows WHERE dataset = ?");
+    let mut values: Vec<String> = vec![dataset.to_string()];
+    for (field, value) in filters {
+        sql.push_str(
+            " AND row_id IN (SELECT row_id FROM structured_row_fields
+              WHERE dataset = ? AND field = ? AND value = ? COLLATE NOCASE)",
+        );
+        values.extend([dataset.to_string(), field.clone(), value.clone()]);
+    }
+    sql.push_str(&format!(" LIMIT {}", limit));
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(values), |row| row.get(0))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+// ─────────────────────────────────────────────────────────────
+// Ingestion
+// ─────────────────────────────────────────────────────────────
+
+/// Stream a CSV/TSV/JSON/JSONL file into the index, one chunk per row.
+pub fn index_structured_file(
+    retriever: &mut Retriever,
+    conn: &mut Connection,
+    path: &Path,
+    config: &StructuredIngestConfig,
+) -> Result<StructuredIngestReport> {
+    init_table(conn)?;
+    let format = config
+        .format
+        .or_else(|| DataFormat::from_path(path))
+        .ok_or_else(|| StructuredError::UnsupportedFormat(path.display().to_string()))?;
+    let dataset = config
+        .dataset
+        .clone()
+        .or_else(|| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "dataset".to_string());
+
+    let mut batch = retriever
+        .batch()
+        .map_err(|e| StructuredError::Index(e.to_string()))?;
+    // Any early return drops the batch, which rolls the index back. Rows
+    // are committed after their chunks, and neither on failure.
+    conn.execute_batch("BEGIN")?;
+    let ingested = ingest_rows(&mut batch, conn, path, format, config, &dataset).and_then(
+        |(columns, rows_indexed, rows_skipped, errors)| {
+            let schema = save_schema(conn, path, format, config, &dataset, columns, rows_indexed)?;
+            batch
+                .commit()
+                .map_err(|e| StructuredError::Index(e.to_string()))?;
+            Ok((schema, rows_indexed, rows_skipped, errors))
+        },
+    );
+    let (schema, rows_indexed, rows_skipped, errors) = match ingested {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            return Err(e);
+        }
+    };
+    conn.execute_batch("COMMIT")?;
+
+    info!(
+        dataset = %dataset,
+        rows = rows_indexed,
+        skipped = rows_skipped,
+        "Structured dataset indexed"
+    );
+    Ok(StructuredIngestReport {
+        dataset,
+        rows_indexed,
+        rows_skipped,
+        errors,
+        schema,
+    })
+}
+
+/// Record the dataset's inferred schema in `structured_datasets`
+fn save_schema(
+    conn: &Connection,
+    path: &Path,
+    format: DataFormat,
+    config: &StructuredIngestConfig,
+    dataset: &str,
+    columns: Vec<ColumnSchema>,
+    row_count: usize,
+) -> Result<DatasetSchema> {
+    let schema = DatasetSchema {
+        dataset: dataset.to_string(),
+        source_path: path
+            .canonicalize()
+            .unwrap_or_else(|_| path.to_path_buf())
+            .to_string_lossy()
+            .to_string(),
+        format,
+        columns,
+        row_count,
+        metadata_columns: config.metadata_columns.clone(),
+        id_column: config.id_column.clone(),
+        ingested_at: Utc::now().to_rfc3339(),
+    };
+    let schema_json =
+        serde_json::to_string(&schema).map_err(|e| StructuredError::Parse(e.to_string()))?;
+    conn.execute(
+        "INSERT INTO structured_datasets (dataset, source_path, format, schema_json, row_count, ingested_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(dataset) DO UPDATE SET
+            source_path = excluded.source_path,
+            format = excluded.format,
+            schema_json = excluded.schema_json,
+            row_count = excluded.row_count,
+            ingested_at = excluded.ingested_at",
+        params![
+            schema.dataset,
+            schema.source_path,
+            serde_json::to_value(format)
+                .ok()
+                .and_then(|v| v.as_str().map(String::from))
+                .unwrap_or_default(),
+            schema_json,
+            schema.row_count as i64,
+            schema.ingested_at,
+        ],
+    )?;
+    Ok(schema)
+}
+
+/// Replace the dataset's rows with those parsed from `path`, inside the open
+/// transaction and batch. Returns the columns, rows indexed, rows skipped and
+/// per-row errors.
+fn ingest_rows(
+    retriever: &mut Retriever,
+    conn: &mut Connection,
+    path: &Path,
+    format: DataFormat,
+    config: &StructuredIngestConfig,
+    dataset: &str,
+) -> Result<(Vec<ColumnSchema>, usize, usize, Vec<String>)> {
+    // Replace rows and chunks from a previous ingestion of the same dataset
+    let old_ids = conn
+        .prepare("SELECT row_id FROM structured_rows WHERE dataset = ?1")?
+        .query_map([dataset], |row| row.get::<_, String>(0))?
+        .map(|row_id| row_id.map(|row_id| format!("{}{}#{}", TABLE_ID_PREFIX, dataset, row_id)))
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    retriever
+        .delete_chunks(&old_ids)
+        .map_err(|e| StructuredError::Index(e.to_string()))?;
+    conn.execute("DELETE FROM structured_rows WHERE dataset = ?1", [dataset])?;
+    conn.execute(
+        "DELETE FROM structured_row_fields WHERE dataset = ?1",
+        [dataset],
+    )?;
+
+    let mut sink = RowSink {
+        retriever,
+        conn,
+        config,
+        dataset: dataset.to_string(),
+        columns: Vec::new(),
+        rows_indexed: 0,
+        row_ids: HashSet::new(),
+        errors: Vec::new(),
+        redaction: redaction::session(&format!("{}{}", TABLE_ID_PREFIX, dataset)),
+    };
+
+    let reader = BufReader::new(File::open(path)?);
+    let (rows_skipped, parse_errors) = match format {
+        DataFormat::Csv => read_delimited(reader, b',', &mut |n, row| sink.ingest(n, row))?,
+        DataFormat::Tsv => read_delimited(reader, b'\t', &mut |n, row| sink.ingest(n, row))?,
+        DataFormat::Jsonl => read_jsonl(reader, &mut |n, row| sink.ingest(n, row))?,
+        DataFormat::Json => read_json_array(reader, &mut |n, row| sink.ingest(n, row))?,
+    };
+
+    let RowSink {
+        columns,
+        rows_indexed,
+        mut errors,
+        redaction,
+        ..
+    } = sink;
+    errors.extend(parse_errors);
+    redaction.finish();
+    Ok((columns, rows_indexed, rows_skipped, errors))
+}
+
+/// Keyword search over rows, optionally limited to one dataset and filtered
+/// on metadata fields (case-insensitive exact match).
+pub fn search_rows(
+    retriever: &Retriever,
+    conn: &Connection,
+    query: &str,
+    dataset: Option<&str>,
+    filters: &HashMap<String, String>,
+    limit: usize,
+) -> Result<Vec<RowHit>> {
+    let hits = retriever
+        .search_hits(query, limit.max(1) * 10)
+        .map_err(|e| StructuredError::Index(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for hit in hits {
+        let Some((hit_dataset, row_id)) = hit
+            .doc_id
+            .strip_prefix(TABLE_ID_PREFIX)
+            .and_then(|rest| rest.rsplit_once('#'))
+        else {
+            continue;
+        };
+        if dataset.is_some_and(|d| d != hit_dataset) {
+            continue;
+        }
+        // Rows dropped by a later re-ingestion are no longer valid
+        if !row_exists(conn, hit_dataset, row_id)? {
+            continue;
+        }
+        let fields = row_fields(conn, hit_dataset, row_id)?;
+        let matches = filters.iter().all(|(field, expected)| {
+            fields
+                .get(field)
+                .is_some_and(|v| v.eq_ignore_ascii_case(expected))
+        });
+        if !matches {
+            continue;
+        }
+        results.push(RowHit {
+            dataset: hit_dataset.to_string(),
+            row_id: row_id.to_string(),
+            content: hit.content,
+            score: hit.score,
+            fields,
+        });
+        if results.len() >= limit {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+type Row = Vec<(String, Value)>;
+
+struct RowSink<'a> {
+    retriever: &'a mut Retriever,
+    conn: &'a mut Connection,
+    config: &'a StructuredIngestConfig,
+    dataset: String,
+    columns: Vec<ColumnSchema>,
+    rows_indexed: usize,
+    /// Ids taken by earlier rows of this ingestion
+    row_ids: HashSet<String>,
+    errors: Vec<String>,
+    redaction: RedactionSession<'static>,
+}
+
+impl RowSink<'_> {
+    /// Index one row; `row_number` is its 1-based position in the source
+    fn ingest(&mut self, row_number: usize, row: Row) -> Result<()> {
+        self.observe_schema(&row);
+        let flat = flatten_row(&row);
+
+        let row_id = self
+            .config
+            .id_column
+            .as_ref()
+            .and_then(|col| flat.iter().find(|(k, _)| k == col))
+            .map(|(_, v)| redaction::redact_id(v).replace('#', "_"))
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| row_number.to_string());
+
+        let text = render_row(&flat, &self.config.text_columns);
+        if text.is_empty() {
+            return Ok(());
+        }
+        let Some(text) = self.redaction.chunk(&text) else {
+            return Ok(());
+        };
+
+        if !self.row_ids.insert(row_id.clone()) {
+            self.errors
+                .push(format!("row {}: duplicate row id {}", row_number, row_id));
+            return Ok(());
+        }
+
+        let chunk_id = format!("{}{}#{}", TABLE_ID_PREFIX, self.dataset, row_id);
+        let vector = embedder::embed(&text);
+        if let Err(e) =
+            self.retriever
+                .index_chunk_with_title(&chunk_id, &self.dataset, &text, &vector)
+        {
+            self.errors.push(format!("{}: {}", chunk_id, e));
+            return Ok(());
+        }
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO structured_rows (dataset, row_id) VALUES (?1, ?2)",
+            params![self.dataset, row_id],
+        )?;
+        for column in &self.config.metadata_columns {
+            if let Some((_, value)) = flat.iter().find(|(k, _)| k == column) {
+                let value = redaction::redact(value);
+                self.conn.execute(
+                    "INSERT OR REPLACE INTO structured_row_fields (dataset, row_id, field, value)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![self.dataset, row_id, column, value],
+                )?;
+            }
+        }
+
+        self.rows_indexed += 1;
+        Ok(())
+    }
+
+    fn observe_schema(&mut self, row: &Row) {
+        for column in self.columns.iter_mut() {
+            if !row.iter().any(|(k, _)| k == &column.name) {
+                column.nullable = true;
+            }
+        }
+        for (name, value) in row {
+            let data_type = ColumnType::of(value);
+            let idx = match self.columns.iter().position(|c| &c.name == name) {
+                Some(idx) => idx,
+                None => {
+                    self.columns.push(ColumnSchema {
+                        name: name.clone(),
+                        data_type: ColumnType::Null,
+                        // Columns first seen after row one were missing before
+                        nullable: self.rows_indexed > 0,
+                        samples: Vec::new(),
+                    });
+                    self.columns.len() - 1
+                }
+            };
+            let column = &mut self.columns[idx];
+            column.data_type = column.data_type.merge(data_type);
+            if data_type == ColumnType::Null {
+                column.nullable = true;
+            } else if column.samples.len() < SAMPLE_VALUES {
+                let sample = redaction::redact(&value_to_text(value));
+                if !column.samples.contains(&sample) {
+                    column.samples.push(sample);
+                }
+            }
+        }
+    }
+}
+
+/// Read CSV/TSV records; returns the number of skipped rows and their errors
+fn read_delimited<R: std::io::Read>(
+    reader: R,
+    delimiter: u8,
+    on_row: &mut dyn FnMut(usize, Row) -> Result<()>,
+) -> Result<(usize, Vec<String>)> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .has_headers(true)
+        .from_reader(reader);
+
+    let headers: Vec<String> = csv_reader
+        .headers()
+        .map_err(|e| StructuredError::Parse(e.to_string()))?
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            let h = h.trim().trim_start_matches('\u{feff}');
+            if h.is_empty() {
+                format!("column_{}", i + 1)
+            } else {
+                h.to_string()
+            }
+        })
+        .collect();
+
+    let mut skipped = 0;
+    let mut errors = Vec::new();
+    let mut record = csv::StringRecord::new();
+    let mut row_number = 0;
+    loop {
+        row_number += 1;
+        match csv_reader.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                let row = headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(h, v)| (h.clone(), infer_value(v)))
+                    .collect();
+                on_row(row_number, row)?;
+            }
+            Err(e) => {
+                skipped += 1;
+                errors.push(e.to_string());
+                // The reader cannot resume after an I/O error
+                if matches!(e.kind(), csv::ErrorKind::Io(_)) {
+                    break;
+                }
+            }
+        }
+    }
+    Ok((skipped, errors))
+}
+
+fn read_jsonl<R: BufRead>(
+    reader: R,
+    on_row: &mut dyn FnMut(usize, Row) -> Result<()>,
+) -> Result<(usize, Vec<String>)> {
+    let mut skipped = 0;
+    let mut errors = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(&line) {
+            Ok(value) => on_row(i + 1, value_to_row(value))?,
+            Err(e) => {
+                skipped += 1;
+                errors.push(format!("line {}: {}", i + 1, e));
+            }
+        }
+    }
+    Ok((skipped, errors))
+}
+
+/// Stream the elements of a top-level JSON array (a single object is one row)
+fn read_json_array<R: std::io::Read>(
+    reader: R,
+    on_row: &mut dyn FnMut(usize, Row) -> Result<()>,
+) -> Result<(usize, Vec<String>)> {
+    struct RowVisitor<'a> {
+        on_row: &'a mut dyn FnMut(usize, Row) -> Result<()>,
+    }
+
+    impl<'de> Visitor<'de> for RowVisitor<'_> {
+        type Value = ();
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a JSON array of objects")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+            let mut row_number = 0;
+            while let Some(value) = seq.next_element::<Value>()? {
+                row_number += 1;
+                (self.on_row)(row_number, value_to_row(value)).map_err(de::Error::custom)?;
+            }
+            Ok(())
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> std::result::Result<(), A::Error> {
+            let value = Value::Object(Map::deserialize(de::value::MapAccessDeserializer::new(
+                map,
+            ))?);
+            (self.on_row)(1, value_to_row(value)).map_err(de::Error::custom)
+        }
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer
+        .deserialize_any(RowVisitor { on_row })
+        .map_err(|e| StructuredError::Parse(e.to_string()))?;
+    Ok((0, Vec::new()))
+}
+
+fn value_to_row(value: Value) -> Row {
+    match value {
+        Value::Object(map) => map.into_iter().collect(),
+        other => vec![("value".to_string(), other)],
+    }
+}
+
+/// CSV cells are text; recover numbers and booleans for the schema
+fn infer_value(raw: &str) -> Value {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Value::Null;
+    }
+    // Keep identifiers like "007" as text
+    let leading_zero = trimmed.len() > 1 && trimmed.starts_with('0') && !trimmed.starts_with("0.");
+    if !leading_zero {
+        if let Ok(i) = trimmed.parse::<i64>() {
+            return Value::from(i);
+        }
+        if let Some(f) = trimmed.parse::<f64>().ok().filter(|f| f.is_finite()) {
+            return Value::from(f);
+        }
+    }
+    match trimmed.to_lowercase().as_str() {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+/// Flatten nested objects into dotted keys with display values
+fn flatten_row(row: &Row) -> Vec<(String, String)> {
+    fn walk(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+        match value {
+            Value::Object(map) => {
+                for (k, v) in map {
+                    walk(&format!("{}.{}", prefix, k), v, out);
+                }
+            }
+            Value::Null => {}
+            other => out.push((prefix.to_string(), value_to_text(other))),
+        }
+    }
+    let mut out = Vec::new();
+    for (name, value) in row {
+        walk(name, value, &mut out);
+    }
+    out
+}
+
+fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        Value::Array(items) if items.iter().all(|v| !v.is_object() && !v.is_array()) => items
+            .iter()
+            .map(value_to_text)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// Render a row as "column: value" lines, restricted to `columns` when given
+pub fn render_row(fields: &[(String, String)], columns: &[String]) -> String {
+    fields
+        .iter()
+        .filter(|(k, v)| {
+            !v.trim().is_empty()
+                && (columns.is_empty()
+                    || columns
+                        .iter()
+                        .any(|c| k == c || k.starts_with(&format!("{}.", c))))
+        })
+        .map(|(k, v)| format!("{}: {}", k, v.trim()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, Retriever, Connection) {
+        let dir = tempfile::tempdir().unwrap();
+        let retriever = Retriever::new_with_vector_file(
+            dir.path().join("index").to_str().unwrap(),
+            dir.path().join("vectors.json").to_str().unwrap(),
+        )
+        .unwrap();
+        (dir, retriever, Connection::open_in_memory().unwrap())
+    }
+
+    #[test]
+    fn test_csv_rows_schema_and_filters() {
+        let (dir, mut retriever, mut conn) = setup();
+        let path = dir.path().join("customers.csv");
+        std::fs::write(
+            &path,
+            "id,name,country,spend,zip\n1,Acme Corp,NL,1200.5,0123\n2,Globex,US,,9000\n3,Initech,nl,300,\n",
+        )
+        .unwrap();
+
+        let config = StructuredIngestConfig {
+            metadata_columns: vec!["country".into()],
+            id_column: Some("id".into()),
+            ..StructuredIngestConfig::default()
+        };
+        let report = index_structured_file(&mut retriever, &mut conn, &path, &config).unwrap();
+        assert_eq!(report.dataset, "customers");
+        assert_eq!(report.rows_indexed, 3);
+
+        let schema = load_schema(&conn, "customers").unwrap().unwrap();
+        let types: Vec<_> = schema
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.data_type, c.nullable))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("id", ColumnType::Integer, false),
+                ("name", ColumnType::String, false),
+                ("country", ColumnType::String, false),
+                ("spend", ColumnType::Float, true),
+                ("zip", ColumnType::String, true),
+            ]
+        );
+
+        let mut filters = HashMap::new();
+        filters.insert("country".to_string(), "NL".to_string());
+        let ids = filter_rows(&conn, "customers", &filters, 10).unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let hits = search_rows(&retriever, &conn, "Globex", None, &filters, 5).unwrap();
+        assert!(hits.is_empty());
+        let hits = search_rows(&retriever, &conn, "Initech", None, &filters, 5).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].row_id, "3");
+        assert_eq!(
+            hits[0].content,
+            "id: 3\nname: Initech\ncountry: nl\nspend: 300"
+        );
+    }
+
+    #[test]
+    fn test_json_array_and_jsonl_flatten_nested_objects() {
+        let (dir, mut retriever, mut conn) = setup();
+        let json = dir.path().join("events.json");
+        std::fs::write(
+            &json,
+            r#"[{"kind": "deploy", "meta": {"env": "prod", "tags": ["a", "b"]}},
+                {"kind": "rollback", "extra": true}]"#,
+        )
+        .unwrap();
+        let jsonl = dir.path().join("logs.jsonl");
+        std::fs::write(
+            &jsonl,
+            "{\"level\": \"warn\"}\nnot json\n\n{\"level\": \"error\"}\n",
+        )
+        .unwrap();
+
+        let config = StructuredIngestConfig {
+            metadata_columns: vec!["meta.env".into()],
+            ..StructuredIngestConfig::default()
+        };
+        let report = index_structured_file(&mut retriever, &mut conn, &json, &config).unwrap();
+        assert_eq!(report.rows_indexed, 2);
+        let extra = report
+            .schema
+            .columns
+            .iter()
+            .find(|c| c.name == "extra")
+            .unwrap();
+        assert!(extra.nullable);
+        assert_eq!(extra.data_type, ColumnType::Boolean);
+
+        let hits = search_rows(
+            &retriever,
+            &conn,
+            "deploy",
+            Some("events"),
+            &HashMap::new(),
+            5,
+        )
+        .unwrap();
+        assert_eq!(
+            hits[0].content,
+            "kind: deploy\nmeta.env: prod\nmeta.tags: a, b"
+        );
+        assert_eq!(
+            hits[0].fields.get("meta.env").map(String::as_str),
+            Some("prod")
+        );
+
+        let report = index_structured_file(
+            &mut retriever,
+            &mut conn,
+            &jsonl,
+            &StructuredIngestConfig::default(),
+        )
+        .unwrap();
+        assert_eq!((report.rows_indexed, report.rows_skipped), (2, 1));
+        assert_eq!(list_datasets(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_malformed_file_rolls_back_and_ends_batch() {
+        let (dir, mut retriever, mut conn) = setup();
+        let broken = dir.path().join("broken.json");
+        std::fs::write(&broken, r#"[{"kind": "orphaned"}, {"kind": "#).unwrap();
+        let config = StructuredIngestConfig::default();
+        assert!(index_structured_file(&mut retriever, &mut conn, &broken, &config).is_err());
+
+        let good = dir.path().join("good.csv");
+        std::fs::write(&good, "kind\nrecovered\n").unwrap();
+        let report = index_structured_file(&mut retriever, &mut conn, &good, &config).unwrap();
+        assert_eq!(report.rows_indexed, 1);
+
+        let none = HashMap::new();
+        assert!(search_rows(&retriever, &conn, "orphaned", None, &none, 5)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            search_rows(&retriever, &conn, "recovered", None, &none, 5)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(!retriever
+            .doc_id_to_vector_idx
+            .contains_key("table:broken#1"));
+    }
+
+    #[test]
+    fn test_failed_reingest_keeps_previous_rows() {
+        let (dir, mut retriever, mut conn) = setup();
+        let config = StructuredIngestConfig {
+            dataset: Some("events".into()),
+            metadata_columns: vec!["kind".into()],
+            ..StructuredIngestConfig::default()
+        };
+        let good = dir.path().join("good.csv");
+        std::fs::write(&good, "kind\ndeploy\nrollback\n").unwrap();
+        index_structured_file(&mut retriever, &mut conn, &good, &config).unwrap();
+
+        // Enough rows to span what used to be several SQLite transactions
+        let rows: Vec<String> = (0..2500)
+            .map(|i| format!(r#"{{"kind": "bulk {}"}}"#, i))
+            .collect();
+        let broken = dir.path().join("broken.json");
+        std::fs::write(&broken, format!("[{}, {{\"kind\": ", rows.join(", "))).unwrap();
+        assert!(index_structured_file(&mut retriever, &mut conn, &broken, &config).is_err());
+
+        let mut filters = HashMap::new();
+        filters.insert("kind".to_string(), "deploy".to_string());
+        assert_eq!(filter_rows(&conn, "events", &filters, 10).unwrap().len(), 1);
+        let stored: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM structured_rows WHERE dataset = 'events'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, 2);
+    }
+
+    #[test]
+    fn test_row_ids_follow_source_position_and_reject_duplicates() {
+        let (dir, mut retriever, mut conn) = setup();
+        let path = dir.path().join("notes.csv");
+        // The blank second record is skipped but keeps its position
+        std::fs::write(&path, "name,note\nAcme,first\n,\nGlobex,third\n").unwrap();
+        let report = index_structured_file(
+            &mut retriever,
+            &mut conn,
+            &path,
+            &StructuredIngestConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(report.rows_indexed, 2);
+        let none = HashMap::new();
+        let hits = search_rows(&retriever, &conn, "Globex", None, &none, 5).unwrap();
+        assert_eq!(hits[0].row_id, "3");
+
+        let path = dir.path().join("customers.csv");
+        std::fs::write(&path, "id,name\n7,Acme Corp\n7,Globex\n").unwrap();
+        let config = StructuredIngestConfig {
+            id_column: Some("id".into()),
+            ..StructuredIngestConfig::default()
+        };
+        let report = index_structured_file(&mut retriever, &mut conn, &path, &config).unwrap();
+        assert_eq!(report.rows_indexed, 1);
+        assert_eq!(report.errors, vec!["row 2: duplicate row id 7"]);
+        let hits = search_rows(&retriever, &conn, "Acme", Some("customers"), &none, 5).unwrap();
+        assert_eq!(hits[0].row_id, "7");
+        assert!(
+            search_rows(&retriever, &conn, "Globex", Some("customers"), &none, 5)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_reingest_replaces_old_chunks() {
+        let (dir, mut retriever, mut conn) = setup();
+        let path = dir.path().join("customers.csv");
+        let config = StructuredIngestConfig {
+            id_column: Some("id".into()),
+            ..StructuredIngestConfig::default()
+        };
+        std::fs::write(&path, "id,name\n1,Acme Corp\n2,Globex\n").unwrap();
+        index_structured_file(&mut retriever, &mut conn, &path, &config).unwrap();
+        std::fs::write(&path, "id,name\n1,Acme Corp\n3,Initech\n").unwrap();
+        index_structured_file(&mut retriever, &mut conn, &path, &config).unwrap();
+
+        assert_eq!(retriever.search_hits("Acme", 5).unwrap().len(), 1);
+        assert!(retriever.search_hits("Globex", 5).unwrap().is_empty());
+        assert_eq!(retriever.search_hits("Initech", 5).unwrap().len(), 1);
+        assert!(!retriever
+            .doc_id_to_vector_idx
+            .contains_key("table:customers#2"));
+    }
+
+    #[test]
+    fn test_render_row_restricts_columns() {
+        let fields = vec![
+            ("a".to_string(), "1".to_string()),
+            ("b.c".to_string(), "2".to_string()),
+            ("d".to_string(), " ".to_string()),
+        ];
+        assert_eq!(render_row(&fields, &[]), "a: 1\nb.c: 2");
+        assert_eq!(render_row(&fields, &["b".to_string()]), "b.c: 2");
+    }
+}
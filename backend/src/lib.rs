@@ -5,6 +5,7 @@ pub mod db {
     pub mod llm_settings;
     pub mod param_hardware;
     pub mod param_store;
+    pub mod prompt_templates;
     pub mod schema_init;
 }
 pub mod agent;
@@ -13,6 +14,7 @@ pub mod chunker;
 pub mod config;
 pub mod embedder;
 pub mod index;
+pub mod ingest;
 pub mod parser;
 pub mod retriever;
 pub mod rules;
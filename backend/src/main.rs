@@ -99,6 +99,16 @@ async fn main() -> std::io::Result<()> {
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
         ag::db::param_store::init_table(&conn)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        ag::ingest::email::init_table(&conn)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        ag::ingest::structured::init_table(&conn)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        ag::ingest::feeds::init_table(&conn)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        ag::ingest::dedup::init_table(&conn)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        ag::security::redaction::init_table(&conn)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
 
         Ok(conn)
     })() {
@@ -117,6 +127,17 @@ async fn main() -> std::io::Result<()> {
     ag::db::chunk_settings::load_active_config(&_db_conn);
     ag::db::llm_settings::load_active_config(&_db_conn);
     ag::db::param_hardware::load_active_config(&_db_conn);
+    ag::db::prompt_templates::load_active_config(&_db_conn);
+    let model_limits = ag::memory::tokenizer::load_active(&ag::db::param_hardware::global_config());
+    info!(
+        embedding_max_tokens = ?model_limits.embedding_max_tokens,
+        generation_context = model_limits.generation_context,
+        "Model token limits resolved"
+    );
+    match ag::memory::llm_router::reload() {
+        Ok(router) => info!(routes = router.routes().len(), "✓ LLM router ready"),
+        Err(e) => warn!(error = %e, "LLM router unavailable; answers will fail until configured"),
+    }
 
     // ─────────────────────────────────────────────────────────────
     // PHASE 4: Initialize Retriever with PathManager
@@ -225,6 +246,14 @@ async fn main() -> std::io::Result<()> {
         });
     }
 
+    // Feed subscriptions are polled in the background; due feeds are
+    // checked every minute
+    actix_web::rt::spawn(ag::ingest::feeds::run_scheduler(
+        Arc::clone(&retriever),
+        config.chunker_mode,
+        config.crawl_allowed_hosts.clone(),
+    ));
+
     // ─────────────────────────────────────────────────────────────
     // PHASE 8: Start Server Immediately (Server Ready Before Indexing Done)
     // ─────────────────────────────────────────────────────────────
@@ -87,6 +87,8 @@ pub struct Episode {
     pub response: String,
     pub context_chunks_used: usize,
     pub success: bool,
+    /// Share of the answer's claims supported by its context, when checked
+    pub grounding_score: Option<f32>,
     pub created_at: i64,
 }
 
@@ -186,7 +188,15 @@ impl AgentMemoryLayer {
                 response TEXT NOT NULL,
                 context_chunks_used INTEGER NOT NULL,
                 success INTEGER NOT NULL,
-                created_at INTEGER NOT NULL
+                created_at INTEGER NOT NULL,
+                grounding_score REAL
+            );
+
+            CREATE TABLE IF NOT EXISTS episode_traces (
+                episode_id TEXT PRIMARY KEY,
+                trace TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY(episode_id) REFERENCES episodes(id)
             );
 
             CREATE TABLE IF NOT EXISTS reflections (
@@ -204,6 +214,14 @@ impl AgentMemoryLayer {
             ",
         )?;
 
+        // Databases created before grounding scores were recorded
+        if conn
+            .prepare("SELECT grounding_score FROM episodes LIMIT 0")
+            .is_err()
+        {
+            conn.execute("ALTER TABLE episodes ADD COLUMN grounding_score REAL", [])?;
+        }
+
         Ok(())
     }
 
@@ -251,6 +269,7 @@ impl AgentMemoryLayer {
         response: String,
         context_chunks_used: usize,
         success: bool,
+        grounding_score: Option<f32>,
     ) -> Result<Episode, Box<dyn std::error::Error>> {
         let episode_id = Uuid::new_v4().to_string();
         let now = Utc::now().timestamp();
@@ -276,8 +295,8 @@ impl AgentMemoryLayer {
         // Store in SQLite
         let conn = Connection::open(&self.db_path)?;
         conn.execute(
-            "INSERT INTO episodes (id, agent_id, query, response, context_chunks_used, success, created_at) 
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            "INSERT INTO episodes (id, agent_id, query, response, context_chunks_used, success, created_at, grounding_score) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 &episode_id,
                 &self.agent.id,
@@ -285,7 +304,8 @@ impl AgentMemoryLayer {
                 &response,
                 context_chunks_used,
                 success as i32,
-                now
+                now,
+                grounding_score
             ],
         )?;
 
@@ -298,10 +318,43 @@ impl AgentMemoryLayer {
             response,
             context_chunks_used,
             success,
+            grounding_score,
             created_at: now,
         })
     }
 
+    /// Store the reasoning trace of the run that produced an episode
+    pub fn record_trace(
+        &self,
+        episode_id: &str,
+        trace: &[String],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let conn = Connection::open(&self.db_path)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO episode_traces (episode_id, trace, created_at) VALUES (?1, ?2, ?3)",
+            params![episode_id, serde_json::to_string(trace)?, Utc::now().timestamp()],
+        )?;
+        debug!(episode_id = %episode_id, lines = trace.len(), "Reasoning trace recorded");
+        Ok(())
+    }
+
+    /// Reasoning trace stored for an episode, if any
+    pub fn get_episode_trace(
+        &self,
+        episode_id: &str,
+    ) -> Result<Option<Vec<String>>, Box<dyn std::error::Error>> {
+        let conn = Connection::open(&self.db_path)?;
+        let mut stmt = conn.prepare("SELECT trace FROM episode_traces WHERE episode_id = ?1")?;
+        let mut rows = stmt.query(params![episode_id])?;
+        match rows.next()? {
+            Some(row) => {
+                let trace: String = row.get(0)?;
+                Ok(Some(serde_json::from_str(&trace)?))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Recall similar episodes from semantic search
     pub async fn recall_similar_episodes(
         &self,
@@ -319,7 +372,7 @@ impl AgentMemoryLayer {
 
         for result in results {
             let mut stmt = conn.prepare(
-                "SELECT id, agent_id, query, response, context_chunks_used, success, created_at 
+                "SELECT id, agent_id, query, response, context_chunks_used, success, created_at, grounding_score 
                  FROM episodes WHERE id = ?1",
             )?;
             let episode = stmt.query_row(params![&result.chunk_id], |row| {
@@ -330,6 +383,7 @@ impl AgentMemoryLayer {
                     response: row.get(3)?,
                     context_chunks_used: row.get(4)?,
                     success: row.get::<_, i32>(5)? != 0,
+                    grounding_score: row.get(7)?,
                     created_at: row.get(6)?,
                 })
             })?;
@@ -425,7 +479,7 @@ impl AgentMemoryLayer {
 
         // Get recent episodes
         let mut stmt = conn.prepare(
-            "SELECT id, agent_id, query, response, context_chunks_used, success, created_at 
+            "SELECT id, agent_id, query, response, context_chunks_used, success, created_at, grounding_score 
              FROM episodes WHERE agent_id = ?1 ORDER BY created_at DESC LIMIT 10",
         )?;
         let episodes = stmt.query_map(params![&self.agent.id], |row| {
@@ -436,6 +490,7 @@ impl AgentMemoryLayer {
                 response: row.get(3)?,
                 context_chunks_used: row.get(4)?,
                 success: row.get::<_, i32>(5)? != 0,
+                grounding_score: row.get(7)?,
                 created_at: row.get(6)?,
             })
         })?;
@@ -549,12 +604,22 @@ mod tests {
                 "Rust is a systems programming language.".to_string(),
                 3,
                 true,
+                Some(0.9),
             )
             .await
             .unwrap();
 
         assert!(episode.success);
         assert_eq!(episode.context_chunks_used, 3);
+        assert_eq!(episode.grounding_score, Some(0.9));
+
+        let context = memory.get_agent_context().unwrap();
+        assert_eq!(context.recent_episodes[0].grounding_score, Some(0.9));
+
+        let trace = vec!["Step 1: Thought".to_string(), "Observation".to_string()];
+        memory.record_trace(&episode.id, &trace).unwrap();
+        assert_eq!(memory.get_episode_trace(&episode.id).unwrap(), Some(trace));
+        assert_eq!(memory.get_episode_trace("missing").unwrap(), None);
     }
 
     #[test]
@@ -0,0 +1,572 @@
+// src/memory/anthropic_provider.rs
+// Anthropic Messages API provider (POST {base_url}/v1/messages)
+
+use crate::db::llm_settings::{self, LlmConfig};
+use crate::memory::llm_http::{self, Attempt, RetryPolicy, UsageTracker};
+use crate::memory::llm_provider::{
+    ChatMessage, Completion, LLMError, LLMProvider, Role, StreamUsage, TokenStream, TokenUsage,
+};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{debug, info};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+pub const DEFAULT_MODEL: &str = "claude-3-5-sonnet-latest";
+const API_VERSION: &str = "2023-06-01";
+
+/// Base URL from ANTHROPIC_BASE_URL, else the Anthropic API
+pub fn default_base_url() -> String {
+    std::env::var("ANTHROPIC_BASE_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+}
+
+pub struct AnthropicProvider {
+    base_url: String,
+    api_key: String,
+    model: String,
+    system_prompt: Option<String>,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    usage: UsageTracker,
+}
+
+#[derive(Serialize)]
+struct Message {
+    role: &'static str,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<Message>,
+    temperature: f32,
+    top_p: f32,
+    top_k: usize,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop_sequences: &'a [String],
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    #[serde(default)]
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    usage: Option<MessagesUsage>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+/// One server-sent event of a streamed message
+#[derive(Deserialize)]
+struct StreamEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    message: Option<StreamMessage>,
+    delta: Option<StreamDelta>,
+    usage: Option<MessagesUsage>,
+    error: Option<ErrorBody>,
+}
+
+#[derive(Deserialize)]
+struct StreamMessage {
+    usage: Option<MessagesUsage>,
+}
+
+#[derive(Deserialize)]
+struct StreamDelta {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MessagesUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+impl From<MessagesUsage> for TokenUsage {
+    fn from(usage: MessagesUsage) -> Self {
+        Self {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: usage.input_tokens + usage.output_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorEnvelope {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(base_url: String, api_key: String, model: String) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            system_prompt: None,
+            client: llm_http::build_client(llm_http::DEFAULT_TIMEOUT),
+            retry: RetryPolicy::default(),
+            usage: UsageTracker::new("anthropic", &model),
+            model,
+        }
+    }
+
+    /// System prompt sent with every request
+    pub fn with_system_prompt(mut self, system_prompt: Option<String>) -> Self {
+        self.system_prompt = system_prompt.filter(|prompt| !prompt.trim().is_empty());
+        self
+    }
+
+    /// Retries after 429, 529 (overloaded), other 5xx and connection errors (default 3)
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.retry.max_retries = max_retries;
+        self
+    }
+
+    /// First retry delay, doubled on each attempt (default 500ms)
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.retry.backoff = backoff;
+        self
+    }
+
+    /// Per-request timeout (default 120s)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = llm_http::build_client(timeout);
+        self
+    }
+
+    /// Tokens used by all generations through this provider
+    pub fn total_usage(&self) -> TokenUsage {
+        self.usage.total()
+    }
+
+    fn request<'a>(
+        &'a self,
+        messages: &[ChatMessage],
+        config: &'a LlmConfig,
+        stream: bool,
+    ) -> MessagesRequest<'a> {
+        let (system, messages) = self.split_messages(messages);
+        MessagesRequest {
+            model: &self.model,
+            // The API requires max_tokens and rejects 0
+            max_tokens: config.max_tokens.max(1),
+            system,
+            messages,
+            // Messages API temperature range is 0.0-1.0
+            temperature: config.temperature.clamp(0.0, 1.0),
+            top_p: config.top_p,
+            top_k: config.top_k,
+            stop_sequences: &config.stop_sequences,
+            stream,
+        }
+    }
+
+    /// System messages go to the `system` field after the provider's own
+    /// system prompt. Tool calls are written into the assistant text, tool
+    /// results become user turns, and consecutive turns of the same role are
+    /// merged since the API expects them to alternate
+    fn split_messages(&self, messages: &[ChatMessage]) -> (Option<String>, Vec<Message>) {
+        let mut system: Vec<&str> = self.system_prompt.iter().map(String::as_str).collect();
+        let mut turns: Vec<Message> = Vec::new();
+        for message in messages {
+            let (role, content) = match message.role {
+                Role::System => {
+                    system.push(&message.content);
+                    continue;
+                }
+                Role::User => ("user", message.content.clone()),
+                Role::Assistant => {
+                    let mut content = message.content.clone();
+                    for call in &message.tool_calls {
+                        if !content.is_empty() {
+                            content.push('\n');
+                        }
+                        content.push_str(&call.describe());
+                    }
+                    ("assistant", content)
+                }
+                Role::Tool => ("user", format!("Tool result: {}", message.content)),
+            };
+            match turns.last_mut() {
+                Some(last) if last.role == role => {
+                    last.content.push_str("\n\n");
+                    last.content.push_str(&content);
+                }
+                _ => turns.push(Message { role, content }),
+            }
+        }
+        let system = (!system.is_empty()).then(|| system.join("\n\n"));
+        (system, turns)
+    }
+
+    async fn attempt(&self, request: &MessagesRequest<'_>) -> Attempt<Completion> {
+        match self.send(request).await {
+            Attempt::Done(response) => match response.json::<MessagesResponse>().await {
+                Ok(body) => self.finish(body),
+                Err(e) => Attempt::Fail(LLMError::InvalidResponse(e.to_string())),
+            },
+            Attempt::Retry(err, retry_after) => Attempt::Retry(err, retry_after),
+            Attempt::Fail(err) => Attempt::Fail(err),
+        }
+    }
+
+    /// POST the request; a successful response is returned unread
+    async fn send(&self, request: &MessagesRequest<'_>) -> Attempt<reqwest::Response> {
+        let url = format!("{}/v1/messages", self.base_url);
+        let response = match self
+            .client
+            .post(&url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(request)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                return Attempt::Retry(
+                    LLMError::ConnectionFailed(format!("{}: {}", self.base_url, e)),
+                    None,
+                )
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Attempt::Done(response);
+        }
+
+        let retry_after = llm_http::retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<ErrorEnvelope>(&body)
+            .map(|envelope| format!("{}: {}", envelope.error.kind, envelope.error.message))
+            .unwrap_or(body);
+        llm_http::classify_status(status.as_u16(), message, retry_after)
+    }
+
+    fn finish(&self, body: MessagesResponse) -> Attempt<Completion> {
+        let usage = body.usage.map(TokenUsage::from);
+        if let Some(usage) = usage {
+            self.usage.record(usage);
+        }
+        if body.stop_reason.as_deref() == Some("max_tokens") {
+            debug!(model = %self.model, "Generation stopped at max_tokens");
+        }
+        let text: String = body
+            .content
+            .into_iter()
+            .filter(|block| block.kind == "text")
+            .map(|block| block.text)
+            .collect();
+        if text.trim().is_empty() {
+            return Attempt::Fail(LLMError::InvalidResponse("no text content".into()));
+        }
+        Attempt::Done(Completion {
+            text: text.trim().to_string(),
+            usage,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for AnthropicProvider {
+    async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+        let config = llm_settings::global_config();
+        self.generate_with_config(prompt, &config).await
+    }
+
+    async fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        self.chat(&[ChatMessage::user(prompt)], config).await
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], config: &LlmConfig) -> Result<String, LLMError> {
+        Ok(self.chat_with_usage(messages, config).await?.text)
+    }
+
+    async fn chat_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        debug!(
+            model = %self.model,
+            messages = messages.len(),
+            temperature = config.temperature,
+            max_tokens = config.max_tokens,
+            system = self.system_prompt.is_some(),
+            "Generating with Anthropic Messages"
+        );
+        let request = self.request(messages, config, false);
+
+        let (completion, retries) = self
+            .retry
+            .run(&self.model, || self.attempt(&request))
+            .await?;
+        let usage = completion.usage.unwrap_or_default();
+        info!(
+            model = %self.model,
+            response_len = completion.text.len(),
+            prompt_tokens = usage.prompt_tokens,
+            completion_tokens = usage.completion_tokens,
+            retries,
+            "Generation complete"
+        );
+        Ok(completion)
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<TokenStream, LLMError> {
+        Ok(self.generate_stream_with_usage(prompt, config).await?.0)
+    }
+
+    async fn generate_stream_with_usage(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<(TokenStream, StreamUsage), LLMError> {
+        debug!(model = %self.model, prompt_len = prompt.len(), "Streaming Anthropic message");
+        let request = self.request(&[ChatMessage::user(prompt)], config, true);
+        let (response, _) = self.retry.run(&self.model, || self.send(&request)).await?;
+
+        // message_start carries input tokens, message_delta the output tokens
+        let tracker = self.usage.clone();
+        let stream_usage = StreamUsage::default();
+        let reported_usage = stream_usage.clone();
+        let mut input_tokens = 0;
+        let tokens = llm_http::lines(response).filter_map(move |line| {
+            let event = match line {
+                Ok(line) => llm_http::sse_data(&line).map(serde_json::from_str::<StreamEvent>),
+                Err(e) => return std::future::ready(Some(Err(e))),
+            };
+            let item = match event {
+                None => None,
+                Some(Err(e)) => Some(Err(LLMError::InvalidResponse(e.to_string()))),
+                Some(Ok(event)) => match event.kind.as_str() {
+                    "message_start" => {
+                        input_tokens = event
+                            .message
+                            .and_then(|message| message.usage)
+                            .map_or(0, |usage| usage.input_tokens);
+                        None
+                    }
+                    "content_block_delta" => event
+                        .delta
+                        .filter(|delta| delta.kind.as_deref() == Some("text_delta"))
+                        .and_then(|delta| delta.text)
+                        .map(Ok),
+                    "message_delta" => {
+                        if let Some(reported) = event.usage {
+                            let usage = MessagesUsage {
+                                input_tokens,
+                                output_tokens: reported.output_tokens,
+                            }
+                            .into();
+                            tracker.record(usage);
+                            reported_usage.set(usage);
+                        }
+                        None
+                    }
+                    "error" => Some(Err(LLMError::GenerationFailed(
+                        event
+                            .error
+                            .map(|error| format!("{}: {}", error.kind, error.message))
+                            .unwrap_or_else(|| "stream error".to_string()),
+                    ))),
+                    _ => None,
+                },
+            };
+            std::future::ready(item)
+        });
+        Ok((Box::pin(tokens), stream_usage))
+    }
+
+    async fn health_check(&self) -> Result<(), LLMError> {
+        let request = self
+            .client
+            .get(format!("{}/v1/models", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION);
+        llm_http::probe(request, &self.base_url).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::llm_http::mock::serve;
+
+    fn message(text: &str) -> String {
+        serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": text}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 20, "output_tokens": 4}
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_messages_request_and_usage() {
+        let (base, requests) = serve(vec![(200, "", message("Bonjour."))]).await;
+        let provider = AnthropicProvider::new(base, "sk-ant".into(), DEFAULT_MODEL.into())
+            .with_system_prompt(Some("Answer in French.".into()));
+        let config = LlmConfig {
+            temperature: 1.4,
+            max_tokens: 128,
+            stop_sequences: vec!["\n\nHuman:".into()],
+            ..LlmConfig::default()
+        };
+
+        let completion = provider
+            .chat_with_usage(&[ChatMessage::user("Say hello")], &config)
+            .await
+            .unwrap();
+        assert_eq!(completion.text, "Bonjour.");
+        let usage = completion.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (20, 4));
+        assert_eq!(provider.total_usage().total_tokens, 24);
+
+        let requests = requests.lock().unwrap();
+        let (head, body) = &requests[0];
+        assert!(head.starts_with("post /v1/messages"));
+        assert!(head.contains("x-api-key: sk-ant"));
+        assert!(head.contains("anthropic-version: 2023-06-01"));
+        let sent: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(sent["system"], "Answer in French.");
+        assert_eq!(sent["max_tokens"], 128);
+        assert_eq!(sent["temperature"], 1.0);
+        assert_eq!(sent["stop_sequences"][0], "\n\nHuman:");
+        assert_eq!(sent["messages"][0]["content"], "Say hello");
+    }
+
+    #[tokio::test]
+    async fn test_backs_off_on_overload_and_rate_limit() {
+        let overloaded =
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let (base, requests) = serve(vec![
+            (529, "", overloaded.to_string()),
+            (429, "Retry-After: 0\r\n", "{}".to_string()),
+            (200, "", message("done")),
+        ])
+        .await;
+        let provider = AnthropicProvider::new(base, "k".into(), "m".into())
+            .with_backoff(Duration::from_millis(1));
+        let text = provider
+            .generate_with_config("hi", &LlmConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(text, "done");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        let (base, _) = serve(vec![(529, "", overloaded.to_string())]).await;
+        let provider = AnthropicProvider::new(base, "k".into(), "m".into()).with_max_retries(0);
+        match provider
+            .generate_with_config("hi", &LlmConfig::default())
+            .await
+        {
+            Err(LLMError::GenerationFailed(msg)) => assert!(msg.contains("overloaded_error")),
+            other => panic!("expected GenerationFailed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_text_deltas_and_usage() {
+        let body = [
+            "event: message_start",
+            r#"data: {"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":9,"output_tokens":1}}}"#,
+            "",
+            "event: content_block_delta",
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Bon"}}"#,
+            "",
+            "event: ping",
+            r#"data: {"type":"ping"}"#,
+            "",
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"jour"}}"#,
+            r#"data: {"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":2}}"#,
+            r#"data: {"type":"message_stop"}"#,
+            "",
+        ]
+        .join("\n");
+        let (base, requests) = serve(vec![(200, "", body)]).await;
+        let provider = AnthropicProvider::new(base, "k".into(), "m".into());
+        let (stream, usage) = provider
+            .generate_stream_with_usage("hi", &LlmConfig::default())
+            .await
+            .unwrap();
+        let tokens: Vec<String> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(tokens.concat(), "Bonjour");
+        let usage = usage.get().unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (9, 2));
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn test_chat_moves_system_and_merges_turns() {
+        let (base, requests) = serve(vec![(200, "", message("Paris"))]).await;
+        let provider = AnthropicProvider::new(base, "k".into(), "m".into())
+            .with_system_prompt(Some("You are terse.".into()));
+        let messages = [
+            ChatMessage::system("Cite sources."),
+            ChatMessage::user("Look it up."),
+            ChatMessage::assistant("Calling search."),
+            ChatMessage::tool("call_1", "Paris is the capital of France."),
+            ChatMessage::user("So what is the capital?"),
+        ];
+        let text = provider
+            .chat(&messages, &LlmConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(text, "Paris");
+
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["system"], "You are terse.\n\nCite sources.");
+        let turns = sent["messages"].as_array().unwrap();
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[2]["role"], "user");
+        assert_eq!(
+            turns[2]["content"],
+            "Tool result: Paris is the capital of France.\n\nSo what is the capital?"
+        );
+    }
+}
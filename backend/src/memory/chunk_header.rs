@@ -0,0 +1,432 @@
+// src/memory/chunk_header.rs
+// Contextual chunk headers
+//
+// A chunk like "it must be set to 4" is useless on its own. When enabled, each
+// chunk gets a short header (document title, heading path, optional one-line
+// LLM summary of the document) that is prepended to the text we embed and
+// indexed as the chunk title for BM25. The stored chunk content is unchanged.
+//
+// Environment variables:
+// - CONTEXT_HEADER_MODES: chunker modes that get headers, comma separated
+//   ("fixed,lightweight,semantic", "all" or "none"; default: none)
+// - CONTEXT_HEADER_SUMMARY: also ask the LLM for a one-line document summary
+
+use crate::config::ChunkerMode;
+use crate::db::{param_hardware, prompt_templates};
+use crate::memory::llm_provider::{build_llm_provider, LLMConfig, LLMProvider};
+use crate::memory::llm_router;
+use crate::memory::tokenizer::Tokenizer;
+use std::env;
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+use tracing::warn;
+
+const MAX_SUMMARY_INPUT_CHARS: usize = 4000;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContextHeaderConfig {
+    pub modes: Vec<ChunkerMode>,
+    pub include_summary: bool,
+}
+
+impl ContextHeaderConfig {
+    pub fn from_env() -> Self {
+        let modes = match env::var("CONTEXT_HEADER_MODES") {
+            Ok(raw) => parse_modes(&raw),
+            Err(_) => Vec::new(),
+        };
+        let include_summary = env::var("CONTEXT_HEADER_SUMMARY")
+            .map(|v| v.to_lowercase() == "true" || v == "1")
+            .unwrap_or(false);
+        Self {
+            modes,
+            include_summary,
+        }
+    }
+
+    /// Process-wide config, read from the environment once
+    pub fn global() -> &'static ContextHeaderConfig {
+        static CONFIG: OnceLock<ContextHeaderConfig> = OnceLock::new();
+        CONFIG.get_or_init(Self::from_env)
+    }
+
+    pub fn enabled_for(&self, mode: ChunkerMode) -> bool {
+        self.modes.contains(&mode)
+    }
+}
+
+fn parse_modes(raw: &str) -> Vec<ChunkerMode> {
+    match raw.trim().to_lowercase().as_str() {
+        "all" => vec![
+            ChunkerMode::Fixed,
+            ChunkerMode::Lightweight,
+            ChunkerMode::Semantic,
+        ],
+        "" | "none" => Vec::new(),
+        list => list
+            .split(',')
+            .filter_map(|m| match m.trim().parse() {
+                Ok(mode) => Some(mode),
+                Err(e) => {
+                    warn!("CONTEXT_HEADER_MODES: {}", e);
+                    None
+                }
+            })
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Heading {
+    offset: usize,
+    level: usize,
+    title: String,
+}
+
+/// Per-document state for building chunk headers. Chunks are located in the
+/// source text in order, so call `header_for` with chunks in document order.
+pub struct DocumentContext {
+    title: String,
+    summary: Option<String>,
+    headings: Vec<Heading>,
+    /// Whitespace-collapsed text and the source offset of each of its bytes
+    normalized: String,
+    offsets: Vec<usize>,
+    cursor: usize,
+}
+
+impl DocumentContext {
+    pub fn new(title: impl Into<String>, text: &str) -> Self {
+        let (normalized, offsets) = normalize_whitespace(text);
+        Self {
+            title: title.into(),
+            summary: None,
+            headings: outline(text),
+            normalized,
+            offsets,
+            cursor: 0,
+        }
+    }
+
+    pub fn with_summary(mut self, summary: Option<String>) -> Self {
+        self.summary = summary.filter(|s| !s.trim().is_empty());
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Headings enclosing `offset` in the source text, outermost first
+    fn heading_path(&self, offset: usize) -> Vec<&str> {
+        let mut path: Vec<&Heading> = Vec::new();
+        for heading in self.headings.iter().take_while(|h| h.offset <= offset) {
+            while path.last().is_some_and(|h| h.level >= heading.level) {
+                path.pop();
+            }
+            path.push(heading);
+        }
+        path.into_iter().map(|h| h.title.as_str()).collect()
+    }
+
+    /// Locate `chunk` after the previous chunk and build its header
+    pub fn header_for(&mut self, chunk: &str) -> String {
+        let (needle, _) = normalize_whitespace(chunk);
+        let needle = prefix(&needle, 64);
+        let found = if needle.is_empty() {
+            None
+        } else {
+            self.normalized[self.cursor..]
+                .find(needle)
+                .map(|i| self.cursor + i)
+                // Overlapping chunkers can start before the previous match
+                .or_else(|| self.normalized.find(needle))
+        };
+        let offset = match found {
+            Some(pos) => {
+                self.cursor = pos;
+                self.offsets.get(pos).copied().unwrap_or(0)
+            }
+            None => self.offsets.get(self.cursor).copied().unwrap_or(0),
+        };
+
+        let mut header = format!("Document: {}", self.title);
+        let path = self.heading_path(offset);
+        if !path.is_empty() {
+            header.push_str("\nSection: ");
+            header.push_str(&path.join(" > "));
+        }
+        if let Some(summary) = &self.summary {
+            header.push_str("\nSummary: ");
+            header.push_str(summary);
+        }
+        header
+    }
+}
+
+/// Text to embed for a chunk with a header
+pub fn contextualize(header: &str, chunk: &str) -> String {
+    format!("{}\n\n{}", header, chunk)
+}
+
+/// Split `chunk` so that each piece embedded with `header` stays within
+/// `max_tokens`. A header never leaves less than a quarter of the budget.
+pub fn fit_under_header<'a>(
+    header: &str,
+    chunk: &'a str,
+    max_tokens: usize,
+    tokenizer: &dyn Tokenizer,
+) -> Vec<&'a str> {
+    let header_tokens = tokenizer.count_tokens(&contextualize(header, ""));
+    let budget = max_tokens.saturating_sub(header_tokens).max(max_tokens / 4);
+    tokenizer
+        .split_to_fit(chunk, budget)
+        .into_iter()
+        .map(str::trim)
+        .filter(|piece| !piece.is_empty())
+        .collect()
+}
+
+/// Title for a document: its first markdown H1, else the file name without
+/// extension
+pub fn document_title(file_name: &str, text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .find_map(|l| l.strip_prefix("# "))
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| {
+            std::path::Path::new(file_name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().replace(['_', '-'], " "))
+                .unwrap_or_else(|| file_name.to_string())
+        })
+}
+
+/// One-line summary of a document from the LLM, or None on failure
+pub async fn summarize_document(
+    provider: &dyn LLMProvider,
+    title: &str,
+    text: &str,
+) -> Option<String> {
+    let prompt = match prompt_templates::render(
+        prompt_templates::DOCUMENT_SUMMARY,
+        Some(provider.model_name()),
+        &[
+            ("title", title),
+            ("text", prefix(text, MAX_SUMMARY_INPUT_CHARS)),
+        ],
+    ) {
+        Ok(prompt) => prompt,
+        Err(e) => {
+            warn!(title, "Document summary prompt failed: {}", e);
+            return None;
+        }
+    };
+    match provider.generate(&prompt).await {
+        Ok(answer) => answer
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(str::to_string),
+        Err(e) => {
+            warn!(title, "Document summary failed: {}", e);
+            None
+        }
+    }
+}
+
+/// The shared router tagged "summary" when installed, else a provider for
+/// the configured backend
+fn summary_provider() -> Option<Box<dyn LLMProvider>> {
+    match llm_router::global() {
+        Some(router) => Some(Box::new(router.tagged("summary"))),
+        None => build_llm_provider(LLMConfig::from_hardware(&param_hardware::global_config()))
+            .map_err(|e| warn!("No provider for document summaries: {}", e))
+            .ok(),
+    }
+}
+
+/// Runtime shared by every blocking summary, built on first use
+fn summary_runtime() -> Option<&'static Runtime> {
+    static RUNTIME: OnceLock<Option<Runtime>> = OnceLock::new();
+    RUNTIME
+        .get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("chunk-summary")
+                .enable_all()
+                .build()
+                .map_err(|e| warn!("Document summary runtime failed: {}", e))
+                .ok()
+        })
+        .as_ref()
+}
+
+/// `summarize_document` with the summary provider, for synchronous indexing
+/// code. Inside a tokio runtime the call is made from a plain thread, since
+/// a runtime thread cannot block on a future.
+pub fn summarize_document_blocking(title: &str, text: &str) -> Option<String> {
+    let provider = summary_provider()?;
+    let runtime = summary_runtime()?;
+    let summarize = || runtime.block_on(summarize_document(provider.as_ref(), title, text));
+    if tokio::runtime::Handle::try_current().is_err() {
+        return summarize();
+    }
+    std::thread::scope(|scope| scope.spawn(summarize).join().ok().flatten())
+}
+
+fn prefix(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn normalize_whitespace(text: &str) -> (String, Vec<usize>) {
+    let mut normalized = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len());
+    let mut pending_space = false;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            pending_space = !normalized.is_empty();
+            continue;
+        }
+        if pending_space {
+            normalized.push(' ');
+            offsets.push(i);
+            pending_space = false;
+        }
+        normalized.push(c);
+        offsets.extend(std::iter::repeat_n(i, c.len_utf8()));
+    }
+    (normalized, offsets)
+}
+
+/// Markdown (`#` and setext) headings plus short ALL-CAPS title lines
+fn outline(text: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut offset = 0;
+    let mut previous: Option<(usize, &str)> = None;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let hashes = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+            headings.push(Heading {
+                offset,
+                level: hashes,
+                title: trimmed[hashes..].trim().to_string(),
+            });
+        } else if !trimmed.is_empty() && trimmed.chars().all(|c| c == '=' || c == '-') {
+            if let Some((prev_offset, prev_title)) = previous {
+                let level = if trimmed.starts_with('=') { 1 } else { 2 };
+                headings.push(Heading {
+                    offset: prev_offset,
+                    level,
+                    title: prev_title.to_string(),
+                });
+            }
+        } else if is_caps_title(trimmed) {
+            headings.push(Heading {
+                offset,
+                level: 1,
+                title: trimmed.trim_end_matches(':').to_string(),
+            });
+        }
+        previous = (!trimmed.is_empty() && hashes == 0).then_some((offset, trimmed));
+        offset += line.len();
+    }
+    headings.sort_by_key(|h| h.offset);
+    headings
+}
+
+fn is_caps_title(line: &str) -> bool {
+    let words = line.split_whitespace().count();
+    let letters = line.chars().filter(|c| c.is_alphabetic()).count();
+    (1..=8).contains(&words)
+        && letters >= 3
+        && line.chars().all(|c| !c.is_lowercase())
+        && !line.starts_with(['-', '*'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANUAL: &str = "# Router Manual\n\nIntro text.\n\n## Installation\n\nUnpack the box.\n\n### Network\n\nThe MTU value\nmust be set to 4 for tunnels.\n\n## Maintenance\n\nReboot weekly.\n";
+
+    #[test]
+    fn test_header_tracks_heading_path() {
+        let title = document_title("router_manual.md", MANUAL);
+        assert_eq!(title, "Router Manual");
+
+        let mut ctx =
+            DocumentContext::new(title, MANUAL).with_summary(Some("Setting up the router.".into()));
+        assert_eq!(
+            ctx.header_for("Intro text."),
+            "Document: Router Manual\nSection: Router Manual\nSummary: Setting up the router."
+        );
+        // Chunkers may re-flow lines; matching ignores whitespace differences
+        assert_eq!(
+            ctx.header_for("The MTU value must be set to 4 for tunnels."),
+            "Document: Router Manual\nSection: Router Manual > Installation > Network\nSummary: Setting up the router."
+        );
+        assert_eq!(
+            ctx.header_for("Reboot weekly."),
+            "Document: Router Manual\nSection: Router Manual > Maintenance\nSummary: Setting up the router."
+        );
+    }
+
+    #[test]
+    fn test_chunk_is_split_to_leave_room_for_its_header() {
+        use crate::memory::tokenizer::HeuristicTokenizer;
+
+        let header = "Document: Router Manual\nSection: Installation > Network";
+        let chunk = "The MTU value must be set to 4 for tunnels. ".repeat(20);
+        let max_tokens = HeuristicTokenizer.count_tokens(&chunk);
+        let pieces = fit_under_header(header, &chunk, max_tokens, &HeuristicTokenizer);
+
+        assert!(pieces.len() > 1);
+        for piece in pieces {
+            let embedded = contextualize(header, piece);
+            assert!(HeuristicTokenizer.count_tokens(&embedded) <= max_tokens);
+        }
+        assert_eq!(
+            fit_under_header(header, "Short.", 100, &HeuristicTokenizer),
+            vec!["Short."]
+        );
+    }
+
+    #[test]
+    fn test_plain_text_headings_and_title() {
+        let text = "OVERVIEW\nThis tool syncs files.\n\nUsage\n-----\nRun it daily.\n";
+        assert_eq!(document_title("sync-tool.txt", text), "sync tool");
+
+        let mut ctx = DocumentContext::new("sync tool", text);
+        assert_eq!(
+            ctx.header_for("Run it daily."),
+            "Document: sync tool\nSection: OVERVIEW > Usage"
+        );
+        assert_eq!(
+            contextualize("Document: sync tool", "Run it daily."),
+            "Document: sync tool\n\nRun it daily."
+        );
+    }
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(parse_modes("all").len(), 3);
+        assert_eq!(parse_modes("none"), Vec::<ChunkerMode>::new());
+        assert_eq!(parse_modes("Semantic, bogus"), vec![ChunkerMode::Semantic]);
+        let config = ContextHeaderConfig {
+            modes: parse_modes("lightweight"),
+            include_summary: false,
+        };
+        assert!(config.enabled_for(ChunkerMode::Lightweight));
+        assert!(!config.enabled_for(ChunkerMode::Fixed));
+    }
+}
@@ -1,8 +1,11 @@
 // src/memory/chunker.rs
 
+use super::tokenizer::{self, Tokenizer};
+use crate::ingest::code::{chunk_source, CodeLanguage};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +26,12 @@ pub struct ChunkMetadata {
     pub start_char: usize,
     pub end_char: usize,
     pub extra: HashMap<String, String>,
+    /// Set on child chunks: the parent section they were cut from
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Set on parent sections: their child chunks, in order
+    #[serde(default)]
+    pub child_ids: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,44 +116,76 @@ impl ChunkerConfig {
         }
     }
 
-    /// Create config optimized for different model sizes
+    /// Derive chunk sizes from a generation context window: a chunk takes at
+    /// most a quarter of the window (256..=1024 tokens) so several retrieved
+    /// chunks fit alongside the prompt.
     pub fn for_model(model_context_size: usize) -> Self {
-        match model_context_size {
-            // Small models (phi, tinyllama) - 2K context
-            0..=2048 => Self {
-                target_size: 384,
-                min_size: 192,
-                max_size: 512,
-                overlap: 50,
-                semantic_similarity_threshold: DEFAULT_SEMANTIC_SIMILARITY_THRESHOLD,
-            },
-            // Medium models (llama2-7b) - 4K context
-            2049..=4096 => Self {
-                target_size: 512,
-                min_size: 256,
-                max_size: 768,
-                overlap: 75,
-                semantic_similarity_threshold: DEFAULT_SEMANTIC_SIMILARITY_THRESHOLD,
-            },
-            // Large models (llama3, mistral) - 8K+ context
-            _ => Self {
-                target_size: 768,
-                min_size: 384,
-                max_size: 1024,
-                overlap: 100,
-                semantic_similarity_threshold: DEFAULT_SEMANTIC_SIMILARITY_THRESHOLD,
-            },
+        let max_size = (model_context_size / 4).clamp(256, 1024);
+        Self {
+            target_size: max_size * 3 / 4,
+            min_size: max_size / 2,
+            max_size,
+            overlap: max_size / 10,
+            semantic_similarity_threshold: DEFAULT_SEMANTIC_SIMILARITY_THRESHOLD,
         }
     }
+
+    /// Config for the active models: sized from the generation context and
+    /// capped by the embedding model's input limit
+    pub fn for_active_models() -> Self {
+        let limits = tokenizer::model_limits();
+        Self::for_model(limits.generation_context).fit_embedding_limit(limits.embedding_max_tokens)
+    }
+
+    /// Cap sizes at what `for_active_models` allows, keeping smaller
+    /// configured sizes
+    pub fn fit_active_models(self) -> Self {
+        self.fit_embedding_limit(Some(Self::for_active_models().max_size))
+    }
+
+    /// Shrink sizes so a chunk (including its overlap) never exceeds the
+    /// embedding model's input limit. No-op when the limit is unknown.
+    pub fn fit_embedding_limit(mut self, limit: Option<usize>) -> Self {
+        let Some(limit) = limit.filter(|l| *l > 0) else {
+            return self;
+        };
+        if self.max_size > limit {
+            let scale = |v: usize| (v * limit / self.max_size).max(1);
+            self.target_size = scale(self.target_size);
+            self.min_size = scale(self.min_size);
+            self.overlap = self.overlap * limit / self.max_size;
+            self.max_size = limit;
+        }
+        self.target_size = self.target_size.min(self.max_size);
+        self.min_size = self.min_size.min(self.target_size);
+        self
+    }
+}
+
+/// How parent sections are cut into indexed child chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ChildSplit {
+    /// Children of at most `max_tokens` tokens
+    Tokens { max_tokens: usize },
+    /// One child per sentence, for sentence-window retrieval
+    Sentences,
 }
 
 pub struct SemanticChunker {
     config: ChunkerConfig,
+    tokenizer: Arc<dyn Tokenizer>,
 }
 
 impl SemanticChunker {
     pub fn new(config: ChunkerConfig) -> Self {
-        Self { config }
+        let tokenizer = tokenizer::embedding_tokenizer();
+        let config = config.fit_embedding_limit(tokenizer.max_input_tokens());
+        Self::with_tokenizer(config, tokenizer)
+    }
+
+    pub fn with_tokenizer(config: ChunkerConfig, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        Self { config, tokenizer }
     }
 
     pub fn with_default() -> Self {
@@ -164,8 +205,22 @@ impl SemanticChunker {
             .unwrap()
             .as_secs() as i64;
 
+        // Known languages get one chunk per symbol with line metadata
+        if let SourceType::Code = source_type {
+            if let Some(language) = CodeLanguage::from_path(std::path::Path::new(&source)) {
+                return chunk_source(&source, language, content, self.config.max_size)
+                    .into_iter()
+                    .map(|mut chunk| {
+                        chunk.metadata.document_id = document_id.clone();
+                        chunk.metadata.created_at = now;
+                        chunk
+                    })
+                    .collect();
+            }
+        }
+
         // Split into semantic units (paragraphs, sections)
-        let units = self.split_into_semantic_units(content, &source_type);
+        let units = self.fit_units(self.split_into_semantic_units(content, &source_type));
 
         // Group units into chunks based on token limits
         let grouped_chunks = self.group_into_chunks(&units);
@@ -190,12 +245,75 @@ impl SemanticChunker {
                         start_char,
                         end_char,
                         extra: HashMap::new(),
+                        parent_id: None,
+                        child_ids: Vec::new(),
                     },
                 }
             })
             .collect()
     }
 
+    /// Small-to-big chunking: the regular chunks become parent sections and
+    /// each is cut into smaller children for indexing. Returns the parents
+    /// followed by all children; children are numbered across the document
+    /// so neighbouring children have adjacent `chunk_index` values.
+    pub fn chunk_document_hierarchical(
+        &self,
+        content: &str,
+        document_id: String,
+        source: String,
+        source_type: SourceType,
+        split: ChildSplit,
+    ) -> Vec<Chunk> {
+        let mut parents = self.chunk_document(content, document_id, source, source_type);
+        let mut children = Vec::new();
+
+        for parent in &mut parents {
+            let base = parent.metadata.start_char;
+            let pieces: Vec<(String, usize, usize)> = match split {
+                ChildSplit::Tokens { max_tokens } => {
+                    let config = ChunkerConfig {
+                        overlap: 0,
+                        ..ChunkerConfig::for_model(0)
+                    }
+                    .fit_embedding_limit(Some(max_tokens.max(1)));
+                    let child_chunker =
+                        SemanticChunker::with_tokenizer(config, self.tokenizer.clone());
+                    let units =
+                        child_chunker.fit_units(child_chunker.split_by_paragraphs(&parent.content));
+                    child_chunker.group_into_chunks(&units)
+                }
+                ChildSplit::Sentences => self
+                    .split_by_sentences(&parent.content, 0)
+                    .into_iter()
+                    .map(|unit| (unit.text, unit.start_char, unit.end_char))
+                    .collect(),
+            };
+
+            for (text, start, end) in pieces {
+                let child = Chunk {
+                    id: Uuid::new_v4().to_string(),
+                    token_count: self.estimate_tokens(&text),
+                    content: text,
+                    chunk_index: children.len(),
+                    metadata: ChunkMetadata {
+                        start_char: base + start,
+                        end_char: base + end,
+                        extra: HashMap::new(),
+                        parent_id: Some(parent.id.clone()),
+                        child_ids: Vec::new(),
+                        ..parent.metadata.clone()
+                    },
+                };
+                parent.metadata.child_ids.push(child.id.clone());
+                children.push(child);
+            }
+        }
+
+        parents.extend(children);
+        parents
+    }
+
     /// Split text into semantic units based on source type
     fn split_into_semantic_units(
         &self,
@@ -210,6 +328,30 @@ impl SemanticChunker {
         }
     }
 
+    /// Hard-split units that alone exceed max_size on token boundaries
+    fn fit_units(&self, units: Vec<SemanticUnit>) -> Vec<SemanticUnit> {
+        let mut fitted = Vec::with_capacity(units.len());
+        for unit in units {
+            if self.estimate_tokens(&unit.text) <= self.config.max_size {
+                fitted.push(unit);
+                continue;
+            }
+            for piece in self
+                .tokenizer
+                .split_to_fit(&unit.text, self.config.max_size)
+            {
+                let offset = piece.as_ptr() as usize - unit.text.as_ptr() as usize;
+                fitted.push(SemanticUnit {
+                    text: piece.to_string(),
+                    start_char: unit.start_char + offset,
+                    end_char: unit.start_char + offset + piece.len(),
+                    boundary_strength: BoundaryStrength::Weak,
+                });
+            }
+        }
+        fitted
+    }
+
     /// Split by paragraphs (double newline or period + newline)
     fn split_by_paragraphs(&self, content: &str) -> Vec<SemanticUnit> {
         let mut units = Vec::new();
@@ -247,10 +389,15 @@ impl SemanticChunker {
         let _current_pos = 0; // or just remove it if unused
 
         // Simple sentence splitting on .!? followed by space and capital
-        let sentence_regex = regex::Regex::new(r"([.!?]+)\s+(?=[A-Z])").unwrap();
+        let sentence_regex = regex::Regex::new(r"[.!?]+\s+").unwrap();
 
         let mut last_end = 0;
-        for mat in sentence_regex.find_iter(text) {
+        for mat in sentence_regex.find_iter(text).filter(|m| {
+            text[m.end()..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_uppercase())
+        }) {
             let sentence = &text[last_end..mat.end()].trim();
             if !sentence.is_empty() {
                 units.push(SemanticUnit {
@@ -388,8 +535,14 @@ impl SemanticChunker {
 
                 // Start new chunk with overlap
                 let overlap_text = self.get_overlap_text(&current_chunk);
-                current_chunk = overlap_text;
-                current_tokens = self.estimate_tokens(&current_chunk);
+                let overlap_tokens = self.estimate_tokens(&overlap_text);
+                if overlap_tokens + unit_tokens <= self.config.max_size {
+                    current_chunk = overlap_text;
+                    current_tokens = overlap_tokens;
+                } else {
+                    current_chunk.clear();
+                    current_tokens = 0;
+                }
                 chunk_start = unit.start_char;
             }
 
@@ -422,17 +575,12 @@ impl SemanticChunker {
 
     /// Get last N tokens for overlap
     fn get_overlap_text(&self, text: &str) -> String {
-        let words: Vec<&str> = text.split_whitespace().collect();
-        let overlap_words = (self.config.overlap * 3 / 4).min(words.len()); // ~0.75 tokens per word
-
-        words[words.len().saturating_sub(overlap_words)..].join(" ")
+        self.tokenizer.tail(text, self.config.overlap).to_string()
     }
 
-    /// Estimate token count (rough approximation: 1 token ≈ 4 chars or 0.75 words)
+    /// Token count with the embedding tokenizer (heuristic when none is loaded)
     fn estimate_tokens(&self, text: &str) -> usize {
-        let char_estimate = text.len() / 4;
-        let word_estimate = text.split_whitespace().count() * 4 / 3;
-        (char_estimate + word_estimate) / 2
+        self.tokenizer.count_tokens(text)
     }
 }
 
@@ -496,4 +644,74 @@ mod tests {
         // Should be roughly 9-12 tokens for this sentence
         assert!(tokens >= 8 && tokens <= 15);
     }
+
+    #[test]
+    fn test_hierarchical_chunking_links_parents_and_children() {
+        let chunker = SemanticChunker::with_tokenizer(
+            ChunkerConfig::default(),
+            Arc::new(tokenizer::HeuristicTokenizer),
+        );
+        let content = "Rust has ownership. Borrowing is checked at compile time. \
+                       Lifetimes name how long references live.\n\n\
+                       Cargo builds crates. It also fetches dependencies.";
+
+        let chunks = chunker.chunk_document_hierarchical(
+            content,
+            "doc4".to_string(),
+            "rust.txt".to_string(),
+            SourceType::Text,
+            ChildSplit::Sentences,
+        );
+
+        let (parents, children): (Vec<_>, Vec<_>) =
+            chunks.iter().partition(|c| c.metadata.parent_id.is_none());
+        assert_eq!(parents.len(), 1);
+        assert_eq!(children.len(), 5);
+        assert_eq!(parents[0].metadata.child_ids.len(), 5);
+        for (idx, child) in children.iter().enumerate() {
+            assert_eq!(child.chunk_index, idx);
+            assert_eq!(
+                child.metadata.parent_id.as_deref(),
+                Some(parents[0].id.as_str())
+            );
+            assert!(parents[0].content.contains(&child.content));
+        }
+        assert_eq!(children[3].content, "Cargo builds crates.");
+    }
+
+    #[test]
+    fn test_config_for_model_context() {
+        let small = ChunkerConfig::for_model(2048);
+        assert_eq!(small.max_size, 512);
+        assert_eq!(ChunkerConfig::for_model(512).max_size, 256);
+        assert_eq!(ChunkerConfig::for_model(32_768).max_size, 1024);
+        assert!(small.min_size < small.target_size && small.target_size < small.max_size);
+
+        let fitted = ChunkerConfig::for_model(8192).fit_embedding_limit(Some(254));
+        assert_eq!(fitted.max_size, 254);
+        assert!(fitted.target_size <= 254 && fitted.min_size <= fitted.target_size);
+        assert_eq!(
+            ChunkerConfig::default().fit_embedding_limit(None).max_size,
+            DEFAULT_MAX_SIZE
+        );
+    }
+
+    #[test]
+    fn test_chunks_respect_max_tokens() {
+        let config = ChunkerConfig::default().fit_embedding_limit(Some(40));
+        let chunker =
+            SemanticChunker::with_tokenizer(config, Arc::new(tokenizer::HeuristicTokenizer));
+        // One long paragraph without sentence breaks
+        let content = "word ".repeat(400);
+
+        let chunks = chunker.chunk_document(
+            &content,
+            "doc3".to_string(),
+            "long.txt".to_string(),
+            SourceType::Text,
+        );
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.token_count <= 40));
+    }
 }
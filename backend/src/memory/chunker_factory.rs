@@ -1,8 +1,10 @@
 use super::chunker::ChunkerConfig;
+use super::tokenizer::{self, Tokenizer};
 use crate::embedder;
 use crate::embedder::similarity;
 use serde::Serialize;
 use std::cell::RefCell;
+use std::sync::Arc;
 
 #[derive(Clone, Copy, Debug)]
 pub enum ChunkerMode {
@@ -27,6 +29,10 @@ pub struct ChunkingStats {
     pub semantic_flushes: usize,
     pub heading_flushes: usize,
     pub size_flushes: usize,
+    /// Chunks closed because a table started
+    pub table_flushes: usize,
+    /// Chunks containing a table or a run of table rows
+    pub table_chunks: usize,
     pub total_segments: usize,
     pub total_chunks: usize,
     pub similarity_observations: usize,
@@ -43,114 +49,254 @@ impl ChunkingStats {
     }
 }
 
+/// Why a chunk ends where it does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    /// End of a line (fixed chunker)
+    Line,
+    /// The next segment was not similar enough to the chunk
+    Semantic,
+    /// The next segment is a heading
+    Heading,
+    /// The chunk reached its target size or the next segment would overflow it
+    Size,
+    /// A table, or a table starts next
+    Table,
+    /// End of the document
+    End,
+}
+
+/// A chunk with the metadata chunkers know about
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextChunk {
+    pub text: String,
+    /// Contains a whole table, or table rows under a repeated header
+    pub table: bool,
+    pub boundary: Boundary,
+}
+
+impl TextChunk {
+    fn line(text: String) -> Self {
+        Self {
+            text,
+            table: false,
+            boundary: Boundary::Line,
+        }
+    }
+
+    fn table(text: String) -> Self {
+        Self {
+            text,
+            table: true,
+            boundary: Boundary::Table,
+        }
+    }
+}
+
 pub trait Chunker {
-    fn chunk_text(&self, text: &str) -> Vec<String>;
+    fn chunk_with_metadata(&self, text: &str) -> Vec<TextChunk>;
+
+    fn chunk_text(&self, text: &str) -> Vec<String> {
+        self.chunk_with_metadata(text)
+            .into_iter()
+            .map(|c| c.text)
+            .collect()
+    }
 
     fn stats(&self) -> Option<ChunkingStats> {
         None
     }
 }
 
+/// One chunk per line; tables are kept whole and lines stay whole, unless
+/// they exceed the default max chunk size (tables are then split by rows)
 pub struct FixedChunker;
 
+impl FixedChunker {
+    fn chunk_with_tokenizer(text: &str, tokenizer: &dyn Tokenizer) -> Vec<TextChunk> {
+        let max_tokens = ChunkerConfig::default().fit_active_models().max_size;
+        let lines: Vec<&str> = text.lines().collect();
+        let mut chunks = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            if let Some(table) = detect_table(&lines[i..]) {
+                i += table.lines;
+                chunks.extend(
+                    table_pieces(&table.header, &table.rows, tokenizer, max_tokens)
+                        .into_iter()
+                        .map(TextChunk::table),
+                );
+                continue;
+            }
+            let line = lines[i].trim();
+            if !line.is_empty() {
+                chunks.extend(
+                    tokenizer
+                        .split_to_fit(line, max_tokens)
+                        .into_iter()
+                        .map(|piece| TextChunk::line(piece.trim().to_string())),
+                );
+            }
+            i += 1;
+        }
+        chunks
+    }
+}
+
 impl Chunker for FixedChunker {
-    fn chunk_text(&self, text: &str) -> Vec<String> {
-        text.lines()
-            .map(str::trim)
-            .filter(|l| !l.is_empty())
-            .map(|l| l.to_string())
-            .collect()
+    fn chunk_with_metadata(&self, text: &str) -> Vec<TextChunk> {
+        Self::chunk_with_tokenizer(text, tokenizer::embedding_tokenizer().as_ref())
     }
 }
 
 pub struct LightweightAdaptiveChunker {
     config: ChunkerConfig,
+    tokenizer: Arc<dyn Tokenizer>,
 }
 
 impl LightweightAdaptiveChunker {
     pub fn new(config: ChunkerConfig) -> Self {
-        Self { config }
+        Self::with_tokenizer(config, tokenizer::embedding_tokenizer())
+    }
+
+    pub fn with_tokenizer(config: ChunkerConfig, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        Self { config, tokenizer }
     }
 }
 
 impl Chunker for LightweightAdaptiveChunker {
-    fn chunk_text(&self, text: &str) -> Vec<String> {
+    fn chunk_with_metadata(&self, text: &str) -> Vec<TextChunk> {
         let mut chunks = Vec::new();
-        let mut current = String::new();
-        let mut current_tokens = 0usize;
-
-        for segment in split_into_segments(text) {
-            if segment.is_empty() {
-                continue;
-            }
-            let seg_tokens = estimate_token_count(&segment);
+        let mut current = ChunkBuffer::default();
+
+        let segments = fit_segments(
+            split_into_segments(text),
+            self.tokenizer.as_ref(),
+            self.config.max_size,
+        );
+        for segment in segments {
+            let segment = match segment {
+                Segment::Table { header, rows } => {
+                    let pieces = table_pieces(
+                        &header,
+                        &rows,
+                        self.tokenizer.as_ref(),
+                        self.config.max_size,
+                    );
+                    let tokens = self.tokenizer.count_tokens(&pieces[0]);
+                    if pieces.len() == 1 && current.tokens + tokens <= self.config.max_size {
+                        // Small table: keep it with the surrounding text
+                        current.push(&pieces[0], tokens, true);
+                    } else {
+                        current.flush_into(&mut chunks, Boundary::Table);
+                        chunks.extend(pieces.into_iter().map(TextChunk::table));
+                    }
+                    if current.tokens >= self.config.target_size {
+                        current.flush_into(&mut chunks, Boundary::Size);
+                    }
+                    continue;
+                }
+                Segment::Text(segment) if segment.is_empty() => continue,
+                Segment::Text(segment) => segment,
+            };
+            let seg_tokens = self.tokenizer.count_tokens(&segment);
             let heading = is_heading_segment(&segment);
 
-            let should_flush = !current.is_empty()
-                && (current_tokens + seg_tokens > self.config.max_size
-                    || (heading && current_tokens >= self.config.min_size));
-
-            if should_flush {
-                chunks.push(current.trim().to_string());
-                current.clear();
-                current_tokens = 0;
-            }
-
             if !current.is_empty() {
-                current.push_str("\n\n");
+                if current.tokens + seg_tokens > self.config.max_size {
+                    current.flush_into(&mut chunks, Boundary::Size);
+                } else if heading && current.tokens >= self.config.min_size {
+                    current.flush_into(&mut chunks, Boundary::Heading);
+                }
             }
-            current.push_str(segment.trim());
-            current_tokens += seg_tokens;
 
-            if current_tokens >= self.config.target_size {
-                chunks.push(current.trim().to_string());
-                current.clear();
-                current_tokens = 0;
-            }
-        }
+            current.push(segment.trim(), seg_tokens, false);
 
-        if !current.trim().is_empty() {
-            chunks.push(current.trim().to_string());
+            if current.tokens >= self.config.target_size {
+                current.flush_into(&mut chunks, Boundary::Size);
+            }
         }
 
-        chunks.into_iter().filter(|c| !c.is_empty()).collect()
+        current.flush_into(&mut chunks, Boundary::End);
+        chunks
     }
 }
 
 pub struct SemanticAdaptiveChunker {
     config: ChunkerConfig,
+    tokenizer: Arc<dyn Tokenizer>,
     last_stats: RefCell<Option<ChunkingStats>>,
 }
 
 impl SemanticAdaptiveChunker {
     pub fn new(config: ChunkerConfig) -> Self {
+        Self::with_tokenizer(config, tokenizer::embedding_tokenizer())
+    }
+
+    pub fn with_tokenizer(config: ChunkerConfig, tokenizer: Arc<dyn Tokenizer>) -> Self {
         Self {
             config,
+            tokenizer,
             last_stats: RefCell::new(None),
         }
     }
 }
 
 impl Chunker for SemanticAdaptiveChunker {
-    fn chunk_text(&self, text: &str) -> Vec<String> {
+    fn chunk_with_metadata(&self, text: &str) -> Vec<TextChunk> {
         let mut stats = ChunkingStats {
             semantic_similarity_threshold: self.config.semantic_similarity_threshold,
             ..ChunkingStats::default()
         };
 
         let mut chunks = Vec::new();
-        let mut current = String::new();
-        let mut current_tokens = 0usize;
+        let mut current = ChunkBuffer::default();
         let mut chunk_embedding_sum: Option<Vec<f32>> = None;
 
-        for segment in split_into_segments(text) {
-            if segment.is_empty() {
-                continue;
-            }
+        let segments = fit_segments(
+            split_into_segments(text),
+            self.tokenizer.as_ref(),
+            self.config.max_size,
+        );
+        for segment in segments {
+            let segment = match segment {
+                Segment::Table { header, rows } => {
+                    stats.total_segments += 1;
+                    let pieces = table_pieces(
+                        &header,
+                        &rows,
+                        self.tokenizer.as_ref(),
+                        self.config.max_size,
+                    );
+                    let tokens = self.tokenizer.count_tokens(&pieces[0]);
+                    if pieces.len() == 1 && current.tokens + tokens <= self.config.max_size {
+                        // Small table: keep it with the surrounding text
+                        let embedding = embedder::embed(&pieces[0]);
+                        add_embedding(&mut chunk_embedding_sum, embedding);
+                        current.push(&pieces[0], tokens, true);
+                    } else {
+                        if !current.is_empty() {
+                            stats.table_flushes += 1;
+                            current.flush_into(&mut chunks, Boundary::Table);
+                        }
+                        chunk_embedding_sum = None;
+                        chunks.extend(pieces.into_iter().map(TextChunk::table));
+                    }
+                    if current.tokens >= self.config.target_size {
+                        stats.size_flushes += 1;
+                        current.flush_into(&mut chunks, Boundary::Size);
+                        chunk_embedding_sum = None;
+                    }
+                    continue;
+                }
+                Segment::Text(segment) if segment.is_empty() => continue,
+                Segment::Text(segment) => segment,
+            };
 
             stats.total_segments += 1;
 
-            let seg_tokens = estimate_token_count(&segment);
+            let seg_tokens = self.tokenizer.count_tokens(&segment);
             let seg_embedding = embedder::embed(&segment);
             let heading = is_heading_segment(&segment);
 
@@ -165,63 +311,51 @@ impl Chunker for SemanticAdaptiveChunker {
 
             let semantic_boundary = similarity_score
                 .map(|score| {
-                    current_tokens >= self.config.min_size
+                    current.tokens >= self.config.min_size
                         && score < self.config.semantic_similarity_threshold
                 })
                 .unwrap_or(false);
 
             let size_overflow =
-                !current.is_empty() && current_tokens + seg_tokens > self.config.max_size;
-            let heading_boundary = heading && current_tokens >= self.config.min_size;
+                !current.is_empty() && current.tokens + seg_tokens > self.config.max_size;
+            let heading_boundary = heading && current.tokens >= self.config.min_size;
 
             if !current.is_empty() && (size_overflow || semantic_boundary || heading_boundary) {
-                if semantic_boundary {
+                let boundary = if semantic_boundary {
                     stats.semantic_flushes += 1;
+                    Boundary::Semantic
                 } else if heading_boundary {
                     stats.heading_flushes += 1;
-                } else if size_overflow {
+                    Boundary::Heading
+                } else {
                     stats.size_flushes += 1;
-                }
+                    Boundary::Size
+                };
 
-                chunks.push(current.trim().to_string());
-                current.clear();
-                current_tokens = 0;
+                current.flush_into(&mut chunks, boundary);
                 chunk_embedding_sum = None;
             }
 
-            if !current.is_empty() {
-                current.push_str("\n\n");
-            }
-            current.push_str(segment.trim());
-            current_tokens += seg_tokens;
-
-            if let Some(sum) = chunk_embedding_sum.as_mut() {
-                for (sum_val, seg_val) in sum.iter_mut().zip(seg_embedding.iter()) {
-                    *sum_val += *seg_val;
-                }
-            } else {
-                chunk_embedding_sum = Some(seg_embedding);
-            }
+            current.push(segment.trim(), seg_tokens, false);
+            add_embedding(&mut chunk_embedding_sum, seg_embedding);
 
-            if current_tokens >= self.config.target_size {
+            if current.tokens >= self.config.target_size {
                 stats.size_flushes += 1;
-                chunks.push(current.trim().to_string());
-                current.clear();
-                current_tokens = 0;
+                current.flush_into(&mut chunks, Boundary::Size);
                 chunk_embedding_sum = None;
             }
         }
 
-        if !current.trim().is_empty() {
+        if !current.is_empty() {
             stats.size_flushes += 1;
-            chunks.push(current.trim().to_string());
+            current.flush_into(&mut chunks, Boundary::End);
         }
 
-        let filtered: Vec<String> = chunks.into_iter().filter(|c| !c.is_empty()).collect();
-        stats.total_chunks = filtered.len();
+        stats.total_chunks = chunks.len();
+        stats.table_chunks = chunks.iter().filter(|c| c.table).count();
         self.last_stats.replace(Some(stats));
 
-        filtered
+        chunks
     }
 
     fn stats(&self) -> Option<ChunkingStats> {
@@ -229,11 +363,68 @@ impl Chunker for SemanticAdaptiveChunker {
     }
 }
 
+fn add_embedding(sum: &mut Option<Vec<f32>>, embedding: Vec<f32>) {
+    match sum.as_mut() {
+        Some(sum) => {
+            for (sum_val, seg_val) in sum.iter_mut().zip(embedding.iter()) {
+                *sum_val += *seg_val;
+            }
+        }
+        None => *sum = Some(embedding),
+    }
+}
+
+/// The chunk being assembled by the adaptive chunkers
+#[derive(Default)]
+struct ChunkBuffer {
+    text: String,
+    tokens: usize,
+    table: bool,
+}
+
+impl ChunkBuffer {
+    fn is_empty(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+
+    fn push(&mut self, segment: &str, tokens: usize, table: bool) {
+        if !self.text.is_empty() {
+            self.text.push_str("\n\n");
+        }
+        self.text.push_str(segment);
+        self.tokens += tokens;
+        self.table |= table;
+    }
+
+    fn flush_into(&mut self, chunks: &mut Vec<TextChunk>, boundary: Boundary) {
+        let buffer = std::mem::take(self);
+        let text = buffer.text.trim();
+        if !text.is_empty() {
+            chunks.push(TextChunk {
+                text: text.to_string(),
+                table: buffer.table,
+                boundary,
+            });
+        }
+    }
+}
+
+/// Build a chunker sized with the active embedding tokenizer. Sizes in
+/// `config` are clamped so no chunk exceeds the embedding model's input limit.
 pub fn create_chunker(mode: ChunkerMode, config: &ChunkerConfig) -> Box<dyn Chunker> {
+    let tokenizer = tokenizer::embedding_tokenizer();
+    let config = config
+        .clone()
+        .fit_active_models()
+        .fit_embedding_limit(tokenizer.max_input_tokens());
     match mode {
         ChunkerMode::Fixed => Box::new(FixedChunker),
-        ChunkerMode::Lightweight => Box::new(LightweightAdaptiveChunker::new(config.clone())),
-        ChunkerMode::Semantic => Box::new(SemanticAdaptiveChunker::new(config.clone())),
+        ChunkerMode::Lightweight => Box::new(LightweightAdaptiveChunker::with_tokenizer(
+            config, tokenizer,
+        )),
+        ChunkerMode::Semantic => {
+            Box::new(SemanticAdaptiveChunker::with_tokenizer(config, tokenizer))
+        }
     }
 }
 
@@ -253,21 +444,93 @@ fn is_heading_segment(segment: &str) -> bool {
     letters > 0 && uppercase * 2 >= letters * 3
 }
 
-fn estimate_token_count(text: &str) -> usize {
-    let char_estimate = text.len() / 4;
-    let word_estimate = text.split_whitespace().count() * 4 / 3;
-    (char_estimate + word_estimate) / 2
+pub(crate) fn estimate_token_count(text: &str) -> usize {
+    tokenizer::count_tokens(text)
 }
 
-fn split_into_segments(text: &str) -> Vec<String> {
+/// A paragraph or list item, or a table detected by the segmenter
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    /// `header` is the header row (plus the markdown separator row)
+    Table {
+        header: String,
+        rows: Vec<String>,
+    },
+}
+
+/// Split text segments longer than `max_tokens`: at sentence ends first, then
+/// on token boundaries for sentences that are still too long. Tables are
+/// split by rows later, so they pass through.
+fn fit_segments(
+    segments: Vec<Segment>,
+    tokenizer: &dyn Tokenizer,
+    max_tokens: usize,
+) -> Vec<Segment> {
+    let mut fitted = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let segment = match segment {
+            Segment::Text(text) if tokenizer.count_tokens(&text) > max_tokens => text,
+            other => {
+                fitted.push(other);
+                continue;
+            }
+        };
+        let mut current = String::new();
+        for sentence in segment.split_inclusive(['.', '!', '?']) {
+            let sentence = sentence.trim();
+            if sentence.is_empty() {
+                continue;
+            }
+            let candidate = if current.is_empty() {
+                sentence.to_string()
+            } else {
+                format!("{} {}", current, sentence)
+            };
+            if tokenizer.count_tokens(&candidate) <= max_tokens {
+                current = candidate;
+                continue;
+            }
+            if !current.is_empty() {
+                fitted.push(Segment::Text(std::mem::take(&mut current)));
+            }
+            let mut pieces = tokenizer.split_to_fit(sentence, max_tokens);
+            if let Some(last) = pieces.pop() {
+                fitted.extend(pieces.into_iter().map(|p| Segment::Text(p.to_string())));
+                current = last.to_string();
+            }
+        }
+        if !current.is_empty() {
+            fitted.push(Segment::Text(current));
+        }
+    }
+    fitted
+}
+
+fn split_into_segments(text: &str) -> Vec<Segment> {
     let mut segments = Vec::new();
     let mut current = String::new();
+    let lines: Vec<&str> = text.lines().collect();
+    let mut i = 0;
+
+    while i < lines.len() {
+        if let Some(table) = detect_table(&lines[i..]) {
+            if !current.is_empty() {
+                segments.push(Segment::Text(std::mem::take(&mut current)));
+            }
+            segments.push(Segment::Table {
+                header: table.header,
+                rows: table.rows,
+            });
+            i += table.lines;
+            continue;
+        }
 
-    for line in text.lines() {
-        let trimmed = line.trim();
+        let trimmed = lines[i].trim();
+        i += 1;
         if trimmed.is_empty() {
             if !current.is_empty() {
-                segments.push(current.trim().to_string());
+                segments.push(Segment::Text(current.trim().to_string()));
                 current.clear();
             }
             continue;
@@ -275,10 +538,10 @@ fn split_into_segments(text: &str) -> Vec<String> {
 
         if trimmed.starts_with('-') || trimmed.starts_with('*') {
             if !current.is_empty() {
-                segments.push(current.trim().to_string());
+                segments.push(Segment::Text(current.trim().to_string()));
                 current.clear();
             }
-            segments.push(trimmed.to_string());
+            segments.push(Segment::Text(trimmed.to_string()));
         } else {
             if !current.is_empty() {
                 current.push(' ');
@@ -288,8 +551,261 @@ fn split_into_segments(text: &str) -> Vec<String> {
     }
 
     if !current.is_empty() {
-        segments.push(current.trim().to_string());
+        segments.push(Segment::Text(current.trim().to_string()));
     }
 
     segments
 }
+
+struct DetectedTable {
+    header: String,
+    rows: Vec<String>,
+    /// Source lines consumed
+    lines: usize,
+}
+
+/// Minimum lines (header included) for delimited or column-aligned blocks
+const MIN_TABLE_LINES: usize = 3;
+
+/// Detect a table starting at `lines[0]`: a markdown pipe table, a CSV/TSV
+/// block (same delimiter count on every line) or a column-aligned block as
+/// produced by PDF text extraction (cells separated by 2+ spaces).
+fn detect_table(lines: &[&str]) -> Option<DetectedTable> {
+    let first = lines.first()?.trim();
+    if first.is_empty() {
+        return None;
+    }
+
+    // Markdown: header row, separator row, body rows
+    if first.starts_with('|') {
+        let separator = lines.get(1)?.trim();
+        let is_separator = separator.starts_with('|')
+            && separator.contains('-')
+            && separator
+                .chars()
+                .all(|c| matches!(c, '|' | '-' | ':' | ' '));
+        if !is_separator {
+            return None;
+        }
+        let rows: Vec<String> = lines[2..]
+            .iter()
+            .map(|l| l.trim())
+            .take_while(|l| l.starts_with('|'))
+            .map(str::to_string)
+            .collect();
+        return (!rows.is_empty()).then(|| DetectedTable {
+            header: format!("{}\n{}", first, separator),
+            lines: rows.len() + 2,
+            rows,
+        });
+    }
+
+    let block = |cells: &dyn Fn(&str) -> usize, min_cells: usize| -> Option<DetectedTable> {
+        let columns = cells(first);
+        if columns < min_cells {
+            return None;
+        }
+        let count = lines
+            .iter()
+            .take_while(|l| !l.trim().is_empty() && cells(l.trim()) == columns)
+            .count();
+        (count >= MIN_TABLE_LINES).then(|| DetectedTable {
+            header: first.to_string(),
+            rows: lines[1..count]
+                .iter()
+                .map(|l| l.trim().to_string())
+                .collect(),
+            lines: count,
+        })
+    };
+
+    // Delimited rows with short cells (prose has long comma-separated clauses)
+    for delimiter in ['\t', ',', ';'] {
+        let min_cells = if delimiter == '\t' { 2 } else { 3 };
+        let cells = |line: &str| -> usize {
+            let fields: Vec<&str> = line.split(delimiter).collect();
+            let short = fields.iter().all(|f| f.trim().chars().count() <= 40);
+            if short && !line.ends_with('.') {
+                fields.len()
+            } else {
+                0
+            }
+        };
+        if let Some(table) = block(&cells, min_cells) {
+            return Some(table);
+        }
+    }
+
+    // Column-aligned text
+    let aligned_cells = |line: &str| -> usize {
+        let cells: Vec<&str> = line
+            .split("  ")
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .collect();
+        let short = cells.iter().all(|c| c.chars().count() <= 40);
+        if short {
+            cells.len()
+        } else {
+            0
+        }
+    };
+    block(&aligned_cells, 2)
+}
+
+/// Render a table whole if it fits `max_tokens`, otherwise as row groups
+/// that each start with the header
+fn table_pieces(
+    header: &str,
+    rows: &[String],
+    tokenizer: &dyn Tokenizer,
+    max_tokens: usize,
+) -> Vec<String> {
+    let render = |rows: &[String]| {
+        let mut text = header.to_string();
+        for row in rows {
+            text.push('\n');
+            text.push_str(row);
+        }
+        text
+    };
+
+    let whole = render(rows);
+    if tokenizer.count_tokens(&whole) <= max_tokens {
+        return vec![whole];
+    }
+
+    // Token counts are not additive across the joined rows, so measure each
+    // candidate piece as rendered
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for i in 1..rows.len() {
+        if tokenizer.count_tokens(&render(&rows[start..=i])) > max_tokens {
+            pieces.push(render(&rows[start..i]));
+            start = i;
+        }
+    }
+    pieces.push(render(&rows[start..]));
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::chunker::DEFAULT_MAX_SIZE;
+    use crate::memory::tokenizer::HeuristicTokenizer;
+
+    fn small_config() -> ChunkerConfig {
+        ChunkerConfig {
+            target_size: 60,
+            min_size: 10,
+            max_size: 80,
+            overlap: 0,
+            semantic_similarity_threshold: 0.0,
+        }
+    }
+
+    fn markdown_table(rows: usize) -> String {
+        let mut table = String::from("| Setting | Value | Notes |\n|---|---|---|\n");
+        for i in 0..rows {
+            table.push_str(&format!("| option_{} | {} | default |\n", i, i * 10));
+        }
+        table
+    }
+
+    #[test]
+    fn test_detects_table_kinds() {
+        let md = markdown_table(2);
+        let lines: Vec<&str> = md.lines().collect();
+        let table = detect_table(&lines).unwrap();
+        assert_eq!(table.header, "| Setting | Value | Notes |\n|---|---|---|");
+        assert_eq!(table.rows.len(), 2);
+
+        let csv = [
+            "name,port,proto",
+            "web,80,tcp",
+            "dns,53,udp",
+            "Then some prose follows.",
+        ];
+        let table = detect_table(&csv).unwrap();
+        assert_eq!((table.header.as_str(), table.lines), ("name,port,proto", 3));
+
+        let pdf = [
+            "Model     Params    Context",
+            "phi-2     2.7B      2048",
+            "llama-3   8B        8192",
+        ];
+        assert_eq!(detect_table(&pdf).unwrap().rows.len(), 2);
+
+        let prose = [
+            "We met Anna, Bob, and Carl at noon.",
+            "It rained, then cleared, then rained.",
+            "Nobody, in the end, minded much.",
+        ];
+        assert!(detect_table(&prose).is_none());
+    }
+
+    #[test]
+    fn test_small_table_stays_whole() {
+        let text = format!(
+            "Configuration reference:\n\n{}\nAfter the table.",
+            markdown_table(3)
+        );
+        let chunker = LightweightAdaptiveChunker::with_tokenizer(
+            small_config(),
+            Arc::new(HeuristicTokenizer),
+        );
+        let chunks = chunker.chunk_with_metadata(&text);
+
+        let table_chunks: Vec<_> = chunks.iter().filter(|c| c.table).collect();
+        assert_eq!(table_chunks.len(), 1);
+        assert!(table_chunks[0].text.contains("| option_0 |"));
+        assert!(table_chunks[0].text.contains("| option_2 |"));
+        assert_eq!(chunks.last().unwrap().boundary, Boundary::End);
+
+        let fixed = FixedChunker.chunk_with_metadata(&text);
+        assert_eq!(fixed.len(), 3);
+        assert!(fixed[1].table);
+        assert_eq!(fixed[0].boundary, Boundary::Line);
+        assert_eq!(fixed[1].boundary, Boundary::Table);
+    }
+
+    #[test]
+    fn test_fixed_chunker_splits_long_lines() {
+        let long = "word ".repeat(4 * DEFAULT_MAX_SIZE);
+        let text = format!("Short line.\n{}", long);
+        let chunks = FixedChunker::chunk_with_tokenizer(&text, &HeuristicTokenizer);
+
+        assert_eq!(chunks[0].text, "Short line.");
+        assert!(chunks.len() > 2);
+        for chunk in &chunks[1..] {
+            assert!(HeuristicTokenizer.count_tokens(&chunk.text) <= DEFAULT_MAX_SIZE);
+            assert_eq!(chunk.boundary, Boundary::Line);
+        }
+    }
+
+    #[test]
+    fn test_large_table_split_by_rows_with_header() {
+        let text = format!("Intro paragraph.\n\n{}", markdown_table(40));
+        let chunker =
+            SemanticAdaptiveChunker::with_tokenizer(small_config(), Arc::new(HeuristicTokenizer));
+        let chunks = chunker.chunk_with_metadata(&text);
+
+        let pieces: Vec<_> = chunks.iter().filter(|c| c.table).collect();
+        assert!(pieces.len() > 1);
+        for piece in &pieces {
+            assert!(piece
+                .text
+                .starts_with("| Setting | Value | Notes |\n|---|---|---|\n| option_"));
+            assert!(HeuristicTokenizer.count_tokens(&piece.text) <= 80);
+        }
+        let rows: usize = pieces.iter().map(|p| p.text.lines().count() - 2).sum();
+        assert_eq!(rows, 40);
+
+        let stats = chunker.stats().unwrap();
+        assert_eq!(stats.table_flushes, 1);
+        assert_eq!(stats.table_chunks, pieces.len());
+        assert_eq!(chunks[0].text, "Intro paragraph.");
+        assert_eq!(chunks[0].boundary, Boundary::Table);
+    }
+}
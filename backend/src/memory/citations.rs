@@ -0,0 +1,393 @@
+// src/memory/citations.rs
+// Inline citations: the prompt shows numbered context blocks, the model
+// cites them with [n] markers, and this module maps every marker back to the
+// chunk, document and heading it points at. The sentence carrying the marker
+// is matched against the cited chunks to find the supporting span; markers
+// naming a block that was never shown, or whose claim does not appear in the
+// cited text, are flagged.
+
+use crate::memory::context_budget::split_sentences;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Share of the claim's content words that must appear in one sentence of
+/// the cited chunk for the citation to count as supported
+const SUPPORT_THRESHOLD: f32 = 0.3;
+/// `[1-40]` style ranges longer than this are not expanded
+const MAX_RANGE: usize = 20;
+/// Claims shorter than this (content words) borrow the previous sentence,
+/// for markers placed after the full stop
+const MIN_CLAIM_WORDS: usize = 2;
+
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "are", "was", "were", "with", "that", "this", "from", "has", "have",
+    "had", "its", "not", "but", "can", "will", "which", "their", "they", "there", "than", "also",
+    "into", "been", "such", "may", "these", "those", "what", "when", "where", "who", "how", "all",
+    "any", "per", "via", "our", "you", "your",
+];
+
+/// A chunk inside a numbered context block
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceChunk {
+    pub chunk_id: String,
+    pub content: String,
+}
+
+/// A numbered context block the model could cite
+#[derive(Debug, Clone, Serialize)]
+pub struct CitationSource {
+    pub number: usize,
+    pub document_id: String,
+    /// Title or file name shown in the block header
+    pub source: String,
+    pub chunks: Vec<SourceChunk>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CitationStatus {
+    /// The cited chunk contains the claim
+    Verified,
+    /// The block exists but the claim was not found in it
+    Unsupported,
+    /// No context block has this number
+    UnknownSource,
+}
+
+/// One citation marker in an answer, resolved against the context
+#[derive(Debug, Clone, Serialize)]
+pub struct Citation {
+    pub number: usize,
+    /// Character offsets of the marker in the answer
+    pub marker_start: usize,
+    pub marker_end: usize,
+    pub status: CitationStatus,
+    pub chunk_id: Option<String>,
+    pub document_id: Option<String>,
+    pub source: Option<String>,
+    /// Heading path of the chunk, when it carries a contextual header
+    pub heading: Option<String>,
+    /// Character offsets of the supporting sentence in the chunk content
+    pub span_start: Option<usize>,
+    pub span_end: Option<usize>,
+    pub quote: Option<String>,
+    /// Share of the claim's content words found in the quote
+    pub support: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CitationReport {
+    pub citations: Vec<Citation>,
+    /// Cited numbers with no matching context block (hallucinated)
+    pub unknown_numbers: Vec<usize>,
+    /// Cited numbers whose claim was not found in the block
+    pub unsupported_numbers: Vec<usize>,
+}
+
+/// A `[n]`, `[n, m]` or `[n-m]` marker: numbers and character span
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    pub numbers: Vec<usize>,
+    pub start: usize,
+    pub end: usize,
+    byte_start: usize,
+    byte_end: usize,
+}
+
+/// Citation markers in order of appearance
+pub fn markers(answer: &str) -> Vec<Marker> {
+    let mut found = Vec::new();
+    let mut open: Option<(usize, usize)> = None;
+    for (chars, (byte, c)) in answer.char_indices().enumerate() {
+        match c {
+            '[' => open = Some((byte, chars)),
+            ']' => {
+                if let Some((byte_start, start)) = open.take() {
+                    if let Some(numbers) = parse_numbers(&answer[byte_start + 1..byte]) {
+                        found.push(Marker {
+                            numbers,
+                            start,
+                            end: chars + 1,
+                            byte_start,
+                            byte_end: byte + 1,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+fn parse_numbers(inner: &str) -> Option<Vec<usize>> {
+    let mut numbers = Vec::new();
+    for part in inner.split([',', ';']) {
+        let part = part.trim();
+        if let Some((a, b)) = part.split_once(['-', '–']) {
+            let (a, b) = (
+                a.trim().parse::<usize>().ok()?,
+                b.trim().parse::<usize>().ok()?,
+            );
+            if a == 0 || b < a || b - a >= MAX_RANGE {
+                return None;
+            }
+            numbers.extend(a..=b);
+        } else {
+            let n = part.parse::<usize>().ok()?;
+            if n == 0 {
+                return None;
+            }
+            numbers.push(n);
+        }
+    }
+    (!numbers.is_empty()).then_some(numbers)
+}
+
+/// Resolve and verify every marker in `answer` against the context blocks
+pub fn verify(answer: &str, sources: &[CitationSource]) -> CitationReport {
+    let sentences = sentence_ranges(answer);
+    let mut report = CitationReport::default();
+
+    for marker in markers(answer) {
+        let claim = claim_for(answer, &sentences, marker.byte_start);
+        let claim_words = content_words(&claim);
+        for &number in &marker.numbers {
+            let mut citation = Citation {
+                number,
+                marker_start: marker.start,
+                marker_end: marker.end,
+                status: CitationStatus::UnknownSource,
+                chunk_id: None,
+                document_id: None,
+                source: None,
+                heading: None,
+                span_start: None,
+                span_end: None,
+                quote: None,
+                support: 0.0,
+            };
+            let Some(source) = sources.iter().find(|s| s.number == number) else {
+                if !report.unknown_numbers.contains(&number) {
+                    report.unknown_numbers.push(number);
+                }
+                report.citations.push(citation);
+                continue;
+            };
+            citation.document_id = Some(source.document_id.clone());
+            citation.source = Some(source.source.clone());
+
+            match best_support(source, &claim_words) {
+                Some(support) => {
+                    citation.chunk_id = Some(support.chunk.chunk_id.clone());
+                    citation.heading = heading_of(&support.chunk.content);
+                    citation.support = support.score;
+                    if support.score >= SUPPORT_THRESHOLD {
+                        citation.status = CitationStatus::Verified;
+                        citation.span_start = Some(support.start);
+                        citation.span_end = Some(support.end);
+                        citation.quote = Some(support.quote);
+                    } else {
+                        citation.status = CitationStatus::Unsupported;
+                    }
+                }
+                None => {
+                    citation.chunk_id = source.chunks.first().map(|c| c.chunk_id.clone());
+                    citation.status = CitationStatus::Unsupported;
+                }
+            }
+            if citation.status == CitationStatus::Unsupported
+                && !report.unsupported_numbers.contains(&number)
+            {
+                report.unsupported_numbers.push(number);
+            }
+            report.citations.push(citation);
+        }
+    }
+    report
+}
+
+struct Support<'a> {
+    chunk: &'a SourceChunk,
+    start: usize,
+    end: usize,
+    quote: String,
+    score: f32,
+}
+
+/// Sentence of the cited block that shares most content words with the claim
+fn best_support<'a>(source: &'a CitationSource, claim: &HashSet<String>) -> Option<Support<'a>> {
+    if claim.is_empty() {
+        return None;
+    }
+    let mut best: Option<Support> = None;
+    for chunk in &source.chunks {
+        let mut chars = 0;
+        for sentence in split_sentences(&chunk.content) {
+            let len = sentence.chars().count();
+            let words = content_words(sentence);
+            let score = claim.intersection(&words).count() as f32 / claim.len() as f32;
+            if best.as_ref().is_none_or(|b| score > b.score) {
+                let trimmed = sentence.trim_end();
+                best = Some(Support {
+                    chunk,
+                    start: chars,
+                    end: chars + trimmed.chars().count(),
+                    quote: trimmed.to_string(),
+                    score,
+                });
+            }
+            chars += len;
+        }
+    }
+    best
+}
+
+/// Byte ranges of the answer's sentences
+fn sentence_ranges(answer: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for sentence in split_sentences(answer) {
+        ranges.push((start, start + sentence.len()));
+        start += sentence.len();
+    }
+    ranges
+}
+
+/// The answer sentence a marker belongs to, without markers. A marker
+/// placed after the full stop ("... blue. [1]") belongs to the sentence
+/// before it
+fn claim_for(answer: &str, sentences: &[(usize, usize)], marker_byte: usize) -> String {
+    let Some(index) = sentences
+        .iter()
+        .position(|&(start, end)| marker_byte >= start && marker_byte < end)
+    else {
+        return String::new();
+    };
+    let text = |i: usize| strip_markers(&answer[sentences[i].0..sentences[i].1]);
+    let claim = text(index);
+    if content_words(&claim).len() < MIN_CLAIM_WORDS && index > 0 {
+        return text(index - 1);
+    }
+    claim
+}
+
+pub(crate) fn strip_markers(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for marker in markers(text) {
+        out.push_str(&text[last..marker.byte_start]);
+        last = marker.byte_end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+pub(crate) fn content_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3 || w.chars().all(|c| c.is_ascii_digit()))
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+        .collect()
+}
+
+/// Heading path from a contextual chunk header ("Section: A > B")
+pub fn heading_of(content: &str) -> Option<String> {
+    content
+        .lines()
+        .take(4)
+        .find_map(|line| line.strip_prefix("Section: "))
+        .map(|heading| heading.trim().to_string())
+        .filter(|heading| !heading.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources() -> Vec<CitationSource> {
+        vec![
+            CitationSource {
+                number: 1,
+                document_id: "sky.md".into(),
+                source: "Sky".into(),
+                chunks: vec![SourceChunk {
+                    chunk_id: "sky.md#0".into(),
+                    content: "Document: Sky\nSection: Optics > Scattering\n\nSunlight reaches the atmosphere. Rayleigh scattering makes the sky look blue."
+                        .into(),
+                }],
+            },
+            CitationSource {
+                number: 2,
+                document_id: "ocean.md".into(),
+                source: "Ocean".into(),
+                chunks: vec![
+                    SourceChunk {
+                        chunk_id: "ocean.md#3".into(),
+                        content: "Tides follow the moon.".into(),
+                    },
+                    SourceChunk {
+                        chunk_id: "ocean.md#4".into(),
+                        content: "Water absorbs red light, so deep water appears blue.".into(),
+                    },
+                ],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_markers_parse_lists_and_ranges() {
+        let found = markers("See [1], [2, 3] and [4-6]. Not [x] or [0] or [].");
+        let numbers: Vec<Vec<usize>> = found.iter().map(|m| m.numbers.clone()).collect();
+        assert_eq!(numbers, vec![vec![1], vec![2, 3], vec![4, 5, 6]]);
+        assert_eq!((found[0].start, found[0].end), (4, 7));
+    }
+
+    #[test]
+    fn test_verified_citation_maps_chunk_heading_and_span() {
+        let answer = "The sky looks blue because of Rayleigh scattering [1]. Deep water appears blue since water absorbs red light [2].";
+        let report = verify(answer, &sources());
+        assert_eq!(report.citations.len(), 2);
+        assert!(report.unknown_numbers.is_empty() && report.unsupported_numbers.is_empty());
+
+        let sky = &report.citations[0];
+        assert_eq!(sky.status, CitationStatus::Verified);
+        assert_eq!(sky.chunk_id.as_deref(), Some("sky.md#0"));
+        assert_eq!(sky.heading.as_deref(), Some("Optics > Scattering"));
+        let content = &sources()[0].chunks[0].content;
+        let quoted: String = content
+            .chars()
+            .skip(sky.span_start.unwrap())
+            .take(sky.span_end.unwrap() - sky.span_start.unwrap())
+            .collect();
+        assert_eq!(quoted, "Rayleigh scattering makes the sky look blue.");
+
+        // Second block: the supporting chunk is the second one
+        assert_eq!(report.citations[1].chunk_id.as_deref(), Some("ocean.md#4"));
+    }
+
+    #[test]
+    fn test_flags_unknown_and_unsupported_numbers() {
+        let answer = "Tides are caused by the moon [2]. Volcanoes erupt every Tuesday [1]. Pluto is made of cheese [7].";
+        let report = verify(answer, &sources());
+        let statuses: Vec<CitationStatus> = report.citations.iter().map(|c| c.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                CitationStatus::Verified,
+                CitationStatus::Unsupported,
+                CitationStatus::UnknownSource
+            ]
+        );
+        assert_eq!(report.unknown_numbers, vec![7]);
+        assert_eq!(report.unsupported_numbers, vec![1]);
+    }
+
+    #[test]
+    fn test_marker_after_full_stop_uses_previous_sentence() {
+        let answer = "Rayleigh scattering makes the sky blue. [1]";
+        let report = verify(answer, &sources());
+        assert_eq!(report.citations[0].status, CitationStatus::Verified);
+    }
+}
@@ -0,0 +1,314 @@
+// src/memory/context_budget.rs
+// Token budget for the context section of a prompt: the model's context
+// window minus the rendered prompt template, conversation history and the
+// tokens reserved for the answer. Passages are packed best-first, sentences
+// already present in an earlier passage (chunk overlap, near-duplicate
+// documents) are dropped, and the passage that overflows is cut at a
+// sentence boundary.
+
+use crate::memory::tokenizer::{self, Tokenizer};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Sentences shorter than this (after normalising) are never treated as
+/// duplicates; short lines like "Yes." legitimately repeat
+const MIN_DEDUP_SENTENCE_CHARS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ContextBudget {
+    pub context_window: usize,
+    /// Prompt template rendered with the query and an empty context
+    pub prompt_tokens: usize,
+    pub history_tokens: usize,
+    pub reserved_answer_tokens: usize,
+    /// What is left for context passages
+    pub available_tokens: usize,
+}
+
+impl ContextBudget {
+    pub fn new(
+        context_window: usize,
+        prompt_tokens: usize,
+        history_tokens: usize,
+        reserved_answer_tokens: usize,
+    ) -> Self {
+        Self {
+            context_window,
+            prompt_tokens,
+            history_tokens,
+            reserved_answer_tokens,
+            available_tokens: context_window
+                .saturating_sub(prompt_tokens)
+                .saturating_sub(history_tokens)
+                .saturating_sub(reserved_answer_tokens),
+        }
+    }
+
+    /// Budget for the active generation model. Its context window is the
+    /// configured num_ctx, capped by the trained context from model metadata
+    pub fn for_active_model(
+        prompt_tokens: usize,
+        history_tokens: usize,
+        reserved_answer_tokens: usize,
+    ) -> Self {
+        Self::new(
+            tokenizer::model_limits().generation_context,
+            prompt_tokens,
+            history_tokens,
+            reserved_answer_tokens,
+        )
+    }
+}
+
+/// How a budget was spent, reported with answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BudgetUsage {
+    #[serde(flatten)]
+    pub budget: ContextBudget,
+    pub used_tokens: usize,
+    pub passages_included: usize,
+    /// Passages that did not fit
+    pub passages_dropped: usize,
+    /// Passages whose every sentence was already in the context
+    pub duplicates_removed: usize,
+    /// The last included passage was cut at a sentence boundary
+    pub truncated: bool,
+}
+
+/// Text for the context with its citation header, e.g. "[2] From a.md: "
+#[derive(Debug, Clone)]
+pub struct Passage {
+    pub header: String,
+    pub text: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PackedContext {
+    pub text: String,
+    /// Indices into the input passages, in context order
+    pub included: Vec<usize>,
+    pub usage: BudgetUsage,
+}
+
+/// Pack passages into the budget, best score first
+pub fn pack(
+    passages: &[Passage],
+    budget: &ContextBudget,
+    tokenizer: &dyn Tokenizer,
+) -> PackedContext {
+    let mut order: Vec<usize> = (0..passages.len()).collect();
+    order.sort_by(|&a, &b| passages[b].score.total_cmp(&passages[a].score));
+
+    let mut usage = BudgetUsage {
+        budget: *budget,
+        used_tokens: 0,
+        passages_included: 0,
+        passages_dropped: 0,
+        duplicates_removed: 0,
+        truncated: false,
+    };
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut text = String::new();
+    let mut included = Vec::new();
+    let mut full = false;
+
+    for i in order {
+        if full {
+            usage.passages_dropped += 1;
+            continue;
+        }
+        let passage = &passages[i];
+        let sentences: Vec<&str> = split_sentences(&passage.text)
+            .into_iter()
+            .filter(|s| {
+                let key = normalize(s);
+                key.len() < MIN_DEDUP_SENTENCE_CHARS || !seen.contains(&key)
+            })
+            .collect();
+        if sentences.iter().all(|s| s.trim().is_empty()) {
+            usage.duplicates_removed += 1;
+            continue;
+        }
+
+        // Header and the blank line after the passage count too
+        let overhead = tokenizer.count_tokens(&passage.header) + tokenizer.count_tokens("\n\n");
+        let remaining = budget.available_tokens.saturating_sub(usage.used_tokens);
+        let mut cost = overhead;
+        let mut fitting = 0;
+        for sentence in &sentences {
+            let next = cost + tokenizer.count_tokens(sentence);
+            if next > remaining {
+                break;
+            }
+            cost = next;
+            fitting += 1;
+        }
+        if fitting == 0 {
+            usage.passages_dropped += 1;
+            full = true;
+            continue;
+        }
+        if fitting < sentences.len() {
+            usage.truncated = true;
+            full = true;
+        }
+
+        let body: String = sentences[..fitting].concat();
+        for sentence in &sentences[..fitting] {
+            seen.insert(normalize(sentence));
+        }
+        text.push_str(&passage.header);
+        text.push_str(body.trim_end());
+        text.push_str("\n\n");
+        usage.used_tokens += cost;
+        usage.passages_included += 1;
+        included.push(i);
+    }
+
+    PackedContext {
+        text,
+        included,
+        usage,
+    }
+}
+
+/// Split after sentence-ending punctuation or a newline, keeping the
+/// trailing whitespace with the sentence so the pieces concatenate back to
+/// the input
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends = match c {
+            '\n' => true,
+            '.' | '!' | '?' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if !ends {
+            continue;
+        }
+        let mut end = i + c.len_utf8();
+        while let Some(&(j, next)) = chars.peek() {
+            if !next.is_whitespace() {
+                break;
+            }
+            end = j + next.len_utf8();
+            chars.next();
+        }
+        sentences.push(&text[start..end]);
+        start = end;
+    }
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+    sentences
+}
+
+fn normalize(sentence: &str) -> String {
+    sentence
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::tokenizer::HeuristicTokenizer;
+
+    fn passage(header: &str, text: &str, score: f32) -> Passage {
+        Passage {
+            header: header.to_string(),
+            text: text.to_string(),
+            score,
+        }
+    }
+
+    #[test]
+    fn test_budget_subtracts_prompt_history_and_answer() {
+        let budget = ContextBudget::new(4096, 300, 200, 1024);
+        assert_eq!(budget.available_tokens, 2572);
+        assert_eq!(ContextBudget::new(100, 80, 30, 10).available_tokens, 0);
+    }
+
+    #[test]
+    fn test_split_sentences_round_trips() {
+        let text = "First one. Second? Third!\nFourth v1.2 stays whole";
+        let sentences = split_sentences(text);
+        assert_eq!(
+            sentences,
+            vec![
+                "First one. ",
+                "Second? ",
+                "Third!\n",
+                "Fourth v1.2 stays whole"
+            ]
+        );
+        assert_eq!(sentences.concat(), text);
+    }
+
+    #[test]
+    fn test_packs_by_score_and_removes_overlap() {
+        let overlap = "The cache is flushed every ten minutes by the scheduler.";
+        let passages = vec![
+            passage("[1] ", "Low scoring but unique text about retries.", 0.2),
+            passage(
+                "[2] ",
+                &format!("Intro sentence for chunk two. {}", overlap),
+                0.9,
+            ),
+            passage(
+                "[3] ",
+                &format!("{} Then the index is compacted.", overlap),
+                0.8,
+            ),
+            passage("[4] ", overlap, 0.7),
+        ];
+        let budget = ContextBudget::new(10_000, 0, 0, 0);
+        let packed = pack(&passages, &budget, &HeuristicTokenizer);
+
+        assert_eq!(packed.included, vec![1, 2, 0]);
+        assert_eq!(packed.usage.duplicates_removed, 1);
+        assert!(packed.text.starts_with("[2] Intro sentence"));
+        assert!(packed
+            .text
+            .contains("[3] Then the index is compacted.\n\n[1] Low scoring"));
+        assert_eq!(packed.text.matches(overlap).count(), 1);
+    }
+
+    #[test]
+    fn test_truncates_last_passage_at_sentence_boundary() {
+        let tokenizer = HeuristicTokenizer;
+        let first = "Alpha beta gamma delta. ".repeat(4);
+        let second = "One two three four five. Six seven eight nine ten. Eleven twelve.";
+        let passages = vec![passage("", &first, 0.9), passage("", second, 0.5)];
+
+        let first_cost = tokenizer.count_tokens(&first) + tokenizer.count_tokens("\n\n");
+        let one_sentence = tokenizer.count_tokens("One two three four five. ");
+        let budget = ContextBudget::new(first_cost + one_sentence + 1, 0, 0, 0);
+        let packed = pack(&passages, &budget, &tokenizer);
+
+        assert!(packed.usage.truncated);
+        assert_eq!(packed.usage.passages_included, 2);
+        assert!(packed.text.ends_with("One two three four five.\n\n"));
+        assert!(packed.usage.used_tokens <= budget.available_tokens);
+    }
+
+    #[test]
+    fn test_drops_what_does_not_fit() {
+        let passages = vec![
+            passage("", "A sentence that is far too long for the budget.", 0.9),
+            passage("", "Short.", 0.1),
+        ];
+        let packed = pack(
+            &passages,
+            &ContextBudget::new(3, 0, 0, 0),
+            &HeuristicTokenizer,
+        );
+        assert!(packed.text.is_empty());
+        assert_eq!(packed.usage.passages_dropped, 2);
+    }
+}
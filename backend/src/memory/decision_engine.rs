@@ -1,20 +1,135 @@
 // src/memory/decision_engine.rs
 // Phase 7: Agent Decision Engine
-// Multi-step reasoning, tool selection, autonomous execution
+// ReAct loop: the model thinks, takes one action (search, refined search,
+// calculator, URL fetch or final answer), observes the result and repeats
+// until it answers or a step, token or time budget runs out. Steps are JSON
+// validated against the action schemas, so any provider can drive the loop.
 
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
 use std::sync::Arc;
-use tracing::{debug, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
-use crate::memory::{AgentMemoryLayer, Episode, RagQueryPipeline, RagQueryRequest};
+use crate::db::{llm_settings, prompt_templates};
+use crate::memory::llm_provider::{ChatMessage, LLMError};
+use crate::memory::query::ContextChunk;
+use crate::memory::{
+    grounding, structured, AgentMemoryLayer, Episode, GroundingReport, RagQueryPipeline,
+};
+use crate::tools::tool_executor::ToolExecutor;
+use crate::tools::{ToolRegistry, ToolType};
+
+/// Grounding score an answer needs to count as a success when the
+/// pipeline's grounding check is on
+const SUCCESS_GROUNDING: f32 = 0.5;
+/// Passages a semantic search returns
+const SEARCH_TOP_K: usize = 5;
+/// Default and upper bound of passages for a refined search
+const REFINED_TOP_K: usize = 8;
+const MAX_REFINED_TOP_K: usize = 20;
+/// Characters of a passage shown in an observation
+const PASSAGE_PREVIEW_CHARS: usize = 600;
+/// Characters of an observation kept in the reasoning trace
+const TRACE_OBSERVATION_CHARS: usize = 300;
+/// Repair rounds for a malformed step before the loop stops
+const STEP_REPAIRS: usize = 1;
+/// Searches in a row without new passages before the loop counts as stuck
+const MAX_STALE_SEARCHES: usize = 2;
+/// Least time the final-answer step gets, even once the time budget is spent
+const FINAL_ANSWER_GRACE: Duration = Duration::from_secs(10);
+/// Passages quoted by the answer given when the final-answer step fails
+const FALLBACK_PASSAGES: usize = 3;
 
 /// Available tools the agent can use
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Tool {
     SemanticSearch,   // Search vector store for relevant chunks
     ReflectOnHistory, // Analyze past episodes for patterns
-    RefinedSearch,    // Search with adjusted parameters based on reflection
-    DirectAnswer,     // Provide answer from retrieved context
+    RefinedSearch,    // Search again with a reworded query and more results
+    DirectAnswer,     // Provide the final answer from the observations
+    Calculator,       // Evaluate an arithmetic expression
+    UrlFetch,         // Fetch the content of a web page
+}
+
+impl Tool {
+    /// Actions offered in the loop, final answer last
+    const ACTIONS: [Tool; 5] = [
+        Tool::SemanticSearch,
+        Tool::RefinedSearch,
+        Tool::Calculator,
+        Tool::UrlFetch,
+        Tool::DirectAnswer,
+    ];
+
+    /// Name of the action in the loop's JSON protocol
+    pub fn action_name(&self) -> &'static str {
+        match self {
+            Tool::SemanticSearch => "semantic_search",
+            Tool::ReflectOnHistory => "reflect_on_history",
+            Tool::RefinedSearch => "refined_search",
+            Tool::DirectAnswer => "final_answer",
+            Tool::Calculator => "calculator",
+            Tool::UrlFetch => "url_fetch",
+        }
+    }
+
+    pub fn from_action(name: &str) -> Option<Tool> {
+        Self::ACTIONS
+            .into_iter()
+            .find(|tool| tool.action_name() == name)
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Tool::SemanticSearch => "search the knowledge base; input {\"query\": string}",
+            Tool::ReflectOnHistory => "review past episodes",
+            Tool::RefinedSearch => {
+                "search again with a reworded or narrower query, returning more passages \
+                 and skipping ones already seen; input {\"query\": string, \"top_k\": 1-20}"
+            }
+            Tool::DirectAnswer => "give the final answer; input {\"answer\": string}",
+            Tool::Calculator => "evaluate arithmetic; input {\"expression\": \"15 * 3\"}",
+            Tool::UrlFetch => "fetch a web page; input {\"url\": \"https://...\"}",
+        }
+    }
+
+    /// JSON Schema of the action's input
+    fn input_schema(&self, registry: &ToolRegistry) -> Value {
+        let tool_parameters = |tool_type: ToolType| {
+            registry
+                .get_tool(&tool_type)
+                .map(|tool| tool.parameters())
+                .unwrap_or_else(|| json!({ "type": "object" }))
+        };
+        match self {
+            Tool::SemanticSearch => json!({
+                "type": "object",
+                "properties": { "query": { "type": "string", "minLength": 1 } },
+                "required": ["query"],
+                "additionalProperties": false
+            }),
+            Tool::RefinedSearch => json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "minLength": 1 },
+                    "top_k": { "type": "integer", "minimum": 1, "maximum": MAX_REFINED_TOP_K }
+                },
+                "required": ["query"],
+                "additionalProperties": false
+            }),
+            Tool::DirectAnswer => json!({
+                "type": "object",
+                "properties": { "answer": { "type": "string", "minLength": 1 } },
+                "required": ["answer"],
+                "additionalProperties": false
+            }),
+            Tool::Calculator => tool_parameters(ToolType::Calculator),
+            Tool::UrlFetch => tool_parameters(ToolType::URLFetch),
+            Tool::ReflectOnHistory => json!({ "type": "object" }),
+        }
+    }
 }
 
 /// Decision made by the agent
@@ -41,6 +156,85 @@ pub struct ExecutionPlan {
     pub estimated_effort: usize, // number of steps
 }
 
+/// Limits on one agent run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AgentBudget {
+    /// Think/act/observe iterations before a final answer is forced
+    pub max_steps: usize,
+    /// Prompt plus completion tokens across the run's model calls
+    pub max_tokens: usize,
+    pub max_duration_ms: u64,
+}
+
+impl Default for AgentBudget {
+    fn default() -> Self {
+        Self {
+            max_steps: 6,
+            max_tokens: 12_000,
+            max_duration_ms: 60_000,
+        }
+    }
+}
+
+impl AgentBudget {
+    /// Defaults overridden by AGENT_MAX_STEPS, AGENT_TOKEN_BUDGET and
+    /// AGENT_TIME_BUDGET_MS
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|v| *v > 0)
+        };
+        Self {
+            max_steps: var("AGENT_MAX_STEPS").map_or(defaults.max_steps, |v| v as usize),
+            max_tokens: var("AGENT_TOKEN_BUDGET").map_or(defaults.max_tokens, |v| v as usize),
+            max_duration_ms: var("AGENT_TIME_BUDGET_MS").unwrap_or(defaults.max_duration_ms),
+        }
+    }
+}
+
+/// Why the loop stopped
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    FinalAnswer,
+    MaxSteps,
+    TokenBudget,
+    TimeBudget,
+    /// The model repeated an action or searches stopped finding anything new
+    LoopDetected,
+    /// The model failed or kept producing malformed steps
+    ModelError,
+}
+
+impl StopReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FinalAnswer => "final_answer",
+            Self::MaxSteps => "max_steps",
+            Self::TokenBudget => "token_budget",
+            Self::TimeBudget => "time_budget",
+            Self::LoopDetected => "loop_detected",
+            Self::ModelError => "model_error",
+        }
+    }
+}
+
+/// One think/act/observe iteration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStep {
+    pub step_number: usize,
+    pub thought: String,
+    pub tool: Tool,
+    pub input: Value,
+    /// Empty for the final answer
+    pub observation: String,
+    pub tokens: usize,
+    pub elapsed_ms: u64,
+}
+
 /// Result of agent execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionResult {
@@ -48,13 +242,30 @@ pub struct ExecutionResult {
     pub answer: String,
     pub steps_executed: usize,
     pub success: bool,
+    pub grounding: Option<GroundingReport>,
     pub reasoning_trace: Vec<String>,
+    pub steps: Vec<AgentStep>,
+    pub stop_reason: StopReason,
+    pub tokens_used: usize,
+    /// Episode the run was recorded as; its trace is stored with it
+    pub episode_id: String,
+}
+
+/// State of one run of the loop
+#[derive(Default)]
+struct LoopState {
+    steps: Vec<AgentStep>,
+    trace: Vec<String>,
+    /// Passages observed so far, numbered from 1 in order
+    passages: Vec<ContextChunk>,
+    tokens: usize,
 }
 
 /// Agent Decision Engine
 pub struct DecisionEngine {
     rag_pipeline: Arc<RagQueryPipeline>,
     agent_memory: Arc<AgentMemoryLayer>,
+    budget: AgentBudget,
 }
 
 impl DecisionEngine {
@@ -64,216 +275,518 @@ impl DecisionEngine {
         Self {
             rag_pipeline,
             agent_memory,
+            budget: AgentBudget::from_env(),
         }
     }
 
-    /// Execute a query with multi-step reasoning
+    /// Replace the budget read from the environment
+    pub fn with_budget(mut self, budget: AgentBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn budget(&self) -> AgentBudget {
+        self.budget
+    }
+
+    /// Answer a query with the think/act/observe loop, then record the
+    /// episode and its reasoning trace
     pub async fn execute_query(
         &self,
         query: &str,
         goal_id: Option<String>,
     ) -> Result<ExecutionResult, Box<dyn std::error::Error>> {
-        info!(query = %query, "Starting agent execution");
-
+        info!(query = %query, max_steps = self.budget.max_steps, "Starting agent execution");
+        let started = Instant::now();
         let mut reasoning_trace = Vec::new();
-        let mut steps_executed = 0;
-
-        // Step 1: Assess situation
-        reasoning_trace.push("Step 1: Assessing query and goal context".to_string());
-        debug!("Assessing query: {}", query);
 
+        // Assess goals and how similar questions went before
         let context = self.agent_memory.get_agent_context()?;
         reasoning_trace.push(format!(
-            "Found {} active goals, {} recent episodes",
+            "Context: {} active goals, {} recent episodes",
             context.active_goals.len(),
             context.recent_episodes.len()
         ));
-
-        // Step 2: Check for similar past queries
-        reasoning_trace.push("Step 2: Checking for similar past queries".to_string());
         let similar_queries = self.agent_memory.recall_similar_episodes(query, 3).await?;
-        steps_executed += 1;
-
-        if !similar_queries.is_empty() {
+        let history = if similar_queries.is_empty() {
+            String::new()
+        } else {
             let success_rate = self.calculate_success_rate(&similar_queries);
             reasoning_trace.push(format!(
                 "Found {} similar queries with {:.1}% success rate",
                 similar_queries.len(),
                 success_rate * 100.0
             ));
-
-            // If past similar queries failed, refine search strategy
             if success_rate < 0.5 {
-                reasoning_trace.push(
-                    "Past similar queries had low success - will refine search parameters"
-                        .to_string(),
-                );
+                "\n\nSimilar questions were answered poorly before: prefer refined_search \
+                 with reworded queries before answering."
+                    .to_string()
+            } else {
+                String::new()
             }
-        }
-
-        // Step 3: Decide on tool/strategy
-        reasoning_trace.push("Step 3: Deciding on search strategy".to_string());
-        let decision = self.make_decision(query, &similar_queries).await?;
-        reasoning_trace.push(format!(
-            "Decision: {:?} (confidence: {:.1}%)",
-            decision.tool,
-            decision.confidence * 100.0
-        ));
-        reasoning_trace.push(format!("Reasoning: {}", decision.reasoning));
-
-        // Step 4: Execute RAG pipeline
-        reasoning_trace.push("Step 4: Executing RAG query pipeline".to_string());
-        let rag_request = RagQueryRequest {
-            query: query.to_string(),
-            top_k: self.determine_top_k(&decision),
-            include_sources: true,
         };
 
-        let rag_response = self.rag_pipeline.query(&rag_request).await?;
-        steps_executed += 1;
-        reasoning_trace.push(format!(
-            "Retrieved {} context chunks",
-            rag_response.total_chunks_used
-        ));
+        let (mut answer, stop_reason, state) = self.react(query, &history, started).await?;
+        reasoning_trace.extend(state.trace);
 
-        // Step 5: Record episode in memory
-        reasoning_trace.push("Step 5: Recording episode in memory".to_string());
-        let success = !rag_response.answer.is_empty();
-        let _episode = self
+        let grounding = if state.passages.is_empty() {
+            None
+        } else {
+            self.rag_pipeline
+                .check_grounding(&answer, &state.passages)
+                .await
+        };
+        if let Some(grounding) = &grounding {
+            reasoning_trace.push(format!(
+                "Grounding score {:.2} ({} of {} claims unsupported{})",
+                grounding.score,
+                grounding.unsupported_claims,
+                grounding.claims.len(),
+                if grounding.abstained {
+                    ", abstained"
+                } else {
+                    ""
+                }
+            ));
+            if grounding.abstained {
+                answer = grounding::ABSTENTION_ANSWER.to_string();
+            }
+        }
+        let success = answer_succeeded(&answer, grounding.as_ref());
+
+        let episode = self
             .agent_memory
             .record_episode(
                 query.to_string(),
-                rag_response.answer.clone(),
-                rag_response.total_chunks_used,
+                answer.clone(),
+                state.passages.len(),
                 success,
+                grounding.as_ref().map(|g| g.score),
             )
             .await?;
-        steps_executed += 1;
+        reasoning_trace.push(format!("Recorded episode {}", episode.id));
 
-        // Step 6: Update goal if provided
         if let Some(goal_id) = goal_id {
-            reasoning_trace.push(format!("Step 6: Updating goal {}", goal_id));
             self.agent_memory.complete_goal(&goal_id)?;
-            steps_executed += 1;
+            reasoning_trace.push(format!("Completed goal {}", goal_id));
         }
 
-        // Step 7: Reflect on execution
-        reasoning_trace.push("Step 7: Reflecting on execution".to_string());
         let _reflection = self.agent_memory.reflect_on_episodes()?;
         reasoning_trace.push("Reflection stored for future learning".to_string());
-        steps_executed += 1;
+        self.agent_memory
+            .record_trace(&episode.id, &reasoning_trace)?;
 
         let plan = ExecutionPlan {
             goal: query.to_string(),
-            steps: vec![
-                PlanStep {
-                    step_number: 1,
-                    action: "Assess context".to_string(),
-                    expected_outcome: "Understand goal and history".to_string(),
-                },
-                PlanStep {
-                    step_number: 2,
-                    action: "Search similar queries".to_string(),
-                    expected_outcome: "Learn from past".to_string(),
-                },
-                PlanStep {
-                    step_number: 3,
-                    action: "Decide strategy".to_string(),
-                    expected_outcome: "Choose best tool".to_string(),
-                },
-                PlanStep {
-                    step_number: 4,
-                    action: "Execute RAG pipeline".to_string(),
-                    expected_outcome: "Generate answer".to_string(),
-                },
-                PlanStep {
-                    step_number: 5,
-                    action: "Record learning".to_string(),
-                    expected_outcome: "Improve future decisions".to_string(),
-                },
-            ],
-            estimated_effort: 5,
+            steps: state
+                .steps
+                .iter()
+                .map(|step| PlanStep {
+                    step_number: step.step_number,
+                    action: format!("{} {}", step.tool.action_name(), step.input),
+                    expected_outcome: step.thought.clone(),
+                })
+                .collect(),
+            estimated_effort: self.budget.max_steps,
         };
 
         info!(
             query = %query,
             success = success,
-            steps = steps_executed,
+            steps = state.steps.len(),
+            stop_reason = stop_reason.as_str(),
+            tokens = state.tokens,
+            duration_ms = started.elapsed().as_millis() as u64,
             "Agent execution completed"
         );
 
         Ok(ExecutionResult {
             plan,
-            answer: rag_response.answer,
-            steps_executed,
+            answer,
+            steps_executed: state.steps.len(),
             success,
+            grounding,
             reasoning_trace,
+            steps: state.steps,
+            stop_reason,
+            tokens_used: state.tokens,
+            episode_id: episode.id,
         })
     }
 
-    /// Make decision about which tool to use
-    async fn make_decision(
+    /// Run the loop until the model answers or a budget runs out; without a
+    /// final answer the model is asked for one from what it observed
+    async fn react(
         &self,
-        _query: &str,
-        similar_queries: &[Episode],
-    ) -> Result<Decision, Box<dyn std::error::Error>> {
-        // Simple heuristic-based decision making
-        // In Phase 8+, could use ML model for this
-
-        if similar_queries.is_empty() {
-            return Ok(Decision {
-                tool: Tool::SemanticSearch,
-                reasoning: "No similar past queries found, starting fresh search".to_string(),
-                confidence: 0.6,
+        query: &str,
+        history: &str,
+        started: Instant,
+    ) -> Result<(String, StopReason, LoopState), Box<dyn std::error::Error>> {
+        let provider = self.rag_pipeline.llm_provider()?;
+        let config = llm_settings::global_config();
+        let registry = ToolRegistry::with_defaults();
+        let schema = step_schema(&registry);
+        let actions = Tool::ACTIONS
+            .iter()
+            .map(|tool| format!("- {}: {}", tool.action_name(), tool.description()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let system = prompt_templates::render(
+            prompt_templates::AGENT_REACT,
+            Some(provider.model_name()),
+            &[("tools", &actions), ("history", history)],
+        )?;
+        let mut conversation = vec![ChatMessage::system(system), ChatMessage::user(query)];
+        let mut state = LoopState::default();
+        let mut seen_actions = HashSet::new();
+        let mut stale_searches = 0;
+        let time_budget = Duration::from_millis(self.budget.max_duration_ms);
+
+        let stop = loop {
+            if state.steps.len() >= self.budget.max_steps {
+                break StopReason::MaxSteps;
+            }
+            if state.tokens >= self.budget.max_tokens {
+                break StopReason::TokenBudget;
+            }
+            let Some(remaining) = time_budget.checked_sub(started.elapsed()) else {
+                break StopReason::TimeBudget;
+            };
+
+            let step_started = Instant::now();
+            let step = structured::generate(
+                provider.as_ref(),
+                &conversation,
+                &schema,
+                &config,
+                STEP_REPAIRS,
+            );
+            let output = match tokio::time::timeout(remaining, step).await {
+                Err(_) => break StopReason::TimeBudget,
+                Ok(Err(e)) => {
+                    warn!(error = %e, "Agent step failed");
+                    state.trace.push(format!("Model error: {}", e));
+                    break StopReason::ModelError;
+                }
+                Ok(Ok(output)) => output,
+            };
+            let tokens = output.tokens;
+            state.tokens += tokens;
+            if !output.valid {
+                state.trace.push(format!(
+                    "Malformed step: {}",
+                    output
+                        .errors
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                ));
+                break StopReason::ModelError;
+            }
+
+            let value = output.value.unwrap_or_default();
+            let thought = value["thought"].as_str().unwrap_or_default().to_string();
+            let tool = value["action"]
+                .as_str()
+                .and_then(Tool::from_action)
+                .unwrap_or(Tool::DirectAnswer);
+            let input = value.get("input").cloned().unwrap_or_else(|| json!({}));
+            let step_number = state.steps.len() + 1;
+            state
+                .trace
+                .push(format!("Step {}: Thought: {}", step_number, thought));
+            state
+                .trace
+                .push(format!("Action: {} {}", tool.action_name(), input));
+
+            if tool == Tool::DirectAnswer {
+                let answer = input["answer"].as_str().unwrap_or_default().to_string();
+                state.steps.push(AgentStep {
+                    step_number,
+                    thought,
+                    tool,
+                    input,
+                    observation: String::new(),
+                    tokens,
+                    elapsed_ms: step_started.elapsed().as_millis() as u64,
+                });
+                state.trace.push("Final answer given".to_string());
+                return Ok((answer, StopReason::FinalAnswer, state));
+            }
+
+            // The same action twice never tells the model anything new
+            let key = format!(
+                "{}:{}",
+                tool.action_name(),
+                input.to_string().to_lowercase()
+            );
+            if !seen_actions.insert(key) {
+                state.trace.push(format!(
+                    "Loop detected: {} repeated with the same input",
+                    tool.action_name()
+                ));
+                break StopReason::LoopDetected;
+            }
+
+            let (observation, new_passages) = self
+                .act(&tool, &input, &registry, &mut state.passages)
+                .await;
+            debug!(
+                step = step_number,
+                action = tool.action_name(),
+                tokens,
+                "Agent step"
+            );
+            state.trace.push(format!(
+                "Observation: {}",
+                preview(&observation, TRACE_OBSERVATION_CHARS)
+            ));
+            state.steps.push(AgentStep {
+                step_number,
+                thought,
+                tool,
+                input,
+                observation: observation.clone(),
+                tokens,
+                elapsed_ms: step_started.elapsed().as_millis() as u64,
             });
-        }
+            conversation.push(ChatMessage::assistant(output.raw));
+            conversation.push(ChatMessage::user(format!("Observation: {}", observation)));
 
-        let success_rate = self.calculate_success_rate(similar_queries);
+            match new_passages {
+                Some(0) => stale_searches += 1,
+                Some(_) => stale_searches = 0,
+                None => {}
+            }
+            if stale_searches >= MAX_STALE_SEARCHES {
+                state.trace.push(format!(
+                    "Loop detected: {} searches in a row found nothing new",
+                    stale_searches
+                ));
+                break StopReason::LoopDetected;
+            }
+        };
 
-        if success_rate > 0.8 {
-            Ok(Decision {
-                tool: Tool::DirectAnswer,
-                reasoning: "Similar past queries succeeded - using similar strategy".to_string(),
-                confidence: 0.9,
-            })
-        } else if success_rate > 0.5 {
-            Ok(Decision {
-                tool: Tool::RefinedSearch,
-                reasoning: "Mixed results from similar queries - refining approach".to_string(),
-                confidence: 0.7,
-            })
-        } else {
-            Ok(Decision {
-                tool: Tool::SemanticSearch,
-                reasoning: "Similar queries had low success - trying new approach".to_string(),
-                confidence: 0.5,
-            })
-        }
+        // Final-answer step from whatever was observed
+        state.trace.push(format!(
+            "Stopped ({}); asking for a final answer",
+            stop.as_str()
+        ));
+        conversation.push(ChatMessage::user(prompt_templates::render(
+            prompt_templates::AGENT_FINAL,
+            Some(provider.model_name()),
+            &[],
+        )?));
+        let limit = time_budget
+            .saturating_sub(started.elapsed())
+            .max(FINAL_ANSWER_GRACE);
+        let answer = tokio::time::timeout(limit, provider.chat_with_usage(&conversation, &config))
+            .await
+            .unwrap_or_else(|_| {
+                Err(LLMError::GenerationFailed(format!(
+                    "no final answer within {} ms",
+                    limit.as_millis()
+                )))
+            });
+        let answer = match answer {
+            Ok(answer) => {
+                state.tokens += structured::tokens_used(answer.usage, &conversation, &answer.text);
+                answer.text
+            }
+            Err(e) => {
+                warn!(error = %e, "Final answer step failed");
+                state.trace.push(format!(
+                    "Final answer failed ({}); answering from observed passages",
+                    e
+                ));
+                fallback_answer(&state.passages)
+            }
+        };
+        Ok((answer, stop, state))
+    }
+
+    /// Run one action. Returns the observation and, for searches, how many
+    /// passages were new
+    async fn act(
+        &self,
+        tool: &Tool,
+        input: &Value,
+        registry: &ToolRegistry,
+        passages: &mut Vec<ContextChunk>,
+    ) -> (String, Option<usize>) {
+        let tool_type = match tool {
+            Tool::SemanticSearch | Tool::RefinedSearch => {
+                let query = input["query"].as_str().unwrap_or_default();
+                let top_k = match tool {
+                    Tool::RefinedSearch => input["top_k"]
+                        .as_u64()
+                        .map_or(REFINED_TOP_K, |k| k as usize)
+                        .clamp(1, MAX_REFINED_TOP_K),
+                    _ => SEARCH_TOP_K,
+                };
+                return match self.rag_pipeline.retrieve(query, top_k).await {
+                    Ok(hits) => {
+                        let (observation, new) = observe_passages(hits, passages);
+                        (observation, Some(new))
+                    }
+                    Err(e) => (format!("Search failed: {}", e), Some(0)),
+                };
+            }
+            Tool::Calculator => ToolType::Calculator,
+            Tool::UrlFetch => ToolType::URLFetch,
+            Tool::ReflectOnHistory | Tool::DirectAnswer => {
+                return (format!("{} is not an action", tool.action_name()), None)
+            }
+        };
+        let observation =
+            match ToolExecutor::execute_with_arguments(registry, &tool_type, input).await {
+                Ok(result) => result.result,
+                Err(e) => format!("Error: {}", e),
+            };
+        (observation, None)
     }
 
-    /// Calculate success rate from episodes
+    /// Calculate success rate from episodes: the mean grounding score,
+    /// with episodes recorded without one counting as 0 or 1
     fn calculate_success_rate(&self, episodes: &[Episode]) -> f32 {
         if episodes.is_empty() {
             return 0.0;
         }
-        let successes = episodes.iter().filter(|e| e.success).count();
-        successes as f32 / episodes.len() as f32
+        let total: f32 = episodes
+            .iter()
+            .map(|e| {
+                e.grounding_score
+                    .unwrap_or(if e.success { 1.0 } else { 0.0 })
+            })
+            .sum();
+        total / episodes.len() as f32
     }
+}
+
+/// `{"thought", "action", "input"}` with the input schema of each action
+fn step_schema(registry: &ToolRegistry) -> Value {
+    let options: Vec<Value> = Tool::ACTIONS
+        .iter()
+        .map(|tool| {
+            json!({
+                "type": "object",
+                "properties": {
+                    "thought": { "type": "string" },
+                    "action": { "const": tool.action_name() },
+                    "input": tool.input_schema(registry)
+                },
+                "required": ["thought", "action", "input"],
+                "additionalProperties": false
+            })
+        })
+        .collect();
+    json!({ "oneOf": options })
+}
 
-    /// Determine top_k based on decision confidence
-    fn determine_top_k(&self, decision: &Decision) -> usize {
-        match decision.tool {
-            Tool::DirectAnswer => 3,
-            Tool::RefinedSearch => 5,
-            Tool::SemanticSearch => 7,
-            Tool::ReflectOnHistory => 3,
+/// Number new hits after the passages seen so far and describe them; hits
+/// already seen are referred to by number. Returns how many were new
+fn observe_passages(hits: Vec<ContextChunk>, passages: &mut Vec<ContextChunk>) -> (String, usize) {
+    if hits.is_empty() {
+        return ("No passages found.".to_string(), 0);
+    }
+    let mut shown = Vec::new();
+    let mut repeated = Vec::new();
+    for hit in hits {
+        match passages.iter().position(|p| p.chunk_id == hit.chunk_id) {
+            Some(i) => repeated.push(format!("[{}]", i + 1)),
+            None => {
+                shown.push(format!(
+                    "[{}] From {}: {}",
+                    passages.len() + 1,
+                    hit.document_id,
+                    preview(&hit.content, PASSAGE_PREVIEW_CHARS)
+                ));
+                passages.push(hit);
+            }
         }
     }
+    let new = shown.len();
+    let mut observation = if shown.is_empty() {
+        "No new passages.".to_string()
+    } else {
+        shown.join("\n")
+    };
+    if !repeated.is_empty() {
+        observation.push_str(&format!("\nAlready seen: {}", repeated.join(", ")));
+    }
+    (observation, new)
+}
+
+fn preview(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
 }
 
+/// An answer succeeded when it is grounded in its context and did not
+/// abstain. Without a grounding check any non-empty answer counts.
+/// Answer quoting the first observed passages, numbered as in the
+/// observations, for when the model gives none
+fn fallback_answer(passages: &[ContextChunk]) -> String {
+    if passages.is_empty() {
+        return "No answer could be generated and no passages were found.".to_string();
+    }
+    let quoted = passages
+        .iter()
+        .take(FALLBACK_PASSAGES)
+        .enumerate()
+        .map(|(i, passage)| {
+            format!(
+                "[{}] {}",
+                i + 1,
+                preview(&passage.content, PASSAGE_PREVIEW_CHARS)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "No answer could be generated; the most relevant passages found:\n{}",
+        quoted
+    )
+}
+
+fn answer_succeeded(answer: &str, grounding: Option<&GroundingReport>) -> bool {
+    match grounding {
+        Some(grounding) => !grounding.abstained && grounding.score >= SUCCESS_GROUNDING,
+        None => !answer.trim().is_empty(),
+    }
+}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::llm_settings::LlmConfig;
+    use crate::memory::{
+        Completion, GroundingConfig, GroundingMethod, LLMError, LLMProvider, RagConfig, TokenUsage,
+        VectorRecord, VectorStore,
+    };
+    use std::sync::Mutex;
+
+    /// Memory over a temporary database that lives as long as the file
+    fn agent_memory() -> (Arc<AgentMemoryLayer>, tempfile::NamedTempFile) {
+        let db = tempfile::NamedTempFile::new().unwrap();
+        let memory = Arc::new(
+            AgentMemoryLayer::new(
+                "test".to_string(),
+                "test".to_string(),
+                db.path().to_path_buf(),
+                std::sync::Arc::new(tokio::sync::RwLock::new(
+                    crate::memory::VectorStore::with_defaults().unwrap(),
+                )),
+                std::sync::Arc::new(crate::embedder::EmbeddingService::new(
+                    crate::embedder::EmbeddingConfig::default(),
+                )),
+            )
+            .unwrap(),
+        );
+        (memory, db)
+    }
 
     #[test]
     fn test_calculate_success_rate() {
@@ -288,20 +801,7 @@ mod tests {
                 std::sync::Arc::new(MockLLM),
                 Default::default(),
             )),
-            Arc::new(
-                AgentMemoryLayer::new(
-                    "test".to_string(),
-                    "test".to_string(),
-                    tempfile::NamedTempFile::new().unwrap().path().to_path_buf(),
-                    std::sync::Arc::new(tokio::sync::RwLock::new(
-                        crate::memory::VectorStore::with_defaults().unwrap(),
-                    )),
-                    std::sync::Arc::new(crate::embedder::EmbeddingService::new(
-                        crate::embedder::EmbeddingConfig::default(),
-                    )),
-                )
-                .unwrap(),
-            ),
+            agent_memory().0,
         );
 
         let episodes = vec![
@@ -312,6 +812,7 @@ mod tests {
                 response: "answer".to_string(),
                 context_chunks_used: 3,
                 success: true,
+                grounding_score: None,
                 created_at: 0,
             },
             Episode {
@@ -321,12 +822,20 @@ mod tests {
                 response: "answer".to_string(),
                 context_chunks_used: 3,
                 success: false,
+                grounding_score: None,
                 created_at: 0,
             },
         ];
 
         let rate = engine.calculate_success_rate(&episodes);
         assert!((rate - 0.5).abs() < 0.01);
+
+        // Grounding scores take precedence over the success flag
+        let mut graded = episodes.clone();
+        graded[0].grounding_score = Some(0.8);
+        graded[1].grounding_score = Some(0.4);
+        let rate = engine.calculate_success_rate(&graded);
+        assert!((rate - 0.6).abs() < 0.01);
     }
 
     struct MockLLM;
@@ -347,4 +856,276 @@ mod tests {
             "mock"
         }
     }
+
+    /// Replies in order, recording the conversations it was sent. Call n
+    /// reports 100 * n tokens of usage
+    struct Scripted {
+        replies: Mutex<Vec<&'static str>>,
+        seen: Mutex<Vec<Vec<ChatMessage>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for Scripted {
+        async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+            self.chat(&[ChatMessage::user(prompt)], &LlmConfig::default())
+                .await
+        }
+        async fn generate_with_config(
+            &self,
+            prompt: &str,
+            config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.chat(&[ChatMessage::user(prompt)], config).await
+        }
+        async fn chat(
+            &self,
+            messages: &[ChatMessage],
+            _config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.seen.lock().unwrap().push(messages.to_vec());
+            self.replies
+                .lock()
+                .unwrap()
+                .pop()
+                .map(str::to_string)
+                .ok_or_else(|| LLMError::GenerationFailed("script exhausted".into()))
+        }
+        async fn chat_with_usage(
+            &self,
+            messages: &[ChatMessage],
+            config: &LlmConfig,
+        ) -> Result<Completion, LLMError> {
+            let text = self.chat(messages, config).await?;
+            let total_tokens = 100 * self.seen.lock().unwrap().len() as u64;
+            Ok(Completion {
+                text,
+                usage: Some(TokenUsage {
+                    total_tokens,
+                    ..TokenUsage::default()
+                }),
+            })
+        }
+        async fn chat_json_with_usage(
+            &self,
+            messages: &[ChatMessage],
+            _schema: &Value,
+            config: &LlmConfig,
+        ) -> Result<Completion, LLMError> {
+            self.chat_with_usage(messages, config).await
+        }
+        fn model_name(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    /// Engine over a knowledge base of `passages`, driven by `replies`
+    async fn scripted_engine(
+        passages: &[&str],
+        replies: &[&'static str],
+    ) -> (DecisionEngine, Arc<Scripted>, tempfile::NamedTempFile) {
+        let mut store = VectorStore::with_defaults().unwrap();
+        for (i, text) in passages.iter().enumerate() {
+            store
+                .add_record(VectorRecord::new(
+                    format!("c{}", i),
+                    "geo".to_string(),
+                    text.to_string(),
+                    crate::embedder::embed(text),
+                    i,
+                    8,
+                    "geo.txt".to_string(),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+        let provider = Arc::new(Scripted {
+            replies: Mutex::new(replies.iter().rev().copied().collect()),
+            seen: Mutex::new(Vec::new()),
+        });
+        let pipeline = RagQueryPipeline::new(
+            Arc::new(crate::embedder::EmbeddingService::new(
+                crate::embedder::EmbeddingConfig::default(),
+            )),
+            Arc::new(tokio::sync::RwLock::new(store)),
+            provider.clone(),
+            RagConfig {
+                grounding: GroundingConfig {
+                    method: GroundingMethod::Lexical,
+                    support_threshold: None,
+                    abstain_below: None,
+                },
+                ..RagConfig::default()
+            },
+        );
+        let (memory, db) = agent_memory();
+        let engine = DecisionEngine::new(Arc::new(pipeline), memory).with_budget(AgentBudget {
+            max_steps: 4,
+            max_tokens: 100_000,
+            max_duration_ms: 30_000,
+        });
+        (engine, provider, db)
+    }
+
+    #[tokio::test]
+    async fn test_react_loop_observes_then_answers() {
+        let (engine, provider, _db) = scripted_engine(
+            &["Paris is the capital of France."],
+            &[
+                r#"{"thought": "Look it up", "action": "semantic_search", "input": {"query": "capital of France"}}"#,
+                r#"{"thought": "Check the arithmetic", "action": "calculator", "input": {"expression": "6 * 7"}}"#,
+                r#"{"thought": "Done", "action": "final_answer", "input": {"answer": "Paris is the capital of France [1]."}}"#,
+            ],
+        )
+        .await;
+
+        let result = engine
+            .execute_query("What is the capital of France?", None)
+            .await
+            .unwrap();
+        assert_eq!(result.stop_reason, StopReason::FinalAnswer);
+        assert_eq!(result.answer, "Paris is the capital of France [1].");
+        assert_eq!(result.steps_executed, 3);
+        assert_eq!(result.steps[0].tool, Tool::SemanticSearch);
+        assert!(result.steps[0]
+            .observation
+            .starts_with("[1] From geo: Paris"));
+        assert!(result.steps[1].observation.contains("42"));
+        assert!(result.success);
+        assert!(result.grounding.as_ref().unwrap().score > 0.5);
+        assert!(result.tokens_used > 0);
+
+        // Each observation goes back to the model before the next step
+        let seen = provider.seen.lock().unwrap();
+        assert_eq!(seen.len(), 3);
+        assert!(seen[1]
+            .last()
+            .unwrap()
+            .content
+            .starts_with("Observation: [1]"));
+
+        // The trace is persisted with the episode
+        assert!(result
+            .reasoning_trace
+            .iter()
+            .any(|line| line == "Step 1: Thought: Look it up"));
+        let stored = engine
+            .agent_memory
+            .get_episode_trace(&result.episode_id)
+            .unwrap();
+        assert_eq!(stored, Some(result.reasoning_trace.clone()));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_sum_the_usage_of_every_attempt() {
+        let (engine, provider, _db) = scripted_engine(
+            &["Paris is the capital of France."],
+            &[
+                "Let me think about that.",
+                r#"{"thought": "Known", "action": "final_answer", "input": {"answer": "Paris."}}"#,
+            ],
+        )
+        .await;
+
+        let result = engine
+            .execute_query("Capital of France?", None)
+            .await
+            .unwrap();
+        assert_eq!(result.stop_reason, StopReason::FinalAnswer);
+        assert_eq!(provider.seen.lock().unwrap().len(), 2);
+        // The repair reported 200 tokens and the first attempt 100
+        assert_eq!(result.tokens_used, 300);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_action_stops_with_final_answer() {
+        let (engine, _provider, _db) = scripted_engine(
+            &["Paris is the capital of France."],
+            &[
+                r#"{"thought": "Search", "action": "semantic_search", "input": {"query": "capital"}}"#,
+                r#"{"thought": "Search again", "action": "semantic_search", "input": {"query": "Capital"}}"#,
+                "Paris [1].",
+            ],
+        )
+        .await;
+
+        let result = engine
+            .execute_query("Capital of France?", None)
+            .await
+            .unwrap();
+        assert_eq!(result.stop_reason, StopReason::LoopDetected);
+        assert_eq!(result.steps_executed, 1);
+        assert_eq!(result.answer, "Paris [1].");
+    }
+
+    #[tokio::test]
+    async fn test_budgets_force_a_final_answer() {
+        let (engine, provider, _db) = scripted_engine(
+            &["Paris is the capital of France."],
+            &[
+                r#"{"thought": "Compute", "action": "calculator", "input": {"expression": "1 + 1"}}"#,
+                r#"{"thought": "Compute", "action": "calculator", "input": {"expression": "2 + 2"}}"#,
+                "2 and 4.",
+            ],
+        )
+        .await;
+        let budget = AgentBudget {
+            max_steps: 2,
+            ..engine.budget()
+        };
+        let engine = engine.with_budget(budget);
+        let result = engine.execute_query("Add things", None).await.unwrap();
+        assert_eq!(result.stop_reason, StopReason::MaxSteps);
+        assert_eq!(result.steps_executed, 2);
+        assert_eq!(result.answer, "2 and 4.");
+        assert_eq!(
+            provider.seen.lock().unwrap()[2].last().unwrap().content,
+            prompt_templates::render(prompt_templates::AGENT_FINAL, None, &[]).unwrap()
+        );
+
+        let (engine, _provider, _db) = scripted_engine(
+            &[],
+            &[
+                r#"{"thought": "Compute", "action": "calculator", "input": {"expression": "1 + 1"}}"#,
+                "2.",
+            ],
+        )
+        .await;
+        let budget = AgentBudget {
+            max_tokens: 1,
+            ..engine.budget()
+        };
+        let engine = engine.with_budget(budget);
+        let result = engine.execute_query("One plus one", None).await.unwrap();
+        assert_eq!(result.stop_reason, StopReason::TokenBudget);
+        assert_eq!(result.steps_executed, 1);
+        assert_eq!(result.answer, "2.");
+        assert!(result.grounding.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_final_answer_falls_back_to_passages() {
+        let (engine, _provider, _db) = scripted_engine(
+            &["Paris is the capital of France."],
+            &[
+                r#"{"thought": "Search", "action": "semantic_search", "input": {"query": "capital"}}"#,
+            ],
+        )
+        .await;
+
+        // The script runs out at the next step and again at the final answer
+        let result = engine
+            .execute_query("Capital of France?", None)
+            .await
+            .unwrap();
+        assert_eq!(result.stop_reason, StopReason::ModelError);
+        assert!(result
+            .answer
+            .contains("[1] Paris is the capital of France."));
+        assert!(result
+            .reasoning_trace
+            .iter()
+            .any(|line| line.starts_with("Final answer failed")));
+        assert!(!result.episode_id.is_empty());
+    }
 }
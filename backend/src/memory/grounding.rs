@@ -0,0 +1,503 @@
+// src/memory/grounding.rs
+// Answer grounding check: after generation every answer sentence (a claim)
+// is scored against the context the model was shown, by lexical overlap,
+// embedding similarity or an LLM judge. The share of supported claims is the
+// grounding score; when abstention is configured, answers scoring below the
+// threshold are replaced with a "not found" answer.
+
+use crate::db::prompt_templates;
+use crate::embedder::{similarity::cosine_similarity, EmbeddingService};
+use crate::memory::citations::{content_words, strip_markers, CitationSource};
+use crate::memory::context_budget::split_sentences;
+use crate::memory::llm_provider::LLMProvider;
+use crate::monitoring::{
+    GROUNDING_ABSTENTIONS_TOTAL, GROUNDING_SCORE, GROUNDING_UNSUPPORTED_CLAIMS_TOTAL,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Answer used in place of a poorly grounded one
+pub const ABSTENTION_ANSWER: &str = "I couldn't find this in the knowledge base.";
+
+/// Sentences with fewer content words ("Sure.", "In summary:") are not
+/// claims and are not checked
+const MIN_CLAIM_WORDS: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroundingMethod {
+    Off,
+    /// Content-word overlap with the best one or two context sentences
+    #[default]
+    Lexical,
+    /// Cosine similarity of sentence embeddings
+    Embedding,
+    /// NLI-style judgement by the answering model
+    Llm,
+}
+
+impl GroundingMethod {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" | "none" | "false" | "0" => Some(Self::Off),
+            "lexical" | "overlap" => Some(Self::Lexical),
+            "embedding" | "embeddings" => Some(Self::Embedding),
+            "llm" | "nli" | "judge" => Some(Self::Llm),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Lexical => "lexical",
+            Self::Embedding => "embedding",
+            Self::Llm => "llm",
+        }
+    }
+
+    /// Claim score at which a sentence counts as supported
+    fn default_threshold(self) -> f32 {
+        match self {
+            Self::Embedding => 0.75,
+            _ => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroundingConfig {
+    pub method: GroundingMethod,
+    /// Per-claim support threshold; None uses the method's default
+    pub support_threshold: Option<f32>,
+    /// Abstain when the grounding score is below this; None never abstains
+    pub abstain_below: Option<f32>,
+}
+
+impl Default for GroundingConfig {
+    fn default() -> Self {
+        Self {
+            method: GroundingMethod::Lexical,
+            support_threshold: None,
+            abstain_below: None,
+        }
+    }
+}
+
+impl GroundingConfig {
+    /// GROUNDING_CHECK (off|lexical|embedding|llm),
+    /// GROUNDING_SUPPORT_THRESHOLD and GROUNDING_ABSTAIN_BELOW
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let fraction = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<f32>().ok())
+                .map(|v| v.clamp(0.0, 1.0))
+        };
+        Self {
+            method: std::env::var("GROUNDING_CHECK")
+                .ok()
+                .and_then(|v| GroundingMethod::parse(&v))
+                .unwrap_or(defaults.method),
+            support_threshold: fraction("GROUNDING_SUPPORT_THRESHOLD"),
+            abstain_below: fraction("GROUNDING_ABSTAIN_BELOW"),
+        }
+    }
+
+    fn threshold(&self, method: GroundingMethod) -> f32 {
+        self.support_threshold
+            .unwrap_or_else(|| method.default_threshold())
+    }
+}
+
+/// One answer sentence and the best evidence found for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimGrounding {
+    pub sentence: String,
+    /// Character offsets of the sentence in the answer
+    pub start: usize,
+    pub end: usize,
+    pub score: f32,
+    pub supported: bool,
+    pub evidence_chunk_id: Option<String>,
+    pub evidence: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroundingReport {
+    /// Method actually used (a missing embedder or failed judge falls back
+    /// to lexical)
+    pub method: GroundingMethod,
+    /// Share of claims supported; 1.0 for answers without claims
+    pub score: f32,
+    pub claims: Vec<ClaimGrounding>,
+    pub unsupported_claims: usize,
+    /// The answer was replaced with `ABSTENTION_ANSWER`
+    pub abstained: bool,
+}
+
+/// A sentence of the context, with the chunk it came from
+struct Evidence<'a> {
+    chunk_id: &'a str,
+    text: String,
+}
+
+/// Check `answer` against the context blocks it was generated from.
+/// Returns None when the check is off. Judging with the LLM needs `judge`;
+/// embedding similarity needs `embedder`.
+pub async fn check(
+    answer: &str,
+    sources: &[CitationSource],
+    config: &GroundingConfig,
+    embedder: Option<&EmbeddingService>,
+    judge: Option<&dyn LLMProvider>,
+) -> Option<GroundingReport> {
+    if config.method == GroundingMethod::Off {
+        return None;
+    }
+    let mut claims = claims(answer);
+    let evidence = evidence(sources);
+
+    let mut method = config.method;
+    let scored = match (method, embedder, judge) {
+        (GroundingMethod::Embedding, Some(embedder), _) => {
+            score_embedding(&mut claims, &evidence, embedder).await;
+            true
+        }
+        (GroundingMethod::Llm, _, Some(judge)) => {
+            match score_llm(&mut claims, sources, &evidence, judge).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Grounding judge failed, falling back to lexical: {}", e);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+    if !scored {
+        method = GroundingMethod::Lexical;
+        score_lexical(&mut claims, &evidence);
+    }
+
+    let threshold = config.threshold(method);
+    for claim in &mut claims {
+        claim.supported = claim.score >= threshold;
+        if !claim.supported {
+            claim.evidence = None;
+            claim.evidence_chunk_id = None;
+        }
+    }
+    let report = report(method, claims, config.abstain_below);
+
+    GROUNDING_SCORE
+        .with_label_values(&[method.as_str()])
+        .observe(report.score as f64);
+    GROUNDING_UNSUPPORTED_CLAIMS_TOTAL.inc_by(report.unsupported_claims as u64);
+    if report.abstained {
+        GROUNDING_ABSTENTIONS_TOTAL.inc();
+    }
+    debug!(
+        method = method.as_str(),
+        score = report.score,
+        claims = report.claims.len(),
+        unsupported = report.unsupported_claims,
+        abstained = report.abstained,
+        "Answer grounding checked"
+    );
+    Some(report)
+}
+
+fn report(
+    method: GroundingMethod,
+    claims: Vec<ClaimGrounding>,
+    abstain_below: Option<f32>,
+) -> GroundingReport {
+    let unsupported_claims = claims.iter().filter(|c| !c.supported).count();
+    let score = if claims.is_empty() {
+        1.0
+    } else {
+        1.0 - unsupported_claims as f32 / claims.len() as f32
+    };
+    GroundingReport {
+        method,
+        score,
+        claims,
+        unsupported_claims,
+        abstained: abstain_below.is_some_and(|threshold| score < threshold),
+    }
+}
+
+/// Answer sentences worth checking, citation markers removed
+fn claims(answer: &str) -> Vec<ClaimGrounding> {
+    let mut claims = Vec::new();
+    let mut chars = 0;
+    for sentence in split_sentences(answer) {
+        let len = sentence.chars().count();
+        let text = strip_markers(sentence).trim().to_string();
+        if content_words(&text).len() >= MIN_CLAIM_WORDS {
+            claims.push(ClaimGrounding {
+                sentence: text,
+                start: chars,
+                end: chars + sentence.trim_end().chars().count(),
+                score: 0.0,
+                supported: false,
+                evidence_chunk_id: None,
+                evidence: None,
+            });
+        }
+        chars += len;
+    }
+    claims
+}
+
+/// Context sentences, plus each pair of adjacent sentences so claims that
+/// merge two sentences can still match
+fn evidence(sources: &[CitationSource]) -> Vec<Evidence<'_>> {
+    let mut evidence = Vec::new();
+    for chunk in sources.iter().flat_map(|s| &s.chunks) {
+        let sentences: Vec<&str> = split_sentences(&chunk.content)
+            .into_iter()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+        for (i, sentence) in sentences.iter().enumerate() {
+            evidence.push(Evidence {
+                chunk_id: &chunk.chunk_id,
+                text: sentence.to_string(),
+            });
+            if let Some(next) = sentences.get(i + 1) {
+                evidence.push(Evidence {
+                    chunk_id: &chunk.chunk_id,
+                    text: format!("{} {}", sentence, next),
+                });
+            }
+        }
+    }
+    evidence
+}
+
+fn set_best(claim: &mut ClaimGrounding, score: f32, evidence: &Evidence) {
+    if score > claim.score || claim.evidence.is_none() {
+        claim.score = score;
+        claim.evidence_chunk_id = Some(evidence.chunk_id.to_string());
+        claim.evidence = Some(evidence.text.clone());
+    }
+}
+
+fn score_lexical(claims: &mut [ClaimGrounding], evidence: &[Evidence]) {
+    let evidence_words: Vec<_> = evidence.iter().map(|e| content_words(&e.text)).collect();
+    for claim in claims {
+        let words = content_words(&claim.sentence);
+        for (e, e_words) in evidence.iter().zip(&evidence_words) {
+            let score = words.intersection(e_words).count() as f32 / words.len().max(1) as f32;
+            set_best(claim, score, e);
+        }
+    }
+}
+
+async fn score_embedding(
+    claims: &mut [ClaimGrounding],
+    evidence: &[Evidence<'_>],
+    embedder: &EmbeddingService,
+) {
+    let mut vectors = Vec::with_capacity(evidence.len());
+    for e in evidence {
+        vectors.push(embedder.embed_text(&e.text).await);
+    }
+    for claim in claims {
+        let claim_vector = embedder.embed_text(&claim.sentence).await;
+        for (e, vector) in evidence.iter().zip(&vectors) {
+            set_best(claim, cosine_similarity(&claim_vector, vector), e);
+        }
+    }
+}
+
+/// Ask the model to label every claim SUPPORTED or UNSUPPORTED. Evidence
+/// for supported claims is the lexically closest context sentence.
+async fn score_llm(
+    claims: &mut [ClaimGrounding],
+    sources: &[CitationSource],
+    evidence: &[Evidence<'_>],
+    judge: &dyn LLMProvider,
+) -> Result<(), String> {
+    if claims.is_empty() {
+        return Ok(());
+    }
+    let context = sources
+        .iter()
+        .map(|s| {
+            let text: Vec<&str> = s.chunks.iter().map(|c| c.content.as_str()).collect();
+            format!("[{}] {}", s.number, text.join("\n\n"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let numbered = claims
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{}. {}", i + 1, c.sentence))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let prompt = prompt_templates::render(
+        prompt_templates::GROUNDING_JUDGE,
+        Some(judge.model_name()),
+        &[("context", &context), ("claims", &numbered)],
+    )
+    .map_err(|e| e.to_string())?;
+    let reply = judge.generate(&prompt).await.map_err(|e| e.to_string())?;
+    let verdicts = parse_verdicts(&reply, claims.len());
+    if verdicts.iter().all(Option::is_none) {
+        return Err(format!("unparseable judge reply: {:.80}", reply));
+    }
+
+    score_lexical(claims, evidence);
+    for (claim, verdict) in claims.iter_mut().zip(verdicts) {
+        // Claims the judge skipped keep their lexical score
+        if let Some(supported) = verdict {
+            claim.score = if supported { 1.0 } else { 0.0 };
+        }
+    }
+    Ok(())
+}
+
+/// "<n>: SUPPORTED" lines, in any order; claims without a line are None
+fn parse_verdicts(reply: &str, claims: usize) -> Vec<Option<bool>> {
+    let mut verdicts = vec![None; claims];
+    for line in reply.lines() {
+        let line = line.trim().trim_start_matches(['-', '*', ' ']);
+        let Some((number, verdict)) = line.split_once([':', '.', ')']) else {
+            continue;
+        };
+        let Ok(n) = number.trim().parse::<usize>() else {
+            continue;
+        };
+        let verdict = verdict.trim().to_ascii_uppercase();
+        let supported = if verdict.starts_with("UNSUPPORTED") || verdict.starts_with("NOT") {
+            false
+        } else if verdict.starts_with("SUPPORTED") {
+            true
+        } else {
+            continue;
+        };
+        if (1..=claims).contains(&n) {
+            verdicts[n - 1] = Some(supported);
+        }
+    }
+    verdicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::citations::SourceChunk;
+    use crate::memory::llm_provider::LLMError;
+
+    fn sources() -> Vec<CitationSource> {
+        vec![CitationSource {
+            number: 1,
+            document_id: "cache.md".into(),
+            source: "Cache".into(),
+            chunks: vec![SourceChunk {
+                chunk_id: "cache.md#0".into(),
+                content: "The cache is flushed every ten minutes by the scheduler. Entries larger than one megabyte are never cached."
+                    .into(),
+            }],
+        }]
+    }
+
+    struct Judge(&'static str);
+
+    #[async_trait::async_trait]
+    impl LLMProvider for Judge {
+        async fn generate(&self, _prompt: &str) -> Result<String, LLMError> {
+            Ok(self.0.to_string())
+        }
+        async fn generate_with_config(
+            &self,
+            prompt: &str,
+            _config: &crate::db::llm_settings::LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.generate(prompt).await
+        }
+        fn model_name(&self) -> &str {
+            "judge"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lexical_flags_unsupported_claims() {
+        let answer = "The scheduler flushes the cache every ten minutes [1]. Cached entries are encrypted with AES keys rotated daily.";
+        let report = check(answer, &sources(), &GroundingConfig::default(), None, None)
+            .await
+            .unwrap();
+        assert_eq!(report.method, GroundingMethod::Lexical);
+        assert_eq!(report.claims.len(), 2);
+        assert!(report.claims[0].supported);
+        assert_eq!(
+            report.claims[0].evidence_chunk_id.as_deref(),
+            Some("cache.md#0")
+        );
+        assert!(!report.claims[1].supported);
+        assert_eq!(report.unsupported_claims, 1);
+        assert_eq!(report.score, 0.5);
+        assert!(!report.abstained);
+    }
+
+    #[tokio::test]
+    async fn test_abstains_below_threshold_and_skips_non_claims() {
+        let config = GroundingConfig {
+            abstain_below: Some(0.6),
+            ..GroundingConfig::default()
+        };
+        let answer = "Sure. Pluto's moons orbit in resonance with each other.";
+        let report = check(answer, &sources(), &config, None, None)
+            .await
+            .unwrap();
+        assert_eq!(report.claims.len(), 1);
+        assert_eq!(report.score, 0.0);
+        assert!(report.abstained);
+
+        let off = GroundingConfig {
+            method: GroundingMethod::Off,
+            ..config
+        };
+        assert!(check(answer, &sources(), &off, None, None).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_llm_judge_verdicts_and_fallback() {
+        let config = GroundingConfig {
+            method: GroundingMethod::Llm,
+            ..GroundingConfig::default()
+        };
+        let answer =
+            "The cache is flushed by the scheduler. Large entries are kept in memory forever.";
+        let judge = Judge("1: SUPPORTED\n2: UNSUPPORTED");
+        let report = check(answer, &sources(), &config, None, Some(&judge))
+            .await
+            .unwrap();
+        assert_eq!(report.method, GroundingMethod::Llm);
+        assert_eq!(
+            report
+                .claims
+                .iter()
+                .map(|c| c.supported)
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+
+        let rambling = Judge("I think these look fine overall.");
+        let report = check(answer, &sources(), &config, None, Some(&rambling))
+            .await
+            .unwrap();
+        assert_eq!(report.method, GroundingMethod::Lexical);
+    }
+
+    #[test]
+    fn test_parse_verdicts() {
+        let verdicts = parse_verdicts("- 2) unsupported\n1. Supported\n9: SUPPORTED\nnoise", 3);
+        assert_eq!(verdicts, vec![Some(true), Some(false), None]);
+        assert_eq!(GroundingMethod::parse("NLI"), Some(GroundingMethod::Llm));
+    }
+}
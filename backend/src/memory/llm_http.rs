@@ -0,0 +1,291 @@
+// src/memory/llm_http.rs
+// HTTP plumbing shared by the hosted providers (OpenAI-compatible, Anthropic):
+// retry with backoff, status classification, usage accounting and
+// line-oriented response streaming
+
+use crate::memory::llm_provider::{LLMError, TokenUsage, ToolSpec};
+use futures_util::Stream;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+/// Health probes give up quickly so a hung backend is marked down
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Upper bound on a server-supplied Retry-After
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Outcome of one HTTP attempt
+pub(crate) enum Attempt<T> {
+    Done(T),
+    Retry(LLMError, Option<Duration>),
+    Fail(LLMError),
+}
+
+/// Retries after 429, 5xx and connection errors with exponential backoff;
+/// a Retry-After header from the server replaces the computed delay
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run `attempt` until it is done, fails for good or retries run out.
+    /// Returns the value and the number of retries it took
+    pub(crate) async fn run<T, F, Fut>(
+        &self,
+        model: &str,
+        mut attempt: F,
+    ) -> Result<(T, u32), LLMError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Attempt<T>>,
+    {
+        let mut retries = 0;
+        loop {
+            let (err, retry_after) = match attempt().await {
+                Attempt::Done(value) => return Ok((value, retries)),
+                Attempt::Fail(err) => return Err(err),
+                Attempt::Retry(err, retry_after) => (err, retry_after),
+            };
+            if retries >= self.max_retries {
+                return Err(err);
+            }
+            let delay = retry_after.unwrap_or(self.backoff * 2u32.pow(retries));
+            warn!(
+                model,
+                attempt = retries + 1,
+                delay_ms = delay.as_millis() as u64,
+                "Retrying LLM request: {}",
+                err
+            );
+            tokio::time::sleep(delay).await;
+            retries += 1;
+        }
+    }
+}
+
+pub(crate) fn build_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+/// Retry-After in whole seconds, capped
+pub(crate) fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(|secs| Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+/// Map a non-success status to an attempt outcome: auth errors are config
+/// errors, 429 and 5xx (including 529 overloaded) are retried
+pub(crate) fn classify_status<T>(
+    status: u16,
+    message: String,
+    retry_after: Option<Duration>,
+) -> Attempt<T> {
+    let message = format!("HTTP {}: {}", status, message);
+    match status {
+        401 | 403 => Attempt::Fail(LLMError::ConfigError(message)),
+        429 => Attempt::Retry(LLMError::RateLimited(message), retry_after),
+        500..=599 => Attempt::Retry(LLMError::GenerationFailed(message), retry_after),
+        _ => Attempt::Fail(LLMError::GenerationFailed(message)),
+    }
+}
+
+/// Send a cheap GET (model listing) and require a success status
+pub(crate) async fn probe(request: reqwest::RequestBuilder, target: &str) -> Result<(), LLMError> {
+    let response = request
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| LLMError::ConnectionFailed(format!("Cannot reach {}: {}", target, e)))?;
+    let status = response.status();
+    match status.as_u16() {
+        200..=299 => Ok(()),
+        401 | 403 => Err(LLMError::ConfigError(format!(
+            "{} rejected credentials (HTTP {})",
+            target, status
+        ))),
+        _ => Err(LLMError::ConnectionFailed(format!(
+            "{} answered HTTP {}",
+            target, status
+        ))),
+    }
+}
+
+/// Tool definitions in the `{"type": "function", "function": {..}}` shape
+/// both OpenAI-compatible servers and Ollama accept
+pub(crate) fn function_tools(tools: &[ToolSpec]) -> Vec<serde_json::Value> {
+    tools
+        .iter()
+        .map(|tool| {
+            serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters
+                }
+            })
+        })
+        .collect()
+}
+
+/// Cumulative token usage of a provider. Clones share state, so a stream
+/// can record usage after the provider call has returned. The usage of a
+/// single call is returned with its reply, never read back from here
+#[derive(Clone)]
+pub(crate) struct UsageTracker {
+    provider: &'static str,
+    model: String,
+    total: Arc<Mutex<TokenUsage>>,
+}
+
+impl UsageTracker {
+    pub(crate) fn new(provider: &'static str, model: &str) -> Self {
+        Self {
+            provider,
+            model: model.to_string(),
+            total: Arc::new(Mutex::new(TokenUsage::default())),
+        }
+    }
+
+    /// Count the usage of a request in the total and llm_tokens_total
+    pub(crate) fn record(&self, usage: TokenUsage) {
+        self.total.lock().unwrap().add(&usage);
+        for (kind, count) in [
+            ("prompt", usage.prompt_tokens),
+            ("completion", usage.completion_tokens),
+        ] {
+            crate::monitoring::LLM_TOKENS_TOTAL
+                .with_label_values(&[self.provider, &self.model, kind])
+                .inc_by(count);
+        }
+    }
+
+    pub(crate) fn total(&self) -> TokenUsage {
+        *self.total.lock().unwrap()
+    }
+}
+
+/// Lines of a streamed response body (NDJSON or server-sent events), without
+/// trailing CR/LF. Dropping the stream drops the response and closes the
+/// connection, which cancels the generation upstream
+pub(crate) fn lines(response: reqwest::Response) -> impl Stream<Item = Result<String, LLMError>> {
+    futures_util::stream::unfold(
+        (Some(response), Vec::<u8>::new()),
+        |(mut response, mut buf)| async move {
+            loop {
+                if let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buf.drain(..=pos).collect();
+                    let line = String::from_utf8_lossy(&line)
+                        .trim_end_matches(['\r', '\n'])
+                        .to_string();
+                    return Some((Ok(line), (response, buf)));
+                }
+                let Some(body) = response.as_mut() else {
+                    if buf.is_empty() {
+                        return None;
+                    }
+                    let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+                    buf.clear();
+                    return Some((Ok(line), (None, buf)));
+                };
+                match body.chunk().await {
+                    Ok(Some(bytes)) => buf.extend_from_slice(&bytes),
+                    Ok(None) => response = None,
+                    Err(e) => {
+                        let err = LLMError::ConnectionFailed(format!("stream interrupted: {}", e));
+                        return Some((Err(err), (None, Vec::new())));
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Payload of an SSE `data:` line; None for event names, comments and blanks
+pub(crate) fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim_start)
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Raw requests seen by the mock server: (head, body)
+    pub(crate) type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Scripted HTTP server: answers each request with the next
+    /// (status, extra headers, body) and records the requests
+    pub(crate) async fn serve(responses: Vec<(u16, &'static str, String)>) -> (String, Requests) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            for (status, headers, body) in responses {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    break;
+                };
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                // Read headers, then Content-Length bytes of body
+                let body_start = loop {
+                    let n = socket.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        break buf.len();
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break pos + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&buf[..body_start]).to_lowercase();
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .and_then(|value| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                while buf.len() < body_start + length {
+                    let n = socket.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                seen.lock().unwrap().push((
+                    head,
+                    String::from_utf8_lossy(&buf[body_start..]).to_string(),
+                ));
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        (format!("http://{}", addr), requests)
+    }
+}
@@ -2,10 +2,21 @@
 // LLM Provider abstraction - pluggable architecture
 // Default: Phi 3.5 via Ollama
 
+use crate::db::api_keys;
 use crate::db::llm_settings::{self, LlmConfig};
+use crate::db::param_hardware::{self, BackendType, HardwareParams};
+use crate::memory::anthropic_provider::{self, AnthropicProvider};
+use crate::memory::llm_http;
+use crate::memory::openai_provider::{self, OpenAIProvider};
+use futures_util::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use tracing::{debug, info, warn};
 
+/// Generated text in arrival order
+pub type TokenStream = Pin<Box<dyn Stream<Item = Result<String, LLMError>> + Send>>;
+
 /// LLM Provider trait - implement this to support new models
 #[async_trait::async_trait]
 pub trait LLMProvider: Send + Sync {
@@ -17,7 +28,269 @@ pub trait LLMProvider: Send + Sync {
         prompt: &str,
         config: &LlmConfig,
     ) -> Result<String, LLMError>;
+    /// Answer a conversation. Backends without a chat endpoint get the
+    /// messages rendered into a single prompt
+    async fn chat(&self, messages: &[ChatMessage], config: &LlmConfig) -> Result<String, LLMError> {
+        self.generate_with_config(&render_chat_prompt(messages), config)
+            .await
+    }
+    /// `chat` with the token usage the backend reported for this call
+    async fn chat_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        Ok(Completion {
+            text: self.chat(messages, config).await?,
+            usage: None,
+        })
+    }
+    /// Answer a conversation with JSON matching `schema`. Backends with a
+    /// JSON mode constrain decoding to the schema; the default relies on the
+    /// messages describing it. Callers still validate the reply
+    async fn chat_json(
+        &self,
+        messages: &[ChatMessage],
+        _schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        self.chat(messages, config).await
+    }
+    /// `chat_json` with the token usage the backend reported for this call
+    async fn chat_json_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        Ok(Completion {
+            text: self.chat_json(messages, schema, config).await?,
+            usage: None,
+        })
+    }
+    /// Answer a conversation, letting the model call one of `tools`
+    /// through the backend's native function calling. `None` means the
+    /// backend has none and the caller should fall back to a JSON protocol
+    async fn chat_tools(
+        &self,
+        _messages: &[ChatMessage],
+        _tools: &[ToolSpec],
+        _config: &LlmConfig,
+    ) -> Result<Option<ToolReply>, LLMError> {
+        Ok(None)
+    }
+    /// Stream generated text as it arrives. Dropping the stream cancels the
+    /// generation upstream. The default yields the whole answer at once
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<TokenStream, LLMError> {
+        let text = self.generate_with_config(prompt, config).await?;
+        Ok(Box::pin(futures_util::stream::once(
+            async move { Ok(text) },
+        )))
+    }
+    /// `generate_stream` with a handle that holds the stream's token usage
+    /// once the backend reports it, usually with the last chunk
+    async fn generate_stream_with_usage(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<(TokenStream, StreamUsage), LLMError> {
+        let stream = self.generate_stream(prompt, config).await?;
+        Ok((stream, StreamUsage::default()))
+    }
     fn model_name(&self) -> &str;
+    /// Cheap reachability check used by the router's background probes;
+    /// never called on the request path
+    async fn health_check(&self) -> Result<(), LLMError> {
+        Ok(())
+    }
+}
+
+/// Speaker of a chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    /// Output of a tool the assistant asked for
+    Tool,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Assistant => "assistant",
+            Self::Tool => "tool",
+        }
+    }
+}
+
+/// One turn of a conversation passed to `LLMProvider::chat`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    /// For tool messages: the id of the call this result answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// For assistant messages: the calls the following tool messages answer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            tool_call_id: None,
+            tool_calls: Vec::new(),
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    /// The assistant turn that asked for `tool_calls`; send it before their
+    /// results so each tool message answers a declared call
+    pub fn assistant_tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            tool_calls,
+            ..Self::new(Role::Assistant, content)
+        }
+    }
+
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+}
+
+/// A function the model may call; `parameters` is a JSON Schema
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// A call the model asked for. Arguments that were not valid JSON are kept
+/// as a string so validation can report them
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+impl ToolCall {
+    /// Arguments as the JSON text the model produced
+    pub fn arguments_json(&self) -> String {
+        match &self.arguments {
+            serde_json::Value::String(raw) => raw.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    /// One-line rendering for backends without native tool calls
+    pub fn describe(&self) -> String {
+        format!(
+            "Tool call {} ({}): {}",
+            self.name,
+            self.id,
+            self.arguments_json()
+        )
+    }
+}
+
+/// Reply to `LLMProvider::chat_tools`: text, tool calls, or both
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ToolReply {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// Render a conversation as a completion prompt for backends without a
+/// chat endpoint: one labelled block per message, ending with an open
+/// assistant turn
+pub fn render_chat_prompt(messages: &[ChatMessage]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        let label = match message.role {
+            Role::System => "System",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+            Role::Tool => "Tool result",
+        };
+        prompt.push_str(label);
+        prompt.push_str(": ");
+        prompt.push_str(message.content.trim());
+        for call in &message.tool_calls {
+            prompt.push('\n');
+            prompt.push_str(&call.describe());
+        }
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Assistant:");
+    prompt
+}
+
+/// Token counts reported by a provider for one request (or a running total)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// Text of one model call and the usage the backend reported for it. Usage
+/// travels with the reply so concurrent calls never see each other's counts
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    pub usage: Option<TokenUsage>,
+}
+
+/// Token usage of one stream, set when the backend reports it
+#[derive(Debug, Clone, Default)]
+pub struct StreamUsage(Arc<Mutex<Option<TokenUsage>>>);
+
+impl StreamUsage {
+    pub fn get(&self) -> Option<TokenUsage> {
+        *self.0.lock().unwrap()
+    }
+
+    pub(crate) fn set(&self, usage: TokenUsage) {
+        *self.0.lock().unwrap() = Some(usage);
+    }
 }
 
 /// Configuration for different LLM providers
@@ -29,8 +302,23 @@ pub enum LLMConfig {
     QwenOllama { ollama_url: String, model: String },
     /// Mistral 7B via Ollama
     MistralOllama { ollama_url: String, model: String },
-    /// OpenAI API (requires API key)
-    OpenAI { api_key: String, model: String },
+    /// OpenAI API or any server speaking /v1/chat/completions (vLLM,
+    /// llama.cpp server, LM Studio). The key may be empty for local servers
+    OpenAI {
+        api_key: String,
+        model: String,
+        #[serde(default = "openai_provider::default_base_url")]
+        base_url: String,
+    },
+    /// Anthropic Messages API. An empty key falls back to the stored key
+    Anthropic {
+        api_key: String,
+        model: String,
+        #[serde(default = "anthropic_provider::default_base_url")]
+        base_url: String,
+        #[serde(default)]
+        system_prompt: Option<String>,
+    },
 }
 
 impl Default for LLMConfig {
@@ -42,6 +330,84 @@ impl Default for LLMConfig {
     }
 }
 
+impl LLMConfig {
+    pub fn is_ollama(&self) -> bool {
+        matches!(
+            self,
+            Self::Phi35Ollama { .. } | Self::QwenOllama { .. } | Self::MistralOllama { .. }
+        )
+    }
+
+    /// Ollama and OpenAI-compatible servers on this machine count as local
+    /// for failover; anything else is a cloud API
+    pub fn is_local(&self) -> bool {
+        match self {
+            Self::OpenAI { base_url, .. } => reqwest::Url::parse(base_url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .is_some_and(|host| {
+                    matches!(
+                        host.as_str(),
+                        "localhost" | "127.0.0.1" | "[::1]" | "0.0.0.0"
+                    )
+                }),
+            Self::Anthropic { .. } => false,
+            _ => true,
+        }
+    }
+
+    /// Provider config for the backend and model selected in the hardware
+    /// config. Local OpenAI-compatible servers use OPENAI_BASE_URL when set
+    pub fn from_hardware(params: &HardwareParams) -> Self {
+        let model = |default: &str| {
+            if params.model.trim().is_empty() {
+                default.to_string()
+            } else {
+                params.model.clone()
+            }
+        };
+        let local_base_url = |default: &str| {
+            std::env::var("OPENAI_BASE_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty())
+                .unwrap_or_else(|| default.to_string())
+        };
+        match params.backend_type {
+            BackendType::Ollama => Self::Phi35Ollama {
+                ollama_url: std::env::var("OLLAMA_HOST")
+                    .unwrap_or_else(|_| "http://localhost:11434".to_string()),
+                model: model("phi:latest"),
+            },
+            BackendType::OpenAi => Self::OpenAI {
+                api_key: String::new(),
+                model: model("gpt-4o-mini"),
+                base_url: openai_provider::DEFAULT_BASE_URL.to_string(),
+            },
+            BackendType::Anthropic => Self::Anthropic {
+                api_key: String::new(),
+                model: model(anthropic_provider::DEFAULT_MODEL),
+                base_url: anthropic_provider::default_base_url(),
+                system_prompt: None,
+            },
+            BackendType::Vllm => Self::OpenAI {
+                api_key: String::new(),
+                model: model("default"),
+                base_url: local_base_url("http://localhost:8000/v1"),
+            },
+            BackendType::LlamaCpp => Self::OpenAI {
+                api_key: String::new(),
+                model: model("default"),
+                base_url: local_base_url("http://localhost:8080/v1"),
+            },
+            BackendType::Custom => Self::OpenAI {
+                api_key: String::new(),
+                model: model("default"),
+                base_url: openai_provider::default_base_url(),
+            },
+        }
+    }
+}
+
 /// Error types for LLM operations
 #[derive(Debug, Clone)]
 pub enum LLMError {
@@ -49,6 +415,8 @@ pub enum LLMError {
     InvalidResponse(String),
     GenerationFailed(String),
     ConfigError(String),
+    /// The backend kept answering 429 after all retries
+    RateLimited(String),
 }
 
 impl std::fmt::Display for LLMError {
@@ -58,6 +426,7 @@ impl std::fmt::Display for LLMError {
             Self::InvalidResponse(msg) => write!(f, "Invalid LLM response: {}", msg),
             Self::GenerationFailed(msg) => write!(f, "Generation failed: {}", msg),
             Self::ConfigError(msg) => write!(f, "Config error: {}", msg),
+            Self::RateLimited(msg) => write!(f, "Rate limited: {}", msg),
         }
     }
 }
@@ -69,6 +438,7 @@ pub struct OllamaProvider {
     url: String,
     model: String,
     client: reqwest::Client,
+    usage: llm_http::UsageTracker,
 }
 
 /// Ollama API options for generation parameters
@@ -140,26 +510,189 @@ struct OllamaRequest {
     options: Option<OllamaOptions>,
 }
 
+#[derive(Serialize)]
+struct OllamaChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<OllamaMessage<'a>>,
+    stream: bool,
+    options: OllamaOptions,
+    /// "json" or a JSON Schema for structured outputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct OllamaMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+    /// Ollama takes arguments as an object and matches results by order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct OllamaChatResponse {
+    message: OllamaChatReply,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+
+#[derive(Deserialize)]
+struct OllamaChatReply {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+/// Ollama returns arguments as an object and gives calls no id
+#[derive(Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunctionCall,
+}
+
+#[derive(Deserialize)]
+struct OllamaFunctionCall {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
 #[derive(Deserialize)]
 struct OllamaResponse {
+    #[serde(default)]
     response: String,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+
+impl OllamaResponse {
+    fn usage(&self) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_eval_count,
+            completion_tokens: self.eval_count,
+            total_tokens: self.prompt_eval_count + self.eval_count,
+        }
+    }
 }
 
 impl OllamaProvider {
     pub fn new(url: String, model: String) -> Self {
         Self {
+            usage: llm_http::UsageTracker::new("ollama", &model),
             url,
             model,
             client: reqwest::Client::new(),
         }
     }
 
-    async fn health_check(&self) -> Result<(), LLMError> {
-        let health_url = format!("{}/api/tags", self.url);
-        self.client.get(&health_url).send().await.map_err(|e| {
-            LLMError::ConnectionFailed(format!("Cannot reach Ollama at {}: {}", self.url, e))
-        })?;
-        Ok(())
+    async fn send(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+        stream: bool,
+    ) -> Result<reqwest::Response, LLMError> {
+        let req = OllamaRequest {
+            model: self.model.clone(),
+            prompt: prompt.to_string(),
+            stream,
+            options: Some(OllamaOptions::from(config)),
+        };
+        self.post("/api/generate", &req).await
+    }
+
+    async fn post(&self, path: &str, body: &impl Serialize) -> Result<reqwest::Response, LLMError> {
+        let url = format!("{}{}", self.url, path);
+
+        let response = self
+            .client
+            .post(&url)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| {
+                LLMError::ConnectionFailed(format!("Cannot reach Ollama at {}: {}", self.url, e))
+            })?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(LLMError::GenerationFailed(format!(
+                "HTTP {}: {}",
+                status.as_u16(),
+                body
+            )));
+        }
+        Ok(response)
+    }
+
+    /// Chat completion; `format` constrains the reply to JSON or a schema,
+    /// `tools` are offered for function calling
+    async fn chat_reply(
+        &self,
+        messages: &[ChatMessage],
+        format: Option<&serde_json::Value>,
+        tools: &[ToolSpec],
+        config: &LlmConfig,
+    ) -> Result<(OllamaChatReply, TokenUsage), LLMError> {
+        debug!(
+            model = %self.model,
+            messages = messages.len(),
+            temperature = config.temperature,
+            structured = format.is_some(),
+            tools = tools.len(),
+            "Chatting with Ollama"
+        );
+        let req = OllamaChatRequest {
+            model: &self.model,
+            messages: messages
+                .iter()
+                .map(|m| OllamaMessage {
+                    role: m.role.as_str(),
+                    content: &m.content,
+                    tool_calls: m
+                        .tool_calls
+                        .iter()
+                        .map(|call| {
+                            serde_json::json!({
+                                "function": {"name": call.name, "arguments": call.arguments}
+                            })
+                        })
+                        .collect(),
+                })
+                .collect(),
+            stream: false,
+            options: OllamaOptions::from(config),
+            format,
+            tools: llm_http::function_tools(tools),
+        };
+        let response = self.post("/api/chat", &req).await?;
+        let reply: OllamaChatResponse = response
+            .json()
+            .await
+            .map_err(|e| LLMError::InvalidResponse(e.to_string()))?;
+        let usage = TokenUsage {
+            prompt_tokens: reply.prompt_eval_count,
+            completion_tokens: reply.eval_count,
+            total_tokens: reply.prompt_eval_count + reply.eval_count,
+        };
+        self.usage.record(usage);
+        info!(
+            model = %self.model,
+            response_len = reply.message.content.len(),
+            tool_calls = reply.message.tool_calls.len(),
+            prompt_tokens = usage.prompt_tokens,
+            completion_tokens = usage.completion_tokens,
+            "Chat complete"
+        );
+        Ok((reply.message, usage))
     }
 }
 
@@ -186,81 +719,225 @@ impl LLMProvider for OllamaProvider {
             "Generating with Ollama"
         );
 
-        // Check connection first
-        if let Err(e) = self.health_check().await {
-            warn!("Ollama health check failed: {}", e);
-            return Err(e);
-        }
-
-        let url = format!("{}/api/generate", self.url);
-        let options = OllamaOptions::from(config);
-        let req = OllamaRequest {
-            model: self.model.clone(),
-            prompt: prompt.to_string(),
-            stream: false,
-            options: Some(options),
-        };
-
-        let response = self
-            .client
-            .post(&url)
-            .json(&req)
-            .send()
-            .await
-            .map_err(|e| LLMError::ConnectionFailed(e.to_string()))?;
+        let response = self.send(prompt, config, false).await?;
 
         let ollama_resp: OllamaResponse = response
             .json()
             .await
             .map_err(|e| LLMError::InvalidResponse(e.to_string()))?;
+        let usage = ollama_resp.usage();
+        self.usage.record(usage);
 
         info!(
             model = %self.model,
             response_len = ollama_resp.response.len(),
             temperature = config.temperature,
+            prompt_tokens = usage.prompt_tokens,
+            completion_tokens = usage.completion_tokens,
             "Generation complete"
         );
         Ok(ollama_resp.response.trim().to_string())
     }
 
+    async fn chat(&self, messages: &[ChatMessage], config: &LlmConfig) -> Result<String, LLMError> {
+        Ok(self.chat_with_usage(messages, config).await?.text)
+    }
+
+    async fn chat_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        let (reply, usage) = self.chat_reply(messages, None, &[], config).await?;
+        Ok(Completion {
+            text: reply.content.trim().to_string(),
+            usage: Some(usage),
+        })
+    }
+
+    async fn chat_json(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        Ok(self
+            .chat_json_with_usage(messages, schema, config)
+            .await?
+            .text)
+    }
+
+    async fn chat_json_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        let (reply, usage) = self.chat_reply(messages, Some(schema), &[], config).await?;
+        Ok(Completion {
+            text: reply.content.trim().to_string(),
+            usage: Some(usage),
+        })
+    }
+
+    async fn chat_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolSpec],
+        config: &LlmConfig,
+    ) -> Result<Option<ToolReply>, LLMError> {
+        let (reply, _) = self.chat_reply(messages, None, tools, config).await?;
+        Ok(Some(ToolReply {
+            content: reply.content.trim().to_string(),
+            tool_calls: reply
+                .tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(i, call)| ToolCall {
+                    id: format!("call_{}", i),
+                    name: call.function.name,
+                    arguments: call.function.arguments,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<TokenStream, LLMError> {
+        Ok(self.generate_stream_with_usage(prompt, config).await?.0)
+    }
+
+    async fn generate_stream_with_usage(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<(TokenStream, StreamUsage), LLMError> {
+        debug!(model = %self.model, prompt_len = prompt.len(), "Streaming with Ollama");
+        let response = self.send(prompt, config, true).await?;
+        let tracker = self.usage.clone();
+        let stream_usage = StreamUsage::default();
+        let reported = stream_usage.clone();
+        // One JSON object per line; the last one has done=true and the counts
+        let tokens = llm_http::lines(response).filter_map(move |line| {
+            let tracker = tracker.clone();
+            let reported = reported.clone();
+            async move {
+                let line = match line {
+                    Ok(line) if line.trim().is_empty() => return None,
+                    Ok(line) => line,
+                    Err(e) => return Some(Err(e)),
+                };
+                match serde_json::from_str::<OllamaResponse>(&line) {
+                    Ok(chunk) => {
+                        if chunk.done {
+                            tracker.record(chunk.usage());
+                            reported.set(chunk.usage());
+                        }
+                        (!chunk.response.is_empty()).then_some(Ok(chunk.response))
+                    }
+                    Err(e) => Some(Err(LLMError::InvalidResponse(e.to_string()))),
+                }
+            }
+        });
+        Ok((Box::pin(tokens), stream_usage))
+    }
+
     fn model_name(&self) -> &str {
         &self.model
     }
+
+    async fn health_check(&self) -> Result<(), LLMError> {
+        let health_url = format!("{}/api/tags", self.url);
+        llm_http::probe(self.client.get(&health_url), &self.url).await
+    }
 }
 
-/// Factory function to create LLM provider from config
+/// Factory function to create LLM provider from config. Ollama backends
+/// are checked for reachability once, here
 pub async fn create_llm_provider(config: LLMConfig) -> Result<Box<dyn LLMProvider>, LLMError> {
+    let ollama = config.is_ollama();
+    let provider = build_llm_provider(config)?;
+    if ollama {
+        if let Err(e) = provider.health_check().await {
+            warn!("Failed to connect to Ollama. Make sure it's running: ollama serve");
+            return Err(e);
+        }
+    }
+    Ok(provider)
+}
+
+/// Build a provider without contacting the backend. Fails only when a
+/// hosted API has no key configured
+pub fn build_llm_provider(config: LLMConfig) -> Result<Box<dyn LLMProvider>, LLMError> {
     match config {
         LLMConfig::Phi35Ollama { ollama_url, model } => {
             info!("Initializing Phi 3.5 via Ollama at {}", ollama_url);
-            let provider = OllamaProvider::new(ollama_url, model);
-            provider.health_check().await.map_err(|e| {
-                warn!("Failed to connect to Ollama. Make sure it's running: ollama serve");
-                e
-            })?;
-            Ok(Box::new(provider))
+            Ok(Box::new(OllamaProvider::new(ollama_url, model)))
         }
         LLMConfig::QwenOllama { ollama_url, model } => {
             info!("Initializing Qwen via Ollama at {}", ollama_url);
-            let provider = OllamaProvider::new(ollama_url, model);
-            provider.health_check().await?;
-            Ok(Box::new(provider))
+            Ok(Box::new(OllamaProvider::new(ollama_url, model)))
         }
         LLMConfig::MistralOllama { ollama_url, model } => {
             info!("Initializing Mistral via Ollama at {}", ollama_url);
-            let provider = OllamaProvider::new(ollama_url, model);
-            provider.health_check().await?;
-            Ok(Box::new(provider))
+            Ok(Box::new(OllamaProvider::new(ollama_url, model)))
         }
         LLMConfig::OpenAI {
-            api_key: _,
-            model: _,
-        } => Err(LLMError::ConfigError(
-            "OpenAI provider not yet implemented".to_string(),
-        )),
+            api_key,
+            model,
+            base_url,
+        } => {
+            info!("Initializing OpenAI-compatible provider at {}", base_url);
+            let api_key = if api_key.is_empty() {
+                api_keys::global_config().get_openai_key()
+            } else {
+                Some(api_key)
+            };
+            if api_key.is_none() && openai_provider::is_openai_host(&base_url) {
+                return Err(LLMError::ConfigError(
+                    "OpenAI API key not configured".to_string(),
+                ));
+            }
+            Ok(Box::new(OpenAIProvider::new(base_url, api_key, model)))
+        }
+        LLMConfig::Anthropic {
+            api_key,
+            model,
+            base_url,
+            system_prompt,
+        } => {
+            info!("Initializing Anthropic provider with model {}", model);
+            let api_key = if api_key.is_empty() {
+                api_keys::global_config()
+                    .get_anthropic_key()
+                    .filter(|key| !key.is_empty())
+                    .ok_or_else(|| {
+                        LLMError::ConfigError("Anthropic API key not configured".to_string())
+                    })?
+            } else {
+                api_key
+            };
+            Ok(Box::new(
+                AnthropicProvider::new(base_url, api_key, model).with_system_prompt(system_prompt),
+            ))
+        }
     }
 }
 
+/// Create the provider selected in the active hardware/backend config
+pub async fn create_configured_provider() -> Result<Box<dyn LLMProvider>, LLMError> {
+    let params = param_hardware::global_config();
+    info!(
+        backend = %params.backend_type,
+        model = %params.model,
+        "Selecting LLM provider from backend config"
+    );
+    create_llm_provider(LLMConfig::from_hardware(&params)).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -285,6 +962,159 @@ mod tests {
         assert_eq!(provider.model_name(), "phi:latest");
     }
 
+    #[test]
+    fn test_openai_config_defaults_base_url() {
+        let config: LLMConfig =
+            serde_json::from_str(r#"{"OpenAI":{"api_key":"","model":"gpt-4o-mini"}}"#).unwrap();
+        match config {
+            LLMConfig::OpenAI { base_url, .. } => {
+                assert_eq!(base_url, openai_provider::default_base_url());
+            }
+            _ => panic!("Expected OpenAI"),
+        }
+    }
+
+    #[test]
+    fn test_config_from_hardware_backend() {
+        let params = HardwareParams {
+            backend_type: BackendType::Anthropic,
+            ..HardwareParams::default()
+        };
+        match LLMConfig::from_hardware(&params) {
+            LLMConfig::Anthropic { model, .. } => {
+                assert_eq!(model, anthropic_provider::DEFAULT_MODEL)
+            }
+            other => panic!("Expected Anthropic, got {:?}", other),
+        }
+
+        let params = HardwareParams {
+            backend_type: BackendType::OpenAi,
+            model: "gpt-4o".to_string(),
+            ..HardwareParams::default()
+        };
+        match LLMConfig::from_hardware(&params) {
+            LLMConfig::OpenAI {
+                model, base_url, ..
+            } => {
+                assert_eq!(model, "gpt-4o");
+                assert_eq!(base_url, openai_provider::DEFAULT_BASE_URL);
+            }
+            other => panic!("Expected OpenAI, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ollama_stream_ndjson() {
+        let body = [
+            r#"{"model":"phi","response":"The","done":false}"#,
+            r#"{"model":"phi","response":" sky","done":false}"#,
+            r#"{"model":"phi","response":"","done":true,"prompt_eval_count":7,"eval_count":2}"#,
+        ]
+        .join("\n");
+        let (base, requests) = llm_http::mock::serve(vec![(200, "", body)]).await;
+        let provider = OllamaProvider::new(base, "phi".to_string());
+        let (stream, usage) = provider
+            .generate_stream_with_usage("Why is the sky blue?", &LlmConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(usage.get(), None);
+        let tokens: Vec<String> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(tokens, vec!["The", " sky"]);
+        assert_eq!(usage.get().unwrap().total_tokens, 9);
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["stream"], true);
+    }
+
+    #[test]
+    fn test_render_chat_prompt() {
+        let prompt = render_chat_prompt(&[
+            ChatMessage::system("Be brief."),
+            ChatMessage::user("Hi"),
+            ChatMessage::assistant_tool_calls(
+                "Hello.",
+                vec![ToolCall {
+                    id: "call_1".into(),
+                    name: "answer".into(),
+                    arguments: serde_json::json!({}),
+                }],
+            ),
+            ChatMessage::tool("call_1", "42"),
+            ChatMessage::user("And now?"),
+        ]);
+        assert_eq!(
+            prompt,
+            "System: Be brief.\n\nUser: Hi\n\nAssistant: Hello.\nTool call answer (call_1): {}\n\nTool result: 42\n\nUser: And now?\n\nAssistant:"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ollama_chat_uses_chat_endpoint() {
+        let reply = r#"{"message":{"role":"assistant","content":" Hi there "},"done":true,"prompt_eval_count":5,"eval_count":3}"#;
+        let (base, requests) = llm_http::mock::serve(vec![(200, "", reply.to_string())]).await;
+        let provider = OllamaProvider::new(base, "phi".to_string());
+        let messages = [ChatMessage::system("Be kind."), ChatMessage::user("Hello")];
+        let completion = provider
+            .chat_with_usage(&messages, &LlmConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(completion.text, "Hi there");
+        assert_eq!(completion.usage.unwrap().total_tokens, 8);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1, "no health check on the request path");
+        assert!(requests[0].0.starts_with("post /api/chat"));
+        let sent: serde_json::Value = serde_json::from_str(&requests[0].1).unwrap();
+        assert_eq!(sent["messages"][0]["role"], "system");
+        assert_eq!(sent["messages"][1]["content"], "Hello");
+        assert_eq!(sent["stream"], false);
+        assert!(sent.get("format").is_none());
+        assert!(sent.get("tools").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ollama_chat_tools_returns_calls() {
+        let reply = r#"{"message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"Calculator","arguments":{"expression":"6 * 7"}}}]},"done":true}"#;
+        let (base, requests) = llm_http::mock::serve(vec![(200, "", reply.to_string())]).await;
+        let provider = OllamaProvider::new(base, "qwen".to_string());
+        let tools = [ToolSpec {
+            name: "Calculator".into(),
+            description: "Arithmetic".into(),
+            parameters: serde_json::json!({"type": "object"}),
+        }];
+        let reply = provider
+            .chat_tools(
+                &[ChatMessage::user("6 times 7?")],
+                &tools,
+                &LlmConfig::default(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.tool_calls.len(), 1);
+        assert_eq!(reply.tool_calls[0].name, "Calculator");
+        assert_eq!(reply.tool_calls[0].arguments["expression"], "6 * 7");
+
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["tools"][0]["type"], "function");
+        assert_eq!(sent["tools"][0]["function"]["name"], "Calculator");
+    }
+
+    #[tokio::test]
+    async fn test_ollama_chat_json_sends_schema_as_format() {
+        let reply = r#"{"message":{"role":"assistant","content":"{\"ok\":true}"},"done":true}"#;
+        let (base, requests) = llm_http::mock::serve(vec![(200, "", reply.to_string())]).await;
+        let provider = OllamaProvider::new(base, "phi".to_string());
+        let schema = serde_json::json!({"type": "object", "required": ["ok"]});
+        let text = provider
+            .chat_json(&[ChatMessage::user("ok?")], &schema, &LlmConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(text, r#"{"ok":true}"#);
+
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["format"], schema);
+    }
+
     #[test]
     fn test_llm_error_display() {
         let err = LLMError::ConnectionFailed("test".to_string());
@@ -0,0 +1,1266 @@
+// src/memory/llm_router.rs
+// Provider router: several LLM backends behind one LLMProvider, tried in an
+// order decided by routing rules (request tag, estimated prompt size, cost)
+// and the failover mode. Each route has a circuit breaker that skips it
+// after repeated failures, and background probes track reachability so the
+// request path never pays for a health check.
+
+use crate::db::api_keys;
+use crate::db::llm_settings::{self, LlmConfig};
+use crate::db::param_hardware::{self, BackendType, HardwareParams};
+use crate::memory::llm_provider::{
+    build_llm_provider, ChatMessage, Completion, LLMConfig, LLMError, LLMProvider, StreamUsage,
+    TokenStream, ToolReply, ToolSpec,
+};
+use crate::memory::{anthropic_provider, tokenizer};
+use crate::monitoring::{
+    LLM_FAILOVERS_TOTAL, LLM_ROUTE_BREAKER_STATE, LLM_ROUTE_HEALTHY, LLM_ROUTE_REQUESTS_TOTAL,
+};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Order in which local and cloud routes are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailoverMode {
+    /// Route priority alone
+    #[default]
+    Priority,
+    /// Local routes first, cloud routes as fallback
+    LocalFirst,
+    /// Cloud routes first, local routes as fallback
+    CloudFirst,
+    /// Only the best matching route; errors are returned as they are
+    Off,
+}
+
+impl FailoverMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "priority" => Some(Self::Priority),
+            "local_first" | "local" => Some(Self::LocalFirst),
+            "cloud_first" | "cloud" => Some(Self::CloudFirst),
+            "off" | "none" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Tie-breaker among routes of the same failover group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingStrategy {
+    /// Lowest priority value first
+    #[default]
+    Priority,
+    /// Lowest cost per 1k tokens first, then priority
+    Cheapest,
+}
+
+#[derive(Debug, Clone)]
+pub struct RouterConfig {
+    pub failover: FailoverMode,
+    pub strategy: RoutingStrategy,
+    /// Interval of the background health probes
+    pub probe_interval: Duration,
+    /// Consecutive failures that open a route's circuit
+    pub breaker_failures: u32,
+    /// How long an open circuit skips the route before a trial request
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            failover: FailoverMode::Priority,
+            strategy: RoutingStrategy::Priority,
+            probe_interval: Duration::from_secs(30),
+            breaker_failures: 3,
+            breaker_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RouterConfig {
+    /// LLM_FAILOVER, LLM_ROUTING, LLM_HEALTH_PROBE_SECS,
+    /// LLM_BREAKER_FAILURES and LLM_BREAKER_COOLDOWN_SECS
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let secs = |key: &str, default: Duration| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default)
+        };
+        Self {
+            failover: std::env::var("LLM_FAILOVER")
+                .ok()
+                .and_then(|v| FailoverMode::parse(&v))
+                .unwrap_or(defaults.failover),
+            strategy: match std::env::var("LLM_ROUTING").as_deref() {
+                Ok("cheapest") => RoutingStrategy::Cheapest,
+                _ => defaults.strategy,
+            },
+            probe_interval: secs("LLM_HEALTH_PROBE_SECS", defaults.probe_interval)
+                .max(Duration::from_secs(1)),
+            breaker_failures: std::env::var("LLM_BREAKER_FAILURES")
+                .ok()
+                .and_then(|v| v.trim().parse::<u32>().ok())
+                .unwrap_or(defaults.breaker_failures)
+                .max(1),
+            breaker_cooldown: secs("LLM_BREAKER_COOLDOWN_SECS", defaults.breaker_cooldown),
+        }
+    }
+}
+
+/// One entry of LLM_ROUTES (a JSON array)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RouteSpec {
+    pub name: String,
+    pub config: LLMConfig,
+    #[serde(default)]
+    pub priority: i32,
+    /// Only requests carrying one of these tags use the route; empty
+    /// accepts every request
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub min_prompt_tokens: Option<usize>,
+    #[serde(default)]
+    pub max_prompt_tokens: Option<usize>,
+    #[serde(default)]
+    pub cost_per_1k_tokens: f64,
+    /// Overrides the local/cloud classification of the config
+    #[serde(default)]
+    pub local: Option<bool>,
+}
+
+/// Which requests a route accepts
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RouteRule {
+    pub tags: Vec<String>,
+    pub min_prompt_tokens: Option<usize>,
+    pub max_prompt_tokens: Option<usize>,
+}
+
+impl RouteRule {
+    fn matches(&self, tag: Option<&str>, prompt_tokens: usize) -> bool {
+        let tag_ok =
+            self.tags.is_empty() || tag.is_some_and(|tag| self.tags.iter().any(|t| t == tag));
+        tag_ok
+            && self
+                .min_prompt_tokens
+                .is_none_or(|min| prompt_tokens >= min)
+            && self
+                .max_prompt_tokens
+                .is_none_or(|max| prompt_tokens <= max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    /// Cooldown over; one trial request decides
+    HalfOpen,
+    Open,
+}
+
+impl BreakerState {
+    fn gauge(self) -> i64 {
+        match self {
+            Self::Closed => 0,
+            Self::HalfOpen => 1,
+            Self::Open => 2,
+        }
+    }
+}
+
+struct CircuitBreaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    fn new() -> Self {
+        Self {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            trial_in_flight: false,
+        }
+    }
+
+    /// Whether a request may use the route now
+    fn admit(&mut self, cooldown: Duration) -> bool {
+        match self.state {
+            BreakerState::Closed => true,
+            BreakerState::Open => {
+                if self.opened_at.is_some_and(|at| at.elapsed() >= cooldown) {
+                    self.state = BreakerState::HalfOpen;
+                    self.trial_in_flight = true;
+                    true
+                } else {
+                    false
+                }
+            }
+            BreakerState::HalfOpen => {
+                if self.trial_in_flight {
+                    false
+                } else {
+                    self.trial_in_flight = true;
+                    true
+                }
+            }
+        }
+    }
+
+    fn success(&mut self) {
+        self.state = BreakerState::Closed;
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+        self.trial_in_flight = false;
+    }
+
+    fn failure(&mut self, threshold: u32) {
+        self.consecutive_failures += 1;
+        self.trial_in_flight = false;
+        if self.state == BreakerState::HalfOpen || self.consecutive_failures >= threshold {
+            self.open();
+        }
+    }
+
+    fn open(&mut self) {
+        self.state = BreakerState::Open;
+        self.opened_at = Some(Instant::now());
+    }
+}
+
+#[derive(Default)]
+struct Health {
+    healthy: Option<bool>,
+    last_probe: Option<DateTime<Utc>>,
+    latency_ms: Option<u64>,
+    last_error: Option<String>,
+}
+
+/// A provider with its routing rule and live state
+pub struct Route {
+    name: String,
+    provider: Arc<dyn LLMProvider>,
+    priority: i32,
+    local: bool,
+    rule: RouteRule,
+    cost_per_1k_tokens: f64,
+    breaker: Mutex<CircuitBreaker>,
+    health: Mutex<Health>,
+    requests: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Route {
+    pub fn new(name: impl Into<String>, provider: Arc<dyn LLMProvider>) -> Self {
+        Self {
+            name: name.into(),
+            provider,
+            priority: 0,
+            local: true,
+            rule: RouteRule::default(),
+            cost_per_1k_tokens: 0.0,
+            breaker: Mutex::new(CircuitBreaker::new()),
+            health: Mutex::new(Health::default()),
+            requests: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_local(mut self, local: bool) -> Self {
+        self.local = local;
+        self
+    }
+
+    pub fn with_rule(mut self, rule: RouteRule) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    pub fn with_cost(mut self, cost_per_1k_tokens: f64) -> Self {
+        self.cost_per_1k_tokens = cost_per_1k_tokens;
+        self
+    }
+
+    /// Build the provider for a spec without contacting it
+    pub fn from_spec(spec: RouteSpec) -> Result<Self, LLMError> {
+        let local = spec.local.unwrap_or_else(|| spec.config.is_local());
+        let provider: Arc<dyn LLMProvider> = Arc::from(build_llm_provider(spec.config)?);
+        Ok(Self::new(spec.name, provider)
+            .with_priority(spec.priority)
+            .with_local(local)
+            .with_rule(RouteRule {
+                tags: spec.tags,
+                min_prompt_tokens: spec.min_prompt_tokens,
+                max_prompt_tokens: spec.max_prompt_tokens,
+            })
+            .with_cost(spec.cost_per_1k_tokens))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn breaker_state(&self) -> BreakerState {
+        self.breaker.lock().unwrap().state
+    }
+
+    fn admit(&self, cooldown: Duration) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+        let before = breaker.state;
+        let admitted = breaker.admit(cooldown);
+        if breaker.state != before {
+            self.publish_state(breaker.state);
+        }
+        admitted
+    }
+
+    fn record_success(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        LLM_ROUTE_REQUESTS_TOTAL
+            .with_label_values(&[&self.name, "ok"])
+            .inc();
+        let mut breaker = self.breaker.lock().unwrap();
+        if breaker.state != BreakerState::Closed {
+            info!(route = %self.name, "LLM route recovered; circuit closed");
+        }
+        breaker.success();
+        self.publish_state(breaker.state);
+    }
+
+    fn record_failure(&self, threshold: u32, err: &LLMError) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        LLM_ROUTE_REQUESTS_TOTAL
+            .with_label_values(&[&self.name, "error"])
+            .inc();
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.failure(threshold);
+        if breaker.state == BreakerState::Open {
+            warn!(
+                route = %self.name,
+                failures = breaker.consecutive_failures,
+                "LLM route circuit opened: {}",
+                err
+            );
+        }
+        self.publish_state(breaker.state);
+    }
+
+    fn record_probe(&self, result: Result<(), LLMError>, latency: Duration) {
+        let healthy = result.is_ok();
+        {
+            let mut health = self.health.lock().unwrap();
+            if health.healthy != Some(healthy) {
+                match &result {
+                    Ok(()) => info!(route = %self.name, "LLM route is up"),
+                    Err(e) => warn!(route = %self.name, "LLM route is down: {}", e),
+                }
+            }
+            health.healthy = Some(healthy);
+            health.last_probe = Some(Utc::now());
+            health.latency_ms = Some(latency.as_millis() as u64);
+            health.last_error = result.err().map(|e| e.to_string());
+        }
+        LLM_ROUTE_HEALTHY
+            .with_label_values(&[&self.name])
+            .set(healthy as i64);
+
+        // A failed probe opens the circuit right away; a passing probe
+        // lets the next request try an open route without waiting out the
+        // cooldown
+        let mut breaker = self.breaker.lock().unwrap();
+        match (healthy, breaker.state) {
+            (false, BreakerState::Closed) => breaker.open(),
+            (true, BreakerState::Open) => {
+                breaker.state = BreakerState::HalfOpen;
+                breaker.trial_in_flight = false;
+            }
+            _ => {}
+        }
+        self.publish_state(breaker.state);
+    }
+
+    fn publish_state(&self, state: BreakerState) {
+        LLM_ROUTE_BREAKER_STATE
+            .with_label_values(&[&self.name])
+            .set(state.gauge());
+    }
+
+    fn status(&self) -> RouteStatus {
+        let breaker = self.breaker.lock().unwrap();
+        let health = self.health.lock().unwrap();
+        RouteStatus {
+            name: self.name.clone(),
+            model: self.provider.model_name().to_string(),
+            priority: self.priority,
+            local: self.local,
+            rule: self.rule.clone(),
+            cost_per_1k_tokens: self.cost_per_1k_tokens,
+            breaker: breaker.state,
+            consecutive_failures: breaker.consecutive_failures,
+            healthy: health.healthy,
+            last_probe: health.last_probe.map(|at| at.to_rfc3339()),
+            probe_latency_ms: health.latency_ms,
+            last_error: health.last_error.clone(),
+            requests: self.requests.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Route state as reported on /monitor/llm/routes
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteStatus {
+    pub name: String,
+    pub model: String,
+    pub priority: i32,
+    pub local: bool,
+    pub rule: RouteRule,
+    pub cost_per_1k_tokens: f64,
+    pub breaker: BreakerState,
+    pub consecutive_failures: u32,
+    /// None until the first probe
+    pub healthy: Option<bool>,
+    pub last_probe: Option<String>,
+    pub probe_latency_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub requests: u64,
+    pub failures: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouterSnapshot {
+    pub failover: FailoverMode,
+    pub strategy: RoutingStrategy,
+    pub probe_interval_secs: u64,
+    pub breaker_failures: u32,
+    pub breaker_cooldown_secs: u64,
+    pub last_route: Option<String>,
+    pub routes: Vec<RouteStatus>,
+}
+
+/// LLMProvider over several routes with failover
+pub struct RoutedProvider {
+    routes: Vec<Route>,
+    config: RouterConfig,
+    /// Route that served the most recent request, for model_name
+    last_route: Mutex<Option<usize>>,
+}
+
+impl RoutedProvider {
+    pub fn new(routes: Vec<Route>, config: RouterConfig) -> Result<Self, LLMError> {
+        if routes.is_empty() {
+            return Err(LLMError::ConfigError(
+                "no LLM routes configured".to_string(),
+            ));
+        }
+        for route in &routes {
+            route.publish_state(BreakerState::Closed);
+        }
+        Ok(Self {
+            routes,
+            config,
+            last_route: Mutex::new(None),
+        })
+    }
+
+    /// Routes from LLM_ROUTES, or the configured backend plus every other
+    /// backend that is usable without further setup
+    pub fn from_env(hardware: &HardwareParams) -> Result<Self, LLMError> {
+        let config = RouterConfig::from_env();
+        let specs = match std::env::var("LLM_ROUTES") {
+            Ok(json) if !json.trim().is_empty() => serde_json::from_str::<Vec<RouteSpec>>(&json)
+                .map_err(|e| LLMError::ConfigError(format!("Invalid LLM_ROUTES: {}", e)))?,
+            _ => default_routes(hardware),
+        };
+        let mut routes = Vec::new();
+        for spec in specs {
+            let name = spec.name.clone();
+            match Route::from_spec(spec) {
+                Ok(route) => routes.push(route),
+                Err(e) => warn!(route = %name, "Skipping LLM route: {}", e),
+            }
+        }
+        Self::new(routes, config)
+    }
+
+    pub fn config(&self) -> &RouterConfig {
+        &self.config
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Provider that routes every request with `tag`
+    pub fn tagged(self: &Arc<Self>, tag: impl Into<String>) -> TaggedProvider {
+        TaggedProvider {
+            router: Arc::clone(self),
+            tag: tag.into(),
+        }
+    }
+
+    /// Candidate routes in the order they are tried. Routes that name the
+    /// request's tag come before catch-all routes
+    fn plan(&self, tag: Option<&str>, prompt_tokens: usize) -> Vec<usize> {
+        let mut candidates: Vec<usize> = (0..self.routes.len())
+            .filter(|&i| self.routes[i].rule.matches(tag, prompt_tokens))
+            .collect();
+        candidates.sort_by(|&a, &b| {
+            let (a, b) = (&self.routes[a], &self.routes[b]);
+            let group = |route: &Route| match self.config.failover {
+                FailoverMode::LocalFirst => !route.local as u8,
+                FailoverMode::CloudFirst => route.local as u8,
+                FailoverMode::Priority | FailoverMode::Off => 0,
+            };
+            let generic = |route: &Route| route.rule.tags.is_empty() as u8;
+            let cost = |route: &Route| match self.config.strategy {
+                RoutingStrategy::Cheapest => route.cost_per_1k_tokens,
+                RoutingStrategy::Priority => 0.0,
+            };
+            generic(a)
+                .cmp(&generic(b))
+                .then(group(a).cmp(&group(b)))
+                .then(cost(a).total_cmp(&cost(b)))
+                .then(a.priority.cmp(&b.priority))
+        });
+        if self.config.failover == FailoverMode::Off {
+            candidates.truncate(1);
+        }
+        candidates
+    }
+
+    /// Run `call` on each candidate route until one succeeds
+    async fn route<'a, T, F, Fut>(
+        &'a self,
+        tag: Option<&str>,
+        prompt: &str,
+        mut call: F,
+    ) -> Result<T, LLMError>
+    where
+        F: FnMut(&'a dyn LLMProvider) -> Fut,
+        Fut: Future<Output = Result<T, LLMError>>,
+    {
+        let prompt_tokens = tokenizer::generation_tokenizer().count_tokens(prompt);
+        let plan = self.plan(tag, prompt_tokens);
+        if plan.is_empty() {
+            return Err(LLMError::ConfigError(format!(
+                "no LLM route accepts tag {:?} with {} prompt tokens",
+                tag, prompt_tokens
+            )));
+        }
+
+        let mut last_err = None;
+        let mut failed: Option<&str> = None;
+        for i in plan {
+            let route = &self.routes[i];
+            if !route.admit(self.config.breaker_cooldown) {
+                LLM_ROUTE_REQUESTS_TOTAL
+                    .with_label_values(&[&route.name, "skipped"])
+                    .inc();
+                debug!(route = %route.name, "LLM route circuit open; skipping");
+                continue;
+            }
+            if let Some(from) = failed {
+                LLM_FAILOVERS_TOTAL
+                    .with_label_values(&[from, &route.name])
+                    .inc();
+                warn!(from, to = %route.name, "Failing over to next LLM route");
+            }
+            match call(route.provider.as_ref()).await {
+                Ok(value) => {
+                    route.record_success();
+                    *self.last_route.lock().unwrap() = Some(i);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    route.record_failure(self.config.breaker_failures, &e);
+                    failed = Some(&route.name);
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            LLMError::ConnectionFailed("all LLM routes are unavailable (circuits open)".to_string())
+        }))
+    }
+
+    pub async fn generate_tagged(
+        &self,
+        tag: Option<&str>,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        self.route(tag, prompt, |provider| {
+            provider.generate_with_config(prompt, config)
+        })
+        .await
+    }
+
+    pub async fn chat_tagged(
+        &self,
+        tag: Option<&str>,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        let text: String = messages.iter().map(|m| m.content.as_str()).collect();
+        self.route(tag, &text, |provider| {
+            provider.chat_with_usage(messages, config)
+        })
+        .await
+    }
+
+    pub async fn chat_json_tagged(
+        &self,
+        tag: Option<&str>,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        let text: String = messages.iter().map(|m| m.content.as_str()).collect();
+        self.route(tag, &text, |provider| {
+            provider.chat_json_with_usage(messages, schema, config)
+        })
+        .await
+    }
+
+    pub async fn chat_tools_tagged(
+        &self,
+        tag: Option<&str>,
+        messages: &[ChatMessage],
+        tools: &[ToolSpec],
+        config: &LlmConfig,
+    ) -> Result<Option<ToolReply>, LLMError> {
+        let text: String = messages.iter().map(|m| m.content.as_str()).collect();
+        self.route(tag, &text, |provider| {
+            provider.chat_tools(messages, tools, config)
+        })
+        .await
+    }
+
+    /// Fails over only while opening the stream; a stream that breaks
+    /// midway ends in an error
+    pub async fn stream_tagged(
+        &self,
+        tag: Option<&str>,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<(TokenStream, StreamUsage), LLMError> {
+        self.route(tag, prompt, |provider| {
+            provider.generate_stream_with_usage(prompt, config)
+        })
+        .await
+    }
+
+    /// Probe every route once, concurrently
+    pub async fn probe_all(&self) {
+        let probes = self.routes.iter().map(|route| async move {
+            let started = Instant::now();
+            let result = route.provider.health_check().await;
+            route.record_probe(result, started.elapsed());
+        });
+        futures_util::future::join_all(probes).await;
+    }
+
+    pub fn snapshot(&self) -> RouterSnapshot {
+        let last_route = *self.last_route.lock().unwrap();
+        RouterSnapshot {
+            failover: self.config.failover,
+            strategy: self.config.strategy,
+            probe_interval_secs: self.config.probe_interval.as_secs(),
+            breaker_failures: self.config.breaker_failures,
+            breaker_cooldown_secs: self.config.breaker_cooldown.as_secs(),
+            last_route: last_route.map(|i| self.routes[i].name.clone()),
+            routes: self.routes.iter().map(Route::status).collect(),
+        }
+    }
+
+    fn current(&self) -> &Route {
+        let last_route = *self.last_route.lock().unwrap();
+        &self.routes[last_route.unwrap_or(0)]
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for RoutedProvider {
+    async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+        let config = llm_settings::global_config();
+        self.generate_tagged(None, prompt, &config).await
+    }
+
+    async fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        self.generate_tagged(None, prompt, config).await
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], config: &LlmConfig) -> Result<String, LLMError> {
+        Ok(self.chat_tagged(None, messages, config).await?.text)
+    }
+
+    async fn chat_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        self.chat_tagged(None, messages, config).await
+    }
+
+    async fn chat_json(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        Ok(self
+            .chat_json_tagged(None, messages, schema, config)
+            .await?
+            .text)
+    }
+
+    async fn chat_json_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        self.chat_json_tagged(None, messages, schema, config).await
+    }
+
+    async fn chat_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolSpec],
+        config: &LlmConfig,
+    ) -> Result<Option<ToolReply>, LLMError> {
+        self.chat_tools_tagged(None, messages, tools, config).await
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<TokenStream, LLMError> {
+        Ok(self.stream_tagged(None, prompt, config).await?.0)
+    }
+
+    async fn generate_stream_with_usage(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<(TokenStream, StreamUsage), LLMError> {
+        self.stream_tagged(None, prompt, config).await
+    }
+
+    /// Model of the route that served the most recent request
+    fn model_name(&self) -> &str {
+        self.current().provider.model_name()
+    }
+
+    /// Up when any route is up
+    async fn health_check(&self) -> Result<(), LLMError> {
+        let mut last_err = None;
+        for route in &self.routes {
+            match route.provider.health_check().await {
+                Ok(()) => return Ok(()),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| LLMError::ConfigError("no LLM routes".to_string())))
+    }
+}
+
+/// Router view that tags every request, e.g. to send summaries to a
+/// cheaper model
+pub struct TaggedProvider {
+    router: Arc<RoutedProvider>,
+    tag: String,
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for TaggedProvider {
+    async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+        let config = llm_settings::global_config();
+        self.generate_with_config(prompt, &config).await
+    }
+
+    async fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        self.router
+            .generate_tagged(Some(&self.tag), prompt, config)
+            .await
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], config: &LlmConfig) -> Result<String, LLMError> {
+        Ok(self.chat_with_usage(messages, config).await?.text)
+    }
+
+    async fn chat_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        self.router
+            .chat_tagged(Some(&self.tag), messages, config)
+            .await
+    }
+
+    async fn chat_json(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        Ok(self
+            .chat_json_with_usage(messages, schema, config)
+            .await?
+            .text)
+    }
+
+    async fn chat_json_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        self.router
+            .chat_json_tagged(Some(&self.tag), messages, schema, config)
+            .await
+    }
+
+    async fn chat_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolSpec],
+        config: &LlmConfig,
+    ) -> Result<Option<ToolReply>, LLMError> {
+        self.router
+            .chat_tools_tagged(Some(&self.tag), messages, tools, config)
+            .await
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<TokenStream, LLMError> {
+        Ok(self.generate_stream_with_usage(prompt, config).await?.0)
+    }
+
+    async fn generate_stream_with_usage(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<(TokenStream, StreamUsage), LLMError> {
+        self.router
+            .stream_tagged(Some(&self.tag), prompt, config)
+            .await
+    }
+
+    fn model_name(&self) -> &str {
+        self.router.model_name()
+    }
+
+    async fn health_check(&self) -> Result<(), LLMError> {
+        self.router.health_check().await
+    }
+}
+
+/// The backend selected in the hardware config first, then local Ollama
+/// and the hosted APIs that have a key stored
+pub fn default_routes(hardware: &HardwareParams) -> Vec<RouteSpec> {
+    let spec = |name: &str, config: LLMConfig, priority: i32| RouteSpec {
+        name: name.to_string(),
+        local: None,
+        config,
+        priority,
+        tags: Vec::new(),
+        min_prompt_tokens: None,
+        max_prompt_tokens: None,
+        cost_per_1k_tokens: 0.0,
+    };
+    let primary = hardware.backend_type;
+    let mut specs = vec![spec(
+        route_name(&primary),
+        LLMConfig::from_hardware(hardware),
+        0,
+    )];
+
+    if primary != BackendType::Ollama {
+        let ollama = HardwareParams {
+            backend_type: BackendType::Ollama,
+            model: String::new(),
+            ..hardware.clone()
+        };
+        specs.push(spec("ollama", LLMConfig::from_hardware(&ollama), 10));
+    }
+    let keys = api_keys::global_config();
+    if primary != BackendType::OpenAi && keys.get_openai_key().is_some() {
+        specs.push(spec(
+            "openai",
+            LLMConfig::OpenAI {
+                api_key: String::new(),
+                model: "gpt-4o-mini".to_string(),
+                base_url: crate::memory::openai_provider::DEFAULT_BASE_URL.to_string(),
+            },
+            20,
+        ));
+    }
+    if primary != BackendType::Anthropic
+        && keys.get_anthropic_key().is_some_and(|key| !key.is_empty())
+    {
+        specs.push(spec(
+            "anthropic",
+            LLMConfig::Anthropic {
+                api_key: String::new(),
+                model: anthropic_provider::DEFAULT_MODEL.to_string(),
+                base_url: anthropic_provider::default_base_url(),
+                system_prompt: None,
+            },
+            30,
+        ));
+    }
+    specs
+}
+
+fn route_name(backend: &BackendType) -> &'static str {
+    match backend {
+        BackendType::Ollama => "ollama",
+        BackendType::LlamaCpp => "llama_cpp",
+        BackendType::OpenAi => "openai",
+        BackendType::Anthropic => "anthropic",
+        BackendType::Vllm => "vllm",
+        BackendType::Custom => "custom",
+    }
+}
+
+static ROUTER: Lazy<RwLock<Option<Arc<RoutedProvider>>>> = Lazy::new(|| RwLock::new(None));
+
+/// The installed router, if any
+pub fn global() -> Option<Arc<RoutedProvider>> {
+    ROUTER.read().unwrap().clone()
+}
+
+/// Build the router for the active hardware config, install it and start
+/// its health probes. The previous router's probe task stops on its own
+pub fn reload() -> Result<Arc<RoutedProvider>, LLMError> {
+    let router = Arc::new(RoutedProvider::from_env(&param_hardware::global_config())?);
+    info!(
+        routes = ?router.routes.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+        failover = ?router.config.failover,
+        strategy = ?router.config.strategy,
+        "LLM router installed"
+    );
+    *ROUTER.write().unwrap() = Some(Arc::clone(&router));
+    start_health_probes(&router);
+    Ok(router)
+}
+
+/// Probe every route periodically until the router is dropped
+pub fn start_health_probes(router: &Arc<RoutedProvider>) -> tokio::task::JoinHandle<()> {
+    let weak = Arc::downgrade(router);
+    let interval = router.config.probe_interval;
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let Some(router) = weak.upgrade() else {
+                debug!("LLM router replaced; stopping health probes");
+                break;
+            };
+            router.probe_all().await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+
+    /// Provider that answers with its name or fails on demand
+    struct Scripted {
+        name: &'static str,
+        failing: AtomicBool,
+        calls: AtomicUsize,
+    }
+
+    impl Scripted {
+        fn new(name: &'static str) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                failing: AtomicBool::new(false),
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn failing(name: &'static str) -> Arc<Self> {
+            let provider = Self::new(name);
+            provider.failing.store(true, Ordering::SeqCst);
+            provider
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for Scripted {
+        async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+            self.generate_with_config(prompt, &LlmConfig::default())
+                .await
+        }
+
+        async fn generate_with_config(
+            &self,
+            _prompt: &str,
+            _config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                Err(LLMError::ConnectionFailed(format!("{} is down", self.name)))
+            } else {
+                Ok(self.name.to_string())
+            }
+        }
+
+        fn model_name(&self) -> &str {
+            self.name
+        }
+
+        async fn health_check(&self) -> Result<(), LLMError> {
+            if self.failing.load(Ordering::SeqCst) {
+                Err(LLMError::ConnectionFailed("down".into()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn route(name: &str, provider: &Arc<Scripted>, priority: i32) -> Route {
+        Route::new(name, provider.clone() as Arc<dyn LLMProvider>).with_priority(priority)
+    }
+
+    async fn ask(router: &RoutedProvider, tag: Option<&str>, prompt: &str) -> String {
+        router
+            .generate_tagged(tag, prompt, &LlmConfig::default())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_to_next_route() {
+        let local = Scripted::failing("local");
+        let cloud = Scripted::new("cloud");
+        let router = RoutedProvider::new(
+            vec![
+                route("t1-local", &local, 0),
+                route("t1-cloud", &cloud, 1).with_local(false),
+            ],
+            RouterConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(ask(&router, None, "hi").await, "cloud");
+        assert_eq!(router.model_name(), "cloud");
+        assert_eq!(
+            LLM_FAILOVERS_TOTAL
+                .with_label_values(&["t1-local", "t1-cloud"])
+                .get(),
+            1
+        );
+        let snapshot = router.snapshot();
+        assert_eq!(snapshot.last_route.as_deref(), Some("t1-cloud"));
+        assert_eq!(snapshot.routes[0].failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_breaker_opens_then_recovers_after_cooldown() {
+        let flaky = Scripted::failing("flaky");
+        let backup = Scripted::new("backup");
+        let config = RouterConfig {
+            breaker_failures: 2,
+            breaker_cooldown: Duration::from_millis(50),
+            ..RouterConfig::default()
+        };
+        let router = RoutedProvider::new(
+            vec![route("t2-flaky", &flaky, 0), route("t2-backup", &backup, 1)],
+            config,
+        )
+        .unwrap();
+
+        ask(&router, None, "a").await;
+        ask(&router, None, "b").await;
+        assert_eq!(router.routes()[0].breaker_state(), BreakerState::Open);
+        // Open circuit: the flaky route is not called at all
+        ask(&router, None, "c").await;
+        assert_eq!(flaky.calls(), 2);
+
+        flaky.failing.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(ask(&router, None, "d").await, "flaky");
+        assert_eq!(router.routes()[0].breaker_state(), BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_half_open_trial_failure_reopens() {
+        let flaky = Scripted::failing("flaky");
+        let backup = Scripted::new("backup");
+        let config = RouterConfig {
+            breaker_failures: 1,
+            breaker_cooldown: Duration::from_millis(20),
+            ..RouterConfig::default()
+        };
+        let router = RoutedProvider::new(
+            vec![route("t3-flaky", &flaky, 0), route("t3-backup", &backup, 1)],
+            config,
+        )
+        .unwrap();
+
+        ask(&router, None, "a").await;
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(ask(&router, None, "b").await, "backup");
+        assert_eq!(flaky.calls(), 2);
+        assert_eq!(router.routes()[0].breaker_state(), BreakerState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_rules_route_by_tag_and_prompt_size() {
+        let small = Scripted::new("small");
+        let large = Scripted::new("large");
+        let summarizer = Scripted::new("summarizer");
+        let router = RoutedProvider::new(
+            vec![
+                route("t4-small", &small, 0).with_rule(RouteRule {
+                    max_prompt_tokens: Some(100),
+                    ..RouteRule::default()
+                }),
+                route("t4-large", &large, 1),
+                route("t4-summary", &summarizer, 5).with_rule(RouteRule {
+                    tags: vec!["summary".to_string()],
+                    ..RouteRule::default()
+                }),
+            ],
+            RouterConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(ask(&router, None, "short question").await, "small");
+        assert_eq!(ask(&router, None, &"word ".repeat(500)).await, "large");
+        // Tagged routes win over catch-all routes and serve only their tag
+        assert_eq!(ask(&router, Some("summary"), "text").await, "summarizer");
+        let router = Arc::new(router);
+        let tagged = router.tagged("summary");
+        assert_eq!(tagged.generate("text").await.unwrap(), "summarizer");
+        assert_eq!(summarizer.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failover_modes_and_cost() {
+        let local = Scripted::new("local");
+        let cloud = Scripted::new("cloud");
+        let routes = || {
+            vec![
+                route("t5-cloud", &cloud, 0)
+                    .with_local(false)
+                    .with_cost(0.5),
+                route("t5-local", &local, 1),
+            ]
+        };
+
+        let local_first = RouterConfig {
+            failover: FailoverMode::LocalFirst,
+            ..RouterConfig::default()
+        };
+        let router = RoutedProvider::new(routes(), local_first).unwrap();
+        assert_eq!(ask(&router, None, "q").await, "local");
+
+        let router = RoutedProvider::new(routes(), RouterConfig::default()).unwrap();
+        assert_eq!(ask(&router, None, "q").await, "cloud");
+
+        let cheapest = RouterConfig {
+            strategy: RoutingStrategy::Cheapest,
+            ..RouterConfig::default()
+        };
+        let router = RoutedProvider::new(routes(), cheapest).unwrap();
+        assert_eq!(ask(&router, None, "q").await, "local");
+
+        // No failover: the cloud error is returned as is
+        cloud.failing.store(true, Ordering::SeqCst);
+        let off = RouterConfig {
+            failover: FailoverMode::Off,
+            ..RouterConfig::default()
+        };
+        let router = RoutedProvider::new(routes(), off).unwrap();
+        assert!(router
+            .generate_tagged(None, "q", &LlmConfig::default())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe_opens_circuit() {
+        let down = Scripted::failing("down");
+        let up = Scripted::new("up");
+        let router = RoutedProvider::new(
+            vec![route("t6-down", &down, 0), route("t6-up", &up, 1)],
+            RouterConfig::default(),
+        )
+        .unwrap();
+
+        router.probe_all().await;
+        let snapshot = router.snapshot();
+        assert_eq!(snapshot.routes[0].healthy, Some(false));
+        assert_eq!(snapshot.routes[0].breaker, BreakerState::Open);
+        assert_eq!(snapshot.routes[1].healthy, Some(true));
+        assert_eq!(ask(&router, None, "q").await, "up");
+        assert_eq!(down.calls(), 0);
+
+        down.failing.store(false, Ordering::SeqCst);
+        router.probe_all().await;
+        assert_eq!(ask(&router, None, "q").await, "down");
+    }
+
+    #[test]
+    fn test_route_spec_parses() {
+        let specs: Vec<RouteSpec> = serde_json::from_str(
+            r#"[{"name":"gpu","config":{"OpenAI":{"api_key":"","model":"m","base_url":"http://localhost:8000/v1"}},"tags":["code"],"cost_per_1k_tokens":0.1}]"#,
+        )
+        .unwrap();
+        let route = Route::from_spec(specs[0].clone()).unwrap();
+        assert!(route.local);
+        assert_eq!(route.rule.tags, vec!["code"]);
+        assert_eq!(
+            FailoverMode::parse("cloud_first"),
+            Some(FailoverMode::CloudFirst)
+        );
+    }
+}
@@ -1,12 +1,22 @@
 // src/memory/mod.rs
 
 pub mod agent;
+pub mod anthropic_provider;
+pub mod chunk_header;
 pub mod chunker;
 pub mod chunker_factory;
+pub mod citations;
+pub mod context_budget;
 pub mod decision_engine;
+pub mod grounding;
+pub mod llm_http;
 pub mod llm_provider;
+pub mod llm_router;
+pub mod openai_provider;
 pub mod persistence;
 pub mod query;
+pub mod structured;
+pub mod tokenizer;
 pub mod vector_store;
 // pub mod multi_agent;  // TODO: Fix after core is stable
 
@@ -14,15 +24,29 @@ pub use agent::{
     Agent, AgentContext, AgentMemoryLayer, Episode, Goal, GoalStatus, Reflection, ReflectionType,
     Task, TaskStatus,
 };
-pub use chunker::{Chunk, ChunkMetadata, ChunkerConfig, SemanticChunker, SourceType};
+pub use anthropic_provider::AnthropicProvider;
+pub use chunker::{ChildSplit, Chunk, ChunkMetadata, ChunkerConfig, SemanticChunker, SourceType};
+pub use citations::{Citation, CitationReport, CitationSource, CitationStatus};
+pub use context_budget::{BudgetUsage, ContextBudget};
 pub use decision_engine::{
-    Decision, DecisionEngine, ExecutionPlan, ExecutionResult, PlanStep, Tool,
+    AgentBudget, AgentStep, Decision, DecisionEngine, ExecutionPlan, ExecutionResult, PlanStep,
+    StopReason, Tool,
 };
-pub use llm_provider::{create_llm_provider, LLMConfig, LLMError, LLMProvider};
+pub use grounding::{GroundingConfig, GroundingMethod, GroundingReport};
+pub use llm_provider::{
+    build_llm_provider, create_configured_provider, create_llm_provider, render_chat_prompt,
+    ChatMessage, Completion, LLMConfig, LLMError, LLMProvider, Role, StreamUsage, TokenStream,
+    TokenUsage,
+};
+pub use llm_router::{FailoverMode, RoutedProvider, RouterConfig, RoutingStrategy};
+pub use openai_provider::OpenAIProvider;
 pub use persistence::{backup_vector_store, load_vector_store, save_vector_store};
 pub use query::{
-    ContextChunk, RagConfig, RagError, RagQueryPipeline, RagQueryRequest, RagQueryResponse,
+    AnswerProvider, ContextChunk, ContextExpansion, RagConfig, RagError, RagQueryPipeline, RagQueryRequest,
+    RagQueryResponse,
 };
+pub use structured::{SchemaError, StructuredError, StructuredOutput};
 pub use vector_store::{
-    SearchResult, StoreStats, VectorRecord, VectorStore, VectorStoreConfig, VectorStoreError,
+    ParentRecord, SearchResult, StoreStats, VectorRecord, VectorStore, VectorStoreConfig,
+    VectorStoreError,
 };
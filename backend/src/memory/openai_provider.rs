@@ -0,0 +1,858 @@
+// src/memory/openai_provider.rs
+// OpenAI-compatible chat completions provider
+// Speaks POST {base_url}/chat/completions, so the same client covers the
+// OpenAI API and local servers such as vLLM, llama.cpp server and LM Studio
+
+use crate::db::llm_settings::{self, LlmConfig};
+use crate::memory::llm_http::{self, Attempt, RetryPolicy, UsageTracker};
+use crate::memory::llm_provider::{
+    ChatMessage, Completion, LLMError, LLMProvider, Role, StreamUsage, TokenStream, TokenUsage,
+    ToolCall, ToolReply, ToolSpec,
+};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+/// The OpenAI API rejects more than four stop sequences
+const MAX_STOP_SEQUENCES: usize = 4;
+
+/// Base URL from OPENAI_BASE_URL, else the OpenAI API
+pub fn default_base_url() -> String {
+    std::env::var("OPENAI_BASE_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+}
+
+/// True for the hosted OpenAI API, which rejects non-standard sampling params
+pub fn is_openai_host(base_url: &str) -> bool {
+    base_url.contains("api.openai.com")
+}
+
+pub struct OpenAIProvider {
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    /// Send top_k, min_p and repeat_penalty (understood by local servers)
+    extended_params: bool,
+    usage: UsageTracker,
+}
+
+#[derive(Serialize)]
+struct WireMessage<'a> {
+    role: &'a str,
+    /// Null on an assistant turn that only carries tool calls
+    content: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<WireToolCallOut>,
+}
+
+#[derive(Serialize)]
+struct WireToolCallOut {
+    id: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    function: WireFunctionCallOut,
+}
+
+#[derive(Serialize)]
+struct WireFunctionCallOut {
+    name: String,
+    /// JSON text, as the API returns it
+    arguments: String,
+}
+
+impl From<&ToolCall> for WireToolCallOut {
+    fn from(call: &ToolCall) -> Self {
+        Self {
+            id: call.id.clone(),
+            kind: "function",
+            function: WireFunctionCallOut {
+                name: call.name.clone(),
+                arguments: call.arguments_json(),
+            },
+        }
+    }
+}
+
+/// The API rejects a tool message unless an earlier assistant message
+/// declared its call id, so results for undeclared calls go as user turns
+fn wire_messages(messages: &[ChatMessage]) -> Vec<WireMessage<'_>> {
+    let mut declared: HashSet<&str> = HashSet::new();
+    messages
+        .iter()
+        .map(|message| {
+            declared.extend(message.tool_calls.iter().map(|call| call.id.as_str()));
+            match (message.role, message.tool_call_id.as_deref()) {
+                (Role::Tool, Some(id)) if declared.contains(id) => WireMessage {
+                    role: "tool",
+                    content: Some(Cow::Borrowed(&message.content)),
+                    tool_call_id: Some(id),
+                    tool_calls: Vec::new(),
+                },
+                (Role::Tool, _) => WireMessage {
+                    role: "user",
+                    content: Some(Cow::Owned(format!("Tool result: {}", message.content))),
+                    tool_call_id: None,
+                    tool_calls: Vec::new(),
+                },
+                (role, _) => WireMessage {
+                    role: role.as_str(),
+                    content: (!message.content.is_empty() || message.tool_calls.is_empty())
+                        .then_some(Cow::Borrowed(message.content.as_str())),
+                    tool_call_id: None,
+                    tool_calls: message
+                        .tool_calls
+                        .iter()
+                        .map(WireToolCallOut::from)
+                        .collect(),
+                },
+            }
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<WireMessage<'a>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    temperature: f32,
+    top_p: f32,
+    max_tokens: usize,
+    frequency_penalty: f32,
+    presence_penalty: f32,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repeat_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+}
+
+/// Structured outputs: `{"type": "json_schema", "json_schema": {..}}`
+#[derive(Serialize)]
+struct ResponseFormat<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    json_schema: JsonSchemaFormat<'a>,
+}
+
+#[derive(Serialize)]
+struct JsonSchemaFormat<'a> {
+    name: &'static str,
+    schema: &'a serde_json::Value,
+    /// Strict mode rejects schemas without additionalProperties: false on
+    /// every object, which callers' schemas rarely have
+    strict: bool,
+}
+
+/// Ask for a final chunk carrying usage when streaming
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    #[serde(default)]
+    choices: Vec<ChatChoice>,
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatChoiceMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoiceMessage {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<WireToolCall>>,
+}
+
+#[derive(Deserialize)]
+struct WireToolCall {
+    #[serde(default)]
+    id: String,
+    function: WireFunctionCall,
+}
+
+/// `arguments` is a JSON-encoded string
+#[derive(Deserialize)]
+struct WireFunctionCall {
+    name: String,
+    #[serde(default)]
+    arguments: String,
+}
+
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: ChunkDelta,
+}
+
+#[derive(Deserialize, Default)]
+struct ChunkDelta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorEnvelope {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    message: String,
+}
+
+impl OpenAIProvider {
+    pub fn new(base_url: String, api_key: Option<String>, model: String) -> Self {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        Self {
+            extended_params: !is_openai_host(&base_url),
+            base_url,
+            api_key: api_key.filter(|key| !key.is_empty()),
+            client: llm_http::build_client(llm_http::DEFAULT_TIMEOUT),
+            retry: RetryPolicy::default(),
+            usage: UsageTracker::new("openai", &model),
+            model,
+        }
+    }
+
+    /// Retries after 429, 5xx and connection errors (default 3)
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.retry.max_retries = max_retries;
+        self
+    }
+
+    /// First retry delay, doubled on each attempt (default 500ms)
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.retry.backoff = backoff;
+        self
+    }
+
+    /// Per-request timeout (default 120s)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = llm_http::build_client(timeout);
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Tokens used by all generations through this provider
+    pub fn total_usage(&self) -> TokenUsage {
+        self.usage.total()
+    }
+
+    fn request<'a>(
+        &'a self,
+        messages: &'a [ChatMessage],
+        config: &'a LlmConfig,
+        stream: bool,
+    ) -> ChatRequest<'a> {
+        let stop = if config.stop_sequences.len() > MAX_STOP_SEQUENCES {
+            warn!(
+                count = config.stop_sequences.len(),
+                "Only the first {} stop sequences are sent", MAX_STOP_SEQUENCES
+            );
+            &config.stop_sequences[..MAX_STOP_SEQUENCES]
+        } else {
+            &config.stop_sequences[..]
+        };
+        let extended = self.extended_params;
+        ChatRequest {
+            model: &self.model,
+            messages: wire_messages(messages),
+            stream,
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+            temperature: config.temperature,
+            top_p: config.top_p,
+            max_tokens: config.max_tokens,
+            frequency_penalty: config.frequency_penalty,
+            presence_penalty: config.presence_penalty,
+            stop,
+            seed: config.seed,
+            top_k: extended.then_some(config.top_k),
+            min_p: extended.then_some(config.min_p),
+            repeat_penalty: extended.then_some(config.repeat_penalty),
+            response_format: None,
+            tools: Vec::new(),
+        }
+    }
+
+    async fn attempt(
+        &self,
+        request: &ChatRequest<'_>,
+    ) -> Attempt<(ChatChoiceMessage, Option<TokenUsage>)> {
+        match self.send(request).await {
+            Attempt::Done(response) => match response.json::<ChatResponse>().await {
+                Ok(body) => self.finish(body),
+                Err(e) => Attempt::Fail(LLMError::InvalidResponse(e.to_string())),
+            },
+            Attempt::Retry(err, retry_after) => Attempt::Retry(err, retry_after),
+            Attempt::Fail(err) => Attempt::Fail(err),
+        }
+    }
+
+    /// POST the request; a successful response is returned unread
+    async fn send(&self, request: &ChatRequest<'_>) -> Attempt<reqwest::Response> {
+        let url = format!("{}/chat/completions", self.base_url);
+        let mut builder = self.client.post(&url).json(request);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+
+        let response = match builder.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                return Attempt::Retry(
+                    LLMError::ConnectionFailed(format!("{}: {}", self.base_url, e)),
+                    None,
+                )
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Attempt::Done(response);
+        }
+
+        let retry_after = llm_http::retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<ErrorEnvelope>(&body)
+            .map(|envelope| envelope.error.message)
+            .unwrap_or(body);
+        llm_http::classify_status(status.as_u16(), message, retry_after)
+    }
+
+    fn finish(&self, body: ChatResponse) -> Attempt<(ChatChoiceMessage, Option<TokenUsage>)> {
+        if let Some(usage) = body.usage {
+            self.usage.record(usage);
+        }
+        let Some(choice) = body.choices.into_iter().next() else {
+            return Attempt::Fail(LLMError::InvalidResponse("no choices returned".into()));
+        };
+        if choice.finish_reason.as_deref() == Some("length") {
+            debug!(model = %self.model, "Generation stopped at max_tokens");
+        }
+        Attempt::Done((choice.message, body.usage))
+    }
+
+    /// Send a non-streaming request with retries; the message comes back
+    /// with the usage reported for it
+    async fn complete_message(
+        &self,
+        request: &ChatRequest<'_>,
+    ) -> Result<(ChatChoiceMessage, Option<TokenUsage>), LLMError> {
+        let ((message, reported), retries) = self
+            .retry
+            .run(&self.model, || self.attempt(request))
+            .await?;
+        let usage = reported.unwrap_or_default();
+        info!(
+            model = %self.model,
+            response_len = message.content.as_ref().map_or(0, String::len),
+            tool_calls = message.tool_calls.as_ref().map_or(0, Vec::len),
+            prompt_tokens = usage.prompt_tokens,
+            completion_tokens = usage.completion_tokens,
+            retries,
+            "Generation complete"
+        );
+        Ok((message, reported))
+    }
+
+    /// Text of a non-streaming completion
+    async fn complete(&self, request: &ChatRequest<'_>) -> Result<Completion, LLMError> {
+        match self.complete_message(request).await? {
+            (
+                ChatChoiceMessage {
+                    content: Some(content),
+                    ..
+                },
+                usage,
+            ) => Ok(Completion {
+                text: content.trim().to_string(),
+                usage,
+            }),
+            _ => Err(LLMError::InvalidResponse("empty message content".into())),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for OpenAIProvider {
+    async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+        let config = llm_settings::global_config();
+        self.generate_with_config(prompt, &config).await
+    }
+
+    async fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        self.chat(&[ChatMessage::user(prompt)], config).await
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], config: &LlmConfig) -> Result<String, LLMError> {
+        Ok(self.chat_with_usage(messages, config).await?.text)
+    }
+
+    async fn chat_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        debug!(
+            model = %self.model,
+            base_url = %self.base_url,
+            messages = messages.len(),
+            temperature = config.temperature,
+            top_p = config.top_p,
+            max_tokens = config.max_tokens,
+            "Generating with chat completions"
+        );
+        let request = self.request(messages, config, false);
+        self.complete(&request).await
+    }
+
+    async fn chat_json(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<String, LLMError> {
+        Ok(self
+            .chat_json_with_usage(messages, schema, config)
+            .await?
+            .text)
+    }
+
+    async fn chat_json_with_usage(
+        &self,
+        messages: &[ChatMessage],
+        schema: &serde_json::Value,
+        config: &LlmConfig,
+    ) -> Result<Completion, LLMError> {
+        debug!(
+            model = %self.model,
+            base_url = %self.base_url,
+            messages = messages.len(),
+            "Generating structured output with chat completions"
+        );
+        let mut request = self.request(messages, config, false);
+        request.response_format = Some(ResponseFormat {
+            kind: "json_schema",
+            json_schema: JsonSchemaFormat {
+                name: "response",
+                schema,
+                strict: false,
+            },
+        });
+        self.complete(&request).await
+    }
+
+    async fn chat_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolSpec],
+        config: &LlmConfig,
+    ) -> Result<Option<ToolReply>, LLMError> {
+        debug!(
+            model = %self.model,
+            base_url = %self.base_url,
+            messages = messages.len(),
+            tools = tools.len(),
+            "Generating with function calling"
+        );
+        let mut request = self.request(messages, config, false);
+        request.tools = llm_http::function_tools(tools);
+        let (message, _) = self.complete_message(&request).await?;
+        Ok(Some(ToolReply {
+            content: message.content.unwrap_or_default().trim().to_string(),
+            tool_calls: message
+                .tool_calls
+                .unwrap_or_default()
+                .into_iter()
+                .map(|call| ToolCall {
+                    arguments: serde_json::from_str(&call.function.arguments)
+                        .unwrap_or(serde_json::Value::String(call.function.arguments)),
+                    id: call.id,
+                    name: call.function.name,
+                })
+                .collect(),
+        }))
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<TokenStream, LLMError> {
+        Ok(self.generate_stream_with_usage(prompt, config).await?.0)
+    }
+
+    async fn generate_stream_with_usage(
+        &self,
+        prompt: &str,
+        config: &LlmConfig,
+    ) -> Result<(TokenStream, StreamUsage), LLMError> {
+        debug!(model = %self.model, prompt_len = prompt.len(), "Streaming chat completion");
+        let messages = [ChatMessage::user(prompt)];
+        let request = self.request(&messages, config, true);
+        // Only the request is retried; a stream that breaks midway ends in an error
+        let (response, _) = self.retry.run(&self.model, || self.send(&request)).await?;
+
+        let tracker = self.usage.clone();
+        let stream_usage = StreamUsage::default();
+        let reported_usage = stream_usage.clone();
+        let tokens = llm_http::lines(response)
+            .take_while(|line| {
+                let done = matches!(line, Ok(line) if llm_http::sse_data(line) == Some("[DONE]"));
+                std::future::ready(!done)
+            })
+            .filter_map(move |line| {
+                let tracker = tracker.clone();
+                let reported_usage = reported_usage.clone();
+                async move {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    let data = llm_http::sse_data(&line)?;
+                    match serde_json::from_str::<ChatChunk>(data) {
+                        Ok(chunk) => {
+                            if let Some(reported) = chunk.usage {
+                                tracker.record(reported);
+                                reported_usage.set(reported);
+                            }
+                            chunk
+                                .choices
+                                .into_iter()
+                                .next()
+                                .and_then(|choice| choice.delta.content)
+                                .filter(|content| !content.is_empty())
+                                .map(Ok)
+                        }
+                        Err(e) => Some(Err(LLMError::InvalidResponse(e.to_string()))),
+                    }
+                }
+            });
+        Ok((Box::pin(tokens), stream_usage))
+    }
+
+    async fn health_check(&self) -> Result<(), LLMError> {
+        let mut request = self.client.get(format!("{}/models", self.base_url));
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        llm_http::probe(request, &self.base_url).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::llm_http::mock::serve;
+
+    fn completion(text: &str) -> String {
+        serde_json::json!({
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": text}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 5, "total_tokens": 17}
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_maps_params_and_usage() {
+        let (base, requests) = serve(vec![(200, "", completion(" Paris. "))]).await;
+        let provider = OpenAIProvider::new(format!("{}/v1", base), None, "local-model".into());
+        let config = LlmConfig {
+            temperature: 0.2,
+            max_tokens: 64,
+            seed: Some(7),
+            stop_sequences: (0..6).map(|i| format!("<s{}>", i)).collect(),
+            ..LlmConfig::default()
+        };
+
+        let completion = provider
+            .chat_with_usage(&[ChatMessage::user("Capital of France?")], &config)
+            .await
+            .unwrap();
+        assert_eq!(completion.text, "Paris.");
+        assert_eq!(completion.usage.unwrap().total_tokens, 17);
+        assert_eq!(provider.total_usage().completion_tokens, 5);
+
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["model"], "local-model");
+        assert_eq!(sent["messages"][0]["role"], "user");
+        assert_eq!(sent["messages"][0]["content"], "Capital of France?");
+        assert_eq!(sent["max_tokens"], 64);
+        assert_eq!(sent["seed"], 7);
+        assert_eq!(sent["stop"].as_array().unwrap().len(), MAX_STOP_SEQUENCES);
+        // Local servers get the extended sampling params
+        assert_eq!(sent["top_k"], config.top_k);
+    }
+
+    #[tokio::test]
+    async fn test_retries_rate_limit_then_succeeds() {
+        let (base, requests) = serve(vec![
+            (
+                429,
+                "Retry-After: 0\r\n",
+                r#"{"error":{"message":"slow down"}}"#.to_string(),
+            ),
+            (503, "", "overloaded".to_string()),
+            (200, "", completion("ok")),
+        ])
+        .await;
+        let provider = OpenAIProvider::new(base, Some("sk-test".into()), "m".into())
+            .with_backoff(Duration::from_millis(1));
+
+        let text = provider
+            .generate_with_config("hi", &LlmConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(text, "ok");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].0.contains("authorization: bearer sk-test"));
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_classified() {
+        let (base, _) = serve(vec![(
+            401,
+            "",
+            r#"{"error":{"message":"Incorrect API key"}}"#.to_string(),
+        )])
+        .await;
+        let provider = OpenAIProvider::new(base, Some("bad".into()), "m".into());
+        match provider
+            .generate_with_config("hi", &LlmConfig::default())
+            .await
+        {
+            Err(LLMError::ConfigError(msg)) => assert!(msg.contains("Incorrect API key")),
+            other => panic!("expected ConfigError, got {:?}", other),
+        }
+
+        let (base, requests) = serve(vec![
+            (429, "", "{}".to_string()),
+            (429, "", "{}".to_string()),
+        ])
+        .await;
+        let provider = OpenAIProvider::new(base, None, "m".into())
+            .with_max_retries(1)
+            .with_backoff(Duration::from_millis(1));
+        assert!(matches!(
+            provider
+                .generate_with_config("hi", &LlmConfig::default())
+                .await,
+            Err(LLMError::RateLimited(_))
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls_keep_their_own_usage() {
+        let reply = |text: &str, total: u64| {
+            serde_json::json!({
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": text}}],
+                "usage": {"prompt_tokens": total - 1, "completion_tokens": 1, "total_tokens": total}
+            })
+            .to_string()
+        };
+        let (base, _) = serve(vec![
+            (200, "", reply("short", 3)),
+            (200, "", reply("long", 70)),
+        ])
+        .await;
+        let provider = OpenAIProvider::new(base, None, "m".into());
+        let config = LlmConfig::default();
+        let first = [ChatMessage::user("a")];
+        let second = [ChatMessage::user("b")];
+        let (a, b) = tokio::join!(
+            provider.chat_with_usage(&first, &config),
+            provider.chat_with_usage(&second, &config)
+        );
+        for completion in [a.unwrap(), b.unwrap()] {
+            let expected = if completion.text == "short" { 3 } else { 70 };
+            assert_eq!(completion.usage.unwrap().total_tokens, expected);
+        }
+        assert_eq!(provider.total_usage().total_tokens, 73);
+    }
+
+    #[tokio::test]
+    async fn test_stream_yields_deltas_and_usage() {
+        let body = [
+            r#"data: {"choices":[{"index":0,"delta":{"role":"assistant"}}]}"#,
+            r#"data: {"choices":[{"index":0,"delta":{"content":"Hel"}}]}"#,
+            "",
+            r#"data: {"choices":[{"index":0,"delta":{"content":"lo"}}]}"#,
+            r#"data: {"choices":[],"usage":{"prompt_tokens":3,"completion_tokens":2,"total_tokens":5}}"#,
+            "data: [DONE]",
+            "",
+        ]
+        .join("\n");
+        let (base, requests) = serve(vec![(200, "", body)]).await;
+        let provider = OpenAIProvider::new(base, None, "m".into());
+        let (stream, usage) = provider
+            .generate_stream_with_usage("hi", &LlmConfig::default())
+            .await
+            .unwrap();
+        let tokens: Vec<String> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(tokens, vec!["Hel", "lo"]);
+        assert_eq!(usage.get().unwrap().total_tokens, 5);
+
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["stream"], true);
+        assert_eq!(sent["stream_options"]["include_usage"], true);
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_roles() {
+        let (base, requests) = serve(vec![(200, "", completion("4"))]).await;
+        let provider = OpenAIProvider::new(base, None, "m".into());
+        let messages = [
+            ChatMessage::system("You are a calculator."),
+            ChatMessage::user("2+2?"),
+            ChatMessage::assistant_tool_calls(
+                "Let me check.",
+                vec![ToolCall {
+                    id: "call_1".into(),
+                    name: "add".into(),
+                    arguments: serde_json::json!({"a": 2, "b": 2}),
+                }],
+            ),
+            ChatMessage::tool("call_1", "4"),
+            ChatMessage::tool("call_9", "undeclared"),
+            ChatMessage::new(Role::Tool, "unattributed"),
+        ];
+        let text = provider
+            .chat(&messages, &LlmConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(text, "4");
+
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        let roles: Vec<&str> = sent["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            roles,
+            vec!["system", "user", "assistant", "tool", "user", "user"]
+        );
+        // The tool result answers the call the assistant turn declared
+        let call = &sent["messages"][2]["tool_calls"][0];
+        assert_eq!(call["id"], "call_1");
+        assert_eq!(call["type"], "function");
+        assert_eq!(call["function"]["name"], "add");
+        let arguments: serde_json::Value =
+            serde_json::from_str(call["function"]["arguments"].as_str().unwrap()).unwrap();
+        assert_eq!(arguments, serde_json::json!({"a": 2, "b": 2}));
+        assert_eq!(sent["messages"][3]["tool_call_id"], "call_1");
+        assert!(sent["messages"][1].get("tool_calls").is_none());
+        assert_eq!(sent["messages"][4]["content"], "Tool result: undeclared");
+        assert!(sent["messages"][4].get("tool_call_id").is_none());
+        assert_eq!(sent["messages"][5]["content"], "Tool result: unattributed");
+    }
+
+    #[tokio::test]
+    async fn test_chat_json_requests_json_schema_format() {
+        let (base, requests) = serve(vec![(200, "", completion("{}"))]).await;
+        let provider = OpenAIProvider::new(base, None, "m".into());
+        let schema = serde_json::json!({"type": "object"});
+        provider
+            .chat_json(&[ChatMessage::user("json")], &schema, &LlmConfig::default())
+            .await
+            .unwrap();
+
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["response_format"]["type"], "json_schema");
+        assert_eq!(sent["response_format"]["json_schema"]["schema"], schema);
+        assert_eq!(sent["response_format"]["json_schema"]["strict"], false);
+    }
+
+    #[tokio::test]
+    async fn test_chat_tools_parses_function_calls() {
+        let body = serde_json::json!({
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": null, "tool_calls": [
+                {"id": "call_9", "type": "function", "function": {"name": "WebSearch", "arguments": "{\"query\": \"rust 2024\"}"}}
+            ]}, "finish_reason": "tool_calls"}]
+        })
+        .to_string();
+        let (base, requests) = serve(vec![(200, "", body)]).await;
+        let provider = OpenAIProvider::new(base, None, "m".into());
+        let tools = [ToolSpec {
+            name: "WebSearch".into(),
+            description: "Search the web".into(),
+            parameters: serde_json::json!({"type": "object"}),
+        }];
+        let reply = provider
+            .chat_tools(&[ChatMessage::user("news?")], &tools, &LlmConfig::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "");
+        assert_eq!(reply.tool_calls[0].id, "call_9");
+        assert_eq!(reply.tool_calls[0].arguments["query"], "rust 2024");
+
+        let sent: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(sent["tools"][0]["function"]["name"], "WebSearch");
+    }
+
+    #[test]
+    fn test_openai_host_omits_extended_params() {
+        let provider = OpenAIProvider::new(
+            "https://api.openai.com/v1/".into(),
+            Some("sk".into()),
+            "gpt-4o-mini".into(),
+        );
+        assert_eq!(provider.base_url(), "https://api.openai.com/v1");
+        let config = LlmConfig::default();
+        let messages = [ChatMessage::user("hi")];
+        let sent = serde_json::to_value(provider.request(&messages, &config, false)).unwrap();
+        assert!(sent.get("top_k").is_none());
+        assert!(sent.get("repeat_penalty").is_none());
+    }
+}
@@ -2,11 +2,21 @@
 // Phase 5: RAG Query Pipeline
 // Retrieval + Context Assembly + LLM Generation (Phi 3.5)
 
+use crate::db::{llm_settings, prompt_templates};
 use crate::embedder::EmbeddingService;
-use crate::memory::llm_provider::LLMProvider;
+use crate::memory::citations::{self, CitationReport, CitationSource, SourceChunk};
+use crate::memory::context_budget::{self, BudgetUsage, ContextBudget, PackedContext, Passage};
+use crate::memory::grounding::{self, GroundingConfig, GroundingReport};
+use crate::memory::llm_provider::{ChatMessage, LLMError, LLMProvider};
+use crate::memory::llm_router;
+use crate::memory::structured::{self, StructuredOutput};
+use crate::memory::tokenizer;
+use crate::memory::vector_store::SearchResult;
 use crate::memory::VectorStore;
+use crate::retriever::Retriever;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 use tracing::{debug, info};
 
 /// RAG query request
@@ -17,6 +27,29 @@ pub struct RagQueryRequest {
     pub top_k: usize,
     #[serde(default)]
     pub include_sources: bool,
+    /// Overrides `RagConfig::context_expansion` for this request
+    #[serde(default)]
+    pub context_expansion: Option<ContextExpansion>,
+    /// Answer with JSON matching this JSON Schema instead of prose
+    #[serde(default)]
+    pub response_schema: Option<serde_json::Value>,
+    /// Repair rounds for invalid structured output
+    #[serde(default)]
+    pub max_repairs: Option<usize>,
+}
+
+/// How matched chunks are widened before they become LLM context
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ContextExpansion {
+    /// Use the matched chunks as-is
+    #[default]
+    None,
+    /// Replace each match with its parent section (small-to-big)
+    Parent,
+    /// Add the `window` chunks before and after each match from the same
+    /// document (sentence-window when children are sentences)
+    Neighbors { window: usize },
 }
 
 /// Context chunk with metadata
@@ -38,6 +71,18 @@ pub struct RagQueryResponse {
     pub context_chunks: Vec<ContextChunk>,
     pub total_chunks_used: usize,
     pub sources: Vec<String>,
+    /// `[n]` markers in the answer resolved to chunks and verified spans
+    pub citations: CitationReport,
+    /// How well the answer is supported by the context; None when the
+    /// check is off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grounding: Option<GroundingReport>,
+    /// Validated JSON when the request had a response_schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured: Option<StructuredOutput>,
+    /// Token budget of the context and how it was spent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_budget: Option<BudgetUsage>,
 }
 
 /// Configuration for RAG pipeline
@@ -45,7 +90,12 @@ pub struct RagQueryResponse {
 pub struct RagConfig {
     pub top_k: usize,
     pub similarity_threshold: f32,
-    pub max_context_length: usize,
+    /// Context window in tokens; None uses the active model's limits
+    pub context_window: Option<usize>,
+    /// Tokens kept free for the answer; None uses the configured max_tokens
+    pub reserved_answer_tokens: Option<usize>,
+    pub context_expansion: ContextExpansion,
+    pub grounding: GroundingConfig,
 }
 
 impl Default for RagConfig {
@@ -53,7 +103,10 @@ impl Default for RagConfig {
         Self {
             top_k: 5,
             similarity_threshold: 0.3,
-            max_context_length: 2000,
+            context_window: None,
+            reserved_answer_tokens: None,
+            context_expansion: ContextExpansion::None,
+            grounding: GroundingConfig::from_env(),
         }
     }
 }
@@ -62,11 +115,45 @@ fn default_top_k() -> usize {
     5
 }
 
+/// Provider a pipeline answers with
+#[derive(Clone)]
+pub enum AnswerProvider {
+    Fixed(Arc<dyn LLMProvider>),
+    /// The installed router's view for this tag, looked up on every request
+    /// so `llm_router::reload` reaches the pipeline
+    Routed(String),
+}
+
+impl AnswerProvider {
+    pub fn resolve(&self) -> Result<Arc<dyn LLMProvider>, LLMError> {
+        match self {
+            Self::Fixed(provider) => Ok(Arc::clone(provider)),
+            Self::Routed(tag) => llm_router::global()
+                .map(|router| Arc::new(router.tagged(tag.as_str())) as Arc<dyn LLMProvider>)
+                .ok_or_else(|| LLMError::ConfigError("No LLM router installed".to_string())),
+        }
+    }
+}
+
+impl From<Arc<dyn LLMProvider>> for AnswerProvider {
+    fn from(provider: Arc<dyn LLMProvider>) -> Self {
+        Self::Fixed(provider)
+    }
+}
+
+impl<P: LLMProvider + 'static> From<Arc<P>> for AnswerProvider {
+    fn from(provider: Arc<P>) -> Self {
+        Self::Fixed(provider)
+    }
+}
+
 /// RAG Query Pipeline
 pub struct RagQueryPipeline {
     embedding_service: std::sync::Arc<EmbeddingService>,
     vector_store: std::sync::Arc<tokio::sync::RwLock<VectorStore>>,
-    llm_provider: Arc<dyn LLMProvider>,
+    /// Main document index, searched alongside the vector store
+    index: Option<Arc<Mutex<Retriever>>>,
+    llm_provider: AnswerProvider,
     config: RagConfig,
 }
 
@@ -75,48 +162,62 @@ impl RagQueryPipeline {
     pub fn new(
         embedding_service: std::sync::Arc<EmbeddingService>,
         vector_store: std::sync::Arc<tokio::sync::RwLock<VectorStore>>,
-        llm_provider: Arc<dyn LLMProvider>,
+        llm_provider: impl Into<AnswerProvider>,
         config: RagConfig,
     ) -> Self {
-        info!(
-            llm_model = llm_provider.model_name(),
-            "Initializing RAG pipeline"
-        );
+        let llm_provider = llm_provider.into();
+        match &llm_provider {
+            AnswerProvider::Fixed(provider) => {
+                info!(
+                    llm_model = provider.model_name(),
+                    "Initializing RAG pipeline"
+                )
+            }
+            AnswerProvider::Routed(tag) => info!(route_tag = %tag, "Initializing RAG pipeline"),
+        }
         Self {
             embedding_service,
             vector_store,
+            index: None,
             llm_provider,
             config,
         }
     }
 
+    /// Also retrieve from the main document index, which holds everything
+    /// ingested through uploads, reindexing and the ingest jobs
+    pub fn with_index(mut self, index: Arc<Mutex<Retriever>>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
     /// Execute the RAG query pipeline
     pub async fn query(&self, req: &RagQueryRequest) -> Result<RagQueryResponse, RagError> {
         info!(query = %req.query, top_k = req.top_k, "Starting RAG query");
+        let provider = self
+            .llm_provider()
+            .map_err(|e| RagError::LLMGenerationFailed(e.to_string()))?;
+        let provider = provider.as_ref();
 
         // Step 1: Embed the query
         debug!("Step 1: Embedding query");
         let query_embedding = self.embedding_service.embed_query(&req.query).await;
 
-        // Step 2: Search vector store
-        debug!("Step 2: Searching vector store");
+        // Step 2: Search the document index and vector store
+        debug!("Step 2: Searching document index and vector store");
         let mut store = self.vector_store.write().await;
-        let search_results = store
-            .search(&query_embedding, req.top_k)
-            .await
-            .map_err(|e| RagError::SearchFailed(e.to_string()))?;
+        let search_results = self
+            .search(&mut store, &req.query, &query_embedding, req.top_k)
+            .await?;
 
         // Step 3: Filter by similarity threshold
         debug!(
             "Step 3: Filtering by threshold ({})",
             self.config.similarity_threshold
         );
-        let filtered_results: Vec<_> = search_results
-            .into_iter()
-            .filter(|r| r.similarity_score >= self.config.similarity_threshold)
-            .collect();
+        let context_chunks = self.above_threshold(search_results);
 
-        if filtered_results.is_empty() {
+        if context_chunks.is_empty() {
             info!("No results found above similarity threshold");
             return Ok(RagQueryResponse {
                 query: req.query.clone(),
@@ -124,28 +225,78 @@ impl RagQueryPipeline {
                 context_chunks: vec![],
                 total_chunks_used: 0,
                 sources: vec![],
+                citations: CitationReport::default(),
+                grounding: None,
+                structured: None,
+                context_budget: None,
             });
         }
 
         // Step 4: Assemble context
         debug!("Step 4: Assembling context");
-        let context_chunks: Vec<ContextChunk> = filtered_results
-            .iter()
-            .map(|r| ContextChunk {
-                chunk_id: r.chunk_id.clone(),
-                document_id: r.document_id.clone(),
-                content: r.content.clone(),
-                similarity_score: r.similarity_score,
-                chunk_index: r.chunk_index,
-                source: String::new(), // Will be filled from metadata if available
-            })
-            .collect();
-
-        let context = self.assemble_context(&context_chunks);
+        let expansion = req
+            .context_expansion
+            .unwrap_or(self.config.context_expansion);
+        let budget = self.context_budget(provider, &req.query);
+        let expanded =
+            self.expand_context(&store, &context_chunks, expansion, budget.available_tokens);
+        drop(store);
+        let (context, citation_sources) = self.assemble_context(&expanded, &budget);
+        debug!(
+            available_tokens = budget.available_tokens,
+            used_tokens = context.usage.used_tokens,
+            passages = context.usage.passages_included,
+            truncated = context.usage.truncated,
+            "Context packed"
+        );
 
         // Step 5: Generate answer with LLM
         debug!("Step 5: Generating answer with LLM");
-        let answer = self.generate_answer(&req.query, &context).await?;
+        let (mut answer, structured) = match &req.response_schema {
+            Some(schema) => {
+                let output = self
+                    .generate_structured(
+                        provider,
+                        &req.query,
+                        &context.text,
+                        schema,
+                        req.max_repairs,
+                    )
+                    .await?;
+                (output.raw.clone(), Some(output))
+            }
+            None => (
+                self.generate_answer(provider, &req.query, &context.text)
+                    .await?,
+                None,
+            ),
+        };
+        // JSON answers have no sentences to ground
+        let grounding = match structured {
+            Some(_) => None,
+            None => {
+                grounding::check(
+                    &answer,
+                    &citation_sources,
+                    &self.config.grounding,
+                    Some(&self.embedding_service),
+                    Some(provider),
+                )
+                .await
+            }
+        };
+        if grounding.as_ref().is_some_and(|g| g.abstained) {
+            info!(query = %req.query, "Answer not grounded in context; abstaining");
+            answer = grounding::ABSTENTION_ANSWER.to_string();
+        }
+        let citations = citations::verify(&answer, &citation_sources);
+        if !citations.unknown_numbers.is_empty() || !citations.unsupported_numbers.is_empty() {
+            debug!(
+                unknown = ?citations.unknown_numbers,
+                unsupported = ?citations.unsupported_numbers,
+                "Answer has unverified citations"
+            );
+        }
 
         // Step 6: Extract unique sources
         debug!("Step 6: Extracting sources");
@@ -170,53 +321,389 @@ impl RagQueryPipeline {
             context_chunks,
             total_chunks_used: total_chunks,
             sources,
+            citations,
+            grounding,
+            structured,
+            context_budget: Some(context.usage),
         })
     }
 
-    /// Assemble context from chunks
-    fn assemble_context(&self, chunks: &[ContextChunk]) -> String {
-        let mut context = String::new();
-        let mut current_length = 0;
+    /// Chunks for `query` above the similarity threshold, without
+    /// generating an answer
+    pub async fn retrieve(&self, query: &str, top_k: usize) -> Result<Vec<ContextChunk>, RagError> {
+        let query_embedding = self.embedding_service.embed_query(query).await;
+        let mut store = self.vector_store.write().await;
+        let results = self
+            .search(&mut store, query, &query_embedding, top_k)
+            .await?;
+        Ok(self.above_threshold(results))
+    }
 
-        for chunk in chunks {
-            if current_length + chunk.content.len() > self.config.max_context_length {
-                context.push_str("\n[... context truncated ...]");
-                break;
-            }
+    /// The `top_k` best matches from the document index and the vector
+    /// store together, by similarity. Index matches come first on ties.
+    async fn search(
+        &self,
+        store: &mut VectorStore,
+        query: &str,
+        query_embedding: &[f32],
+        top_k: usize,
+    ) -> Result<Vec<SearchResult>, RagError> {
+        let mut results = match &self.index {
+            Some(index) => index
+                .lock()
+                .map_err(|_| RagError::SearchFailed("document index lock poisoned".into()))?
+                .hybrid_hits(query, query_embedding, top_k)
+                .map_err(|e| RagError::SearchFailed(e.to_string()))?
+                .into_iter()
+                .map(|hit| {
+                    // Chunk ids are "<document>#<n>"
+                    let (document_id, chunk_index) = match hit.doc_id.rsplit_once('#') {
+                        Some((document, n)) => (document.to_string(), n.parse().unwrap_or(0)),
+                        None => (hit.doc_id.clone(), 0),
+                    };
+                    SearchResult {
+                        chunk_id: hit.doc_id,
+                        document_id,
+                        content: hit.content,
+                        similarity_score: hit.score,
+                        chunk_index,
+                    }
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        results.extend(
+            store
+                .search(&query_embedding.to_vec(), top_k)
+                .await
+                .map_err(|e| RagError::SearchFailed(e.to_string()))?,
+        );
+        results.sort_by(|a, b| {
+            b.similarity_score
+                .partial_cmp(&a.similarity_score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        results.truncate(top_k);
+        Ok(results)
+    }
 
-            context.push_str(&format!(
-                "From {} (chunk {}): {}\n\n",
-                chunk.document_id, chunk.chunk_index, chunk.content
-            ));
-            current_length += chunk.content.len();
-        }
+    /// Run the configured grounding check of `answer` against `chunks`,
+    /// each chunk numbered as its own source
+    pub async fn check_grounding(
+        &self,
+        answer: &str,
+        chunks: &[ContextChunk],
+    ) -> Option<GroundingReport> {
+        let sources: Vec<CitationSource> = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| CitationSource {
+                number: i + 1,
+                document_id: chunk.document_id.clone(),
+                source: chunk.source.clone(),
+                chunks: vec![SourceChunk {
+                    chunk_id: chunk.chunk_id.clone(),
+                    content: chunk.content.clone(),
+                }],
+            })
+            .collect();
+        // Without a provider the grounding check falls back to lexical
+        let judge = self.llm_provider().ok();
+        grounding::check(
+            answer,
+            &sources,
+            &self.config.grounding,
+            Some(&self.embedding_service),
+            judge.as_deref(),
+        )
+        .await
+    }
 
-        context
+    /// Provider the pipeline answers with right now
+    pub fn llm_provider(&self) -> Result<Arc<dyn LLMProvider>, LLMError> {
+        self.llm_provider.resolve()
     }
 
-    /// Generate answer from query and context using LLM
-    async fn generate_answer(&self, query: &str, context: &str) -> Result<String, RagError> {
-        debug!("Step 5: Generating answer with LLM");
+    fn above_threshold(&self, results: Vec<SearchResult>) -> Vec<ContextChunk> {
+        results
+            .into_iter()
+            .filter(|r| r.similarity_score >= self.config.similarity_threshold)
+            .map(|r| ContextChunk {
+                chunk_id: r.chunk_id,
+                document_id: r.document_id,
+                content: r.content,
+                similarity_score: r.similarity_score,
+                chunk_index: r.chunk_index,
+                source: String::new(), // Will be filled from metadata if available
+            })
+            .collect()
+    }
+
+    /// Context window minus the rendered prompt and the answer reserve
+    fn context_budget(&self, provider: &dyn LLMProvider, query: &str) -> ContextBudget {
+        let model = Some(provider.model_name());
+        let tokenizer = tokenizer::generation_tokenizer();
+        let prompt_tokens: usize = [
+            prompt_templates::render(prompt_templates::RAG_SYSTEM, model, &[]),
+            prompt_templates::render(
+                prompt_templates::RAG_ANSWER,
+                model,
+                &[("query", query), ("context", "")],
+            ),
+        ]
+        .iter()
+        .map(|text| match text {
+            Ok(text) => tokenizer.count_tokens(text),
+            Err(_) => tokenizer.count_tokens(query),
+        })
+        .sum();
+        let reserved = self
+            .config
+            .reserved_answer_tokens
+            .unwrap_or_else(|| llm_settings::global_config().max_tokens);
+        match self.config.context_window {
+            Some(window) => ContextBudget::new(window, prompt_tokens, 0, reserved),
+            None => ContextBudget::for_active_model(prompt_tokens, 0, reserved),
+        }
+    }
+
+    /// Widen matched chunks into their parents or neighbours. A match is only
+    /// expanded while the added text fits the token budget; otherwise the
+    /// match itself is kept.
+    fn expand_context(
+        &self,
+        store: &VectorStore,
+        hits: &[ContextChunk],
+        expansion: ContextExpansion,
+        budget_tokens: usize,
+    ) -> Vec<ContextChunk> {
+        if expansion == ContextExpansion::None {
+            return hits.to_vec();
+        }
 
-        // Build prompt template
-        let prompt = format!(
-            r#"You are a helpful assistant. Answer the following question based on the provided context.
+        let tokenizer = tokenizer::generation_tokenizer();
+        let mut seen = HashSet::new();
+        let mut expanded = Vec::new();
+        let mut used = 0;
+
+        for hit in hits {
+            let candidates: Vec<ContextChunk> = match expansion {
+                ContextExpansion::None => Vec::new(),
+                ContextExpansion::Parent => store
+                    .parent_of(&hit.chunk_id)
+                    .map(|parent| ContextChunk {
+                        chunk_id: parent.chunk_id.clone(),
+                        document_id: parent.document_id.clone(),
+                        content: parent.content.clone(),
+                        similarity_score: hit.similarity_score,
+                        chunk_index: parent.chunk_index,
+                        source: hit.source.clone(),
+                    })
+                    .into_iter()
+                    .collect(),
+                ContextExpansion::Neighbors { window } => store
+                    .document_neighbors(&hit.document_id, hit.chunk_index, window)
+                    .into_iter()
+                    .map(|record| ContextChunk {
+                        chunk_id: record.chunk_id.clone(),
+                        document_id: record.document_id.clone(),
+                        content: record.content.clone(),
+                        similarity_score: hit.similarity_score,
+                        chunk_index: record.chunk_index,
+                        source: record.source.clone(),
+                    })
+                    .collect(),
+            };
+
+            // Already covered by an earlier expansion (e.g. a sibling's parent)
+            let covered =
+                !candidates.is_empty() && candidates.iter().all(|c| seen.contains(&c.chunk_id));
+            let new: Vec<ContextChunk> = candidates
+                .into_iter()
+                .filter(|c| !seen.contains(&c.chunk_id))
+                .collect();
+            let added: usize = new.iter().map(|c| tokenizer.count_tokens(&c.content)).sum();
+            let chosen = if !new.is_empty() && used + added <= budget_tokens {
+                new
+            } else if covered || seen.contains(&hit.chunk_id) {
+                Vec::new()
+            } else {
+                vec![hit.clone()]
+            };
+
+            for chunk in chosen {
+                used += tokenizer.count_tokens(&chunk.content);
+                seen.insert(chunk.chunk_id.clone());
+                expanded.push(chunk);
+            }
+        }
+
+        debug!(
+            ?expansion,
+            hits = hits.len(),
+            expanded = expanded.len(),
+            "Context expanded"
+        );
+        expanded
+    }
 
-Question: {}
+    /// Assemble context from chunks. Chunks from the same document with
+    /// consecutive indexes are merged into one numbered passage, then
+    /// passages are packed into the token budget best score first. Returns
+    /// the citation source of every passage that made it into the context.
+    fn assemble_context(
+        &self,
+        chunks: &[ContextChunk],
+        budget: &ContextBudget,
+    ) -> (PackedContext, Vec<CitationSource>) {
+        let mut documents: Vec<(&str, Vec<&ContextChunk>)> = Vec::new();
+        for chunk in chunks {
+            match documents
+                .iter_mut()
+                .find(|(doc, _)| *doc == chunk.document_id)
+            {
+                Some((_, doc_chunks)) => doc_chunks.push(chunk),
+                None => documents.push((&chunk.document_id, vec![chunk])),
+            }
+        }
 
-Context:
-{}
+        // Runs of consecutive chunks from one document
+        let mut merged: Vec<Vec<&ContextChunk>> = Vec::new();
+        for (document_id, mut doc_chunks) in documents {
+            doc_chunks.sort_by_key(|c| c.chunk_index);
+            for chunk in doc_chunks {
+                match merged.last_mut() {
+                    Some(run)
+                        if run[0].document_id == document_id
+                            && chunk.chunk_index == run[run.len() - 1].chunk_index + 1 =>
+                    {
+                        run.push(chunk)
+                    }
+                    _ => merged.push(vec![chunk]),
+                }
+            }
+        }
+        let score = |run: &[&ContextChunk]| {
+            run.iter()
+                .map(|c| c.similarity_score)
+                .fold(f32::MIN, f32::max)
+        };
+        // Number passages in the order they will be packed
+        merged.sort_by(|a, b| score(b).total_cmp(&score(a)));
+
+        let passages: Vec<Passage> = merged
+            .iter()
+            .enumerate()
+            .map(|(i, run)| {
+                let (first, last) = (run[0].chunk_index, run[run.len() - 1].chunk_index);
+                let location = if first == last {
+                    format!("chunk {}", first)
+                } else {
+                    format!("chunks {}-{}", first, last)
+                };
+                Passage {
+                    header: format!("[{}] From {} ({}): ", i + 1, run[0].document_id, location),
+                    text: run
+                        .iter()
+                        .map(|c| c.content.as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n\n"),
+                    score: score(run),
+                }
+            })
+            .collect();
 
-Answer:"#,
-            query, context
+        let packed = context_budget::pack(
+            &passages,
+            budget,
+            tokenizer::generation_tokenizer().as_ref(),
         );
+        let sources = packed
+            .included
+            .iter()
+            .map(|&i| {
+                let run = &merged[i];
+                CitationSource {
+                    number: i + 1,
+                    document_id: run[0].document_id.clone(),
+                    source: if run[0].source.is_empty() {
+                        run[0].document_id.clone()
+                    } else {
+                        run[0].source.clone()
+                    },
+                    chunks: run
+                        .iter()
+                        .map(|c| SourceChunk {
+                            chunk_id: c.chunk_id.clone(),
+                            content: c.content.clone(),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        (packed, sources)
+    }
+
+    async fn generate_answer(
+        &self,
+        provider: &dyn LLMProvider,
+        query: &str,
+        context: &str,
+    ) -> Result<String, RagError> {
+        debug!("Step 5: Generating answer with LLM");
+        let messages = self.answer_messages(provider, query, context)?;
 
         // Call LLM provider
-        self.llm_provider
-            .generate(&prompt)
+        provider
+            .chat(&messages, &llm_settings::global_config())
             .await
             .map_err(|e| RagError::LLMGenerationFailed(e.to_string()))
     }
+
+    async fn generate_structured(
+        &self,
+        provider: &dyn LLMProvider,
+        query: &str,
+        context: &str,
+        schema: &serde_json::Value,
+        max_repairs: Option<usize>,
+    ) -> Result<StructuredOutput, RagError> {
+        debug!("Step 5: Generating structured answer with LLM");
+        let messages = self.answer_messages(provider, query, context)?;
+        structured::generate(
+            provider,
+            &messages,
+            schema,
+            &llm_settings::global_config(),
+            max_repairs.unwrap_or(structured::DEFAULT_MAX_REPAIRS),
+        )
+        .await
+        .map_err(|e| match e {
+            structured::StructuredError::InvalidSchema(msg) => RagError::InvalidSchema(msg),
+            other => RagError::LLMGenerationFailed(other.to_string()),
+        })
+    }
+
+    fn answer_messages(
+        &self,
+        provider: &dyn LLMProvider,
+        query: &str,
+        context: &str,
+    ) -> Result<[ChatMessage; 2], RagError> {
+        let model = Some(provider.model_name());
+        let render = |name: &str, vars: &[(&str, &str)]| {
+            prompt_templates::render(name, model, vars)
+                .map_err(|e| RagError::ContextAssemblyFailed(e.to_string()))
+        };
+        let messages = [
+            ChatMessage::system(render(prompt_templates::RAG_SYSTEM, &[])?),
+            ChatMessage::user(render(
+                prompt_templates::RAG_ANSWER,
+                &[("query", query), ("context", context)],
+            )?),
+        ];
+        Ok(messages)
+    }
 }
 
 /// Error types for RAG operations
@@ -227,6 +714,7 @@ pub enum RagError {
     NoResultsFound,
     ContextAssemblyFailed(String),
     LLMGenerationFailed(String),
+    InvalidSchema(String),
 }
 
 impl std::fmt::Display for RagError {
@@ -237,6 +725,7 @@ impl std::fmt::Display for RagError {
             Self::NoResultsFound => write!(f, "No results found"),
             Self::ContextAssemblyFailed(msg) => write!(f, "Context assembly failed: {}", msg),
             Self::LLMGenerationFailed(msg) => write!(f, "LLM generation failed: {}", msg),
+            Self::InvalidSchema(msg) => write!(f, "Invalid response schema: {}", msg),
         }
     }
 }
@@ -252,7 +741,8 @@ mod tests {
         let config = RagConfig::default();
         assert_eq!(config.top_k, 5);
         assert_eq!(config.similarity_threshold, 0.3);
-        assert_eq!(config.max_context_length, 2000);
+        assert_eq!(config.context_window, None);
+        assert_eq!(config.reserved_answer_tokens, None);
     }
 
     #[test]
@@ -309,12 +799,139 @@ mod tests {
             },
         ];
 
-        let context = pipeline.assemble_context(&chunks);
+        let context = pipeline
+            .assemble_context(&chunks, &ContextBudget::new(4096, 0, 0, 0))
+            .0
+            .text;
         assert!(context.contains("First chunk content"));
         assert!(context.contains("Second chunk content"));
         assert!(context.contains("doc1"));
     }
 
+    fn test_pipeline() -> RagQueryPipeline {
+        struct MockLLM;
+
+        #[async_trait::async_trait]
+        impl LLMProvider for MockLLM {
+            async fn generate(
+                &self,
+                _prompt: &str,
+            ) -> Result<String, crate::memory::llm_provider::LLMError> {
+                Ok("test".to_string())
+            }
+            async fn generate_with_config(
+                &self,
+                _prompt: &str,
+                _config: &crate::db::llm_settings::LlmConfig,
+            ) -> Result<String, crate::memory::llm_provider::LLMError> {
+                Ok("test".to_string())
+            }
+            fn model_name(&self) -> &str {
+                "mock"
+            }
+        }
+
+        RagQueryPipeline::new(
+            std::sync::Arc::new(EmbeddingService::new(
+                crate::embedder::EmbeddingConfig::default(),
+            )),
+            std::sync::Arc::new(tokio::sync::RwLock::new(
+                crate::memory::VectorStore::with_defaults().unwrap(),
+            )),
+            std::sync::Arc::new(MockLLM),
+            RagConfig::default(),
+        )
+    }
+
+    async fn sentence_store() -> VectorStore {
+        use crate::memory::{ParentRecord, VectorRecord};
+
+        let mut store = VectorStore::with_defaults().unwrap();
+        store.add_parent(ParentRecord {
+            chunk_id: "p0".to_string(),
+            document_id: "doc1".to_string(),
+            content: "Alpha one. Alpha two. Alpha three.".to_string(),
+            chunk_index: 0,
+            token_count: 9,
+        });
+        for (idx, text) in ["Alpha one.", "Alpha two.", "Alpha three.", "Beta one."]
+            .iter()
+            .enumerate()
+        {
+            let mut record = VectorRecord::new(
+                format!("c{}", idx),
+                "doc1".to_string(),
+                text.to_string(),
+                crate::embedder::embed(text),
+                idx,
+                3,
+                "doc1.txt".to_string(),
+                0,
+            );
+            if idx < 3 {
+                record = record.with_parent("p0");
+            }
+            store.add_record(record).await.unwrap();
+        }
+        store
+    }
+
+    fn hit(chunk_id: &str, chunk_index: usize, content: &str) -> ContextChunk {
+        ContextChunk {
+            chunk_id: chunk_id.to_string(),
+            document_id: "doc1".to_string(),
+            content: content.to_string(),
+            similarity_score: 0.9,
+            chunk_index,
+            source: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parent_expansion_dedups_siblings() {
+        let store = sentence_store().await;
+        let pipeline = test_pipeline();
+        let hits = vec![hit("c1", 1, "Alpha two."), hit("c2", 2, "Alpha three.")];
+
+        let expanded = pipeline.expand_context(&store, &hits, ContextExpansion::Parent, 2000);
+        assert_eq!(expanded.len(), 1);
+        assert_eq!(expanded[0].chunk_id, "p0");
+
+        // A parent that does not fit the budget leaves the match unexpanded
+        let expanded = pipeline.expand_context(&store, &hits[..1], ContextExpansion::Parent, 3);
+        assert_eq!(expanded[0].chunk_id, "c1");
+    }
+
+    #[tokio::test]
+    async fn test_neighbor_expansion_merges_adjacent_chunks() {
+        let store = sentence_store().await;
+        let pipeline = test_pipeline();
+        let hits = vec![hit("c3", 3, "Beta one."), hit("c1", 1, "Alpha two.")];
+
+        let expanded = pipeline.expand_context(
+            &store,
+            &hits,
+            ContextExpansion::Neighbors { window: 1 },
+            2000,
+        );
+        assert_eq!(expanded.len(), 4);
+
+        let (context, sources) =
+            pipeline.assemble_context(&expanded, &ContextBudget::new(2000, 0, 0, 0));
+        assert_eq!(
+            context.text,
+            "[1] From doc1 (chunks 0-3): Alpha one.\n\nAlpha two.\n\nAlpha three.\n\nBeta one.\n\n"
+        );
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].number, 1);
+        let ids: Vec<&str> = sources[0]
+            .chunks
+            .iter()
+            .map(|c| c.chunk_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["c0", "c1", "c2", "c3"]);
+    }
+
     #[tokio::test]
     async fn test_generate_answer_placeholder() {
         // Create a mock LLM provider for testing
@@ -351,7 +968,9 @@ mod tests {
             RagConfig::default(),
         );
 
-        let answer = pipeline.generate_answer("test query", "test context").await;
+        let answer = pipeline
+            .generate_answer(&MockLLM, "test query", "test context")
+            .await;
 
         assert!(answer.is_ok());
         let ans = answer.unwrap();
@@ -0,0 +1,724 @@
+// src/memory/structured.rs
+// Structured JSON output: the caller passes a JSON Schema, the provider is
+// asked for JSON through its native JSON mode when it has one (Ollama
+// `format`, OpenAI `response_format`) and through the system message
+// otherwise. The reply is parsed and validated here; invalid replies are sent
+// back with the validation errors for a bounded number of repair rounds.
+//
+// The validator covers the keywords extraction schemas use: type, enum,
+// const, properties, required, additionalProperties, items, min/maxItems,
+// uniqueItems, min/maxLength, pattern, minimum/maximum (and exclusive),
+// allOf/anyOf/oneOf and not. Other keywords ($ref, format, ..) are ignored.
+
+use crate::db::llm_settings::LlmConfig;
+use crate::db::prompt_templates;
+use crate::memory::llm_provider::{
+    ChatMessage, Completion, LLMError, LLMProvider, Role, TokenUsage,
+};
+use crate::memory::tokenizer;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// Repair rounds after the first attempt when the caller does not say
+pub const DEFAULT_MAX_REPAIRS: usize = 2;
+/// Upper bound on caller-requested repair rounds
+pub const MAX_REPAIRS_LIMIT: usize = 5;
+
+const TYPES: &[&str] = &[
+    "null", "boolean", "object", "array", "number", "integer", "string",
+];
+
+#[derive(Debug, Error)]
+pub enum StructuredError {
+    #[error("Invalid JSON Schema: {0}")]
+    InvalidSchema(String),
+    #[error(transparent)]
+    Llm(#[from] LLMError),
+    #[error("Output does not match the schema: {0}")]
+    Deserialize(String),
+}
+
+/// One validation failure; `path` is a JSON pointer into the value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaError {
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// Result of a structured generation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructuredOutput {
+    /// Parsed JSON of the last reply; present even when it is invalid
+    pub value: Option<Value>,
+    pub valid: bool,
+    /// Validation errors of the last reply; empty when valid
+    pub errors: Vec<SchemaError>,
+    /// Generations made, including repairs
+    pub attempts: usize,
+    /// Text of the last reply
+    pub raw: String,
+    /// Tokens spent across all attempts, each counted from the usage
+    /// reported for that call or estimated when there was none
+    #[serde(default)]
+    pub tokens: usize,
+}
+
+impl StructuredOutput {
+    /// Deserialize a valid value into `T`
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, StructuredError> {
+        match (&self.value, self.valid) {
+            (Some(value), true) => serde_json::from_value(value.clone())
+                .map_err(|e| StructuredError::Deserialize(e.to_string())),
+            _ => Err(StructuredError::Deserialize(
+                self.errors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            )),
+        }
+    }
+}
+
+/// Generate JSON matching `schema`, repairing invalid replies up to
+/// `max_repairs` times. Running out of repairs is not an error: the output
+/// reports `valid: false` with the remaining errors.
+pub async fn generate(
+    provider: &dyn LLMProvider,
+    messages: &[ChatMessage],
+    schema: &Value,
+    config: &LlmConfig,
+    max_repairs: usize,
+) -> Result<StructuredOutput, StructuredError> {
+    check_schema(schema)?;
+    let instruction = prompt_templates::render(
+        prompt_templates::STRUCTURED_OUTPUT,
+        Some(provider.model_name()),
+        &[(
+            "schema",
+            &serde_json::to_string_pretty(schema).unwrap_or_default(),
+        )],
+    )
+    .map_err(|e| StructuredError::InvalidSchema(e.to_string()))?;
+
+    let mut conversation = messages.to_vec();
+    match conversation.first_mut() {
+        Some(first) if first.role == Role::System => {
+            first.content = format!("{}\n\n{}", first.content, instruction);
+        }
+        _ => conversation.insert(0, ChatMessage::system(instruction)),
+    }
+
+    let max_repairs = max_repairs.min(MAX_REPAIRS_LIMIT);
+    let mut attempts = 0;
+    let mut tokens = 0;
+    loop {
+        attempts += 1;
+        let Completion { text: raw, usage } = provider
+            .chat_json_with_usage(&conversation, schema, config)
+            .await?;
+        tokens += tokens_used(usage, &conversation, &raw);
+        let (value, errors) = match extract_json(&raw) {
+            Ok(value) => {
+                let errors = validate(&value, schema);
+                (Some(value), errors)
+            }
+            Err(e) => (
+                None,
+                vec![SchemaError {
+                    path: String::new(),
+                    message: format!("not valid JSON: {}", e),
+                }],
+            ),
+        };
+
+        if errors.is_empty() || attempts > max_repairs {
+            info!(
+                model = provider.model_name(),
+                attempts,
+                valid = errors.is_empty(),
+                errors = errors.len(),
+                "Structured generation complete"
+            );
+            return Ok(StructuredOutput {
+                value,
+                valid: errors.is_empty(),
+                errors,
+                attempts,
+                raw,
+                tokens,
+            });
+        }
+
+        debug!(
+            attempt = attempts,
+            errors = errors.len(),
+            "Repairing structured output"
+        );
+        let listed = errors
+            .iter()
+            .map(|e| format!("- {}", e))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let repair = prompt_templates::render(
+            prompt_templates::STRUCTURED_REPAIR,
+            Some(provider.model_name()),
+            &[("errors", &listed)],
+        )
+        .map_err(|e| StructuredError::InvalidSchema(e.to_string()))?;
+        conversation.push(ChatMessage::assistant(raw));
+        conversation.push(ChatMessage::user(repair));
+    }
+}
+
+/// Tokens of one model call: the provider's count when it reports one,
+/// otherwise an estimate of prompt plus reply
+pub fn tokens_used(usage: Option<TokenUsage>, messages: &[ChatMessage], reply: &str) -> usize {
+    match usage {
+        Some(usage) => usage.total_tokens as usize,
+        None => {
+            let tokenizer = tokenizer::generation_tokenizer();
+            messages
+                .iter()
+                .map(|m| tokenizer.count_tokens(&m.content))
+                .sum::<usize>()
+                + tokenizer.count_tokens(reply)
+        }
+    }
+}
+
+/// The JSON value in a reply, tolerating code fences and prose around it
+pub fn extract_json(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+    let first_error = match serde_json::from_str(text) {
+        Ok(value) => return Ok(value),
+        Err(e) => e.to_string(),
+    };
+    let start = text.find(['{', '[']).ok_or(first_error.clone())?;
+    let end = matching_close(&text[start..]).ok_or(first_error.clone())?;
+    serde_json::from_str(&text[start..start + end]).map_err(|e| e.to_string())
+}
+
+/// Byte length of the object or array `text` starts with
+fn matching_close(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Reject schemas the validator would misread: non-object schemas, unknown
+/// type names, malformed required lists and patterns that do not compile
+pub fn check_schema(schema: &Value) -> Result<(), StructuredError> {
+    let invalid = |path: &str, message: &str| {
+        Err(StructuredError::InvalidSchema(format!(
+            "{}: {}",
+            if path.is_empty() { "/" } else { path },
+            message
+        )))
+    };
+    fn walk(schema: &Value, path: &str, errors: &mut Vec<(String, String)>) {
+        let object = match schema {
+            Value::Bool(_) => return,
+            Value::Object(object) => object,
+            _ => {
+                errors.push((path.into(), "schema must be an object or boolean".into()));
+                return;
+            }
+        };
+        if let Some(kind) = object.get("type") {
+            let names: Vec<&Value> = match kind {
+                Value::Array(names) => names.iter().collect(),
+                other => vec![other],
+            };
+            for name in names {
+                if !name.as_str().is_some_and(|n| TYPES.contains(&n)) {
+                    errors.push((path.into(), format!("unknown type {}", name)));
+                }
+            }
+        }
+        if let Some(required) = object.get("required") {
+            if !required
+                .as_array()
+                .is_some_and(|r| r.iter().all(Value::is_string))
+            {
+                errors.push((path.into(), "required must be an array of strings".into()));
+            }
+        }
+        if let Some(pattern) = object.get("pattern") {
+            if pattern
+                .as_str()
+                .is_none_or(|p| regex::Regex::new(p).is_err())
+            {
+                errors.push((path.into(), format!("invalid pattern {}", pattern)));
+            }
+        }
+        if let Some(properties) = object.get("properties") {
+            match properties.as_object() {
+                Some(properties) => {
+                    for (name, sub) in properties {
+                        walk(sub, &format!("{}/properties/{}", path, name), errors);
+                    }
+                }
+                None => errors.push((path.into(), "properties must be an object".into())),
+            }
+        }
+        for key in ["items", "additionalProperties", "not"] {
+            if let Some(sub) = object.get(key) {
+                match sub {
+                    Value::Array(subs) if key == "items" => {
+                        for (i, sub) in subs.iter().enumerate() {
+                            walk(sub, &format!("{}/items/{}", path, i), errors);
+                        }
+                    }
+                    sub => walk(sub, &format!("{}/{}", path, key), errors),
+                }
+            }
+        }
+        for key in ["allOf", "anyOf", "oneOf"] {
+            if let Some(subs) = object.get(key) {
+                match subs.as_array() {
+                    Some(subs) => {
+                        for (i, sub) in subs.iter().enumerate() {
+                            walk(sub, &format!("{}/{}/{}", path, key, i), errors);
+                        }
+                    }
+                    None => errors.push((path.into(), format!("{} must be an array", key))),
+                }
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    walk(schema, "", &mut errors);
+    match errors.first() {
+        Some((path, message)) => invalid(path, message),
+        None => Ok(()),
+    }
+}
+
+/// Validate `value` against `schema`; empty when valid
+pub fn validate(value: &Value, schema: &Value) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, "", &mut errors);
+    errors
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+    let mut fail = |message: String| {
+        errors.push(SchemaError {
+            path: path.to_string(),
+            message,
+        })
+    };
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return fail("no value is allowed here".into()),
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(kind) = schema.get("type") {
+        let allowed: Vec<&str> = match kind {
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            other => other.as_str().into_iter().collect(),
+        };
+        if !allowed.iter().any(|t| has_type(value, t)) {
+            return fail(format!(
+                "expected {}, found {}",
+                allowed.join(" or "),
+                type_name(value)
+            ));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            fail(format!("must be one of {}", Value::Array(options.clone())));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            fail(format!("must equal {}", expected));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        fail(format!("missing required property '{}'", name));
+                    }
+                }
+            }
+            for (key, item) in object {
+                let item_path = format!("{}/{}", path, escape_pointer(key));
+                match properties.and_then(|p| p.get(key)) {
+                    Some(sub) => validate_at(item, sub, &item_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => errors.push(SchemaError {
+                            path: item_path,
+                            message: "additional property is not allowed".into(),
+                        }),
+                        Some(sub) => validate_at(item, sub, &item_path, errors),
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    fail(format!("must have at least {} items", min));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if items.len() as u64 > max {
+                    fail(format!("must have at most {} items", max));
+                }
+            }
+            if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+                for (i, item) in items.iter().enumerate() {
+                    if items[..i].contains(item) {
+                        fail(format!("item {} is a duplicate", i));
+                    }
+                }
+            }
+            match schema.get("items") {
+                Some(Value::Array(tuple)) => {
+                    for (i, (item, sub)) in items.iter().zip(tuple).enumerate() {
+                        validate_at(item, sub, &format!("{}/{}", path, i), errors);
+                    }
+                }
+                Some(sub) => {
+                    for (i, item) in items.iter().enumerate() {
+                        validate_at(item, sub, &format!("{}/{}", path, i), errors);
+                    }
+                }
+                None => {}
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < min {
+                    fail(format!("must be at least {} characters", min));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > max {
+                    fail(format!("must be at most {} characters", max));
+                }
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                if let Ok(re) = regex::Regex::new(pattern) {
+                    if !re.is_match(text) {
+                        fail(format!("must match pattern {}", pattern));
+                    }
+                }
+            }
+        }
+        Value::Number(number) => {
+            let n = number.as_f64().unwrap_or_default();
+            let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum").filter(|min| n < *min) {
+                fail(format!("must be >= {}", min));
+            }
+            if let Some(max) = bound("maximum").filter(|max| n > *max) {
+                fail(format!("must be <= {}", max));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+                fail(format!("must be > {}", min));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+                fail(format!("must be < {}", max));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(subs) = schema.get("allOf").and_then(Value::as_array) {
+        for sub in subs {
+            validate_at(value, sub, path, errors);
+        }
+    }
+    let matching = |subs: &Vec<Value>| {
+        subs.iter()
+            .filter(|sub| validate(value, sub).is_empty())
+            .count()
+    };
+    if let Some(subs) = schema.get("anyOf").and_then(Value::as_array) {
+        if matching(subs) == 0 {
+            errors.push(SchemaError {
+                path: path.to_string(),
+                message: "does not match any allowed schema (anyOf)".into(),
+            });
+        }
+    }
+    if let Some(subs) = schema.get("oneOf").and_then(Value::as_array) {
+        let count = matching(subs);
+        if count != 1 {
+            errors.push(SchemaError {
+                path: path.to_string(),
+                message: format!("must match exactly one schema (oneOf), matched {}", count),
+            });
+        }
+    }
+    if let Some(sub) = schema.get("not") {
+        if validate(value, sub).is_empty() {
+            errors.push(SchemaError {
+                path: path.to_string(),
+                message: "must not match the schema in not".into(),
+            });
+        }
+    }
+}
+
+fn has_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "integer" => value
+            .as_f64()
+            .is_some_and(|n| n.fract() == 0.0 && n.is_finite()),
+        other => type_name(value) == other || (other == "number" && value.is_number()),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// JSON pointer token escaping (RFC 6901)
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    fn requirements_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["requirements"],
+            "additionalProperties": false,
+            "properties": {
+                "requirements": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "text"],
+                        "properties": {
+                            "id": { "type": "string", "pattern": "^REQ-[0-9]+$" },
+                            "text": { "type": "string", "minLength": 1 },
+                            "priority": { "enum": ["must", "should", "may"] }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Replies in order, recording the conversations it was sent
+    struct Scripted {
+        replies: Mutex<Vec<&'static str>>,
+        seen: Mutex<Vec<Vec<ChatMessage>>>,
+    }
+
+    impl Scripted {
+        fn new(replies: &[&'static str]) -> Self {
+            Self {
+                replies: Mutex::new(replies.iter().rev().copied().collect()),
+                seen: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for Scripted {
+        async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+            self.chat(&[ChatMessage::user(prompt)], &LlmConfig::default())
+                .await
+        }
+        async fn generate_with_config(
+            &self,
+            prompt: &str,
+            config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.chat(&[ChatMessage::user(prompt)], config).await
+        }
+        async fn chat(
+            &self,
+            messages: &[ChatMessage],
+            _config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.seen.lock().unwrap().push(messages.to_vec());
+            self.replies
+                .lock()
+                .unwrap()
+                .pop()
+                .map(str::to_string)
+                .ok_or_else(|| LLMError::GenerationFailed("script exhausted".into()))
+        }
+        fn model_name(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_pointer_paths() {
+        let value = json!({
+            "requirements": [
+                { "id": "REQ-1", "text": "Log in", "priority": "must" },
+                { "id": "R2", "priority": "later" }
+            ],
+            "extra": true
+        });
+        let errors = validate(&value, &requirements_schema());
+        let mut paths: Vec<&str> = errors.iter().map(|e| e.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                "/extra",
+                "/requirements/1",
+                "/requirements/1/id",
+                "/requirements/1/priority"
+            ]
+        );
+        assert!(errors
+            .iter()
+            .any(|e| e.path == "/requirements/1" && e.message.contains("'text'")));
+        assert!(validate(
+            &json!({"requirements": [{"id": "REQ-7", "text": "x"}]}),
+            &requirements_schema()
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_types_and_combinators() {
+        assert!(validate(&json!(3), &json!({"type": "integer"})).is_empty());
+        assert!(validate(&json!(3.0), &json!({"type": "integer"})).is_empty());
+        assert!(!validate(&json!(3.5), &json!({"type": "integer"})).is_empty());
+        assert!(validate(&json!(null), &json!({"type": ["string", "null"]})).is_empty());
+        let one_of = json!({"oneOf": [{"type": "number"}, {"type": "integer"}]});
+        assert_eq!(validate(&json!(2), &one_of).len(), 1);
+        assert!(validate(&json!(2.5), &one_of).is_empty());
+        assert!(check_schema(&json!({"type": "text"})).is_err());
+        assert!(check_schema(&json!({"properties": {"a": {"pattern": "("}}})).is_err());
+        assert!(check_schema(&requirements_schema()).is_ok());
+    }
+
+    #[test]
+    fn test_extract_json_from_fenced_or_chatty_reply() {
+        let reply = "Sure! Here it is:\n```json\n{\"a\": [1, \"}\"]}\n```\nAnything else?";
+        assert_eq!(extract_json(reply).unwrap(), json!({"a": [1, "}"]}));
+        assert!(extract_json("no json here").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_repairs_invalid_output_then_succeeds() {
+        let provider = Scripted::new(&[
+            "not json at all",
+            r#"{"requirements": [{"id": "7", "text": "Export CSV"}]}"#,
+            r#"{"requirements": [{"id": "REQ-7", "text": "Export CSV"}]}"#,
+        ]);
+        let messages = [ChatMessage::user("Extract the requirements")];
+        let output = generate(
+            &provider,
+            &messages,
+            &requirements_schema(),
+            &LlmConfig::default(),
+            DEFAULT_MAX_REPAIRS,
+        )
+        .await
+        .unwrap();
+        assert!(output.valid);
+        assert_eq!(output.attempts, 3);
+
+        #[derive(Deserialize)]
+        struct Requirement {
+            id: String,
+        }
+        #[derive(Deserialize)]
+        struct Extracted {
+            requirements: Vec<Requirement>,
+        }
+        let typed: Extracted = output.parse().unwrap();
+        assert_eq!(typed.requirements[0].id, "REQ-7");
+
+        // The schema went into the system message, errors into the repairs
+        let seen = provider.seen.lock().unwrap();
+        assert_eq!(seen[0][0].role, Role::System);
+        assert!(seen[0][0].content.contains("\"requirements\""));
+        let last = &seen[2];
+        assert_eq!(last.len(), 6);
+        assert!(last[5].content.contains("/requirements/0/id"));
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_repairs() {
+        let provider = Scripted::new(&["{}", "{}"]);
+        let output = generate(
+            &provider,
+            &[ChatMessage::user("Extract")],
+            &requirements_schema(),
+            &LlmConfig::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert!(!output.valid);
+        assert_eq!(output.attempts, 2);
+        assert_eq!(output.value, Some(json!({})));
+        assert!(output.parse::<Value>().is_err());
+    }
+}
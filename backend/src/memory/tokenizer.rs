@@ -0,0 +1,789 @@
+// src/memory/tokenizer.rs
+// Token counting for chunk sizing and prompt budgets
+//
+// Chunkers measure text with the active *embedding* tokenizer so chunks fit
+// the embedding model's input limit; prompt assembly uses the *generation*
+// tokenizer and context window. Tokenizers load from a HuggingFace
+// `tokenizer.json` or from the vocabulary stored in a GGUF model file. When
+// nothing is configured the old chars/words heuristic is used.
+//
+// Environment variables:
+// - EMBEDDING_TOKENIZER: tokenizer.json, model directory or .gguf for the embedder
+// - GENERATION_TOKENIZER: same for the generation model (defaults to the
+//   hardware config `model` when that is a local path)
+
+use crate::db::param_hardware::HardwareParams;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use thiserror::Error;
+use tracing::{info, warn};
+
+#[derive(Debug, Error)]
+pub enum TokenizerError {
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("invalid tokenizer file: {0}")]
+    Format(String),
+    #[error("unsupported tokenizer: {0}")]
+    Unsupported(String),
+}
+
+impl From<std::io::Error> for TokenizerError {
+    fn from(err: std::io::Error) -> Self {
+        TokenizerError::Io(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, TokenizerError>;
+
+pub trait Tokenizer: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn count_tokens(&self, text: &str) -> usize;
+
+    /// Byte ranges of the tokens in `text`
+    fn token_spans(&self, text: &str) -> Vec<(usize, usize)>;
+
+    /// Content tokens the model accepts, after special tokens (None = unknown)
+    fn max_input_tokens(&self) -> Option<usize> {
+        None
+    }
+
+    /// Split `text` into consecutive pieces of at most `max_tokens` tokens
+    fn split_to_fit<'a>(&self, text: &'a str, max_tokens: usize) -> Vec<&'a str> {
+        let max_tokens = max_tokens.max(1);
+        if self.count_tokens(text) <= max_tokens {
+            return vec![text];
+        }
+        let spans = self.token_spans(text);
+        // Tokenizers can count a piece differently in isolation (merges across
+        // the cut, heuristic estimates), so shrink the window until all fit
+        let mut window = max_tokens;
+        loop {
+            let pieces = split_spans(text, &spans, window);
+            if window == 1 || pieces.iter().all(|p| self.count_tokens(p) <= max_tokens) {
+                return pieces;
+            }
+            window = (window * 3 / 4).max(1);
+        }
+    }
+
+    /// The last `max_tokens` tokens of `text` (used for chunk overlap)
+    fn tail<'a>(&self, text: &'a str, max_tokens: usize) -> &'a str {
+        if max_tokens == 0 {
+            return "";
+        }
+        let spans = self.token_spans(text);
+        if spans.len() <= max_tokens {
+            return text;
+        }
+        let start = floor_char_boundary(text, spans[spans.len() - max_tokens].0);
+        text[start..].trim_start()
+    }
+}
+
+fn split_spans<'a>(text: &'a str, spans: &[(usize, usize)], window: usize) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, group) in spans.chunks(window).enumerate() {
+        let end = if (i + 1) * window >= spans.len() {
+            text.len()
+        } else {
+            floor_char_boundary(text, group.last().map_or(start, |s| s.1))
+        };
+        if end > start {
+            let piece = text[start..end].trim();
+            if !piece.is_empty() {
+                pieces.push(piece);
+            }
+            start = end;
+        }
+    }
+    pieces
+}
+
+fn floor_char_boundary(text: &str, mut idx: usize) -> usize {
+    idx = idx.min(text.len());
+    while !text.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+/// Fallback estimate: average of chars/4 and words*4/3
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        let char_estimate = text.len() / 4;
+        let word_estimate = text.split_whitespace().count() * 4 / 3;
+        (char_estimate + word_estimate) / 2
+    }
+
+    /// Words, with long words cut into 4-byte pieces
+    fn token_spans(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+        let mut word_start = None;
+        for (i, c) in text
+            .char_indices()
+            .chain(std::iter::once((text.len(), ' ')))
+        {
+            match (c.is_whitespace(), word_start) {
+                (false, None) => word_start = Some(i),
+                (true, Some(start)) => {
+                    let mut piece_start = start;
+                    while i - piece_start > 4 {
+                        let cut = floor_char_boundary(text, piece_start + 4);
+                        if cut == piece_start {
+                            break;
+                        }
+                        spans.push((piece_start, cut));
+                        piece_start = cut;
+                    }
+                    spans.push((piece_start, i));
+                    word_start = None;
+                }
+                _ => {}
+            }
+        }
+        spans
+    }
+}
+
+/// A HuggingFace `tokenizers` tokenizer, loaded from tokenizer.json or
+/// built from a GGUF vocabulary
+pub struct HfTokenizer {
+    name: String,
+    inner: tokenizers::Tokenizer,
+    max_input_tokens: Option<usize>,
+    /// Context window the model was trained with, when the files say
+    context_length: Option<usize>,
+}
+
+impl HfTokenizer {
+    /// Load `tokenizer.json`, or a model directory containing one. The input
+    /// limit comes from the tokenizer's truncation settings or the usual
+    /// sibling config files.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let (dir, file) = if path.is_dir() {
+            (path.to_path_buf(), path.join("tokenizer.json"))
+        } else {
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            (dir, path.to_path_buf())
+        };
+        let inner = tokenizers::Tokenizer::from_file(&file)
+            .map_err(|e| TokenizerError::Format(format!("{}: {}", file.display(), e)))?;
+
+        let read_json = |name: &str| -> Option<Value> {
+            let text = std::fs::read_to_string(dir.join(name)).ok()?;
+            serde_json::from_str(&text).ok()
+        };
+        let as_limit = |v: Option<&Value>| {
+            v.and_then(Value::as_u64)
+                .filter(|n| *n > 0 && *n < 1_000_000)
+                .map(|n| n as usize)
+        };
+        let model_config = read_json("config.json");
+        let context_length = as_limit(
+            model_config
+                .as_ref()
+                .and_then(|c| c.get("max_position_embeddings")),
+        );
+        let limit = inner
+            .get_truncation()
+            .map(|t| t.max_length)
+            .or_else(|| {
+                as_limit(
+                    read_json("sentence_bert_config.json")
+                        .as_ref()
+                        .and_then(|c| c.get("max_seq_length")),
+                )
+            })
+            .or_else(|| {
+                as_limit(
+                    read_json("tokenizer_config.json")
+                        .as_ref()
+                        .and_then(|c| c.get("model_max_length")),
+                )
+            })
+            .or(context_length);
+
+        let name = dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| file.display().to_string());
+        Ok(Self::new(name, inner, limit, context_length))
+    }
+
+    /// Build a tokenizer from the vocabulary embedded in a GGUF model.
+    /// Supports `gpt2` (byte-level BPE), `llama` (SentencePiece) and `bert`
+    /// (WordPiece) vocabularies.
+    pub fn from_gguf(path: &Path) -> Result<Self> {
+        let meta = read_gguf_metadata(path)?;
+        let tokens: Vec<String> = meta
+            .get("tokenizer.ggml.tokens")
+            .and_then(GgufValue::as_strings)
+            .ok_or_else(|| TokenizerError::Format("GGUF has no tokenizer.ggml.tokens".into()))?;
+        let model = meta
+            .get("tokenizer.ggml.model")
+            .and_then(GgufValue::as_str)
+            .unwrap_or("llama");
+        let unk_id = meta
+            .get("tokenizer.ggml.unknown_token_id")
+            .and_then(GgufValue::as_u64)
+            .map(|id| id as usize);
+
+        let vocab_map = || -> Value {
+            Value::Object(
+                tokens
+                    .iter()
+                    .enumerate()
+                    .map(|(id, t)| (t.clone(), json!(id)))
+                    .collect(),
+            )
+        };
+        let (model_json, pre_tokenizer, normalizer) = match model {
+            "gpt2" => {
+                let merges = meta
+                    .get("tokenizer.ggml.merges")
+                    .and_then(GgufValue::as_strings)
+                    .unwrap_or_default();
+                (
+                    json!({
+                        "type": "BPE", "dropout": null, "unk_token": null,
+                        "continuing_subword_prefix": null, "end_of_word_suffix": null,
+                        "fuse_unk": false, "byte_fallback": false,
+                        "vocab": vocab_map(), "merges": merges,
+                    }),
+                    json!({"type": "ByteLevel", "add_prefix_space": false, "trim_offsets": true, "use_regex": true}),
+                    Value::Null,
+                )
+            }
+            "llama" => {
+                let scores = meta
+                    .get("tokenizer.ggml.scores")
+                    .and_then(GgufValue::as_floats)
+                    .unwrap_or_default();
+                let vocab: Vec<Value> = tokens
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| json!([t, scores.get(i).copied().unwrap_or(0.0)]))
+                    .collect();
+                let has_byte_tokens = tokens.iter().any(|t| t == "<0x00>");
+                (
+                    json!({
+                        "type": "Unigram", "unk_id": unk_id.unwrap_or(0),
+                        "vocab": vocab, "byte_fallback": has_byte_tokens,
+                    }),
+                    json!({"type": "Metaspace", "replacement": "▁", "prepend_scheme": "first", "split": false}),
+                    Value::Null,
+                )
+            }
+            "bert" => (
+                json!({
+                    "type": "WordPiece",
+                    "unk_token": unk_id.and_then(|id| tokens.get(id)).cloned().unwrap_or_else(|| "[UNK]".into()),
+                    "continuing_subword_prefix": "##", "max_input_chars_per_word": 100,
+                    "vocab": vocab_map(),
+                }),
+                json!({"type": "BertPreTokenizer"}),
+                json!({"type": "BertNormalizer", "clean_text": true, "handle_chinese_chars": true,
+                       "strip_accents": null, "lowercase": true}),
+            ),
+            other => {
+                return Err(TokenizerError::Unsupported(format!(
+                    "GGUF tokenizer model '{}'",
+                    other
+                )))
+            }
+        };
+
+        let tokenizer_json = json!({
+            "version": "1.0", "truncation": null, "padding": null, "added_tokens": [],
+            "normalizer": normalizer, "pre_tokenizer": pre_tokenizer,
+            "post_processor": null, "decoder": null, "model": model_json,
+        });
+        let inner = tokenizers::Tokenizer::from_bytes(tokenizer_json.to_string())
+            .map_err(|e| TokenizerError::Format(format!("{}: {}", path.display(), e)))?;
+
+        let architecture = meta
+            .get("general.architecture")
+            .and_then(GgufValue::as_str)
+            .unwrap_or("llama");
+        let context_length = meta
+            .get(&format!("{}.context_length", architecture))
+            .and_then(GgufValue::as_u64)
+            .map(|n| n as usize);
+        let name = path
+            .file_stem()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        Ok(Self::new(name, inner, context_length, context_length))
+    }
+
+    fn new(
+        name: String,
+        mut inner: tokenizers::Tokenizer,
+        limit: Option<usize>,
+        context_length: Option<usize>,
+    ) -> Self {
+        // Count what the model would see, not a truncated/padded encoding
+        let _ = inner.with_truncation(None);
+        inner.with_padding(None);
+        let special_tokens = inner.encode("", true).map(|e| e.len()).unwrap_or(0);
+        Self {
+            name,
+            inner,
+            max_input_tokens: limit.map(|l| l.saturating_sub(special_tokens)),
+            context_length,
+        }
+    }
+
+    pub fn context_length(&self) -> Option<usize> {
+        self.context_length
+    }
+}
+
+impl Tokenizer for HfTokenizer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        match self.inner.encode(text, false) {
+            Ok(encoding) => encoding.len(),
+            Err(_) => HeuristicTokenizer.count_tokens(text),
+        }
+    }
+
+    fn token_spans(&self, text: &str) -> Vec<(usize, usize)> {
+        match self.inner.encode(text, false) {
+            Ok(encoding) => encoding.get_offsets().to_vec(),
+            Err(_) => HeuristicTokenizer.token_spans(text),
+        }
+    }
+
+    fn max_input_tokens(&self) -> Option<usize> {
+        self.max_input_tokens
+    }
+}
+
+/// Load a tokenizer from a tokenizer.json, a model directory or a .gguf file
+pub fn load_tokenizer(path: &Path) -> Result<HfTokenizer> {
+    let is_gguf = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gguf"));
+    if is_gguf {
+        HfTokenizer::from_gguf(path)
+    } else if path.exists() {
+        HfTokenizer::from_file(path)
+    } else {
+        Err(TokenizerError::Io(format!("{} not found", path.display())))
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// GGUF metadata
+// ─────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Array(Vec<GgufValue>),
+}
+
+impl GgufValue {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            GgufValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            GgufValue::UInt(n) => Some(*n),
+            GgufValue::Int(n) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    fn as_strings(&self) -> Option<Vec<String>> {
+        match self {
+            GgufValue::Array(items) => items.iter().map(|v| v.as_str().map(String::from)).collect(),
+            _ => None,
+        }
+    }
+
+    fn as_floats(&self) -> Option<Vec<f64>> {
+        match self {
+            GgufValue::Array(items) => items
+                .iter()
+                .map(|v| match v {
+                    GgufValue::Float(f) => Some(*f),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Read the key/value metadata section of a GGUF (v2/v3) file. Tensor data
+/// is never touched, so this is cheap even for multi-GB models.
+pub fn read_gguf_metadata(path: &Path) -> Result<HashMap<String, GgufValue>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"GGUF" {
+        return Err(TokenizerError::Format(format!(
+            "{} is not a GGUF file",
+            path.display()
+        )));
+    }
+    let version = read_u32(&mut reader)?;
+    if version < 2 {
+        return Err(TokenizerError::Unsupported(format!(
+            "GGUF version {}",
+            version
+        )));
+    }
+    let _tensor_count = read_u64(&mut reader)?;
+    let kv_count = read_u64(&mut reader)?;
+    let mut meta = HashMap::new();
+    for _ in 0..kv_count {
+        let key = read_gguf_string(&mut reader)?;
+        let value_type = read_u32(&mut reader)?;
+        let value = read_gguf_value(&mut reader, value_type)?;
+        meta.insert(key, value);
+    }
+    Ok(meta)
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_gguf_string(reader: &mut impl Read) -> Result<String> {
+    let len = read_u64(reader)?;
+    if len > 1 << 24 {
+        return Err(TokenizerError::Format(format!("string of {} bytes", len)));
+    }
+    let mut buf = vec![0u8; len as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn read_gguf_value(reader: &mut impl Read, value_type: u32) -> Result<GgufValue> {
+    Ok(match value_type {
+        0 => GgufValue::UInt(u8::from_le_bytes(read_bytes(reader)?) as u64),
+        1 => GgufValue::Int(i8::from_le_bytes(read_bytes(reader)?) as i64),
+        2 => GgufValue::UInt(u16::from_le_bytes(read_bytes(reader)?) as u64),
+        3 => GgufValue::Int(i16::from_le_bytes(read_bytes(reader)?) as i64),
+        4 => GgufValue::UInt(read_u32(reader)? as u64),
+        5 => GgufValue::Int(i32::from_le_bytes(read_bytes(reader)?) as i64),
+        6 => GgufValue::Float(f32::from_le_bytes(read_bytes(reader)?) as f64),
+        7 => GgufValue::Bool(read_bytes::<1>(reader)?[0] != 0),
+        8 => GgufValue::Str(read_gguf_string(reader)?),
+        9 => {
+            let item_type = read_u32(reader)?;
+            let len = read_u64(reader)?;
+            if len > 1 << 24 {
+                return Err(TokenizerError::Format(format!("array of {} items", len)));
+            }
+            let mut items = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                items.push(read_gguf_value(reader, item_type)?);
+            }
+            GgufValue::Array(items)
+        }
+        10 => GgufValue::UInt(read_u64(reader)?),
+        11 => GgufValue::Int(i64::from_le_bytes(read_bytes(reader)?)),
+        12 => GgufValue::Float(f64::from_le_bytes(read_bytes(reader)?)),
+        other => {
+            return Err(TokenizerError::Format(format!(
+                "unknown GGUF value type {}",
+                other
+            )))
+        }
+    })
+}
+
+// ─────────────────────────────────────────────────────────────
+// Active tokenizers
+// ─────────────────────────────────────────────────────────────
+
+/// Token limits of the configured models
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct ModelLimits {
+    /// Content tokens per embedding input (None = unknown, no cap)
+    pub embedding_max_tokens: Option<usize>,
+    /// Generation context window: the configured num_ctx, capped by the
+    /// model's trained context when known
+    pub generation_context: usize,
+}
+
+struct ActiveTokenizers {
+    embedding: Arc<dyn Tokenizer>,
+    generation: Arc<dyn Tokenizer>,
+    limits: ModelLimits,
+}
+
+static ACTIVE: OnceLock<RwLock<ActiveTokenizers>> = OnceLock::new();
+
+fn active() -> &'static RwLock<ActiveTokenizers> {
+    ACTIVE.get_or_init(|| {
+        RwLock::new(ActiveTokenizers {
+            embedding: Arc::new(HeuristicTokenizer),
+            generation: Arc::new(HeuristicTokenizer),
+            limits: ModelLimits {
+                embedding_max_tokens: None,
+                generation_context: HardwareParams::default().num_ctx,
+            },
+        })
+    })
+}
+
+/// Tokenizer used to size chunks
+pub fn embedding_tokenizer() -> Arc<dyn Tokenizer> {
+    active().read().unwrap().embedding.clone()
+}
+
+/// Tokenizer used to budget prompts for the generation model
+pub fn generation_tokenizer() -> Arc<dyn Tokenizer> {
+    active().read().unwrap().generation.clone()
+}
+
+pub fn model_limits() -> ModelLimits {
+    active().read().unwrap().limits
+}
+
+/// Count tokens with the active embedding tokenizer
+pub fn count_tokens(text: &str) -> usize {
+    embedding_tokenizer().count_tokens(text)
+}
+
+/// Resolve and install the embedding and generation tokenizers for the
+/// current hardware config. Failures fall back to the heuristic with a warning.
+pub fn load_active(hardware: &HardwareParams) -> ModelLimits {
+    let load = |role: &str, path: Option<PathBuf>| -> Option<HfTokenizer> {
+        let path = path?;
+        match load_tokenizer(&path) {
+            Ok(tokenizer) => {
+                info!(
+                    role,
+                    tokenizer = tokenizer.name(),
+                    max_input_tokens = ?tokenizer.max_input_tokens(),
+                    "Tokenizer loaded"
+                );
+                Some(tokenizer)
+            }
+            Err(e) => {
+                warn!(role, path = %path.display(), "Tokenizer unavailable, using heuristic: {}", e);
+                None
+            }
+        }
+    };
+    let env_path = |key: &str| {
+        std::env::var(key)
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from)
+    };
+    let model_path = Some(PathBuf::from(hardware.model.trim()))
+        .filter(|p| !hardware.model.trim().is_empty() && p.exists());
+
+    let embedding = load("embedding", env_path("EMBEDDING_TOKENIZER"));
+    let generation = load(
+        "generation",
+        env_path("GENERATION_TOKENIZER").or(model_path),
+    );
+
+    let limits = ModelLimits {
+        embedding_max_tokens: embedding.as_ref().and_then(|t| t.max_input_tokens()),
+        generation_context: generation
+            .as_ref()
+            .and_then(|t| t.context_length())
+            .map_or(hardware.num_ctx, |trained| trained.min(hardware.num_ctx)),
+    };
+    let to_dyn = |t: Option<HfTokenizer>| -> Arc<dyn Tokenizer> {
+        match t {
+            Some(t) => Arc::new(t),
+            None => Arc::new(HeuristicTokenizer),
+        }
+    };
+    *active().write().unwrap() = ActiveTokenizers {
+        embedding: to_dyn(embedding),
+        generation: to_dyn(generation),
+        limits,
+    };
+    limits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn wordpiece_json(truncation: Value) -> String {
+        json!({
+            "version": "1.0", "truncation": truncation, "padding": null, "added_tokens": [],
+            "normalizer": {"type": "Lowercase"},
+            "pre_tokenizer": {"type": "Whitespace"},
+            "post_processor": {
+                "type": "TemplateProcessing",
+                "single": [{"SpecialToken": {"id": "[CLS]", "type_id": 0}},
+                           {"Sequence": {"id": "A", "type_id": 0}},
+                           {"SpecialToken": {"id": "[SEP]", "type_id": 0}}],
+                "pair": [{"Sequence": {"id": "A", "type_id": 0}}, {"Sequence": {"id": "B", "type_id": 1}}],
+                "special_tokens": {
+                    "[CLS]": {"id": "[CLS]", "ids": [1], "tokens": ["[CLS]"]},
+                    "[SEP]": {"id": "[SEP]", "ids": [2], "tokens": ["[SEP]"]}
+                }
+            },
+            "decoder": null,
+            "model": {
+                "type": "WordPiece", "unk_token": "[UNK]", "continuing_subword_prefix": "##",
+                "max_input_chars_per_word": 100,
+                "vocab": {"[UNK]": 0, "[CLS]": 1, "[SEP]": 2, "the": 3, "quick": 4, "brown": 5,
+                          "fox": 6, "jump": 7, "##s": 8, "over": 9, "lazy": 10, "dog": 11, ".": 12}
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_hf_tokenizer_counts_real_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("tokenizer.json"),
+            wordpiece_json(json!({"direction": "Right", "max_length": 8, "strategy": "LongestFirst", "stride": 0})),
+        )
+        .unwrap();
+        let tokenizer = load_tokenizer(dir.path()).unwrap();
+
+        // "jumps" is two word pieces; truncation in the file must not cap counts
+        let text = "The quick brown fox jumps over the lazy dog. The quick brown fox.";
+        assert_eq!(tokenizer.count_tokens(text), 16);
+        // 8-token model limit minus [CLS]/[SEP]
+        assert_eq!(tokenizer.max_input_tokens(), Some(6));
+
+        let pieces = tokenizer.split_to_fit(text, 6);
+        assert_eq!(pieces.len(), 3);
+        assert!(pieces.iter().all(|p| tokenizer.count_tokens(p) <= 6));
+        assert_eq!(pieces.join(" "), text);
+        assert_eq!(tokenizer.tail(text, 3), "brown fox.");
+    }
+
+    #[test]
+    fn test_limit_from_sentence_transformers_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("tokenizer.json"),
+            wordpiece_json(Value::Null),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("sentence_bert_config.json"),
+            r#"{"max_seq_length": 256, "do_lower_case": false}"#,
+        )
+        .unwrap();
+        let tokenizer = HfTokenizer::from_file(&dir.path().join("tokenizer.json")).unwrap();
+        assert_eq!(tokenizer.max_input_tokens(), Some(254));
+    }
+
+    fn gguf_string(out: &mut Vec<u8>, s: &str) {
+        out.extend((s.len() as u64).to_le_bytes());
+        out.extend(s.as_bytes());
+    }
+
+    fn gguf_kv_string(out: &mut Vec<u8>, key: &str, value: &str) {
+        gguf_string(out, key);
+        out.extend(8u32.to_le_bytes());
+        gguf_string(out, value);
+    }
+
+    fn gguf_kv_strings(out: &mut Vec<u8>, key: &str, values: &[&str]) {
+        gguf_string(out, key);
+        out.extend(9u32.to_le_bytes());
+        out.extend(8u32.to_le_bytes());
+        out.extend((values.len() as u64).to_le_bytes());
+        for v in values {
+            gguf_string(out, v);
+        }
+    }
+
+    #[test]
+    fn test_gguf_bpe_vocabulary() {
+        let mut out = b"GGUF".to_vec();
+        out.extend(3u32.to_le_bytes());
+        out.extend(0u64.to_le_bytes());
+        out.extend(5u64.to_le_bytes());
+        gguf_kv_string(&mut out, "general.architecture", "llama");
+        gguf_string(&mut out, "llama.context_length");
+        out.extend(4u32.to_le_bytes());
+        out.extend(4096u32.to_le_bytes());
+        gguf_kv_string(&mut out, "tokenizer.ggml.model", "gpt2");
+        gguf_kv_strings(
+            &mut out,
+            "tokenizer.ggml.tokens",
+            &[
+                "h", "e", "l", "o", "Ġ", "he", "ll", "hell", "hello", "Ġhello",
+            ],
+        );
+        gguf_kv_strings(
+            &mut out,
+            "tokenizer.ggml.merges",
+            &["h e", "l l", "he ll", "hell o", "Ġ hello"],
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tiny.gguf");
+        File::create(&path).unwrap().write_all(&out).unwrap();
+
+        let tokenizer = load_tokenizer(&path).unwrap();
+        assert_eq!(tokenizer.name(), "tiny");
+        assert_eq!(tokenizer.context_length(), Some(4096));
+        assert_eq!(tokenizer.count_tokens("hello hello hello"), 3);
+        assert_eq!(tokenizer.count_tokens("hell"), 1);
+    }
+
+    #[test]
+    fn test_heuristic_split_keeps_text() {
+        let text = "alpha beta gamma delta epsilon zeta eta theta";
+        let pieces = HeuristicTokenizer.split_to_fit(text, 4);
+        assert!(pieces.len() > 1);
+        // Long words may be cut mid-word, but no text is lost
+        assert_eq!(pieces.concat().replace(' ', ""), text.replace(' ', ""));
+        assert!(pieces
+            .iter()
+            .all(|p| HeuristicTokenizer.count_tokens(p) <= 4));
+        assert!(matches!(
+            load_tokenizer(Path::new("/nonexistent/tokenizer.json")),
+            Err(TokenizerError::Io(_))
+        ));
+    }
+}
@@ -38,6 +38,10 @@ pub struct VectorRecord {
     pub source: String,
     pub created_at: i64,
 
+    /// Larger section this chunk was cut from (parent-document retrieval)
+    #[serde(default)]
+    pub parent_id: Option<String>,
+
     // NEW: Fields for Phase 4 memory bounds
     #[serde(default)]
     pub relevance_score: f32,
@@ -68,6 +72,7 @@ impl VectorRecord {
             token_count,
             source,
             created_at,
+            parent_id: None,
             // NEW: Initialize Phase 4 fields
             relevance_score: 0.5,
             last_accessed: Instant::now(),
@@ -80,6 +85,22 @@ impl VectorRecord {
         self.relevance_score = score.clamp(0.0, 1.0);
         self
     }
+
+    /// Link to the parent section returned as context for this chunk
+    pub fn with_parent(mut self, parent_id: impl Into<String>) -> Self {
+        self.parent_id = Some(parent_id.into());
+        self
+    }
+}
+
+/// A parent section: kept for context expansion, never searched directly
+#[derive(Debug, Clone, Serialize)]
+pub struct ParentRecord {
+    pub chunk_id: String,
+    pub document_id: String,
+    pub content: String,
+    pub chunk_index: usize,
+    pub token_count: usize,
 }
 
 /// Search result with similarity score
@@ -146,6 +167,7 @@ impl StoreMetrics {
 pub struct VectorStore {
     config: VectorStoreConfig,
     records: Vec<VectorRecord>,
+    parents: HashMap<String, ParentRecord>,
 
     // NEW: Phase 4 memory bounds tracking
     index_map: HashMap<String, usize>,
@@ -172,6 +194,7 @@ impl VectorStore {
         Ok(Self {
             config,
             records: Vec::with_capacity(max_vectors),
+            parents: HashMap::new(),
             index_map: HashMap::new(),
             insertion_counter: 0,
             metrics: StoreMetrics::default(),
@@ -251,6 +274,39 @@ impl VectorStore {
         Ok(())
     }
 
+    /// Store a parent section for child chunks to expand into
+    pub fn add_parent(&mut self, parent: ParentRecord) {
+        self.parents.insert(parent.chunk_id.clone(), parent);
+    }
+
+    pub fn get_parent(&self, parent_id: &str) -> Option<&ParentRecord> {
+        self.parents.get(parent_id)
+    }
+
+    pub fn parent_of(&self, chunk_id: &str) -> Option<&ParentRecord> {
+        let record = &self.records[*self.index_map.get(chunk_id)?];
+        self.parents.get(record.parent_id.as_deref()?)
+    }
+
+    /// Chunks of `document_id` within `window` positions of `chunk_index`
+    /// (including it), ordered by chunk index
+    pub fn document_neighbors(
+        &self,
+        document_id: &str,
+        chunk_index: usize,
+        window: usize,
+    ) -> Vec<&VectorRecord> {
+        let mut neighbors: Vec<&VectorRecord> = self
+            .records
+            .iter()
+            .filter(|r| {
+                r.document_id == document_id && r.chunk_index.abs_diff(chunk_index) <= window
+            })
+            .collect();
+        neighbors.sort_by_key(|r| r.chunk_index);
+        neighbors
+    }
+
     /// Search for similar vectors
     pub async fn search(
         &mut self,
@@ -365,6 +421,8 @@ impl VectorStore {
             self.records.pop();
         }
 
+        self.parents.retain(|_, p| p.document_id != document_id);
+
         let deleted_count = initial_len - self.records.len();
         info!(deleted_count = deleted_count, "Documents deleted");
 
@@ -410,6 +468,7 @@ impl VectorStore {
     pub async fn clear(&mut self) -> Result<(), VectorStoreError> {
         info!("Clearing all records from vector store");
         self.records.clear();
+        self.parents.clear();
         self.index_map.clear();
         Ok(())
     }
@@ -554,6 +613,7 @@ mod tests {
             token_count: 10,
             source: "test.txt".to_string(),
             created_at: 0,
+            parent_id: None,
             relevance_score: 0.5,
             last_accessed: Instant::now(),
             insertion_order: 0,
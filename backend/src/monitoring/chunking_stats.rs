@@ -27,6 +27,10 @@ pub struct ChunkingStatsSnapshot {
     pub tokens: usize,
     pub duration_ms: u64,
     pub stats: Option<ChunkingStats>,
+    /// Chunks indexed with a contextual header
+    pub header_chunks: usize,
+    /// Tokens added by those headers (embedding input only)
+    pub header_tokens: usize,
 }
 
 impl ChunkingStatsSnapshot {
@@ -46,8 +50,16 @@ impl ChunkingStatsSnapshot {
             tokens,
             duration_ms,
             stats,
+            header_chunks: 0,
+            header_tokens: 0,
         }
     }
+
+    pub fn with_headers(mut self, header_chunks: usize, header_tokens: usize) -> Self {
+        self.header_chunks = header_chunks;
+        self.header_tokens = header_tokens;
+        self
+    }
 }
 
 fn current_capacity() -> usize {
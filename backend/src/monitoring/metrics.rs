@@ -1,7 +1,7 @@
 use once_cell::sync::Lazy;
 use prometheus::{
-    core::Collector, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts,
-    Registry, TextEncoder,
+    core::Collector, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
 };
 
 // Global Prometheus registry
@@ -178,6 +178,84 @@ pub static RATE_LIMIT_DROPS_BY_ROUTE: Lazy<IntCounterVec> = Lazy::new(|| {
     cv
 });
 
+pub static PII_FINDINGS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let opts = Opts::new(
+        "pii_findings_total",
+        "Sensitive values found at ingestion, partitioned by detector type",
+    )
+    .const_label("service", service)
+    .const_label("env", env_name);
+    let cv = IntCounterVec::new(opts, &["type"]).unwrap();
+    REGISTRY.register(Box::new(cv.clone())).ok();
+    cv
+});
+
+pub static LLM_TOKENS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let opts = Opts::new(
+        "llm_tokens_total",
+        "Tokens reported by LLM backends, partitioned by provider, model and kind",
+    )
+    .const_label("service", service)
+    .const_label("env", env_name);
+    let cv = IntCounterVec::new(opts, &["provider", "model", "kind"]).unwrap();
+    REGISTRY.register(Box::new(cv.clone())).ok();
+    cv
+});
+
+pub static LLM_ROUTE_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let opts = Opts::new(
+        "llm_route_requests_total",
+        "LLM requests per router route, partitioned by outcome (ok, error, skipped)",
+    )
+    .const_label("service", service)
+    .const_label("env", env_name);
+    let cv = IntCounterVec::new(opts, &["route", "outcome"]).unwrap();
+    REGISTRY.register(Box::new(cv.clone())).ok();
+    cv
+});
+
+pub static LLM_FAILOVERS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let opts = Opts::new(
+        "llm_failovers_total",
+        "Requests the LLM router moved from a failed route to the next one",
+    )
+    .const_label("service", service)
+    .const_label("env", env_name);
+    let cv = IntCounterVec::new(opts, &["from", "to"]).unwrap();
+    REGISTRY.register(Box::new(cv.clone())).ok();
+    cv
+});
+
+pub static LLM_ROUTE_BREAKER_STATE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let opts = Opts::new(
+        "llm_route_breaker_state",
+        "Circuit breaker state per LLM route (0=closed, 1=half_open, 2=open)",
+    )
+    .const_label("service", service)
+    .const_label("env", env_name);
+    let g = IntGaugeVec::new(opts, &["route"]).unwrap();
+    REGISTRY.register(Box::new(g.clone())).ok();
+    g
+});
+
+pub static LLM_ROUTE_HEALTHY: Lazy<IntGaugeVec> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let opts = Opts::new(
+        "llm_route_healthy",
+        "Result of the last background health probe per LLM route (1=up, 0=down)",
+    )
+    .const_label("service", service)
+    .const_label("env", env_name);
+    let g = IntGaugeVec::new(opts, &["route"]).unwrap();
+    REGISTRY.register(Box::new(g.clone())).ok();
+    g
+});
+
 // State gauges
 pub static DOCUMENTS_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
     let (service, env_name) = service_and_env();
@@ -203,6 +281,21 @@ pub static VECTORS_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
     g
 });
 
+pub static DELETED_VECTOR_SLOTS: Lazy<IntGauge> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let g = IntGauge::with_opts(
+        Opts::new(
+            "deleted_vector_slots",
+            "Zeroed vector slots of deleted chunks awaiting compaction",
+        )
+        .const_label("service", service)
+        .const_label("env", env_name),
+    )
+    .unwrap();
+    REGISTRY.register(Box::new(g.clone())).ok();
+    g
+});
+
 pub static INDEX_SIZE_BYTES: Lazy<IntGauge> = Lazy::new(|| {
     let (service, env_name) = service_and_env();
     let g = IntGauge::with_opts(
@@ -215,6 +308,49 @@ pub static INDEX_SIZE_BYTES: Lazy<IntGauge> = Lazy::new(|| {
     g
 });
 
+pub static GROUNDING_SCORE: Lazy<prometheus::HistogramVec> = Lazy::new(|| {
+    use prometheus::{histogram_opts, HistogramVec};
+    let (service, env_name) = service_and_env();
+    let mut opts = histogram_opts!(
+        "grounding_score",
+        "Share of answer claims supported by the retrieved context",
+        vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0]
+    );
+    opts.common_opts = opts
+        .common_opts
+        .const_label("service", service)
+        .const_label("env", env_name);
+    let hv = HistogramVec::new(opts, &["method"]).unwrap();
+    REGISTRY.register(Box::new(hv.clone())).ok();
+    hv
+});
+
+pub static GROUNDING_UNSUPPORTED_CLAIMS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let opts = Opts::new(
+        "grounding_unsupported_claims_total",
+        "Answer sentences the grounding check found no support for",
+    )
+    .const_label("service", service)
+    .const_label("env", env_name);
+    let c = IntCounter::with_opts(opts).unwrap();
+    REGISTRY.register(Box::new(c.clone())).ok();
+    c
+});
+
+pub static GROUNDING_ABSTENTIONS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    let (service, env_name) = service_and_env();
+    let opts = Opts::new(
+        "grounding_abstentions_total",
+        "Answers replaced by an abstention because their grounding score was too low",
+    )
+    .const_label("service", service)
+    .const_label("env", env_name);
+    let c = IntCounter::with_opts(opts).unwrap();
+    REGISTRY.register(Box::new(c.clone())).ok();
+    c
+});
+
 pub static REQUEST_LATENCY_MS: Lazy<prometheus::HistogramVec> = Lazy::new(|| {
     use prometheus::{histogram_opts, HistogramVec};
     let (service, env_name) = service_and_env();
@@ -232,6 +368,7 @@ pub static REQUEST_LATENCY_MS: Lazy<prometheus::HistogramVec> = Lazy::new(|| {
 pub fn refresh_retriever_gauges(retriever: &crate::retriever::Retriever) {
     DOCUMENTS_TOTAL.set(retriever.metrics.total_documents_indexed as i64);
     VECTORS_TOTAL.set(retriever.metrics.total_vectors as i64);
+    DELETED_VECTOR_SLOTS.set(retriever.metrics.deleted_vector_slots as i64);
     if let Ok(size) = retriever.metrics.get_index_size_bytes() {
         INDEX_SIZE_BYTES.set(size as i64);
     }
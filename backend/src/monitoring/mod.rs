@@ -34,9 +34,11 @@ pub mod ui_metrics;
 pub use crate::monitoring::metrics::{
     export_prometheus, observe_reindex_duration_ms, observe_search_latency_ms,
     refresh_retriever_gauges, APP_INFO, CACHE_HITS_TOTAL, CACHE_MISSES_TOTAL, DOCUMENTS_TOTAL,
-    INDEX_SIZE_BYTES, RATE_LIMIT_DROPS_BY_ROUTE, RATE_LIMIT_DROPS_TOTAL, REGISTRY,
-    REINDEX_FAILURE_TOTAL, REINDEX_SUCCESS_TOTAL, SEARCH_LATENCY_MS, STARTUP_DURATION_MS,
-    VECTORS_TOTAL,
+    GROUNDING_ABSTENTIONS_TOTAL, GROUNDING_SCORE, GROUNDING_UNSUPPORTED_CLAIMS_TOTAL,
+    INDEX_SIZE_BYTES, LLM_FAILOVERS_TOTAL, LLM_ROUTE_BREAKER_STATE, LLM_ROUTE_HEALTHY,
+    LLM_ROUTE_REQUESTS_TOTAL, LLM_TOKENS_TOTAL, PII_FINDINGS_TOTAL, RATE_LIMIT_DROPS_BY_ROUTE,
+    RATE_LIMIT_DROPS_TOTAL, REGISTRY, REINDEX_FAILURE_TOTAL, REINDEX_SUCCESS_TOTAL,
+    SEARCH_LATENCY_MS, STARTUP_DURATION_MS, VECTORS_TOTAL,
 };
 pub use alerting_hooks::{AlertingHooksConfig, ReindexCompletionEvent};
 pub use chunking_stats::{
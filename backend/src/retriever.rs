@@ -19,10 +19,16 @@ use tantivy::{
     directory::MmapDirectory,
     query::QueryParser,
     query::QueryParserError,
-    schema::{Field, Schema, Value, STORED, TEXT},
-    Index, IndexWriter, TantivyError,
+    query::TermQuery,
+    schema::{Field, IndexRecordOption, Schema, Value, FAST, INDEXED, STORED, STRING, TEXT},
+    Index, IndexWriter, TantivyError, Term,
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+
+/// Hits returned by `search` and `hybrid_search`
+const SEARCH_RESULT_LIMIT: usize = 10;
+/// Keyword hits fetched before near-duplicate copies are collapsed
+const SEARCH_FETCH_LIMIT: usize = 20;
 
 /// Custom error type for Retriever operations
 #[derive(Debug, Serialize, Deserialize)]
@@ -82,6 +88,54 @@ struct VectorStorage {
     doc_id_to_vector_idx: HashMap<String, usize>,
 }
 
+/// Keyword hit with the stored fields of the matching chunk
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub doc_id: String,
+    pub title: String,
+    pub content: String,
+    pub score: f32,
+    /// The chunk holds a table (see `index_table_chunk`)
+    pub table: bool,
+    #[serde(flatten)]
+    pub fields: ChunkFields,
+}
+
+/// Metadata stored in its own index fields so queries can filter on it,
+/// e.g. `symbol:"Config::load"` or `line_start:[10 TO 40]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkFields {
+    /// Canonical root of the repository a code chunk came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// File path relative to the repository root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol_kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_start: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_end: Option<u64>,
+    /// Publication time of a feed entry, Unix seconds, e.g.
+    /// `published:[1735689600 TO *]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<i64>,
+}
+
+/// Index fields behind `ChunkFields`
+struct MetadataFields {
+    repository: Field,
+    path: Field,
+    symbol: Field,
+    symbol_kind: Field,
+    line_start: Field,
+    line_end: Field,
+    published: Field,
+}
+
 /// Metrics for monitoring Retriever performance
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetrieverMetrics {
@@ -93,6 +147,9 @@ pub struct RetrieverMetrics {
     pub max_search_latency_us: u128,
     pub total_documents_indexed: usize,
     pub total_vectors: usize,
+    /// Zeroed slots of deleted chunks, dropped when the vectors are saved
+    #[serde(default)]
+    pub deleted_vector_slots: usize,
     pub index_path: String,
     pub last_updated: u64,
 }
@@ -108,6 +165,7 @@ impl Default for RetrieverMetrics {
             max_search_latency_us: 0,
             total_documents_indexed: 0,
             total_vectors: 0,
+            deleted_vector_slots: 0,
             index_path: String::new(),
             last_updated: 0,
         }
@@ -158,12 +216,16 @@ pub struct Retriever {
     pub title_field: Field,
     pub content_field: Field,
     pub doc_id_field: Field,
+    /// Set to "true" on chunks holding a table
+    pub table_field: Field,
+    metadata_fields: MetadataFields,
     pub doc_id_to_vector_idx: HashMap<String, usize>,
     pub vector_file_path: String,
     pub auto_save_threshold: usize,
     documents_since_save: Arc<AtomicUsize>,
     index_writer: Option<IndexWriter>,
     batch_mode: bool,
+    batch_journal: BatchJournal,
     search_cache: LruCache<String, Vec<String>>,
     cache_enabled: bool,
     // Phase 11 Step 2: L2 Cache integration
@@ -175,6 +237,148 @@ pub struct Retriever {
     index_dir_path: String,
 }
 
+/// What the open batch changed outside the tantivy writer, so an aborted
+/// batch can be undone and a committed one can finish its deletions
+#[derive(Default)]
+struct BatchJournal {
+    /// Vectors are only appended, so everything past this length is new
+    vectors_len: usize,
+    /// Mappings overwritten by the batch, with their previous slot
+    replaced: Vec<(String, Option<usize>)>,
+    /// Vector slots dropped on commit, unless the id was indexed again
+    deleted: Vec<(String, usize)>,
+}
+
+/// An open batch that is rolled back when dropped without `commit`, so an
+/// early return cannot leave the retriever stuck in batch mode
+pub struct IndexBatch<'a> {
+    retriever: &'a mut Retriever,
+    committed: bool,
+}
+
+impl IndexBatch<'_> {
+    /// Commit the batch and save vectors (see `Retriever::commit`)
+    pub fn commit(mut self) -> Result<(), RetrieverError> {
+        self.retriever.commit()?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl std::ops::Deref for IndexBatch<'_> {
+    type Target = Retriever;
+
+    fn deref(&self) -> &Retriever {
+        self.retriever
+    }
+}
+
+impl std::ops::DerefMut for IndexBatch<'_> {
+    fn deref_mut(&mut self) -> &mut Retriever {
+        self.retriever
+    }
+}
+
+impl Drop for IndexBatch<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.retriever.abort_batch();
+        }
+    }
+}
+
+/// Open or create the index. An index written with another schema (doc_id
+/// used to be tokenized, the table flag is newer) is migrated first, see
+/// `migrate_index`.
+fn open_index(index_dir: &str, schema: Schema) -> Result<Index, RetrieverError> {
+    let dir = MmapDirectory::open(index_dir)?;
+    let exists = Index::exists(&dir).map_err(|e| RetrieverError::IndexError(e.to_string()))?;
+    if !exists {
+        return Ok(Index::open_or_create(dir, schema)?);
+    }
+    let existing = Index::open(dir)?;
+    if existing.schema() == schema {
+        return Ok(existing);
+    }
+    migrate_index(existing, index_dir, &schema)?;
+    Ok(Index::open_in_dir(index_dir)?)
+}
+
+/// Copy the stored fields of every live document into a new index with the
+/// current schema, built next to the live one. The live index is then
+/// renamed to `<index_dir>.bak-<timestamp>` and the copy renamed into its
+/// place, like `reindex_atomic` does, so a failure at any point leaves
+/// either the old index or its backup.
+fn migrate_index(old: Index, index_dir: &str, schema: &Schema) -> Result<(), RetrieverError> {
+    let live = Path::new(index_dir);
+    let staging = live.with_extension("migrate");
+    warn!(
+        index_dir,
+        staging = ?staging,
+        "Index schema changed; migrating documents into a new index"
+    );
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let old_schema = old.schema();
+    let copied = {
+        let new = Index::create_in_dir(&staging, schema.clone())?;
+        let mut writer: IndexWriter = new.writer(50_000_000)?;
+        let searcher = old.reader()?.searcher();
+        let mut copied = 0;
+        for (ord, segment) in searcher.segment_readers().iter().enumerate() {
+            for doc in segment.doc_ids_alive() {
+                let stored: tantivy::TantivyDocument =
+                    searcher.doc(tantivy::DocAddress::new(ord as u32, doc))?;
+                let mut copy = tantivy::TantivyDocument::default();
+                for (field, entry) in schema.fields() {
+                    let Ok(from) = old_schema.get_field(entry.name()) else {
+                        continue;
+                    };
+                    for value in stored.get_all(from) {
+                        if let Some(text) = value.as_str() {
+                            copy.add_text(field, text);
+                        } else if let Some(number) = value.as_u64() {
+                            copy.add_u64(field, number);
+                        } else if let Some(number) = value.as_i64() {
+                            copy.add_i64(field, number);
+                        }
+                    }
+                }
+                writer.add_document(copy)?;
+                copied += 1;
+            }
+        }
+        writer.commit()?;
+        writer.wait_merging_threads()?;
+        copied
+    };
+    drop(old);
+
+    let ts = Utc::now().format("%Y%m%d%H%M%S").to_string();
+    let backup = live.with_extension(format!("bak-{}", ts));
+    fs::rename(live, &backup)
+        .map_err(|e| RetrieverError::IoError(format!("index backup rename failed: {}", e)))?;
+    if let Err(e) = fs::rename(&staging, live) {
+        // Put the old index back rather than leave none
+        let restored = fs::rename(&backup, live);
+        return Err(RetrieverError::IoError(format!(
+            "migrated index rename failed: {} (old index restored: {})",
+            e,
+            restored.is_ok()
+        )));
+    }
+    warn!(
+        index_dir,
+        backup = ?backup,
+        documents = copied,
+        "Index migrated to the current schema; the old index is kept as a backup"
+    );
+    Ok(())
+}
+
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
     let magnitude_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
@@ -422,11 +626,22 @@ impl Retriever {
         let mut schema_builder = Schema::builder();
         let title_field = schema_builder.add_text_field("title", TEXT | STORED);
         let content_field = schema_builder.add_text_field("content", TEXT | STORED);
-        let doc_id_field = schema_builder.add_text_field("doc_id", TEXT | STORED);
+        // Raw so chunks can be deleted by exact id
+        let doc_id_field = schema_builder.add_text_field("doc_id", STRING | STORED);
+        let table_field = schema_builder.add_text_field("table", STRING | STORED);
+        let metadata_fields = MetadataFields {
+            repository: schema_builder.add_text_field("repository", STRING | STORED),
+            path: schema_builder.add_text_field("path", STRING | STORED),
+            symbol: schema_builder.add_text_field("symbol", STRING | STORED),
+            symbol_kind: schema_builder.add_text_field("symbol_kind", STRING | STORED),
+            // Fast so range queries work
+            line_start: schema_builder.add_u64_field("line_start", INDEXED | STORED | FAST),
+            line_end: schema_builder.add_u64_field("line_end", INDEXED | STORED | FAST),
+            published: schema_builder.add_i64_field("published", INDEXED | STORED | FAST),
+        };
         let schema = schema_builder.build();
         fs::create_dir_all(index_dir)?;
-        let dir = MmapDirectory::open(index_dir)?;
-        let index = Index::open_or_create(dir, schema)?;
+        let index = open_index(index_dir, schema)?;
 
         let vector_file_path_owned = vector_file_path.to_string();
 
@@ -436,12 +651,15 @@ impl Retriever {
             title_field,
             content_field,
             doc_id_field,
+            table_field,
+            metadata_fields,
             doc_id_to_vector_idx: HashMap::new(),
             vector_file_path: vector_file_path_owned.clone(),
             auto_save_threshold: 100,
             documents_since_save: Arc::new(AtomicUsize::new(0)),
             index_writer: None,
             batch_mode: false,
+            batch_journal: BatchJournal::default(),
             search_cache: LruCache::new(NonZeroUsize::new(100).unwrap()),
             cache_enabled: true,
             // Phase 11 Step 2: Initialize L2 cache (300 seconds = 5 minute TTL)
@@ -574,14 +792,41 @@ impl Retriever {
         }
         self.index_writer = Some(self.index.writer(256_000_000)?);
         self.batch_mode = true;
+        self.batch_journal = BatchJournal {
+            vectors_len: self.vectors.len(),
+            ..BatchJournal::default()
+        };
         debug!("Batch indexing mode started");
         Ok(())
     }
 
+    /// Begin a batch that is rolled back unless committed
+    pub fn batch(&mut self) -> Result<IndexBatch<'_>, RetrieverError> {
+        self.begin_batch()?;
+        Ok(IndexBatch {
+            retriever: self,
+            committed: false,
+        })
+    }
+
     pub fn end_batch(&mut self) -> Result<(), RetrieverError> {
         if let Some(mut writer) = self.index_writer.take() {
-            writer.commit()?;
+            let committed = writer.commit();
             self.batch_mode = false;
+            let journal = std::mem::take(&mut self.batch_journal);
+            if let Err(e) = committed {
+                self.undo_batch_vectors(journal);
+                return Err(e.into());
+            }
+            for (chunk_id, idx) in journal.deleted {
+                if let Some(vector) = self.vectors.get_mut(idx) {
+                    vector.iter_mut().for_each(|v| *v = 0.0);
+                    self.metrics.deleted_vector_slots += 1;
+                }
+                if self.doc_id_to_vector_idx.get(&chunk_id) == Some(&idx) {
+                    self.doc_id_to_vector_idx.remove(&chunk_id);
+                }
+            }
             self.clear_cache();
             if let Ok(reader) = self.index.reader() {
                 self.metrics.total_documents_indexed = reader.searcher().num_docs() as usize;
@@ -595,6 +840,33 @@ impl Retriever {
         }
     }
 
+    /// Drop the open batch: the writer rolls back to the last commit and
+    /// vectors added since `begin_batch` are removed
+    pub fn abort_batch(&mut self) {
+        let Some(mut writer) = self.index_writer.take() else {
+            return;
+        };
+        if let Err(e) = writer.rollback() {
+            error!("Failed to roll back batch: {}", e);
+        }
+        self.batch_mode = false;
+        let journal = std::mem::take(&mut self.batch_journal);
+        self.undo_batch_vectors(journal);
+        self.clear_cache();
+        warn!("Batch indexing aborted, changes rolled back");
+    }
+
+    fn undo_batch_vectors(&mut self, journal: BatchJournal) {
+        for (chunk_id, previous) in journal.replaced.into_iter().rev() {
+            match previous {
+                Some(idx) => self.doc_id_to_vector_idx.insert(chunk_id, idx),
+                None => self.doc_id_to_vector_idx.remove(&chunk_id),
+            };
+        }
+        self.vectors.truncate(journal.vectors_len);
+        self.metrics.total_vectors = self.vectors.len();
+    }
+
     pub fn add_documents_batch(
         &mut self,
         documents: Vec<(String, String, String)>,
@@ -606,8 +878,7 @@ impl Retriever {
         let mut count = 0;
         for (doc_id, title, content) in documents {
             // ← unpack all 3 values
-            if let Err(e) = self.add_document_to_batch(&doc_id, &title, &content) {
-                // ← pass all 3 to add_document_to_batch
+            if let Err(e) = self.add_document(&doc_id, &title, &content) {
                 error!("Failed to add document '{}': {}", doc_id, e);
             } else {
                 count += 1;
@@ -621,17 +892,11 @@ impl Retriever {
 
     fn add_document_to_batch(
         &mut self,
-        doc_id: &str,
-        title: &str,
-        content: &str,
+        doc: tantivy::TantivyDocument,
     ) -> Result<(), RetrieverError> {
         if !self.batch_mode {
             return Err(RetrieverError::IndexError("Not in batch mode".to_string()));
         }
-        let mut doc = tantivy::TantivyDocument::default();
-        doc.add_text(self.doc_id_field, doc_id);
-        doc.add_text(self.title_field, title);
-        doc.add_text(self.content_field, content);
         if let Some(writer) = &mut self.index_writer {
             writer.add_document(doc)?;
             Ok(())
@@ -671,7 +936,8 @@ impl Retriever {
         let parser =
             QueryParser::for_index(&self.index, vec![self.title_field, self.content_field]);
         let query = parser.parse_query(query_str)?;
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(10))?;
+        // Over-fetch so collapsing near-duplicate copies still leaves 10 hits
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(SEARCH_FETCH_LIMIT))?;
         let mut results = Vec::new();
         for (_score, doc_address) in top_docs {
             let doc = searcher.doc::<tantivy::TantivyDocument>(doc_address)?;
@@ -682,6 +948,8 @@ impl Retriever {
                 .to_string();
             results.push(content);
         }
+        let mut results = crate::ingest::dedup::collapse_hits(results, |c| c.as_str());
+        results.truncate(SEARCH_RESULT_LIMIT);
         if self.cache_enabled {
             self.search_cache
                 .put(query_str.to_string(), results.clone());
@@ -702,6 +970,142 @@ impl Retriever {
         Ok(results)
     }
 
+    /// Keyword search that keeps the stored doc id, title and score with each hit.
+    /// Bypasses the result caches; callers use it when they need chunk identity
+    /// (e.g. code definitions with line ranges) rather than plain content.
+    pub fn search_hits(
+        &self,
+        query_str: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, RetrieverError> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let parser =
+            QueryParser::for_index(&self.index, vec![self.title_field, self.content_field]);
+        let query = parser.parse_query(query_str)?;
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit.max(1)))?;
+        let mut hits = Vec::with_capacity(top_docs.len());
+        for (score, doc_address) in top_docs {
+            let doc = searcher.doc::<tantivy::TantivyDocument>(doc_address)?;
+            hits.push(self.hit_from_doc(&doc, score));
+        }
+        Ok(hits)
+    }
+
+    /// `hybrid_search` that keeps chunk identity: keyword and vector matches
+    /// are fused by reciprocal rank, and each hit's `score` is the cosine
+    /// similarity of its stored vector to `query_vector`. The query is parsed
+    /// leniently since it is usually a question rather than query syntax.
+    pub fn hybrid_hits(
+        &self,
+        query: &str,
+        query_vector: &[f32],
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, RetrieverError> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let parser =
+            QueryParser::for_index(&self.index, vec![self.title_field, self.content_field]);
+        let (keyword_query, _) = parser.parse_query_lenient(query);
+        let keyword_docs =
+            searcher.search(&keyword_query, &TopDocs::with_limit(SEARCH_FETCH_LIMIT))?;
+
+        let k = 60.0;
+        let mut fused: HashMap<String, (f32, Option<tantivy::TantivyDocument>)> = HashMap::new();
+        for (rank, (_score, doc_address)) in keyword_docs.into_iter().enumerate() {
+            let doc = searcher.doc::<tantivy::TantivyDocument>(doc_address)?;
+            let doc_id = doc
+                .get_first(self.doc_id_field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string();
+            let entry = fused.entry(doc_id).or_insert((0.0, None));
+            entry.0 += 1.0 / (k + rank as f32 + 1.0);
+            entry.1 = Some(doc);
+        }
+        let ids_by_idx: HashMap<usize, &String> = self
+            .doc_id_to_vector_idx
+            .iter()
+            .map(|(id, idx)| (*idx, id))
+            .collect();
+        let vector_matches = self
+            .vector_search(query_vector, SEARCH_FETCH_LIMIT)
+            .into_iter()
+            .filter_map(|(idx, _)| ids_by_idx.get(&idx).map(|id| (*id).clone()));
+        for (rank, doc_id) in vector_matches.enumerate() {
+            fused.entry(doc_id).or_insert((0.0, None)).0 += 1.0 / (k + rank as f32 + 1.0);
+        }
+
+        let mut ranked: Vec<_> = fused.into_iter().collect();
+        ranked.sort_by(|a, b| {
+            b.1 .0
+                .partial_cmp(&a.1 .0)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let mut hits = Vec::with_capacity(limit);
+        for (doc_id, (_, doc)) in ranked {
+            if hits.len() >= limit {
+                break;
+            }
+            let doc = match doc {
+                Some(doc) => doc,
+                // Vector-only matches are looked up by their exact id
+                None => {
+                    let term = Term::from_field_text(self.doc_id_field, &doc_id);
+                    let query = TermQuery::new(term, IndexRecordOption::Basic);
+                    match searcher.search(&query, &TopDocs::with_limit(1))?.first() {
+                        Some((_, address)) => searcher.doc(*address)?,
+                        None => continue,
+                    }
+                }
+            };
+            let similarity = self
+                .doc_id_to_vector_idx
+                .get(&doc_id)
+                .and_then(|idx| self.vectors.get(*idx))
+                .map(|vector| cosine_similarity(query_vector, vector))
+                .unwrap_or(0.0);
+            hits.push(self.hit_from_doc(&doc, similarity));
+        }
+        Ok(hits)
+    }
+
+    fn hit_from_doc(&self, doc: &tantivy::TantivyDocument, score: f32) -> SearchHit {
+        let text = |field: Field| {
+            doc.get_first(field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
+        SearchHit {
+            doc_id: text(self.doc_id_field),
+            title: text(self.title_field),
+            content: text(self.content_field),
+            score,
+            table: doc.get_first(self.table_field).is_some(),
+            fields: self.fields_from_doc(doc),
+        }
+    }
+
+    fn fields_from_doc(&self, doc: &tantivy::TantivyDocument) -> ChunkFields {
+        let meta = &self.metadata_fields;
+        let text = |field: Field| {
+            doc.get_first(field)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let number = |field: Field| doc.get_first(field).and_then(|v| v.as_u64());
+        ChunkFields {
+            repository: text(meta.repository),
+            path: text(meta.path),
+            symbol: text(meta.symbol),
+            symbol_kind: text(meta.symbol_kind),
+            line_start: number(meta.line_start),
+            line_end: number(meta.line_end),
+            published: doc.get_first(meta.published).and_then(|v| v.as_i64()),
+        }
+    }
+
     pub fn add_vector(&mut self, vector: Vec<f32>) {
         self.vectors.push(vector);
         self.metrics.total_vectors += 1;
@@ -711,6 +1115,10 @@ impl Retriever {
     pub fn add_vector_with_id(&mut self, doc_id: String, vector: Vec<f32>) {
         let idx = self.vectors.len();
         self.vectors.push(vector);
+        if self.batch_mode {
+            let previous = self.doc_id_to_vector_idx.get(&doc_id).copied();
+            self.batch_journal.replaced.push((doc_id.clone(), previous));
+        }
         self.doc_id_to_vector_idx.insert(doc_id, idx);
         self.metrics.total_vectors += 1;
         self.check_auto_save();
@@ -778,10 +1186,13 @@ impl Retriever {
         }
         let mut merged_results: Vec<(String, f32)> = score_map.into_iter().collect();
         merged_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        Ok(merged_results
+        let merged: Vec<String> = merged_results
             .into_iter()
-            .take(10)
             .map(|(content, _)| content)
+            .collect();
+        Ok(crate::ingest::dedup::collapse_hits(merged, |c| c.as_str())
+            .into_iter()
+            .take(SEARCH_RESULT_LIMIT)
             .collect())
     }
 
@@ -843,13 +1254,49 @@ impl Retriever {
         title: &str,
         content: &str,
     ) -> Result<(), RetrieverError> {
-        if self.batch_mode {
-            return self.add_document_to_batch(doc_id, title, content);
-        }
+        self.add_chunk_document(doc_id, title, content, false, &ChunkFields::default())
+    }
+
+    fn add_chunk_document(
+        &mut self,
+        doc_id: &str,
+        title: &str,
+        content: &str,
+        table: bool,
+        fields: &ChunkFields,
+    ) -> Result<(), RetrieverError> {
         let mut doc = tantivy::TantivyDocument::default();
         doc.add_text(self.doc_id_field, doc_id);
         doc.add_text(self.title_field, title);
         doc.add_text(self.content_field, content);
+        if table {
+            doc.add_text(self.table_field, "true");
+        }
+        let meta = &self.metadata_fields;
+        for (field, value) in [
+            (meta.repository, &fields.repository),
+            (meta.path, &fields.path),
+            (meta.symbol, &fields.symbol),
+            (meta.symbol_kind, &fields.symbol_kind),
+        ] {
+            if let Some(value) = value {
+                doc.add_text(field, value);
+            }
+        }
+        for (field, value) in [
+            (meta.line_start, fields.line_start),
+            (meta.line_end, fields.line_end),
+        ] {
+            if let Some(value) = value {
+                doc.add_u64(field, value);
+            }
+        }
+        if let Some(published) = fields.published {
+            doc.add_i64(meta.published, published);
+        }
+        if self.batch_mode {
+            return self.add_document_to_batch(doc);
+        }
         let mut index_writer = self.index.writer(256_000_000)?;
         index_writer.add_document(doc)?;
         index_writer.commit()?;
@@ -866,6 +1313,36 @@ impl Retriever {
         Ok(())
     }
 
+    /// Drop the zeroed, unmapped slots left by `delete_chunks` and renumber
+    /// the mappings. Skipped inside a batch, whose journal holds slot numbers
+    pub fn compact_vectors(&mut self) -> usize {
+        if self.batch_mode || self.metrics.deleted_vector_slots == 0 {
+            return 0;
+        }
+        let mapped: HashSet<usize> = self.doc_id_to_vector_idx.values().copied().collect();
+        let mut new_idx = vec![None; self.vectors.len()];
+        let mut kept = Vec::with_capacity(self.vectors.len());
+        for (idx, vector) in std::mem::take(&mut self.vectors).into_iter().enumerate() {
+            if mapped.contains(&idx) || vector.iter().any(|v| *v != 0.0) {
+                new_idx[idx] = Some(kept.len());
+                kept.push(vector);
+            }
+        }
+        let dropped = new_idx.len() - kept.len();
+        self.vectors = kept;
+        for idx in self.doc_id_to_vector_idx.values_mut() {
+            if let Some(Some(moved)) = new_idx.get(*idx) {
+                *idx = *moved;
+            }
+        }
+        self.metrics.total_vectors = self.vectors.len();
+        self.metrics.deleted_vector_slots = 0;
+        if dropped > 0 {
+            self.clear_cache();
+        }
+        dropped
+    }
+
     fn parity_repair(&mut self) -> usize {
         let mapped_indices: std::collections::HashSet<usize> =
             self.doc_id_to_vector_idx.values().cloned().collect();
@@ -890,6 +1367,11 @@ impl Retriever {
                 info!("Temp save allowed during reindex: {}", filename);
             }
         }
+        // Deleted slots would otherwise be mapped back as unmapped vectors
+        let compacted = self.compact_vectors();
+        if compacted > 0 {
+            info!("Compaction: dropped {} deleted vector slots", compacted);
+        }
         // Ensure parity before writing
         let repaired = self.parity_repair();
         if repaired > 0 {
@@ -966,6 +1448,129 @@ impl Retriever {
         Ok(())
     }
 
+    /// Same as `index_chunk`, but stores a searchable title (e.g. a code symbol)
+    /// instead of repeating the chunk id.
+    pub fn index_chunk_with_title(
+        &mut self,
+        chunk_id: &str,
+        title: &str,
+        chunk_text: &str,
+        vector: &[f32],
+    ) -> Result<(), RetrieverError> {
+        self.add_document(chunk_id, title, chunk_text)?;
+        self.add_vector_with_id(chunk_id.to_string(), vector.to_vec());
+        Ok(())
+    }
+
+    /// Same as `index_chunk_with_title`, and marks the chunk as holding a
+    /// table so search hits can tell
+    pub fn index_table_chunk(
+        &mut self,
+        chunk_id: &str,
+        title: &str,
+        chunk_text: &str,
+        vector: &[f32],
+    ) -> Result<(), RetrieverError> {
+        self.add_chunk_document(chunk_id, title, chunk_text, true, &ChunkFields::default())?;
+        self.add_vector_with_id(chunk_id.to_string(), vector.to_vec());
+        Ok(())
+    }
+
+    /// Same as `index_chunk_with_title`, and stores `fields` in their own
+    /// index fields so search can filter on them
+    pub fn index_chunk_with_fields(
+        &mut self,
+        chunk_id: &str,
+        title: &str,
+        chunk_text: &str,
+        vector: &[f32],
+        fields: &ChunkFields,
+    ) -> Result<(), RetrieverError> {
+        self.add_chunk_document(chunk_id, title, chunk_text, false, fields)?;
+        self.add_vector_with_id(chunk_id.to_string(), vector.to_vec());
+        Ok(())
+    }
+
+    /// Ids of the committed chunks whose `repository` field is exactly `repository`
+    pub fn chunk_ids_in_repository(&self, repository: &str) -> Result<Vec<String>, RetrieverError> {
+        let searcher = self.index.reader()?.searcher();
+        let term = Term::from_field_text(self.metadata_fields.repository, repository);
+        let query = TermQuery::new(term, IndexRecordOption::Basic);
+        let addresses = searcher.search(&query, &tantivy::collector::DocSetCollector)?;
+        let mut ids = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            let doc = searcher.doc::<tantivy::TantivyDocument>(address)?;
+            if let Some(id) = doc.get_first(self.doc_id_field).and_then(|v| v.as_str()) {
+                ids.push(id.to_string());
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Ids of the indexed chunks starting with `prefix`, e.g. every chunk of one source
+    pub fn chunk_ids_with_prefix(&self, prefix: &str) -> Vec<String> {
+        self.doc_id_to_vector_idx
+            .keys()
+            .filter(|id| id.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// Remove chunks by id from the keyword index and drop their vector mappings.
+    /// Vector slots are zeroed rather than removed so other indices stay valid
+    /// until `compact_vectors` drops them on the next save.
+    /// Uses the open batch writer when there is one, otherwise commits directly;
+    /// inside a batch the vectors are dropped when it commits.
+    pub fn delete_chunks(&mut self, chunk_ids: &[String]) -> Result<usize, RetrieverError> {
+        if chunk_ids.is_empty() {
+            return Ok(0);
+        }
+        let terms: Vec<Term> = chunk_ids
+            .iter()
+            .map(|chunk_id| Term::from_field_text(self.doc_id_field, chunk_id))
+            .collect();
+        match self.index_writer.as_mut() {
+            Some(writer) => {
+                for term in terms {
+                    writer.delete_term(term);
+                }
+                // Vectors go when the batch commits, so an abort keeps them
+                let mut removed = 0;
+                for chunk_id in chunk_ids {
+                    if let Some(&idx) = self.doc_id_to_vector_idx.get(chunk_id) {
+                        self.batch_journal.deleted.push((chunk_id.clone(), idx));
+                        removed += 1;
+                    }
+                }
+                return Ok(removed);
+            }
+            None => {
+                let mut writer: IndexWriter = self.index.writer(256_000_000)?;
+                for term in terms {
+                    writer.delete_term(term);
+                }
+                writer.commit()?;
+                if let Ok(reader) = self.index.reader() {
+                    reader.reload()?;
+                    self.metrics.total_documents_indexed = reader.searcher().num_docs() as usize;
+                }
+            }
+        }
+
+        let mut removed = 0;
+        for chunk_id in chunk_ids {
+            if let Some(idx) = self.doc_id_to_vector_idx.remove(chunk_id) {
+                if let Some(vector) = self.vectors.get_mut(idx) {
+                    vector.iter_mut().for_each(|v| *v = 0.0);
+                    self.metrics.deleted_vector_slots += 1;
+                }
+                removed += 1;
+            }
+        }
+        self.clear_cache();
+        Ok(removed)
+    }
+
     fn check_disk_space(&self, min_free_bytes: u64) -> Result<(), RetrieverError> {
         let path = Path::new(&self.index_dir_path);
         let available_space = fs2::available_space(path)
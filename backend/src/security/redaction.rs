@@ -0,0 +1,767 @@
+// src/security/redaction.rs
+// PII redaction at ingestion
+//
+// Every ingestion path passes chunk text (and titles) through a redaction
+// session before embedding and keyword indexing, so detected values never
+// reach tantivy or the vector store. Detectors are regexes, most with a
+// checksum or structural check (IBAN mod-97, Luhn, SSN ranges, BSN 11-proef)
+// to keep false positives down, plus custom patterns from the environment. Each document
+// gets a report of what was found, stored in `redaction_reports`, and
+// `pii_findings_total{type}` counts findings.
+//
+// Environment variables:
+// - PII_POLICY: off | mask | hash | drop_chunk (default: off)
+// - PII_DETECTORS: detector types to run, comma separated (default: all of
+//   email, phone, iban, national_id, credit_card, api_key)
+// - PII_CUSTOM_PATTERNS: extra detectors as a JSON object of type -> regex,
+//   e.g. {"employee_id": "EMP-\\d{6}"}
+// - PII_HASH_SALT: salt for the hash policy
+
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+pub const BUILTIN_DETECTORS: [&str; 6] = [
+    "email",
+    "phone",
+    "iban",
+    "national_id",
+    "credit_card",
+    "api_key",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionPolicy {
+    Off,
+    /// Replace each value with its type, e.g. `[EMAIL]`
+    Mask,
+    /// Replace each value with its type and a salted hash, so equal values
+    /// stay linkable: `[EMAIL:3f9a1c2b7d4e]`
+    Hash,
+    /// Do not index chunks that contain a finding
+    DropChunk,
+}
+
+impl std::str::FromStr for RedactionPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "off" | "none" | "" => Ok(RedactionPolicy::Off),
+            "mask" => Ok(RedactionPolicy::Mask),
+            "hash" => Ok(RedactionPolicy::Hash),
+            "drop_chunk" | "drop-chunk" | "drop" => Ok(RedactionPolicy::DropChunk),
+            other => Err(format!("unknown redaction policy: {}", other)),
+        }
+    }
+}
+
+impl RedactionPolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            RedactionPolicy::Off => "off",
+            RedactionPolicy::Mask => "mask",
+            RedactionPolicy::Hash => "hash",
+            RedactionPolicy::DropChunk => "drop_chunk",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedactionConfig {
+    pub policy: RedactionPolicy,
+    /// Built-in detector types to run (None = all)
+    pub detectors: Option<Vec<String>>,
+    /// Custom detectors as (type, regex)
+    pub custom_patterns: Vec<(String, String)>,
+    pub hash_salt: String,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            policy: RedactionPolicy::Off,
+            detectors: None,
+            custom_patterns: Vec::new(),
+            hash_salt: String::new(),
+        }
+    }
+}
+
+impl RedactionConfig {
+    pub fn from_env() -> Self {
+        let policy = match env::var("PII_POLICY") {
+            Ok(raw) => raw.parse().unwrap_or_else(|e| {
+                warn!("PII_POLICY: {}", e);
+                RedactionPolicy::Off
+            }),
+            Err(_) => RedactionPolicy::Off,
+        };
+        let detectors = env::var("PII_DETECTORS").ok().map(|raw| {
+            raw.split(',')
+                .map(|d| d.trim().to_lowercase())
+                .filter(|d| !d.is_empty())
+                .collect()
+        });
+        let custom_patterns = match env::var("PII_CUSTOM_PATTERNS") {
+            Ok(raw) => match serde_json::from_str::<BTreeMap<String, String>>(&raw) {
+                Ok(map) => map.into_iter().collect(),
+                Err(e) => {
+                    warn!("PII_CUSTOM_PATTERNS is not a JSON object of strings: {}", e);
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+        Self {
+            policy,
+            detectors,
+            custom_patterns,
+            hash_salt: env::var("PII_HASH_SALT").unwrap_or_default(),
+        }
+    }
+}
+
+/// A detected value: byte range in the scanned text and its type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+struct Detector {
+    kind: String,
+    regex: Regex,
+    /// Capture group holding the value (0 = whole match)
+    group: usize,
+    validate: fn(&str) -> bool,
+}
+
+impl Detector {
+    fn new(kind: &str, pattern: &str, group: usize, validate: fn(&str) -> bool) -> Self {
+        Self {
+            kind: kind.to_string(),
+            regex: Regex::new(pattern).expect("built-in PII pattern"),
+            group,
+            validate,
+        }
+    }
+}
+
+fn builtin_detectors(kind: &str) -> Vec<Detector> {
+    match kind {
+        "email" => vec![Detector::new(
+            "email",
+            r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b",
+            0,
+            |_| true,
+        )],
+        "phone" => vec![
+            Detector::new("phone", r"\+\d[\d\s().-]{6,18}\d", 0, |v| {
+                (8..=15).contains(&digit_count(v))
+            }),
+            Detector::new("phone", r"\(?\b\d{3}\)?[\s.-]\d{3}[\s.-]\d{4}\b", 0, |_| {
+                true
+            }),
+        ],
+        "iban" => vec![Detector::new(
+            "iban",
+            r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b",
+            0,
+            valid_iban,
+        )],
+        "national_id" => vec![
+            Detector::new("national_id", r"\b\d{3}-\d{2}-\d{4}\b", 0, valid_ssn),
+            // Dutch BSN, bare or as 1234.56.782
+            Detector::new(
+                "national_id",
+                r"\b(?:\d{9}|\d{4}\.\d{2}\.\d{3})\b",
+                0,
+                valid_bsn,
+            ),
+        ],
+        "credit_card" => vec![Detector::new(
+            "credit_card",
+            r"\b\d(?:[ -]?\d){12,18}\b",
+            0,
+            |v| (13..=19).contains(&digit_count(v)) && luhn(v),
+        )],
+        "api_key" => vec![
+            Detector::new(
+                "api_key",
+                r"\b(?:sk|pk|rk)-[A-Za-z0-9_-]{20,}|\bAKIA[0-9A-Z]{16}\b|\bgh[pousr]_[A-Za-z0-9]{36,}\b|\bxox[abprs]-[A-Za-z0-9-]{10,}|\bAIza[0-9A-Za-z_-]{35}\b|\beyJ[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}",
+                0,
+                |_| true,
+            ),
+            // Values assigned to key-like names: api_key = "...", token: ...
+            Detector::new(
+                "api_key",
+                r#"(?i)\b(?:api[_-]?key|access[_-]?key|secret(?:[_-]?key)?|auth[_-]?token|token|password|passwd)\b["']?\s*[:=]\s*["']?([A-Za-z0-9_\-./+=]{12,})"#,
+                1,
+                |_| true,
+            ),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+fn digit_count(value: &str) -> usize {
+    value.chars().filter(char::is_ascii_digit).count()
+}
+
+/// ISO 13616 check: rearranged, letters as numbers, mod 97 == 1
+fn valid_iban(value: &str) -> bool {
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let rearranged = compact[4..].chars().chain(compact[..4].chars());
+    let mut remainder: u32 = 0;
+    for c in rearranged {
+        let digits = match c.to_digit(36) {
+            Some(d) => d,
+            None => return false,
+        };
+        remainder = if digits >= 10 {
+            (remainder * 100 + digits) % 97
+        } else {
+            (remainder * 10 + digits) % 97
+        };
+    }
+    remainder == 1
+}
+
+/// US SSN: no 000/666/9xx area, 00 group or 0000 serial
+fn valid_ssn(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [area, group, serial] = parts[..] else {
+        return false;
+    };
+    area != "000" && area != "666" && !area.starts_with('9') && group != "00" && serial != "0000"
+}
+
+/// Dutch BSN 11-proef: 9·d1 + 8·d2 + … + 2·d8 − d9 is a non-zero multiple of 11
+fn valid_bsn(value: &str) -> bool {
+    let digits: Vec<i32> = value
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|d| d as i32)
+        .collect();
+    if digits.len() != 9 {
+        return false;
+    }
+    let sum: i32 = digits[..8]
+        .iter()
+        .zip((2..=9).rev())
+        .map(|(d, weight)| d * weight)
+        .sum::<i32>()
+        - digits[8];
+    sum != 0 && sum % 11 == 0
+}
+
+fn luhn(value: &str) -> bool {
+    let mut sum = 0;
+    for (i, d) in value
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+    {
+        sum += if i % 2 == 1 {
+            let doubled = d * 2;
+            if doubled > 9 {
+                doubled - 9
+            } else {
+                doubled
+            }
+        } else {
+            d
+        };
+    }
+    sum % 10 == 0
+}
+
+pub struct Redactor {
+    policy: RedactionPolicy,
+    detectors: Vec<Detector>,
+    salt: String,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Self {
+        let mut detectors = Vec::new();
+        for kind in BUILTIN_DETECTORS {
+            let enabled = config
+                .detectors
+                .as_ref()
+                .is_none_or(|list| list.iter().any(|d| d == kind));
+            if enabled {
+                detectors.extend(builtin_detectors(kind));
+            }
+        }
+        for (kind, pattern) in &config.custom_patterns {
+            match Regex::new(pattern) {
+                Ok(regex) => detectors.push(Detector {
+                    kind: kind.trim().to_lowercase(),
+                    regex,
+                    group: 0,
+                    validate: |_| true,
+                }),
+                Err(e) => warn!(kind, "Invalid custom PII pattern: {}", e),
+            }
+        }
+        Self {
+            policy: config.policy,
+            detectors,
+            salt: config.hash_salt.clone(),
+        }
+    }
+
+    pub fn policy(&self) -> RedactionPolicy {
+        self.policy
+    }
+
+    /// Non-overlapping findings in `text`, in order. Where detectors overlap
+    /// the earliest, then longest, match wins.
+    pub fn find(&self, text: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for detector in &self.detectors {
+            for caps in detector.regex.captures_iter(text) {
+                let Some(m) = caps.get(detector.group) else {
+                    continue;
+                };
+                if (detector.validate)(m.as_str()) {
+                    findings.push(Finding {
+                        kind: detector.kind.clone(),
+                        start: m.start(),
+                        end: m.end(),
+                    });
+                }
+            }
+        }
+        findings.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+        let mut kept: Vec<Finding> = Vec::with_capacity(findings.len());
+        for finding in findings {
+            if kept.last().is_none_or(|last| finding.start >= last.end) {
+                kept.push(finding);
+            }
+        }
+        kept
+    }
+
+    /// `text` with findings replaced (hashed under the hash policy, masked
+    /// otherwise)
+    pub fn replace(&self, text: &str, findings: &[Finding]) -> String {
+        self.substitute(text, findings, self.policy == RedactionPolicy::Hash)
+    }
+
+    fn substitute(&self, text: &str, findings: &[Finding], hash: bool) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for finding in findings {
+            out.push_str(&text[last..finding.start]);
+            let label = finding.kind.to_uppercase();
+            if hash {
+                let digest = Sha256::new()
+                    .chain_update(self.salt.as_bytes())
+                    .chain_update(finding.kind.as_bytes())
+                    .chain_update(&text.as_bytes()[finding.start..finding.end])
+                    .finalize();
+                let short: String = digest[..6].iter().map(|b| format!("{:02x}", b)).collect();
+                out.push_str(&format!("[{}:{}]", label, short));
+            } else {
+                out.push_str(&format!("[{}]", label));
+            }
+            last = finding.end;
+        }
+        out.push_str(&text[last..]);
+        out
+    }
+
+    /// `text` with its findings replaced, without a report
+    pub fn redact(&self, text: &str) -> String {
+        self.replace(text, &self.find(text))
+    }
+
+    /// Like `redact`, but findings are hashed under every policy so that
+    /// distinct identifiers stay distinct
+    pub fn redact_id(&self, text: &str) -> String {
+        self.substitute(text, &self.find(text), true)
+    }
+
+    pub fn session(&self, document: &str) -> RedactionSession<'_> {
+        RedactionSession {
+            redactor: Some(self),
+            report: RedactionReport::new(document, self.policy),
+        }
+    }
+}
+
+/// The redactor configured from the environment, or None when redaction is off
+pub fn active() -> Option<&'static Redactor> {
+    static REDACTOR: OnceLock<Option<Redactor>> = OnceLock::new();
+    REDACTOR
+        .get_or_init(|| {
+            let config = RedactionConfig::from_env();
+            if config.policy == RedactionPolicy::Off {
+                return None;
+            }
+            let redactor = Redactor::new(&config);
+            info!(
+                policy = config.policy.as_str(),
+                detectors = redactor.detectors.len(),
+                "PII redaction enabled"
+            );
+            Some(redactor)
+        })
+        .as_ref()
+}
+
+/// Redaction session for one document with the active redactor. When
+/// redaction is off, text passes through unchanged and nothing is recorded.
+pub fn session(document: &str) -> RedactionSession<'static> {
+    match active() {
+        Some(redactor) => redactor.session(document),
+        None => RedactionSession {
+            redactor: None,
+            report: RedactionReport::new(document, RedactionPolicy::Off),
+        },
+    }
+}
+
+/// Redact a copy of text that a session already accounts for, such as a
+/// stored field or an LLM prompt. Nothing is recorded.
+pub fn redact(text: &str) -> String {
+    match active() {
+        Some(redactor) => redactor.redact(text),
+        None => text.to_string(),
+    }
+}
+
+/// Redact an identifier, e.g. a row key taken from the data
+pub fn redact_id(text: &str) -> String {
+    match active() {
+        Some(redactor) => redactor.redact_id(text),
+        None => text.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RedactionReport {
+    pub document: String,
+    pub policy: RedactionPolicy,
+    /// Findings per detector type
+    pub findings: BTreeMap<String, usize>,
+    pub total_findings: usize,
+    pub chunks_seen: usize,
+    pub chunks_redacted: usize,
+    pub chunks_dropped: usize,
+    pub updated_at: Option<String>,
+}
+
+impl RedactionReport {
+    fn new(document: &str, policy: RedactionPolicy) -> Self {
+        Self {
+            document: document.to_string(),
+            policy,
+            findings: BTreeMap::new(),
+            total_findings: 0,
+            chunks_seen: 0,
+            chunks_redacted: 0,
+            chunks_dropped: 0,
+            updated_at: None,
+        }
+    }
+
+    fn count(&mut self, findings: &[Finding]) {
+        for finding in findings {
+            *self.findings.entry(finding.kind.clone()).or_default() += 1;
+            self.total_findings += 1;
+        }
+    }
+}
+
+pub struct RedactionSession<'a> {
+    redactor: Option<&'a Redactor>,
+    report: RedactionReport,
+}
+
+impl RedactionSession<'_> {
+    /// Redact a title or other short field. Fields cannot be dropped, so the
+    /// drop_chunk policy masks them.
+    pub fn field(&mut self, text: &str) -> String {
+        let Some(redactor) = self.redactor else {
+            return text.to_string();
+        };
+        let findings = redactor.find(text);
+        if findings.is_empty() {
+            return text.to_string();
+        }
+        self.report.count(&findings);
+        redactor.replace(text, &findings)
+    }
+
+    /// Redact a chunk; None means the chunk must not be indexed
+    pub fn chunk(&mut self, text: &str) -> Option<String> {
+        let Some(redactor) = self.redactor else {
+            return Some(text.to_string());
+        };
+        self.report.chunks_seen += 1;
+        let findings = redactor.find(text);
+        if findings.is_empty() {
+            return Some(text.to_string());
+        }
+        self.report.count(&findings);
+        if redactor.policy == RedactionPolicy::DropChunk {
+            self.report.chunks_dropped += 1;
+            return None;
+        }
+        self.report.chunks_redacted += 1;
+        Some(redactor.replace(text, &findings))
+    }
+
+    pub fn report(&self) -> &RedactionReport {
+        &self.report
+    }
+
+    /// Count findings in Prometheus and store the report. Returns None when
+    /// redaction is off.
+    pub fn finish(self) -> Option<RedactionReport> {
+        self.redactor?;
+        let report = self.report;
+        for (kind, count) in &report.findings {
+            crate::monitoring::PII_FINDINGS_TOTAL
+                .with_label_values(&[kind.as_str()])
+                .inc_by(*count as u64);
+        }
+        if report.total_findings > 0 {
+            info!(
+                document = %report.document,
+                policy = report.policy.as_str(),
+                findings = report.total_findings,
+                chunks_redacted = report.chunks_redacted,
+                chunks_dropped = report.chunks_dropped,
+                "PII redacted"
+            );
+        }
+        match crate::ingest::open_db() {
+            Ok(conn) => {
+                if let Err(e) = save_report(&conn, &report) {
+                    warn!(document = %report.document, "Failed to store redaction report: {}", e);
+                }
+            }
+            Err(e) => warn!(document = %report.document, "Redaction report not stored: {}", e),
+        }
+        Some(report)
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Reports
+// ─────────────────────────────────────────────────────────────
+
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS redaction_reports (
+            document TEXT PRIMARY KEY,
+            policy TEXT NOT NULL,
+            findings_json TEXT NOT NULL,
+            total_findings INTEGER NOT NULL,
+            chunks_seen INTEGER NOT NULL,
+            chunks_redacted INTEGER NOT NULL,
+            chunks_dropped INTEGER NOT NULL,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );",
+    )
+}
+
+pub fn save_report(conn: &Connection, report: &RedactionReport) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO redaction_reports
+            (document, policy, findings_json, total_findings, chunks_seen, chunks_redacted,
+             chunks_dropped, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, CURRENT_TIMESTAMP)",
+        params![
+            report.document,
+            report.policy.as_str(),
+            serde_json::to_string(&report.findings).unwrap_or_else(|_| "{}".into()),
+            report.total_findings as i64,
+            report.chunks_seen as i64,
+            report.chunks_redacted as i64,
+            report.chunks_dropped as i64,
+        ],
+    )?;
+    Ok(())
+}
+
+const SELECT_REPORT: &str = "SELECT document, policy, findings_json, total_findings,
+        chunks_seen, chunks_redacted, chunks_dropped, updated_at
+     FROM redaction_reports";
+
+fn row_to_report(row: &rusqlite::Row) -> rusqlite::Result<RedactionReport> {
+    let count = |i: usize| -> rusqlite::Result<usize> { Ok(row.get::<_, i64>(i)?.max(0) as usize) };
+    Ok(RedactionReport {
+        document: row.get(0)?,
+        policy: row
+            .get::<_, String>(1)?
+            .parse()
+            .unwrap_or(RedactionPolicy::Off),
+        findings: serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or_default(),
+        total_findings: count(3)?,
+        chunks_seen: count(4)?,
+        chunks_redacted: count(5)?,
+        chunks_dropped: count(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+pub fn get_report(conn: &Connection, document: &str) -> rusqlite::Result<Option<RedactionReport>> {
+    conn.query_row(
+        &format!("{} WHERE document = ?1", SELECT_REPORT),
+        params![document],
+        row_to_report,
+    )
+    .optional()
+}
+
+/// Most recent reports, optionally only those with findings
+pub fn list_reports(
+    conn: &Connection,
+    with_findings_only: bool,
+    limit: usize,
+) -> rusqlite::Result<Vec<RedactionReport>> {
+    let filter = if with_findings_only {
+        " WHERE total_findings > 0"
+    } else {
+        ""
+    };
+    conn.prepare(&format!(
+        "{}{} ORDER BY updated_at DESC, document LIMIT ?1",
+        SELECT_REPORT, filter
+    ))?
+    .query_map(params![limit as i64], row_to_report)?
+    .collect()
+}
+
+pub fn delete_report(conn: &Connection, document: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM redaction_reports WHERE document = ?1",
+        params![document],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(policy: RedactionPolicy) -> Redactor {
+        Redactor::new(&RedactionConfig {
+            policy,
+            custom_patterns: vec![("employee_id".into(), r"\bEMP-\d{6}\b".into())],
+            ..RedactionConfig::default()
+        })
+    }
+
+    fn kinds(redactor: &Redactor, text: &str) -> Vec<String> {
+        redactor.find(text).into_iter().map(|f| f.kind).collect()
+    }
+
+    #[test]
+    fn test_detectors_validate_checksums() {
+        let r = redactor(RedactionPolicy::Mask);
+        assert_eq!(
+            kinds(&r, "Mail jane.doe@example.co.uk today"),
+            vec!["email"]
+        );
+        assert_eq!(
+            kinds(&r, "Call +44 20 7946 0958 or (555) 123-4567"),
+            vec!["phone", "phone"]
+        );
+        assert_eq!(kinds(&r, "IBAN GB82 WEST 1234 5698 7654 32"), vec!["iban"]);
+        // Wrong check digits
+        assert!(kinds(&r, "IBAN GB83 WEST 1234 5698 7654 32").is_empty());
+        assert_eq!(kinds(&r, "SSN 123-45-6789"), vec!["national_id"]);
+        assert!(kinds(&r, "SSN 666-45-6789").is_empty());
+        assert_eq!(
+            kinds(&r, "BSN 111222333, ook 1234.56.782"),
+            vec!["national_id", "national_id"]
+        );
+        // Fails the 11-proef
+        assert!(kinds(&r, "BSN 111222334").is_empty());
+        assert!(kinds(&r, "Ticket 000000000").is_empty());
+        assert_eq!(kinds(&r, "Card 4111 1111 1111 1111"), vec!["credit_card"]);
+        assert!(kinds(&r, "Order 4111 1111 1111 1112").is_empty());
+        assert_eq!(
+            kinds(
+                &r,
+                "export OPENAI_KEY=sk-abcdefghijklmnopqrstuvwx and api_key: \"Zx81kLm02PqRt55u\""
+            ),
+            vec!["api_key", "api_key"]
+        );
+        assert_eq!(kinds(&r, "Badge EMP-004211"), vec!["employee_id"]);
+        assert!(kinds(&r, "Released 2024-01-15, version 1.2.3 on 10.0.0.1").is_empty());
+    }
+
+    #[test]
+    fn test_policies() {
+        let text = "Contact jane@example.com about EMP-004211.";
+
+        let mask_redactor = redactor(RedactionPolicy::Mask);
+        let mut mask = mask_redactor.session("doc.txt");
+        assert_eq!(
+            mask.chunk(text).unwrap(),
+            "Contact [EMAIL] about [EMPLOYEE_ID]."
+        );
+        assert_eq!(mask.report().findings.get("email"), Some(&1));
+        assert_eq!(mask.report().chunks_redacted, 1);
+
+        let hash = redactor(RedactionPolicy::Hash);
+        let mut session = hash.session("doc.txt");
+        let first = session.chunk(text).unwrap();
+        let second = session.chunk("Reply to jane@example.com").unwrap();
+        let token = &first["Contact ".len().."Contact [EMAIL:123456789abc]".len()];
+        assert!(token.starts_with("[EMAIL:") && !first.contains("jane"));
+        assert!(second.contains(token));
+
+        let drop = redactor(RedactionPolicy::DropChunk);
+        let mut session = drop.session("doc.txt");
+        assert_eq!(session.chunk(text), None);
+        assert_eq!(session.chunk("Nothing here."), Some("Nothing here.".into()));
+        assert_eq!(session.field("From jane@example.com"), "From [EMAIL]");
+        let report = session.report();
+        assert_eq!((report.chunks_seen, report.chunks_dropped), (2, 1));
+        assert_eq!(report.total_findings, 3);
+
+        // Copies are masked without a report; ids are always hashed
+        assert_eq!(drop.redact("jane@example.com"), "[EMAIL]");
+        let id = drop.redact_id("jane@example.com");
+        assert!(id.starts_with("[EMAIL:") && id != drop.redact_id("joe@example.com"));
+    }
+
+    #[test]
+    fn test_report_storage() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        let r = redactor(RedactionPolicy::Mask);
+        let mut session = r.session("hr.txt");
+        session.chunk("SSN 123-45-6789");
+        save_report(&conn, session.report()).unwrap();
+        save_report(&conn, r.session("clean.txt").report()).unwrap();
+
+        let stored = get_report(&conn, "hr.txt").unwrap().unwrap();
+        assert_eq!(stored.findings.get("national_id"), Some(&1));
+        assert_eq!(stored.policy, RedactionPolicy::Mask);
+        assert_eq!(list_reports(&conn, true, 10).unwrap().len(), 1);
+        assert_eq!(list_reports(&conn, false, 10).unwrap().len(), 2);
+        delete_report(&conn, "hr.txt").unwrap();
+        assert!(get_report(&conn, "hr.txt").unwrap().is_none());
+    }
+}
@@ -100,6 +100,25 @@ impl Tool for CalculatorTool {
         }
     }
 
+    fn input_argument(&self) -> &'static str {
+        "expression"
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "expression": {
+                    "type": "string",
+                    "minLength": 1,
+                    "description": "Arithmetic expression with two operands, e.g. 15 * 3"
+                }
+            },
+            "required": ["expression"],
+            "additionalProperties": false
+        })
+    }
+
     async fn execute(&self, query: &str) -> Result<ToolResult, String> {
         let start = Instant::now();
 
@@ -2,8 +2,11 @@
 // Phase 9: Tool Registry and Interfaces
 // Provides tool abstraction for agent decision engine
 
+use crate::memory::llm_provider::ToolSpec;
+use crate::memory::structured::{self, SchemaError};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
 pub mod calculator;
 pub mod query_optimizer;
@@ -49,6 +52,23 @@ impl ToString for ToolType {
     }
 }
 
+impl ToolType {
+    /// Inverse of `to_string`, used to map function-call names back
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ToolType::SemanticSearch,
+            ToolType::WebSearch,
+            ToolType::DatabaseQuery,
+            ToolType::Calculator,
+            ToolType::URLFetch,
+            ToolType::CodeExecution,
+            ToolType::ImageGeneration,
+        ]
+        .into_iter()
+        .find(|t| t.to_string() == name)
+    }
+}
+
 // ============ Tool Result ============
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,6 +95,23 @@ pub trait Tool: Send + Sync {
     fn description(&self) -> String;
     fn success_rate(&self) -> f32;
 
+    /// Name of the string argument carrying the tool's input
+    fn input_argument(&self) -> &'static str {
+        "query"
+    }
+
+    /// JSON Schema of the arguments the model passes when calling the tool
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                self.input_argument(): { "type": "string", "minLength": 1 }
+            },
+            "required": [self.input_argument()],
+            "additionalProperties": false
+        })
+    }
+
     async fn execute(&self, query: &str) -> Result<ToolResult, String>;
 
     fn update_success(&mut self, success: bool);
@@ -104,6 +141,15 @@ impl ToolRegistry {
         }
     }
 
+    /// Registry with the tools that have an implementation
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(calculator::CalculatorTool::new()));
+        registry.register(Box::new(web_search::WebSearchTool::new()));
+        registry.register(Box::new(url_fetch::URLFetchTool::new()));
+        registry
+    }
+
     pub fn register(&mut self, tool: Box<dyn Tool>) {
         let name = tool.tool_type().to_string();
         self.tools.push(tool);
@@ -133,6 +179,56 @@ impl ToolRegistry {
             .collect()
     }
 
+    /// Function-calling definitions of the registered tools
+    pub fn definitions(&self) -> Vec<ToolSpec> {
+        self.tools
+            .iter()
+            .map(|t| ToolSpec {
+                name: t.tool_type().to_string(),
+                description: t.description(),
+                parameters: t.parameters(),
+            })
+            .collect()
+    }
+
+    /// Check model-supplied arguments against the tool's schema and return
+    /// the input to execute it with
+    pub fn validate_arguments(
+        &self,
+        tool_type: &ToolType,
+        arguments: &Value,
+    ) -> Result<String, Vec<SchemaError>> {
+        let Some(tool) = self.get_tool(tool_type) else {
+            return Err(vec![SchemaError {
+                path: String::new(),
+                message: format!("unknown tool {}", tool_type.to_string()),
+            }]);
+        };
+        let errors = structured::validate(arguments, &tool.parameters());
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        arguments
+            .get(tool.input_argument())
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                vec![SchemaError {
+                    path: format!("/{}", tool.input_argument()),
+                    message: "expected a string".to_string(),
+                }]
+            })
+    }
+
+    /// Arguments equivalent to passing `query` straight to the tool, for
+    /// selections made without the model
+    pub fn arguments_for_query(&self, tool_type: &ToolType, query: &str) -> Value {
+        let key = self
+            .get_tool(tool_type)
+            .map_or("query", |t| t.input_argument());
+        json!({ key: query })
+    }
+
     pub fn get_stats(&self, tool_type: &ToolType) -> Option<ToolStats> {
         let name = tool_type.to_string();
         self.tool_stats.get(&name).cloned()
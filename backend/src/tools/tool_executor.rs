@@ -5,7 +5,7 @@
 use crate::tools::calculator::CalculatorTool;
 use crate::tools::url_fetch::URLFetchTool;
 use crate::tools::web_search::WebSearchTool;
-use crate::tools::{Tool, ToolResult, ToolType};
+use crate::tools::{Tool, ToolRegistry, ToolResult, ToolType};
 use serde::{Deserialize, Serialize};
 use std::time::Instant;
 
@@ -67,6 +67,37 @@ impl ToolExecutor {
         Ok(result)
     }
 
+    /// Execute a tool with model-supplied arguments, validated against the
+    /// tool's schema first. Tools outside the registry take a "query"
+    pub async fn execute_with_arguments(
+        registry: &ToolRegistry,
+        tool_type: &ToolType,
+        arguments: &serde_json::Value,
+    ) -> Result<ToolResult, String> {
+        let input = if registry.get_tool(tool_type).is_some() {
+            registry
+                .validate_arguments(tool_type, arguments)
+                .map_err(|errors| {
+                    format!(
+                        "Invalid arguments for {}: {}",
+                        tool_type.to_string(),
+                        errors
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join("; ")
+                    )
+                })?
+        } else {
+            arguments
+                .get("query")
+                .and_then(serde_json::Value::as_str)
+                .ok_or_else(|| format!("{} needs a query argument", tool_type.to_string()))?
+                .to_string()
+        };
+        Self::execute_tool(tool_type, &input, None).await
+    }
+
     /// Extract relevant data from tool result
     pub fn extract_data(result: &str) -> String {
         // Try to extract numbers if it's a calculation result
@@ -136,6 +167,28 @@ mod tests {
         assert!(result.unwrap().success);
     }
 
+    #[tokio::test]
+    async fn test_execute_with_arguments_validates_first() {
+        let registry = ToolRegistry::with_defaults();
+        let result = ToolExecutor::execute_with_arguments(
+            &registry,
+            &ToolType::Calculator,
+            &serde_json::json!({"expression": "6 * 7"}),
+        )
+        .await
+        .unwrap();
+        assert!(result.result.contains("42"));
+
+        let err = ToolExecutor::execute_with_arguments(
+            &registry,
+            &ToolType::URLFetch,
+            &serde_json::json!({"url": "example.com"}),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("/url"), "{}", err);
+    }
+
     #[test]
     fn test_extract_data() {
         let result = "5 + 3 = 8";
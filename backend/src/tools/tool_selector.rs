@@ -1,8 +1,23 @@
-// src/tools/tool_selector.rs - UPDATED v2
-// Phase 9: Tool Selection Logic (Improved Intent Detection)
-
-use crate::tools::ToolType;
+// src/tools/tool_selector.rs - UPDATED v3
+// Phase 9: Tool Selection Logic
+// The model picks the tool and its arguments: through native function
+// calling when the provider has it, otherwise through a JSON reply
+// constrained to the tool schemas. Keyword intent detection is the offline
+// fallback when no model answers or its arguments do not validate.
+
+use crate::db::llm_settings::LlmConfig;
+use crate::db::prompt_templates;
+use crate::memory::llm_provider::{ChatMessage, LLMProvider, ToolCall, ToolSpec};
+use crate::memory::structured::{self, SchemaError};
+use crate::tools::{ToolRegistry, ToolType};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+/// Confidence reported for a tool the model chose with valid arguments
+const MODEL_CONFIDENCE: f32 = 0.9;
+/// Tool name in the JSON protocol for "no tool needed"
+const NO_TOOL: &str = "none";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolSelection {
@@ -11,6 +26,63 @@ pub struct ToolSelection {
     pub reasoning: String,
     pub confidence: f32,
     pub intent: QueryIntent,
+    /// Arguments for the primary tool; validated when the model chose them
+    pub arguments: Value,
+    pub method: SelectionMethod,
+    /// Model that made the choice; None for keyword selections
+    pub model: Option<String>,
+    /// Why the model's choice was rejected, when it fell back to keywords
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub argument_errors: Vec<SchemaError>,
+    /// The model's function call, kept so its result can answer the call id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call: Option<ToolCall>,
+}
+
+impl ToolSelection {
+    /// Turns to append to the selection conversation once the tool ran: the
+    /// assistant call and a tool message answering it, or for selections
+    /// made without function calling the JSON choice and a user turn
+    pub fn follow_up(&self, result: &str) -> Vec<ChatMessage> {
+        match &self.tool_call {
+            Some(call) => vec![
+                ChatMessage::assistant_tool_calls("", vec![call.clone()]),
+                ChatMessage::tool(call.id.clone(), result),
+            ],
+            None => vec![
+                ChatMessage::assistant(
+                    json!({
+                        "tool": self.primary_tool.to_string(),
+                        "arguments": self.arguments
+                    })
+                    .to_string(),
+                ),
+                ChatMessage::user(format!("Tool result: {}", result)),
+            ],
+        }
+    }
+}
+
+/// How a tool selection was made
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionMethod {
+    /// The provider's native function calling
+    FunctionCall,
+    /// A JSON reply validated against the tool schemas
+    JsonProtocol,
+    /// Keyword intent detection
+    Keyword,
+}
+
+impl SelectionMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FunctionCall => "function_call",
+            Self::JsonProtocol => "json_protocol",
+            Self::Keyword => "keyword",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -40,6 +112,21 @@ impl ToString for QueryIntent {
     }
 }
 
+impl QueryIntent {
+    /// Intent a model-chosen tool stands for
+    pub fn for_tool(tool: &ToolType) -> Self {
+        match tool {
+            ToolType::Calculator => QueryIntent::Math,
+            ToolType::WebSearch => QueryIntent::WebSearch,
+            ToolType::URLFetch => QueryIntent::UrlFetch,
+            ToolType::DatabaseQuery => QueryIntent::Database,
+            ToolType::CodeExecution => QueryIntent::CodeExecution,
+            ToolType::ImageGeneration => QueryIntent::ImageGeneration,
+            ToolType::SemanticSearch => QueryIntent::SemanticSearch,
+        }
+    }
+}
+
 pub struct ToolSelector;
 
 impl ToolSelector {
@@ -80,11 +167,215 @@ impl ToolSelector {
         QueryIntent::SemanticSearch
     }
 
-    /// Select best tool(s) for query
+    /// Select best tool(s) for query with keyword intent detection
     pub fn select_tools(query: &str) -> ToolSelection {
         let intent = Self::detect_intent(query);
+        let (primary, secondary, confidence) = Self::plan(&intent);
+
+        let reasoning = format!(
+            "Query intent: {}. Selected {} (confidence: {:.2}). \
+             Fallback tools: {:?}",
+            intent.to_string(),
+            primary.to_string(),
+            confidence,
+            secondary.iter().map(|t| t.to_string()).collect::<Vec<_>>()
+        );
 
-        let (primary, secondary, confidence) = match intent {
+        ToolSelection {
+            arguments: ToolRegistry::with_defaults().arguments_for_query(&primary, query),
+            primary_tool: primary,
+            secondary_tools: secondary,
+            reasoning,
+            confidence,
+            intent,
+            method: SelectionMethod::Keyword,
+            model: None,
+            argument_errors: Vec::new(),
+            tool_call: None,
+        }
+    }
+
+    /// Let the model choose among the registry's tools. Falls back to
+    /// keyword selection without a provider, when the model fails, or when
+    /// its arguments do not match the tool's schema
+    pub async fn select_with_model(
+        query: &str,
+        provider: Option<&dyn LLMProvider>,
+        registry: &ToolRegistry,
+        config: &LlmConfig,
+    ) -> ToolSelection {
+        let Some(provider) = provider else {
+            return Self::select_tools(query);
+        };
+        let system = match prompt_templates::render(
+            prompt_templates::TOOL_SELECTION,
+            Some(provider.model_name()),
+            &[],
+        ) {
+            Ok(system) => system,
+            Err(e) => {
+                warn!(error = %e, "Tool selection prompt unavailable, using keywords");
+                return Self::select_tools(query);
+            }
+        };
+        let messages = [ChatMessage::system(system), ChatMessage::user(query)];
+        let definitions = registry.definitions();
+
+        let mut tool_call = None;
+        let (choice, method) = match provider.chat_tools(&messages, &definitions, config).await {
+            Ok(Some(reply)) => {
+                tool_call = reply.tool_calls.into_iter().next();
+                let choice = tool_call
+                    .as_ref()
+                    .map(|call| (call.name.clone(), call.arguments.clone()));
+                (Ok(choice), SelectionMethod::FunctionCall)
+            }
+            Ok(None) => (
+                Self::choose_with_json(provider, &messages, &definitions, config).await,
+                SelectionMethod::JsonProtocol,
+            ),
+            Err(e) => (
+                Err(vec![Self::error(e.to_string())]),
+                SelectionMethod::FunctionCall,
+            ),
+        };
+        let model = provider.model_name().to_string();
+
+        let resolved = choice.and_then(|choice| match choice {
+            None => Ok(None),
+            Some((name, arguments)) => {
+                let tool = ToolType::from_name(&name)
+                    .filter(|t| registry.get_tool(t).is_some())
+                    .ok_or_else(|| vec![Self::error(format!("unknown tool {}", name))])?;
+                registry.validate_arguments(&tool, &arguments)?;
+                Ok(Some((tool, arguments)))
+            }
+        });
+
+        match resolved {
+            Ok(choice) => {
+                let (primary, arguments) =
+                    choice.unwrap_or_else(|| (ToolType::SemanticSearch, json!({ "query": query })));
+                let intent = QueryIntent::for_tool(&primary);
+                let (_, secondary, _) = Self::plan(&intent);
+                info!(
+                    tool = %primary.to_string(),
+                    method = method.as_str(),
+                    model = %model,
+                    "Model selected tool"
+                );
+                ToolSelection {
+                    reasoning: format!(
+                        "{} chose {} via {} with arguments {}",
+                        model,
+                        primary.to_string(),
+                        method.as_str(),
+                        arguments
+                    ),
+                    primary_tool: primary,
+                    secondary_tools: secondary,
+                    confidence: MODEL_CONFIDENCE,
+                    intent,
+                    arguments,
+                    method,
+                    model: Some(model),
+                    argument_errors: Vec::new(),
+                    tool_call,
+                }
+            }
+            Err(errors) => {
+                warn!(
+                    model = %model,
+                    method = method.as_str(),
+                    errors = errors.len(),
+                    "Model tool choice rejected, using keywords"
+                );
+                let mut selection = Self::select_tools(query);
+                selection.reasoning = format!(
+                    "{} Model choice rejected: {}",
+                    selection.reasoning,
+                    errors
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                );
+                selection.argument_errors = errors;
+                selection
+            }
+        }
+    }
+
+    /// JSON protocol for providers without function calling: one object
+    /// naming the tool (or "none") and its arguments, repaired until it
+    /// matches one of the tool schemas
+    async fn choose_with_json(
+        provider: &dyn LLMProvider,
+        messages: &[ChatMessage],
+        definitions: &[ToolSpec],
+        config: &LlmConfig,
+    ) -> Result<Option<(String, Value)>, Vec<SchemaError>> {
+        let schema = Self::protocol_schema(definitions);
+        let output = structured::generate(
+            provider,
+            messages,
+            &schema,
+            config,
+            structured::DEFAULT_MAX_REPAIRS,
+        )
+        .await
+        .map_err(|e| vec![Self::error(e.to_string())])?;
+        if !output.valid {
+            return Err(output.errors);
+        }
+        let value = output.value.unwrap_or_default();
+        match value.get("tool").and_then(Value::as_str) {
+            Some(NO_TOOL) | None => Ok(None),
+            Some(name) => Ok(Some((
+                name.to_string(),
+                value.get("arguments").cloned().unwrap_or_else(|| json!({})),
+            ))),
+        }
+    }
+
+    /// `{"tool": <name>, "arguments": {..}}` for each tool, or
+    /// `{"tool": "none"}`
+    fn protocol_schema(definitions: &[ToolSpec]) -> Value {
+        let mut options: Vec<Value> = definitions
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "object",
+                    "description": tool.description,
+                    "properties": {
+                        "tool": { "const": tool.name },
+                        "arguments": tool.parameters
+                    },
+                    "required": ["tool", "arguments"],
+                    "additionalProperties": false
+                })
+            })
+            .collect();
+        options.push(json!({
+            "type": "object",
+            "description": "No tool is needed",
+            "properties": { "tool": { "const": NO_TOOL } },
+            "required": ["tool"],
+            "additionalProperties": false
+        }));
+        json!({ "oneOf": options })
+    }
+
+    fn error(message: String) -> SchemaError {
+        SchemaError {
+            path: String::new(),
+            message,
+        }
+    }
+
+    /// Primary tool, fallbacks and confidence for an intent
+    fn plan(intent: &QueryIntent) -> (ToolType, Vec<ToolType>, f32) {
+        match intent {
             QueryIntent::Math => (ToolType::Calculator, vec![ToolType::SemanticSearch], 0.95),
             QueryIntent::WebSearch => (
                 ToolType::WebSearch,
@@ -115,23 +406,6 @@ impl ToolSelector {
                 (ToolType::SemanticSearch, vec![ToolType::WebSearch], 0.60)
             }
             QueryIntent::Unknown => (ToolType::SemanticSearch, vec![ToolType::WebSearch], 0.50),
-        };
-
-        let reasoning = format!(
-            "Query intent: {}. Selected {} (confidence: {:.2}). \
-             Fallback tools: {:?}",
-            intent.to_string(),
-            primary.to_string(),
-            confidence,
-            secondary.iter().map(|t| t.to_string()).collect::<Vec<_>>()
-        );
-
-        ToolSelection {
-            primary_tool: primary,
-            secondary_tools: secondary,
-            reasoning,
-            confidence,
-            intent,
         }
     }
 
@@ -280,6 +554,144 @@ impl ToolSelector {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::memory::llm_provider::{LLMError, Role, ToolReply};
+    use std::sync::Mutex;
+
+    /// Answers function calls with `calls` when set (native mode), chat
+    /// with scripted replies otherwise
+    struct Scripted {
+        calls: Option<Vec<ToolCall>>,
+        replies: Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for Scripted {
+        async fn generate(&self, prompt: &str) -> Result<String, LLMError> {
+            self.chat(&[ChatMessage::user(prompt)], &LlmConfig::default())
+                .await
+        }
+        async fn generate_with_config(
+            &self,
+            prompt: &str,
+            config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.chat(&[ChatMessage::user(prompt)], config).await
+        }
+        async fn chat(
+            &self,
+            _messages: &[ChatMessage],
+            _config: &LlmConfig,
+        ) -> Result<String, LLMError> {
+            self.replies
+                .lock()
+                .unwrap()
+                .pop()
+                .map(str::to_string)
+                .ok_or_else(|| LLMError::ConnectionFailed("offline".into()))
+        }
+        async fn chat_tools(
+            &self,
+            _messages: &[ChatMessage],
+            _tools: &[ToolSpec],
+            _config: &LlmConfig,
+        ) -> Result<Option<ToolReply>, LLMError> {
+            Ok(self.calls.clone().map(|tool_calls| ToolReply {
+                content: String::new(),
+                tool_calls,
+            }))
+        }
+        fn model_name(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    fn native(name: &str, arguments: Value) -> Scripted {
+        Scripted {
+            calls: Some(vec![ToolCall {
+                id: "call_0".into(),
+                name: name.into(),
+                arguments,
+            }]),
+            replies: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn json_protocol(replies: &[&'static str]) -> Scripted {
+        Scripted {
+            calls: None,
+            replies: Mutex::new(replies.iter().rev().copied().collect()),
+        }
+    }
+
+    async fn select(query: &str, provider: &Scripted) -> ToolSelection {
+        ToolSelector::select_with_model(
+            query,
+            Some(provider),
+            &ToolRegistry::with_defaults(),
+            &LlmConfig::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_model_function_call_selects_tool() {
+        let provider = native("Calculator", json!({"expression": "15 * 3"}));
+        // Keywords would pick WebSearch for "what is"
+        let selection = select("What is fifteen times three?", &provider).await;
+        assert_eq!(selection.primary_tool, ToolType::Calculator);
+        assert_eq!(selection.intent, QueryIntent::Math);
+        assert_eq!(selection.method, SelectionMethod::FunctionCall);
+        assert_eq!(selection.arguments["expression"], "15 * 3");
+        assert_eq!(selection.model.as_deref(), Some("scripted"));
+
+        // The result answers the call the assistant turn declares
+        let turns = selection.follow_up("45");
+        assert_eq!(turns[0].role, Role::Assistant);
+        assert_eq!(turns[0].tool_calls[0].id, "call_0");
+        assert_eq!(turns[0].tool_calls[0].name, "Calculator");
+        assert_eq!(turns[1], ChatMessage::tool("call_0", "45"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_arguments_fall_back_to_keywords() {
+        let provider = native("URLFetch", json!({"link": "https://example.com"}));
+        let selection = select("Fetch https://example.com", &provider).await;
+        assert_eq!(selection.method, SelectionMethod::Keyword);
+        assert_eq!(selection.primary_tool, ToolType::URLFetch);
+        assert_eq!(selection.arguments["url"], "Fetch https://example.com");
+        assert!(!selection.argument_errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_json_protocol_repairs_then_selects() {
+        let provider = json_protocol(&[
+            r#"{"tool": "WebSearch", "arguments": {}}"#,
+            r#"{"tool": "WebSearch", "arguments": {"query": "rust release"}}"#,
+        ]);
+        let selection = select("Any news on the Rust release?", &provider).await;
+        assert_eq!(selection.method, SelectionMethod::JsonProtocol);
+        assert_eq!(selection.primary_tool, ToolType::WebSearch);
+        assert_eq!(selection.arguments, json!({"query": "rust release"}));
+        assert!(selection.tool_call.is_none());
+        let turns = selection.follow_up("Rust 1.90 is out");
+        assert!(turns[0].tool_calls.is_empty());
+        assert!(turns[0].content.contains("WebSearch"));
+        assert_eq!(turns[1], ChatMessage::user("Tool result: Rust 1.90 is out"));
+
+        let provider = json_protocol(&[r#"{"tool": "none"}"#]);
+        let selection = select("Summarize our onboarding doc", &provider).await;
+        assert_eq!(selection.primary_tool, ToolType::SemanticSearch);
+        assert_eq!(selection.method, SelectionMethod::JsonProtocol);
+    }
+
+    #[tokio::test]
+    async fn test_offline_model_falls_back_to_keywords() {
+        let selection = select("Calculate 100 * 2", &json_protocol(&[])).await;
+        assert_eq!(selection.method, SelectionMethod::Keyword);
+        assert_eq!(selection.primary_tool, ToolType::Calculator);
+        assert_eq!(selection.arguments["expression"], "Calculate 100 * 2");
+        assert!(selection.model.is_none());
+    }
 
     #[test]
     fn test_detect_math_intent() {
@@ -55,6 +55,25 @@ impl Tool for URLFetchTool {
         }
     }
 
+    fn input_argument(&self) -> &'static str {
+        "url"
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "pattern": "^https?://\\S+$",
+                    "description": "Absolute http(s) URL of the page to fetch"
+                }
+            },
+            "required": ["url"],
+            "additionalProperties": false
+        })
+    }
+
     async fn execute(&self, query: &str) -> Result<ToolResult, String> {
         let start = Instant::now();
 
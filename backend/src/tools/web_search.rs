@@ -32,6 +32,21 @@ impl Tool for WebSearchTool {
         0.85
     }
 
+    fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "minLength": 1,
+                    "description": "Search terms"
+                }
+            },
+            "required": ["query"],
+            "additionalProperties": false
+        })
+    }
+
     async fn execute(&self, query: &str) -> Result<ToolResult, String> {
         let start = Instant::now();
 
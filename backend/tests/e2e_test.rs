@@ -114,6 +114,7 @@ mod e2e_tests {
                 "Test response".to_string(),
                 3,
                 true,
+                None,
             )
             .await
             .expect("Failed to record episode");
@@ -141,6 +142,7 @@ mod e2e_tests {
                 "Rust is a systems language".to_string(),
                 3,
                 true,
+                None,
             )
             .await
             .expect("Failed to record episode");
@@ -164,6 +166,7 @@ mod e2e_tests {
                     format!("Response {}", i),
                     3,
                     i % 2 == 0,
+                    None,
                 )
                 .await
                 .expect("Failed to record episode");
@@ -679,4 +679,150 @@ mod tests {
             .health_check()
             .expect("Health check should pass after repairing mappings");
     }
+
+    #[test]
+    fn test_delete_chunks_matches_exact_ids() {
+        let dir = tempdir().expect("Failed to create temp directory");
+        let vector_file = dir.path().join("vectors.json");
+        let mut retriever = make_retriever_with_vector_file(dir.path(), &vector_file);
+
+        retriever
+            .index_chunk("feed:1#2", "entry about otters", &vec![1.0, 0.0])
+            .unwrap();
+        retriever
+            .index_chunk("https://a/feed/1/2#0", "page about otters", &vec![0.0, 1.0])
+            .unwrap();
+
+        let removed = retriever.delete_chunks(&["feed:1#2".to_string()]).unwrap();
+        assert_eq!(removed, 1);
+        let hits = retriever.search_hits("otters", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].doc_id, "https://a/feed/1/2#0");
+    }
+
+    #[test]
+    fn test_deleted_vector_slots_are_compacted_on_save() {
+        let dir = tempdir().expect("Failed to create temp directory");
+        let vector_file = dir.path().join("vectors.json");
+        let mut retriever = make_retriever_with_vector_file(dir.path(), &vector_file);
+
+        for (i, vector) in [[1.0, 0.0], [0.0, 1.0], [0.6, 0.8]].iter().enumerate() {
+            retriever
+                .index_chunk(&format!("doc#{}", i), "otters", &vector.to_vec())
+                .unwrap();
+        }
+        retriever
+            .delete_chunks(&["doc#0".to_string(), "doc#1".to_string()])
+            .unwrap();
+        assert_eq!(retriever.metrics.deleted_vector_slots, 2);
+
+        retriever.force_save().unwrap();
+        assert_eq!(retriever.metrics.deleted_vector_slots, 0);
+        assert_eq!(retriever.vectors, vec![vec![0.6, 0.8]]);
+        assert_eq!(retriever.doc_id_to_vector_idx.len(), 1);
+        assert_eq!(retriever.doc_id_to_vector_idx["doc#2"], 0);
+        retriever
+            .health_check()
+            .expect("Health check should pass after compaction");
+    }
+
+    #[test]
+    fn test_table_chunks_are_flagged_in_hits() {
+        let dir = tempdir().expect("Failed to create temp directory");
+        let vector_file = dir.path().join("vectors.json");
+        let mut retriever = make_retriever_with_vector_file(dir.path(), &vector_file);
+
+        retriever
+            .index_table_chunk("doc#0", "doc", "| port | 8080 |", &[1.0, 0.0])
+            .unwrap();
+        retriever
+            .index_chunk_with_title("doc#1", "doc", "The port is 8080.", &[0.0, 1.0])
+            .unwrap();
+
+        let hits = retriever.search_hits("8080", 10).unwrap();
+        let flagged: Vec<_> = hits.iter().map(|h| (h.doc_id.as_str(), h.table)).collect();
+        assert_eq!(hits.len(), 2);
+        assert!(flagged.contains(&("doc#0", true)));
+        assert!(flagged.contains(&("doc#1", false)));
+    }
+
+    #[test]
+    fn test_hybrid_hits_keep_chunk_ids_and_accept_questions() {
+        let dir = tempdir().expect("Failed to create temp directory");
+        let vector_file = dir.path().join("vectors.json");
+        let mut retriever = make_retriever_with_vector_file(dir.path(), &vector_file);
+
+        retriever
+            .index_chunk_with_title(
+                "plants.txt#0",
+                "plants",
+                "Photosynthesis needs light.",
+                &[1.0, 0.0],
+            )
+            .unwrap();
+        retriever
+            .index_chunk_with_title("rocks.txt#0", "rocks", "Granite is igneous.", &[0.0, 1.0])
+            .unwrap();
+
+        // Query syntax such as a trailing colon is taken as plain text
+        let hits = retriever
+            .hybrid_hits("note: what needs photosynthesis?", &[1.0, 0.0], 5)
+            .unwrap();
+        assert_eq!(hits[0].doc_id, "plants.txt#0");
+        assert_eq!(hits[0].content, "Photosynthesis needs light.");
+        assert!((hits[0].score - 1.0).abs() < 1e-6);
+        // The vector match is found without a keyword match
+        let rocks = hits.iter().find(|h| h.doc_id == "rocks.txt#0").unwrap();
+        assert_eq!(rocks.score, 0.0);
+
+        retriever
+            .delete_chunks(&["plants.txt#0".to_string()])
+            .unwrap();
+        let hits = retriever
+            .hybrid_hits("photosynthesis", &[1.0, 0.0], 5)
+            .unwrap();
+        assert!(hits.iter().all(|h| h.doc_id != "plants.txt#0"));
+    }
+
+    #[test]
+    fn test_index_with_tokenized_doc_id_is_migrated() {
+        use tantivy::schema::{Schema, STORED, TEXT};
+
+        let dir = tempdir().expect("Failed to create temp directory");
+        let index_dir = dir.path().join("index");
+        std::fs::create_dir_all(&index_dir).unwrap();
+        {
+            let mut builder = Schema::builder();
+            let title = builder.add_text_field("title", TEXT | STORED);
+            let content = builder.add_text_field("content", TEXT | STORED);
+            let doc_id = builder.add_text_field("doc_id", TEXT | STORED);
+            let index = tantivy::Index::create_in_dir(&index_dir, builder.build()).unwrap();
+            let mut writer: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+            let mut doc = tantivy::TantivyDocument::default();
+            doc.add_text(doc_id, "code:repo/lib.rs#L1-L9");
+            doc.add_text(title, "fn parse");
+            doc.add_text(content, "fn parse() {}");
+            writer.add_document(doc).unwrap();
+            writer.commit().unwrap();
+        }
+
+        let mut retriever =
+            make_retriever_with_vector_file(&index_dir, &dir.path().join("vectors.json"));
+        let hits = retriever.search_hits("parse", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].doc_id, "code:repo/lib.rs#L1-L9");
+
+        retriever
+            .delete_chunks(&["code:repo/lib.rs#L1-L9".to_string()])
+            .unwrap();
+        assert!(retriever.search_hits("parse", 10).unwrap().is_empty());
+
+        // The old index is kept next to the live one, and no staging is left
+        let siblings: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(siblings.iter().any(|name| name.starts_with("index.bak-")));
+        assert!(!siblings.iter().any(|name| name == "index.migrate"));
+    }
 }
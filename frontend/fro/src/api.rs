@@ -822,6 +822,110 @@ pub async fn fetch_recent_logs(limit: usize) -> Result<LogsResponse, String> {
         .map_err(|e| format!("Failed to parse response: {}", e))
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FeedSubscription {
+    pub id: i64,
+    pub url: String,
+    pub title: Option<String>,
+    pub poll_interval_secs: u64,
+    pub fetch_full_article: bool,
+    pub retention_days: Option<u32>,
+    pub enabled: bool,
+    pub last_polled_at: Option<String>,
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub entry_count: usize,
+    pub latest_entry_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FeedsResponse {
+    pub status: String,
+    #[serde(default)]
+    pub subscriptions: Vec<FeedSubscription>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FeedResponse {
+    pub status: String,
+    pub subscription: Option<FeedSubscription>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NewFeedRequest {
+    pub url: String,
+    pub poll_interval_secs: Option<u64>,
+    pub fetch_full_article: bool,
+    pub retention_days: Option<u32>,
+}
+
+pub async fn fetch_feeds() -> Result<FeedsResponse, String> {
+    let url = format!("{}/feeds", API_BASE_URL);
+    gloo_net::http::Request::get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))
+}
+
+pub async fn create_feed(payload: &NewFeedRequest) -> Result<FeedResponse, String> {
+    let url = format!("{}/feeds", API_BASE_URL);
+    let resp: FeedResponse = gloo_net::http::Request::post(&url)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(payload).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Failed to create request: {:?}", e))?
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+    if resp.status != "success" {
+        return Err(resp.message.unwrap_or_else(|| "Subscription failed".into()));
+    }
+    Ok(resp)
+}
+
+pub async fn set_feed_enabled(id: i64, enabled: bool) -> Result<FeedResponse, String> {
+    let url = format!("{}/feeds/{}", API_BASE_URL, id);
+    gloo_net::http::Request::put(&url)
+        .header("Content-Type", "application/json")
+        .body(serde_json::json!({ "enabled": enabled }).to_string())
+        .map_err(|e| format!("Failed to create request: {:?}", e))?
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))
+}
+
+pub async fn poll_feed(id: i64) -> Result<serde_json::Value, String> {
+    let url = format!("{}/feeds/{}/poll", API_BASE_URL, id);
+    gloo_net::http::Request::post(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))
+}
+
+pub async fn delete_feed(id: i64) -> Result<serde_json::Value, String> {
+    let url = format!("{}/feeds/{}", API_BASE_URL, id);
+    gloo_net::http::Request::delete(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UploadResponse {
     pub status: String,
@@ -1,8 +1,8 @@
 use crate::components::header::Header;
 use crate::pages::{
     About, Config, ConfigHardware, ConfigOther, ConfigPrompt, ConfigSampling, Home, MonitorCache,
-    MonitorIndex, MonitorLogs, MonitorOverview, MonitorRateLimits, MonitorRequests, PageNotFound,
-    Parameters,
+    MonitorFeeds, MonitorIndex, MonitorLogs, MonitorOverview, MonitorRateLimits, MonitorRequests,
+    PageNotFound, Parameters,
 };
 use dioxus::prelude::*;
 
@@ -38,6 +38,8 @@ pub enum Route {
         MonitorRateLimits {},
         #[route("/monitor/logs")]
         MonitorLogs {},
+        #[route("/monitor/feeds")]
+        MonitorFeeds {},
     #[end_layout]
     #[route("/:..segments")]
     PageNotFound { segments: Vec<String> },
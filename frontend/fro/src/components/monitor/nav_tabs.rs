@@ -15,6 +15,7 @@ pub fn NavTabs(props: NavTabsProps) -> Element {
         ("Index", Route::MonitorIndex {}),
         ("Rate Limits", Route::MonitorRateLimits {}),
         ("Logs", Route::MonitorLogs {}),
+        ("Feeds", Route::MonitorFeeds {}),
     ];
 
     rsx! {
@@ -0,0 +1,278 @@
+use crate::{api, app::Route, components::monitor::*};
+use dioxus::prelude::*;
+use gloo_timers::future::TimeoutFuture;
+
+#[derive(Clone, Default)]
+struct FeedsState {
+    loading: bool,
+    error: Option<String>,
+    data: Option<Vec<api::FeedSubscription>>,
+    /// Result of the last add/poll/delete action
+    notice: Option<String>,
+}
+
+#[component]
+pub fn MonitorFeeds() -> Element {
+    let state = use_signal(|| FeedsState {
+        loading: true,
+        ..Default::default()
+    });
+    let mut new_url = use_signal(String::new);
+    let mut new_retention = use_signal(String::new);
+    let mut new_full_article = use_signal(|| false);
+
+    {
+        let mut state = state.clone();
+        use_future(move || async move {
+            loop {
+                match api::fetch_feeds().await {
+                    Ok(resp) => {
+                        let notice = state.read().notice.clone();
+                        state.set(FeedsState {
+                            loading: false,
+                            error: None,
+                            data: Some(resp.subscriptions),
+                            notice,
+                        })
+                    }
+                    Err(err) => {
+                        let previous = state.read().data.clone();
+                        let notice = state.read().notice.clone();
+                        state.set(FeedsState {
+                            loading: false,
+                            error: Some(err),
+                            data: previous,
+                            notice,
+                        });
+                    }
+                }
+                TimeoutFuture::new(10_000).await;
+            }
+        });
+    }
+
+    let refresh = move |mut state: Signal<FeedsState>| async move {
+        if let Ok(resp) = api::fetch_feeds().await {
+            state.write().data = Some(resp.subscriptions);
+        }
+    };
+
+    let add_feed = {
+        let state = state.clone();
+        move |_| {
+            let mut state = state.clone();
+            let url = new_url.read().trim().to_string();
+            if url.is_empty() {
+                return;
+            }
+            let payload = api::NewFeedRequest {
+                url,
+                poll_interval_secs: None,
+                fetch_full_article: *new_full_article.read(),
+                retention_days: new_retention.read().trim().parse().ok(),
+            };
+            spawn(async move {
+                match api::create_feed(&payload).await {
+                    Ok(_) => {
+                        state.write().notice = Some(format!("Subscribed to {}", payload.url));
+                        new_url.set(String::new());
+                        new_retention.set(String::new());
+                        refresh(state).await;
+                    }
+                    Err(err) => state.write().notice = Some(format!("Subscribe failed: {}", err)),
+                }
+            });
+        }
+    };
+
+    let snapshot = state.read().clone();
+    let subscriptions = snapshot.data.clone().unwrap_or_default();
+    let total_entries: usize = subscriptions.iter().map(|s| s.entry_count).sum();
+    let failing = subscriptions
+        .iter()
+        .filter(|s| s.last_error.is_some())
+        .count();
+
+    rsx! {
+        div { class: "space-y-6",
+            Breadcrumb {
+                items: vec![
+                    BreadcrumbItem::new("Home", Some(Route::Home {})),
+                    BreadcrumbItem::new("Monitor", Some(Route::MonitorOverview {})),
+                    BreadcrumbItem::new("Feeds", None),
+                ],
+            }
+
+            NavTabs { active: Route::MonitorFeeds {} }
+
+            div { class: "grid grid-cols-1 gap-4 md:grid-cols-3",
+                StatCard {
+                    title: "Subscriptions".into(),
+                    value: subscriptions.len().to_string().into(),
+                }
+                StatCard {
+                    title: "Indexed entries".into(),
+                    value: total_entries.to_string().into(),
+                }
+                StatCard {
+                    title: "Failing".into(),
+                    value: failing.to_string().into(),
+                }
+            }
+
+            Panel { title: Some("Add subscription".into()),
+                div { class: "flex flex-wrap gap-3 items-center text-xs text-gray-300",
+                    input {
+                        class: "bg-gray-800 border border-gray-700 rounded px-2 py-1 text-white w-96",
+                        placeholder: "https://example.com/feed.xml",
+                        value: "{new_url}",
+                        oninput: move |evt| new_url.set(evt.value()),
+                    }
+                    input {
+                        class: "bg-gray-800 border border-gray-700 rounded px-2 py-1 text-white w-32",
+                        placeholder: "Retention (days)",
+                        value: "{new_retention}",
+                        oninput: move |evt| new_retention.set(evt.value()),
+                    }
+                    label { class: "flex items-center gap-1",
+                        input {
+                            r#type: "checkbox",
+                            checked: *new_full_article.read(),
+                            onchange: move |evt| new_full_article.set(evt.checked()),
+                        }
+                        "Fetch full article"
+                    }
+                    button {
+                        class: "px-3 py-1 rounded bg-teal-600 text-white",
+                        onclick: add_feed,
+                        "Subscribe"
+                    }
+                }
+                if let Some(notice) = snapshot.notice.clone() {
+                    div { class: "text-gray-400 text-xs", "{notice}" }
+                }
+            }
+
+            Panel { title: Some("Subscriptions".into()), refresh: Some("10s".into()),
+                if snapshot.loading {
+                    div { class: "text-gray-400 text-sm", "Loading subscriptions…" }
+                } else if let Some(err) = snapshot.error.clone() {
+                    div { class: "text-red-400 text-sm", "Failed to load subscriptions: {err}" }
+                } else if subscriptions.is_empty() {
+                    div { class: "text-gray-500 text-sm", "No feed subscriptions yet." }
+                } else {
+                    div { class: "overflow-x-auto",
+                        table { class: "w-full text-left text-xs text-gray-300",
+                            thead { class: "bg-gray-800",
+                                tr {
+                                    for head in ["Feed", "Entries", "Latest entry", "Last poll", "Interval", "Retention", "Status", ""] {
+                                        th { class: "px-3 py-2 font-semibold", "{head}" }
+                                    }
+                                }
+                            }
+                            tbody {
+                                for sub in subscriptions {
+                                    FeedRow { key: "{sub.id}", sub: sub.clone(), state: state.clone() }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn FeedRow(sub: api::FeedSubscription, state: Signal<FeedsState>) -> Element {
+    let id = sub.id;
+    let enabled = sub.enabled;
+    let name = sub.title.clone().unwrap_or_else(|| sub.url.clone());
+    let status = match (&sub.last_error, enabled) {
+        (Some(err), _) => format!("error: {}", err),
+        (None, false) => "paused".to_string(),
+        (None, true) => "ok".to_string(),
+    };
+    let status_class = if sub.last_error.is_some() {
+        "text-red-400"
+    } else if enabled {
+        "text-green-400"
+    } else {
+        "text-yellow-400"
+    };
+
+    let run = move |action: &'static str| {
+        let mut state = state.clone();
+        spawn(async move {
+            let result = match action {
+                "poll" => api::poll_feed(id).await.map(|v| {
+                    let report = &v["report"];
+                    match report.get("entries_ingested") {
+                        Some(n) => format!("Polled {}: {} new entries", id, n),
+                        None => v["message"].as_str().unwrap_or("Poll failed").to_string(),
+                    }
+                }),
+                "toggle" => api::set_feed_enabled(id, !enabled).await.map(|_| {
+                    format!(
+                        "Subscription {} {}",
+                        id,
+                        if enabled { "paused" } else { "resumed" }
+                    )
+                }),
+                _ => api::delete_feed(id)
+                    .await
+                    .map(|_| format!("Deleted subscription {}", id)),
+            };
+            let notice = result.unwrap_or_else(|e| format!("Action failed: {}", e));
+            let data = api::fetch_feeds().await.ok().map(|r| r.subscriptions);
+            let mut s = state.write();
+            s.notice = Some(notice);
+            if data.is_some() {
+                s.data = data;
+            }
+        });
+    };
+
+    rsx! {
+        tr { class: "odd:bg-gray-900 even:bg-gray-800 border-b border-gray-700",
+            td { class: "px-3 py-2 text-gray-200",
+                div { "{name}" }
+                div { class: "text-[10px] text-gray-500", "{sub.url}" }
+            }
+            td { class: "px-3 py-2 text-gray-400", "{sub.entry_count}" }
+            td { class: "px-3 py-2 text-gray-400", {format_ts(sub.latest_entry_at.as_deref())} }
+            td { class: "px-3 py-2 text-gray-400", {format_ts(sub.last_polled_at.as_deref())} }
+            td { class: "px-3 py-2 text-gray-400", "{sub.poll_interval_secs / 60} min" }
+            td { class: "px-3 py-2 text-gray-400",
+                {sub.retention_days.map(|d| format!("{} days", d)).unwrap_or_else(|| "forever".into())}
+            }
+            td { class: "px-3 py-2 {status_class}", "{status}" }
+            td { class: "px-3 py-2 flex gap-2",
+                button {
+                    class: "px-2 py-1 rounded border border-slate-500 text-slate-200",
+                    onclick: move |_| run("poll"),
+                    "Poll now"
+                }
+                button {
+                    class: "px-2 py-1 rounded border border-slate-500 text-slate-200",
+                    onclick: move |_| run("toggle"),
+                    if enabled { "Pause" } else { "Resume" }
+                }
+                button {
+                    class: "px-2 py-1 rounded border border-red-500 text-red-300",
+                    onclick: move |_| run("delete"),
+                    "Delete"
+                }
+            }
+        }
+    }
+}
+
+fn format_ts(ts: Option<&str>) -> String {
+    match ts {
+        // RFC 3339 -> "YYYY-MM-DD HH:MM"
+        Some(ts) if ts.len() >= 16 => ts[..16].replace('T', " "),
+        Some(ts) => ts.to_string(),
+        None => "-".to_string(),
+    }
+}
@@ -1,4 +1,5 @@
 pub mod cache;
+pub mod feeds;
 pub mod index_page;
 pub mod logs;
 pub mod overview;
@@ -6,6 +7,7 @@ pub mod rate_limits;
 pub mod requests;
 
 pub use cache::MonitorCache;
+pub use feeds::MonitorFeeds;
 pub use index_page::MonitorIndex;
 pub use logs::MonitorLogs;
 pub use overview::MonitorOverview;
@@ -1,4 +1,6 @@
 #!/usr/bin/env bash
+# Full reindex comparison across chunker modes. For a quick look at the chunks
+# one document produces under different settings, use POST /chunking/preview.
 set -euo pipefail
 
 if ! command -v cargo >/dev/null; then
@@ -12,6 +14,8 @@ if ! command -v curl >/dev/null; then
 fi
 
 MODES=("fixed" "lightweight")
+# Contextual chunk headers: run every mode without and with them
+HEADER_VARIANTS=("off" "on")
 OUTDIR="chunker_reports"
 mkdir -p "$OUTDIR"
 DATA_FILE="$OUTDIR/metrics_$(date +%Y%m%d_%H%M%S).jsonl"
@@ -92,27 +96,40 @@ rm chunker_eval.rs
 
 run_for_mode() {
   local mode="$1"
-  echo "\n=== Running reindex for CHUNKER_MODE=$mode ==="
+  local headers="$2"
+  local header_modes="none"
+  if [ "$headers" = "on" ]; then
+    header_modes="$mode"
+  fi
+  echo "\n=== Running reindex for CHUNKER_MODE=$mode headers=$headers ==="
   export CHUNKER_MODE="$mode"
   cargo build --bin ag >/dev/null
   local last_log
-  last_log=$(APP_BIN="$APP_BIN" CHUNKER_MODE="$mode" "$HELPER_BIN")
+  last_log=$(APP_BIN="$APP_BIN" CHUNKER_MODE="$mode" CONTEXT_HEADER_MODES="$header_modes" "$HELPER_BIN")
   LOG_LINES+=("$last_log")
+  HEADER_LABELS+=("$headers")
 }
 
 declare -a LOG_LINES
+declare -a HEADER_LABELS
 for mode in "${MODES[@]}"; do
-  run_for_mode "$mode"
+  for headers in "${HEADER_VARIANTS[@]}"; do
+    run_for_mode "$mode" "$headers"
+  done
 done
 
 echo "\nJSON summary (append to $DATA_FILE):"
-for line in "${LOG_LINES[@]}"; do
+for idx in "${!LOG_LINES[@]}"; do
+  line="${LOG_LINES[$idx]}"
+  HEADERS="${HEADER_LABELS[$idx]}"
   FILE=$(echo "$line" | sed -n "s/.*file='\([^']*\)'.*/\1/p")
   MODE=$(echo "$line" | sed -n "s/.*mode=\([^ ]*\).*/\1/p")
-  CHUNKS=$(echo "$line" | sed -n "s/.*chunks=\([0-9]*\).*/\1/p")
-  TOKENS=$(echo "$line" | sed -n "s/.*tokens=\([0-9]*\).*/\1/p")
+  CHUNKS=$(echo "$line" | sed -n "s/.* chunks=\([0-9]*\).*/\1/p")
+  TOKENS=$(echo "$line" | sed -n "s/.* tokens=\([0-9]*\).*/\1/p")
   DURATION=$(echo "$line" | sed -n "s/.*duration_ms=\([0-9]*\).*/\1/p")
-  JSON="{\"file\":\"$FILE\",\"mode\":\"$MODE\",\"chunks\":$CHUNKS,\"tokens\":$TOKENS,\"duration_ms\":$DURATION}"
+  HEADER_CHUNKS=$(echo "$line" | sed -n "s/.*header_chunks=\([0-9]*\).*/\1/p")
+  HEADER_TOKENS=$(echo "$line" | sed -n "s/.*header_tokens=\([0-9]*\).*/\1/p")
+  JSON="{\"file\":\"$FILE\",\"mode\":\"$MODE\",\"headers\":\"$HEADERS\",\"chunks\":$CHUNKS,\"tokens\":$TOKENS,\"header_chunks\":${HEADER_CHUNKS:-0},\"header_tokens\":${HEADER_TOKENS:-0},\"duration_ms\":$DURATION}"
   echo "$JSON" | tee -a "$DATA_FILE"
 done
 